@@ -1,5 +1,6 @@
+// The transpiled code holds its own main(), generated from set_entry
 #[cfg(not(feature = "transpile"))]
-mod transpiled;
+include!("transpiled.rs");
 
 static PROGRAM: &str = r#"
 # --- Overview ---
@@ -9,14 +10,35 @@ static PROGRAM: &str = r#"
 # *Streams* are (potentially infinite) sources of information that correspond to a given *Alphabet* and *Clock* - They are stateful and once a character or time marker is read, it is forever removed from the stream.
 
 # This script first defines an Alphabet, a Clock, then a set of programs.
+# Statements end with a ; - until then, they may wrap onto as many lines as they need.
 # ---
 
+# Constants (defconst NAME,VALUE;) are defined before everything else - anywhere a number or moment is expected, they can be used in
+# integer expressions (BUF*2, 2*(TICK+1), ...)
+defconst BUF,0x50;
+
+# The generated code also gets a fn main() that runs this program and writes its exits to stdout (or use --bin=NAME)
+set_entry hello_world;
+
+# Macros are expanded before anything else sees their statements - $PARAMs are replaced by the invocation's arguments, and
+# labels defined inside get renamed per expansion. Bodies may also span several lines, closed by a lone }
+defmacro end_with(VAL, EXIT) {
+push_val            $VAL,$EXIT;
+push_moment         1,$EXIT;
+}
+
 defalphabet ASCII;
 
 # Defines the maximum number of bits a 'character' (atom of data) might take up
 set_char_type   u8;
 
-# Defines the 'characters' that can move through a stream
+# Derives for the generated character enum, on top of Copy, Clone and Debug - set_visibility (pub by default) works
+# the same way on alphabets, clocks and programs
+set_derives     PartialEq|Eq|Hash;
+
+# Defines the 'characters' that can move through a stream - a comment block right above one (or above a label, or
+# right below a def... header) becomes the documentation of the generated code
+
 def_char            0x0,NULL_BYTE;
 def_char            0x1,START_OF_HEADING;
 def_char            0x2,START_OF_TEXT;
@@ -146,25 +168,40 @@ def_char            0x7D,CLOSE_CURLY_BRACKET;
 def_char            0x7E,TILDE;
 def_char            0x7F,DELETE;
 
+# Defines named 'classes' of characters, which can be referenced by class-aware instructions
+def_class           WHITESPACE,SPACE|TAB|LINE_FEED|CARRIAGE_RETURN;
+
 defclock CounterClock;
 
 # Defines the maximum number of bits that a moment of time might take up
 set_moment_type      u32;
 
 # Defines what kind of thing the clock represents, could also be:
-    UNIX_TIMESTAMP
-    NATURAL_MILLISECONDS
-    NATURAL_SECONDS
-    NATURAL_MINUTES
-    NATURAL_HOURS
-    ...
+#   UNIX_TIMESTAMP
+#   NATURAL_MILLISECONDS
+#   NATURAL_SECONDS
+#   NATURAL_MINUTES
+#   NATURAL_HOURS
+#   ...
 set_clock_repr      QUANTITY;
 
 
 # --- Programs ---
+# Programs may be generic over alphabets and clocks (defprogram NAME<A: alphabet, C: clock>;) - streams then use $A and $C in their place
 # Quick explanation of functions:
+# expect_aligned    EXIT,EXIT                   - Expects every moment on the first EXIT to also be present on the second - checked by check_expectations()
+# set_priority      PRIORITY                    - Scheduling priority of the program (defaults to 0) - higher priorities are stepped first
+# set_fuel          FUEL                        - Units of work (labels, waits on empty gateways) a run of the label chain may take before failing with OutOfFuel
+# set_visibility    VISIBILITY                  - Visibility of the generated program struct - pub (the default), pub(crate), pub(super), ...
+# set_error_mode    MODE                        - What label functions do on errors - propagate (return a Result, the default), panic, or abort_program (record it in error() and stop)
+# set_on_full       POLICY                      - What forward_duration does when its exit has no room for the duration - error (the default), yield (wait for room before stepping), or drop_duration
 # reg_gateway       NAME,ALPHABET,CLOCK,BUF     - Register an input stream (Input of program) with BUF buffer size
 # reg_exit          NAME,ALPHABET,CLOCK,BUF     - Register an exit stream (Output of program) with BUF buffer size
+#                                               BUF may also be dynamic (or dynamic(CAP)) - a heap buffer growing as needed (up to CAP items), which needs the runtime's alloc feature
+#                                               Streams take an optional overflow policy last (NAME,ALPHABET,CLOCK,BUF,overwrite) - reject (the default) fails pushes onto a full buffer, overwrite evicts its oldest duration
+#                                               Streams of the entry program may also read from stdin (a single gateway, ...,from=stdin) or write to stdout (exits, ...,to=stdout) as it runs - which needs the runtime's std feature
+#                                               Durations read from stdin end with each line, unless set with moments=read (each read) or moments=N (every N bytes)
+# reg_error_exit    NAME,ALPHABET,CLOCK,BUF     - Register an exit receiving (instead of failing on) raw values popped off of gateways that are not characters of their alphabet
 # start_moment      INITIAL_MOMENT,EXIT         - Defines the "initial" moment that your exit clock will start at
 # push_char         CHAR,EXIT                   - Push a character onto the exit stream - can either directly be a character from the related alphabet or a hexadecimal representation of bits.
 # push_moment       INCREMENT_BY,EXIT           - Push a time marker onto the exit stream, representing INCREMENTED_BY moments passing
@@ -172,15 +209,33 @@ set_clock_repr      QUANTITY;
 # label             LABEL;                      - A nice label to make it easier to define jumps
 # jump_earlier      LABEL,GATEWAY,GATEWAY       - Jumps to a given label, if A is earlier than B - Can only jump *forward* in the program
 # jump_later        LABEL,GATEWAY,GATEWAY       - Jumps to a given label, if A is later than B - Can only jump *forward* in the program
+# jump_if_class     LABEL,GATEWAY,CLASS         - Jumps to a given label, if the next item in GATEWAY is a character within CLASS
+# jump_overflowed   LABEL,EXIT                  - Jumps to a given label, if EXIT rejected a push (due to being full) since the last jump_overflowed on it
 # forward_duration  GATEWAY,EXIT                - Pops characters off of GATEWAY until it hits the next duration, while PUSHing each of those characters to EXIT
+# filter            GATEWAY,EXIT,CLASS          - Like forward_duration, but drops characters within CLASS (or equal to a hexadecimal value)
+# filter_class      GATEWAY,EXIT,CLASS          - Like filter, but CLASS can only be a class
+# filter_keep       GATEWAY,EXIT,CLASS          - Like forward_duration, but only keeps characters within CLASS (or equal to a hexadecimal value)
+# tee               GATEWAY,EXIT,EXIT           - Like forward_duration, but PUSHes every character and the closing moment to both EXITs - stops early (leaving items in GATEWAY) while either EXIT is full
+# drop_duration     GATEWAY                     - Pops characters off of GATEWAY until (and including) the next moment, without PUSHing them anywhere
+# skip_until_moment GATEWAY,MOMENT              - Discards items of GATEWAY until its current moment reaches MOMENT - either a literal, or Time(STREAM)
+# sync              GATEWAY|...,EXIT|...        - Pushes the earliest pending moment of all GATEWAYs to every EXIT - GATEWAYs whose duration ends there also forward its characters to their EXIT
+# zip               GATEWAY|...,EXIT            - Forwards the characters of every GATEWAY whose duration ends at the earliest pending moment (in declaration order), then that moment, to EXIT
+# split             GATEWAY,EXIT|...,STRATEGY   - Routes each duration of GATEWAY to one EXIT - STRATEGY is round_robin, by_moment_parity (moment modulo the number of EXITs) or by_class(CLASS) (per character: in CLASS to the first EXIT, the rest to the second)
+# window            GATEWAY,EXIT,K              - Forwards K consecutive durations of GATEWAY to EXIT as a single duration, closed by the K-th moment
+# count_chars       GATEWAY,EXIT                - Pops a duration off of GATEWAY, PUSHing the number of its characters (as decimal digits) and its moment to EXIT
+# min_moment        GATEWAY,EXIT                - Pops a duration off of GATEWAY, PUSHing the moment it started at (as decimal digits) and its moment to EXIT
+# max_moment        GATEWAY,EXIT                - Pops a duration off of GATEWAY, PUSHing the moment it ended at (as decimal digits) and its moment to EXIT
+# delay             GATEWAY,EXIT,N              - Like forward_duration, but the moment PUSHed to EXIT is N moments later - needs an addable (integer) clock
+# throttle          GATEWAY,EXIT,N              - Forwards a duration, then drops durations from GATEWAY until at least N moments have elapsed on its clock
 # connect           PROGRAM(GATEWAY...),NAME    - Forwards GATEWAYs to PROGRAM. Exits of the program can be pulled from NAME
 # reg_exit_gateway  NAME(EXIT),NAME             - Registers a new Gateway, from the Exit of the connected program
+#                                               (programs that connect to others also get a PipelineNAME struct, which runs them together)
 
 defprogram hello_world;
 # Outputs "Hello, World!" in ASCII, within a single moment of time
 
 # Exits: Output stream for the program
-reg_exit            A,ASCII,CounterClock,0x50;
+reg_exit            A,ASCII,CounterClock,BUF;
 
 # All streams have clocks. What moment does this one start at?
 start_moment        0,A;
@@ -199,10 +254,10 @@ push_char           O_LOWERCASE,A;
 push_char           R_LOWERCASE,A;
 push_char           L_LOWERCASE,A;
 push_char           D_LOWERCASE,A;
-push_val            0x21,A;
-push_moment         1,A;
+end_with            0x21,A;                   # Trailing comments are fine too - this pushes "!" and ends the moment
 
-defprogram sync2;
+defprogram sync2<A: alphabet,
+                 C: clock>;
 # Ensures that two streams are in sync with each other, so that no time duration is missed.
 
 # Example:
@@ -211,10 +266,10 @@ defprogram sync2;
 #  Exit C:    |1 |2 B |3 D |4 |5
 #  Exit D:    |1 A |2 B |3 D |4 |5 E
 
-reg_gateway         A,ASCII,CounterClock,0x50;
-reg_gateway         B,ASCII,CounterClock,0x50;
-reg_exit            C,ASCII,CounterClock,0x50;
-reg_exit            D,ASCII,CounterClock,0x50;
+reg_gateway         A,$A,$C,BUF;
+reg_gateway         B,$A,$C,BUF;
+reg_exit            C,$A,$C,BUF;
+reg_exit            D,$A,$C,BUF;
 
 label main;
 jump_earlier        a_earlier,A,B;
@@ -234,7 +289,7 @@ forward_moment      B,C;
 forward_duration    B,D;
 forward_moment      B,D;
 
-defprogram zip2;
+defprogram zip2<A: alphabet, C: clock>;
 # Interleaves two streams of data - if both occurred in the same moment, the first stream's data comes first.
 
 # Example:
@@ -242,9 +297,9 @@ defprogram zip2;
 # Gateway B:    1| B 3| D
 # Exit C:       1| AB 3| CD 4| E
 
-reg_gateway         A,ASCII,CounterClock,0x50;
-reg_gateway         B,ASCII,CounterClock,0x50;
-reg_exit            E,ASCII,CounterClock,0x50;
+reg_gateway         A,$A,$C,BUF;
+reg_gateway         B,$A,$C,BUF;
+reg_exit            E,$A,$C,BUF;
 
 connect             sync2(A|B),SYNCED;
 reg_exit_gateway    SYNCED(C),C;
@@ -259,15 +314,10 @@ push_moment         Time(C),E;
 #[cfg(feature = "transpile")]
 fn main() {
     use parser::Parser;
-    use std::io::prelude::*;
-    
-    let mut parser = Parser::new("program");
-    let reader = std::io::BufReader::new(PROGRAM.as_bytes());
 
-    for line in reader.lines() {
-        if line.is_ok() {
-            parser.parse_line(line.unwrap());
-        }
+    let mut parser = Parser::new("program");
+    if let Err(diagnostic) = parser.parse_str(PROGRAM) {
+        panic!("Parsing Error:\n{}", diagnostic.render(PROGRAM));
     }
 
     match parser.generate() {
@@ -277,30 +327,7 @@ fn main() {
         }
 
         Err(err) => {
-            panic!("Parsing Error:\n{}", err);
+            panic!("Parsing Error:\n{}", err.render(PROGRAM));
         }
     }
 }
-
-#[cfg(not(feature = "transpile"))]
-fn main() {
-    use transpiled::{GatewayLike, StreamItem};
-    let mut prog = transpiled::ProgramHelloWorld::new();
-    prog.label_root();
-
-    loop {
-        match prog.exit_a.pop() {
-            StreamItem::Character(chr) => {
-                println!("Character: {:?}", chr);
-            }
-
-            StreamItem::Moment(moment) => {
-                println!("Moment: {:?}", moment);
-            }
-
-            StreamItem::Empty => {
-                break;
-            }
-        }
-    }
-}
\ No newline at end of file
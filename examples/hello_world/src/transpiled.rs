@@ -1,212 +1,8 @@
-use core::default::Default;
-use core::fmt::Debug;
-
-#[derive(Debug)]
-pub enum AlphabetError<CharRep: Debug> {
-    UnknownCharacter(CharRep),
-    UnexpectedError(&'static str),
-    NameNotFound(),
-}
-pub trait AlphabetLike {
-    type CharRep: Copy + Clone + Debug;
-    type CharEnum: Copy + Clone + Debug;
-    fn char_with_name(rep: &str) -> Result<Self::CharEnum, AlphabetError<&str>>;
-    fn to_char(rep: Self::CharRep) -> Result<Self::CharEnum, AlphabetError<Self::CharRep>>;
-    fn to_val(rep: Self::CharEnum) -> Self::CharRep;
-}
-
-pub enum ClockMoment<MomentRep> {
-    UnixSeconds(MomentRep),
-    UnixMilliseconds(MomentRep),
-    Quantity(MomentRep),
-}
-pub trait ClockLike {
-    type MomentRep: Copy + Clone + Debug;
-    fn represents(&self) -> &str;
-    fn to_moment(rep: Self::MomentRep) -> ClockMoment<Self::MomentRep>;
-}
-pub trait AddableClockLike<MomentRep: core::ops::Add<Output = MomentRep>> {
-    fn add(moment: ClockMoment<MomentRep>, rep: MomentRep) -> ClockMoment<MomentRep> {
-        match moment {
-            ClockMoment::Quantity(orig_rep) => ClockMoment::Quantity(orig_rep + rep),
-            ClockMoment::UnixMilliseconds(orig_rep) => {
-                ClockMoment::UnixMilliseconds(orig_rep + rep)
-            }
-            ClockMoment::UnixSeconds(orig_rep) => ClockMoment::UnixSeconds(orig_rep + rep),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub enum ExitError {
-    BufferFull,
-}
-pub trait ExitLike<Alphabet: AlphabetLike, Clock: ClockLike> {
-    type InternalItem;
-    type Item;
-    fn set_initial_moment(&mut self, monent: Clock::MomentRep);
-    fn accepting_pushes(&mut self) -> bool;
-    fn push(&mut self, chr: Alphabet::CharEnum) -> Result<(), ExitError>;
-    fn push_moment(&mut self, moment: Clock::MomentRep) -> Result<(), ExitError>;
-    fn push_with_name(&mut self, chr_name: &str) -> Result<(), ExitError> {
-        self.push(
-            Alphabet::char_with_name(chr_name)
-                .unwrap_or_else(|_| panic!("Unknown char name: {}", chr_name)),
-        )
-    }
-}
-pub trait GatewayLike<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
-    type InternalItem;
-    type Item;
-    fn pop(&mut self) -> Self::Item;
-    fn forward_duration<Exit: ExitLike<Alphabet, Clock>>(
-        &mut self,
-        exit: &mut Exit,
-    ) -> Result<(), ExitError>;
-    fn current_moment(&self) -> Option<Clock::MomentRep>;
-    fn is_empty(&self) -> bool;
-    fn next_is_character(&self) -> bool;
-    fn next_is_moment(&self) -> bool;
-}
-#[derive(Copy, Clone, Debug)]
-pub enum StreamItem<CharacterRep, Moment> {
-    Empty,
-    Character(CharacterRep),
-    Moment(Moment),
-}
-impl<CharacterRep, Moment> Default for StreamItem<CharacterRep, Moment> {
-    fn default() -> Self {
-        Self::Empty
-    }
-}
-pub struct Stream<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
-    buffer: [StreamItem<Alphabet::CharRep, Clock::MomentRep>; BUFFER_SIZE],
-    idx: usize,
-    buffered_total: usize,
-    buffered_moments: usize,
-    buffered_characters: usize,
-    last_seen_moment: Option<Clock::MomentRep>,
-}
-impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>
-    Stream<Alphabet, Clock, BUFFER_SIZE>
-{
-    pub const fn new() -> Self {
-        Self {
-            buffer: [StreamItem::Empty; BUFFER_SIZE],
-            idx: 0,
-            buffered_total: 0,
-            buffered_moments: 0,
-            buffered_characters: 0,
-            last_seen_moment: None,
-        }
-    }
-    fn inc_index(&mut self) {
-        self.idx = (self.idx + 1) % BUFFER_SIZE;
-    }
-}
-impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> ExitLike<Alphabet, Clock>
-    for Stream<Alphabet, Clock, BUFFER_SIZE>
-{
-    type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
-    type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep>;
-    fn set_initial_moment(&mut self, moment: Clock::MomentRep) {
-        self.last_seen_moment = Some(moment);
-    }
-    fn accepting_pushes(&mut self) -> bool {
-        self.buffered_total < BUFFER_SIZE
-    }
-    fn push(&mut self, chr: Alphabet::CharEnum) -> Result<(), ExitError> {
-        if self.accepting_pushes() {
-            self.buffer[self.idx + self.buffered_total % BUFFER_SIZE] =
-                Self::InternalItem::Character(Alphabet::to_val(chr));
-            self.buffered_characters += 1;
-            self.buffered_total += 1;
-            Ok(())
-        } else {
-            Err(ExitError::BufferFull)
-        }
-    }
-    fn push_moment(&mut self, moment: Clock::MomentRep) -> Result<(), ExitError> {
-        if self.accepting_pushes() {
-            self.buffer[self.idx + self.buffered_total % BUFFER_SIZE] =
-                Self::InternalItem::Moment(moment);
-            self.buffered_moments += 1;
-            self.buffered_total += 1;
-            Ok(())
-        } else {
-            Err(ExitError::BufferFull)
-        }
-    }
-}
-impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>
-    GatewayLike<Alphabet, Clock, BUFFER_SIZE> for Stream<Alphabet, Clock, BUFFER_SIZE>
-{
-    type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
-    type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep>;
-    fn pop(&mut self) -> Self::Item {
-        let last = core::mem::take(&mut self.buffer[self.idx]);
-        match last {
-            Self::InternalItem::Character(chr) => {
-                self.inc_index();
-                self.buffered_characters -= 1;
-                self.buffered_total -= 1;
-                Self::Item::Character(Alphabet::to_char(chr).unwrap_or_else(|err| {
-                    panic!("Unexpected character received in stream: {:?}", err);
-                }))
-            }
-            Self::InternalItem::Moment(moment) => {
-                self.inc_index();
-                self.buffered_moments -= 1;
-                self.buffered_total -= 1;
-                self.last_seen_moment = Some(moment);
-                Self::Item::Moment(moment)
-            }
-            Self::InternalItem::Empty => Self::Item::Empty,
-        }
-    }
-    fn forward_duration<Exit: ExitLike<Alphabet, Clock>>(
-        &mut self,
-        exit: &mut Exit,
-    ) -> Result<(), ExitError> {
-        while self.next_is_character() {
-            match self.pop() {
-                Self::Item::Character(chr) => {
-                    let result = exit.push(chr);
-                    match result {
-                        Ok(_) => (),
-                        Err(err) => return Err(err),
-                    }
-                }
-                item => panic!(
-                    "Expected to pop Character off Gateway. Popped something else:\n{:?}",
-                    item
-                ),
-            }
-        }
-        Ok(())
-    }
-    fn current_moment(&self) -> Option<Clock::MomentRep> {
-        self.last_seen_moment
-    }
-    fn is_empty(&self) -> bool {
-        self.buffered_total == 0
-    }
-    fn next_is_character(&self) -> bool {
-        match self.buffer[self.idx] {
-            Self::InternalItem::Character(_) => true,
-            _ => false,
-        }
-    }
-    fn next_is_moment(&self) -> bool {
-        match self.buffer[self.idx] {
-            Self::InternalItem::Moment(_) => true,
-            _ => false,
-        }
-    }
-}
+// @generated by time_lang 0.1.0 from source fnv1a64:b43c714237970e82 - do not edit
+pub use time_lang_runtime::*;
 
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CharAscii {
     NullByte(),
     StartOfHeading(),
@@ -337,8 +133,26 @@ pub enum CharAscii {
     Tilde(),
     Delete(),
 }
+impl CharAscii {
+    pub const fn is_whitespace(&self) -> bool {
+        matches!(
+            self,
+            CharAscii::Space()
+                | CharAscii::Tab()
+                | CharAscii::LineFeed()
+                | CharAscii::CarriageReturn()
+        )
+    }
+}
 pub struct AlphabetAscii {}
 impl AlphabetAscii {
+    #[allow(non_upper_case_globals)]
+    pub const ClassWhitespace: &'static [CharAscii] = &[
+        CharAscii::Space(),
+        CharAscii::Tab(),
+        CharAscii::LineFeed(),
+        CharAscii::CarriageReturn(),
+    ];
     fn char_with_name(name: &str) -> Result<CharAscii, AlphabetError<&str>> {
         use CharAscii::*;
         match name {
@@ -744,6 +558,8 @@ impl AlphabetAscii {
 impl AlphabetLike for AlphabetAscii {
     type CharRep = u8;
     type CharEnum = CharAscii;
+    const NAME: &'static str = "ASCII";
+    const HASH: u64 = 0x3b08722c095f5f76;
     fn char_with_name(name: &str) -> Result<CharAscii, AlphabetError<&str>> {
         <AlphabetAscii>::char_with_name(name)
     }
@@ -766,6 +582,7 @@ impl ClockCounterClock {
 }
 impl ClockLike for ClockCounterClock {
     type MomentRep = u32;
+    const REPRESENTS: &'static str = "QUANTITY";
     fn represents(&self) -> &str {
         <ClockCounterClock>::represents()
     }
@@ -775,93 +592,119 @@ impl ClockLike for ClockCounterClock {
 }
 impl AddableClockLike<u32> for ClockCounterClock {}
 
+#[doc = " Outputs \"Hello, World!\" in ASCII, within a single moment of time"]
+#[doc = " "]
+#[doc = " Exits: Output stream for the program"]
 pub struct ProgramHelloWorld {
-    pub exit_a: Stream<AlphabetAscii, ClockCounterClock, 0x50>,
+    pub exit_a: Stream<AlphabetAscii, ClockCounterClock, 80>,
 }
 impl ProgramHelloWorld {
     pub const fn new() -> Self {
         Self {
-            exit_a: <Stream<AlphabetAscii, ClockCounterClock, 0x50>>::new(),
+            exit_a: <Stream<AlphabetAscii, ClockCounterClock, 80>>::new(),
         }
     }
-    pub fn label_root(&mut self) {
+    pub fn label_root(&mut self) -> Result<(), StreamError> {
         self.exit_a.set_initial_moment(0);
+        self.exit_a.push_moment(1)?;
         self.exit_a
-            .push_moment(1)
-            .expect("Could not push_moment to Exit (A)");
-        self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::HUppercase())
-            .expect("Could not push_char (\"H_UPPERCASE\")");
-        self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::ELowercase())
-            .expect("Could not push_char (\"E_LOWERCASE\")");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::HUppercase())?;
         self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::LLowercase())
-            .expect("Could not push_char (\"L_LOWERCASE\")");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::ELowercase())?;
         self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::LLowercase())
-            .expect("Could not push_char (\"L_LOWERCASE\")");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::LLowercase())?;
         self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::OLowercase())
-            .expect("Could not push_char (\"O_LOWERCASE\")");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::LLowercase())?;
         self.exit_a
-            .push(
-                AlphabetAscii::to_char(0x2C)
-                    .expect("No character found in Alphabet (ASCII): \"0x2C\""),
-            )
-            .expect("Could not push_val to Exit (A)");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::OLowercase())?;
         self.exit_a
-            .push(
-                AlphabetAscii::to_char(0x20)
-                    .expect("No character found in Alphabet (ASCII): \"0x20\""),
-            )
-            .expect("Could not push_val to Exit (A)");
+            .push(AlphabetAscii::to_char(0x2C).map_err(|_| StreamError::UnknownChar)?)?;
         self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::WUppercase())
-            .expect("Could not push_char (\"W_UPPERCASE\")");
+            .push(AlphabetAscii::to_char(0x20).map_err(|_| StreamError::UnknownChar)?)?;
         self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::OLowercase())
-            .expect("Could not push_char (\"O_LOWERCASE\")");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::WUppercase())?;
         self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::RLowercase())
-            .expect("Could not push_char (\"R_LOWERCASE\")");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::OLowercase())?;
         self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::LLowercase())
-            .expect("Could not push_char (\"L_LOWERCASE\")");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::RLowercase())?;
         self.exit_a
-            .push(<AlphabetAscii as AlphabetLike>::CharEnum::DLowercase())
-            .expect("Could not push_char (\"D_LOWERCASE\")");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::LLowercase())?;
         self.exit_a
-            .push(
-                AlphabetAscii::to_char(0x21)
-                    .expect("No character found in Alphabet (ASCII): \"0x21\""),
-            )
-            .expect("Could not push_val to Exit (A)");
+            .push(<AlphabetAscii as AlphabetLike>::CharEnum::DLowercase())?;
         self.exit_a
-            .push_moment(1)
-            .expect("Could not push_moment to Exit (A)");
+            .push(AlphabetAscii::to_char(0x21).map_err(|_| StreamError::UnknownChar)?)?;
+        self.exit_a.push_moment(1)?;
+        Ok(())
+    }
+    #[doc = r" Runs the program - its root label, then `main` (or the root again) for as long as the whole durations it reads are buffered"]
+    pub fn run(&mut self) -> Result<(), StreamError> {
+        self.label_root()?;
+        while self.has_pending_input() {
+            self.label_root()?;
+        }
+        Ok(())
+    }
+    #[doc = r" Checks the program's `expect_aligned` expectations against what is currently buffered on its exits"]
+    pub fn check_expectations(
+        &self,
+    ) -> Result<(), ExpectationError<<ClockCounterClock as ClockLike>::MomentRep>> {
+        Ok(())
+    }
+}
+impl ProgramLike for ProgramHelloWorld {
+    const PRIORITY: u8 = 0;
+    fn has_pending_input(&self) -> bool {
+        false
+    }
+    fn close_gateways(&mut self) {}
+    fn propagate_close(&mut self) -> bool {
+        if true {
+            self.exit_a.close();
+            true
+        } else {
+            false
+        }
+    }
+    fn start(&mut self) -> Result<(), StreamError> {
+        self.label_root()
+    }
+    fn run_step(&mut self) -> Result<bool, StreamError> {
+        if !self.has_pending_input() {
+            return Ok(false);
+        }
+        self.label_root()?;
+        Ok(true)
     }
 }
 
-pub struct ProgramSync2 {
-    pub gateway_a: Stream<AlphabetAscii, ClockCounterClock, 0x50>,
-    pub gateway_b: Stream<AlphabetAscii, ClockCounterClock, 0x50>,
-    pub exit_c: Stream<AlphabetAscii, ClockCounterClock, 0x50>,
-    pub exit_d: Stream<AlphabetAscii, ClockCounterClock, 0x50>,
+#[doc = " Ensures that two streams are in sync with each other, so that no time duration is missed."]
+#[doc = " "]
+#[doc = " Example:"]
+#[doc = "  Gateway A: |2 B |3 D |4"]
+#[doc = "  Gateway B: |1 A |2 C |5 E"]
+#[doc = "  Exit C:    |1 |2 B |3 D |4 |5"]
+#[doc = "  Exit D:    |1 A |2 B |3 D |4 |5 E"]
+pub struct ProgramSync2<A: AlphabetLike, C: ClockLike> {
+    pub gateway_a: Stream<A, C, 80>,
+    pub gateway_b: Stream<A, C, 80>,
+    pub exit_c: Stream<A, C, 80>,
+    pub exit_d: Stream<A, C, 80>,
 }
-impl ProgramSync2 {
+impl<A: AlphabetLike, C: ClockLike> ProgramSync2<A, C> {
     pub const fn new() -> Self {
         Self {
-            gateway_a: <Stream<AlphabetAscii, ClockCounterClock, 0x50>>::new(),
-            gateway_b: <Stream<AlphabetAscii, ClockCounterClock, 0x50>>::new(),
-            exit_c: <Stream<AlphabetAscii, ClockCounterClock, 0x50>>::new(),
-            exit_d: <Stream<AlphabetAscii, ClockCounterClock, 0x50>>::new(),
+            gateway_a: <Stream<A, C, 80>>::new(),
+            gateway_b: <Stream<A, C, 80>>::new(),
+            exit_c: <Stream<A, C, 80>>::new(),
+            exit_d: <Stream<A, C, 80>>::new(),
         }
     }
-    pub fn label_root(&mut self) {}
-    pub fn label_main(&mut self) {
-        if ClockCounterClock::represents() != ClockCounterClock::represents() {
-            panic ! ("(Clock of) Gateway A and (Clock of) Gateway B being compared while not representing the same thing");
+    pub fn label_root(&mut self) -> Result<(), StreamError> {
+        Ok(())
+    }
+    pub fn label_main(&mut self) -> Result<(), StreamError> {
+        if <C as ClockLike>::REPRESENTS != <C as ClockLike>::REPRESENTS {
+            return Err(StreamError::ClockMismatch);
         }
         match (
             self.gateway_a.current_moment(),
@@ -875,8 +718,8 @@ impl ProgramSync2 {
             }
             _ => (),
         }
-        if ClockCounterClock::represents() != ClockCounterClock::represents() {
-            panic ! ("(Clock of) Gateway A and (Clock of) Gateway B being compared while not representing the same thing");
+        if <C as ClockLike>::REPRESENTS != <C as ClockLike>::REPRESENTS {
+            return Err(StreamError::ClockMismatch);
         }
         match (
             self.gateway_a.current_moment(),
@@ -891,157 +734,338 @@ impl ProgramSync2 {
             _ => (),
         }
         loop {
-            match self.gateway_a.pop() {
-                StreamItem::Character(chr) => {
-                    self.exit_c
-                        .push(chr)
-                        .expect("Failed to forward character from Gateway A to Exit C");
-                }
-                StreamItem::Moment(moment) => {
-                    self.exit_c
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway A to Exit C");
-                    break;
+            if forward_raw(&mut self.gateway_a, &mut self.exit_c)? {
+                break;
+            }
+            match forward_chars(&mut self.gateway_a, &mut self.exit_c)? {
+                StreamItem::InvalidChar(_) => {
+                    return Err(StreamError::InvalidChar);
                 }
                 StreamItem::Empty => continue,
+                _ => break,
             }
         }
-        if self.gateway_a.next_is_moment() {
-            match self.gateway_a.pop() {
-                StreamItem::Moment(moment) => {
-                    self.exit_c
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway A to Exit C");
-                }
-                _ => {
-                    panic ! ("Unreachable Code - unexpectedly popped a non-moment when calling forward_moment()");
+        forward_moment(&mut self.gateway_a, &mut self.exit_c)?;
+        loop {
+            if forward_raw(&mut self.gateway_b, &mut self.exit_d)? {
+                break;
+            }
+            match forward_chars(&mut self.gateway_b, &mut self.exit_d)? {
+                StreamItem::InvalidChar(_) => {
+                    return Err(StreamError::InvalidChar);
                 }
+                StreamItem::Empty => continue,
+                _ => break,
             }
-        } else {
-            panic ! ("Tried to forward_moment from {} to {} when the next item in the gateway, is not a Moment" , "A" , "C")
         }
+        forward_moment(&mut self.gateway_b, &mut self.exit_d)?;
+        Ok(())
+    }
+    pub fn label_a_earlier(&mut self) -> Result<(), StreamError> {
+        forward_moment(&mut self.gateway_a, &mut self.exit_d)?;
         loop {
-            match self.gateway_b.pop() {
-                StreamItem::Character(chr) => {
-                    self.exit_d
-                        .push(chr)
-                        .expect("Failed to forward character from Gateway B to Exit D");
-                }
-                StreamItem::Moment(moment) => {
-                    self.exit_d
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway B to Exit D");
-                    break;
+            if forward_raw(&mut self.gateway_a, &mut self.exit_c)? {
+                break;
+            }
+            match forward_chars(&mut self.gateway_a, &mut self.exit_c)? {
+                StreamItem::InvalidChar(_) => {
+                    return Err(StreamError::InvalidChar);
                 }
                 StreamItem::Empty => continue,
+                _ => break,
             }
         }
-        if self.gateway_b.next_is_moment() {
-            match self.gateway_b.pop() {
-                StreamItem::Moment(moment) => {
-                    self.exit_d
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway B to Exit D");
-                }
-                _ => {
-                    panic ! ("Unreachable Code - unexpectedly popped a non-moment when calling forward_moment()");
+        forward_moment(&mut self.gateway_a, &mut self.exit_c)?;
+        Ok(())
+    }
+    pub fn label_a_later(&mut self) -> Result<(), StreamError> {
+        forward_moment(&mut self.gateway_b, &mut self.exit_c)?;
+        loop {
+            if forward_raw(&mut self.gateway_b, &mut self.exit_d)? {
+                break;
+            }
+            match forward_chars(&mut self.gateway_b, &mut self.exit_d)? {
+                StreamItem::InvalidChar(_) => {
+                    return Err(StreamError::InvalidChar);
                 }
+                StreamItem::Empty => continue,
+                _ => break,
             }
-        } else {
-            panic ! ("Tried to forward_moment from {} to {} when the next item in the gateway, is not a Moment" , "B" , "D")
         }
+        forward_moment(&mut self.gateway_b, &mut self.exit_d)?;
+        Ok(())
     }
-    pub fn label_a_earlier(&mut self) {
-        if self.gateway_a.next_is_moment() {
-            match self.gateway_a.pop() {
-                StreamItem::Moment(moment) => {
-                    self.exit_d
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway A to Exit D");
-                }
-                _ => {
-                    panic ! ("Unreachable Code - unexpectedly popped a non-moment when calling forward_moment()");
-                }
-            }
+    #[doc = r" Runs the program - its root label, then `main` (or the root again) for as long as the whole durations it reads are buffered"]
+    pub fn run(&mut self) -> Result<(), StreamError> {
+        self.label_root()?;
+        while self.has_pending_input() {
+            self.label_main()?;
+        }
+        Ok(())
+    }
+    #[doc = r" Checks the program's `expect_aligned` expectations against what is currently buffered on its exits"]
+    pub fn check_expectations(&self) -> Result<(), ExpectationError<<C as ClockLike>::MomentRep>> {
+        Ok(())
+    }
+}
+impl<A: AlphabetLike, C: ClockLike> ProgramLike for ProgramSync2<A, C> {
+    const PRIORITY: u8 = 0;
+    fn has_pending_input(&self) -> bool {
+        self.gateway_a.has_duration() && self.gateway_b.has_duration()
+    }
+    fn close_gateways(&mut self) {
+        self.gateway_a.close();
+        self.gateway_b.close();
+    }
+    fn propagate_close(&mut self) -> bool {
+        if self.gateway_a.is_drained() && self.gateway_b.is_drained() {
+            self.exit_c.close();
+            self.exit_d.close();
+            true
         } else {
-            panic ! ("Tried to forward_moment from {} to {} when the next item in the gateway, is not a Moment" , "A" , "D")
+            false
+        }
+    }
+    fn start(&mut self) -> Result<(), StreamError> {
+        self.label_root()
+    }
+    fn run_step(&mut self) -> Result<bool, StreamError> {
+        if !self.has_pending_input() {
+            return Ok(false);
         }
+        self.label_main()?;
+        Ok(true)
+    }
+}
+
+#[doc = " Interleaves two streams of data - if both occurred in the same moment, the first stream's data comes first."]
+#[doc = " "]
+#[doc = " Example:"]
+#[doc = " Gateway A:    1| A 3| C 4| E"]
+#[doc = " Gateway B:    1| B 3| D"]
+#[doc = " Exit C:       1| AB 3| CD 4| E"]
+pub struct ProgramZip2<A: AlphabetLike, C: ClockLike> {
+    pub gateway_a: Stream<A, C, 80>,
+    pub gateway_b: Stream<A, C, 80>,
+    pub gateway_c: Stream<A, C, 80>,
+    pub gateway_d: Stream<A, C, 80>,
+    pub exit_e: Stream<A, C, 80>,
+}
+impl<A: AlphabetLike, C: ClockLike> ProgramZip2<A, C> {
+    pub const fn new() -> Self {
+        Self {
+            gateway_a: <Stream<A, C, 80>>::new(),
+            gateway_b: <Stream<A, C, 80>>::new(),
+            gateway_c: <Stream<A, C, 80>>::new(),
+            gateway_d: <Stream<A, C, 80>>::new(),
+            exit_e: <Stream<A, C, 80>>::new(),
+        }
+    }
+    pub fn label_root(&mut self) -> Result<(), StreamError> {
+        Ok(())
+    }
+    pub fn label_main(&mut self) -> Result<(), StreamError> {
         loop {
-            match self.gateway_a.pop() {
-                StreamItem::Character(chr) => {
-                    self.exit_c
-                        .push(chr)
-                        .expect("Failed to forward character from Gateway A to Exit C");
-                }
-                StreamItem::Moment(moment) => {
-                    self.exit_c
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway A to Exit C");
-                    break;
+            if forward_raw(&mut self.gateway_c, &mut self.exit_e)? {
+                break;
+            }
+            match forward_chars(&mut self.gateway_c, &mut self.exit_e)? {
+                StreamItem::InvalidChar(_) => {
+                    return Err(StreamError::InvalidChar);
                 }
                 StreamItem::Empty => continue,
+                _ => break,
             }
         }
-        if self.gateway_a.next_is_moment() {
-            match self.gateway_a.pop() {
-                StreamItem::Moment(moment) => {
-                    self.exit_c
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway A to Exit C");
-                }
-                _ => {
-                    panic ! ("Unreachable Code - unexpectedly popped a non-moment when calling forward_moment()");
+        loop {
+            if forward_raw(&mut self.gateway_d, &mut self.exit_e)? {
+                break;
+            }
+            match forward_chars(&mut self.gateway_d, &mut self.exit_e)? {
+                StreamItem::InvalidChar(_) => {
+                    return Err(StreamError::InvalidChar);
                 }
+                StreamItem::Empty => continue,
+                _ => break,
             }
-        } else {
-            panic ! ("Tried to forward_moment from {} to {} when the next item in the gateway, is not a Moment" , "A" , "C")
         }
+        self.exit_e.push_moment(
+            self.gateway_c
+                .current_moment()
+                .ok_or(StreamError::NoMoment)?,
+        )?;
+        Ok(())
     }
-    pub fn label_a_later(&mut self) {
-        if self.gateway_b.next_is_moment() {
-            match self.gateway_b.pop() {
-                StreamItem::Moment(moment) => {
-                    self.exit_c
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway B to Exit C");
-                }
-                _ => {
-                    panic ! ("Unreachable Code - unexpectedly popped a non-moment when calling forward_moment()");
-                }
-            }
+    #[doc = r" Runs the program - its root label, then `main` (or the root again) for as long as the whole durations it reads are buffered"]
+    pub fn run(&mut self) -> Result<(), StreamError> {
+        self.label_root()?;
+        while self.has_pending_input() {
+            self.label_main()?;
+        }
+        Ok(())
+    }
+    #[doc = r" Checks the program's `expect_aligned` expectations against what is currently buffered on its exits"]
+    pub fn check_expectations(&self) -> Result<(), ExpectationError<<C as ClockLike>::MomentRep>> {
+        Ok(())
+    }
+}
+impl<A: AlphabetLike, C: ClockLike> ProgramLike for ProgramZip2<A, C> {
+    const PRIORITY: u8 = 0;
+    fn has_pending_input(&self) -> bool {
+        self.gateway_c.has_duration() && self.gateway_d.has_duration()
+    }
+    fn close_gateways(&mut self) {
+        self.gateway_a.close();
+        self.gateway_b.close();
+    }
+    fn propagate_close(&mut self) -> bool {
+        if self.gateway_a.is_drained()
+            && self.gateway_b.is_drained()
+            && self.gateway_c.is_drained()
+            && self.gateway_d.is_drained()
+        {
+            self.exit_e.close();
+            true
         } else {
-            panic ! ("Tried to forward_moment from {} to {} when the next item in the gateway, is not a Moment" , "B" , "C")
+            false
         }
-        loop {
-            match self.gateway_b.pop() {
+    }
+    fn start(&mut self) -> Result<(), StreamError> {
+        self.label_root()
+    }
+    fn run_step(&mut self) -> Result<bool, StreamError> {
+        if !self.has_pending_input() {
+            return Ok(false);
+        }
+        self.label_main()?;
+        Ok(true)
+    }
+}
+#[doc = " Program (zip2), together with every program it connects to"]
+pub struct PipelineZip2<A: AlphabetLike, C: ClockLike> {
+    pub program: ProgramZip2<A, C>,
+    pub synced: ProgramSync2<A, C>,
+    scheduler: Scheduler<2>,
+}
+impl<A: AlphabetLike, C: ClockLike> PipelineZip2<A, C> {
+    pub const fn new() -> Self {
+        Self {
+            program: <ProgramZip2<A, C>>::new(),
+            synced: <ProgramSync2<A, C>>::new(),
+            scheduler: Scheduler::new(STARVATION_LIMIT),
+        }
+    }
+    #[doc = r" Moves everything that fits across connected streams - returns whether anything moved"]
+    pub fn transfer(&mut self) -> Result<bool, StreamError> {
+        let mut moved = false;
+        moved |= transfer(&mut self.program.gateway_a, &mut self.synced.gateway_a)?;
+        moved |= transfer(&mut self.program.gateway_b, &mut self.synced.gateway_b)?;
+        moved |= transfer(&mut self.synced.exit_c, &mut self.program.gateway_c)?;
+        moved |= transfer(&mut self.synced.exit_d, &mut self.program.gateway_d)?;
+        Ok(moved)
+    }
+    #[doc = r" Runs every program of the pipeline, until none of them has anything left to do - or can make progress"]
+    pub fn run(&mut self) -> Result<(), StreamError> {
+        self.start()?;
+        while self.has_pending_input() && self.run_step()? {}
+        Ok(())
+    }
+    #[doc = r" The program of the pipeline the scheduler caught starving, if any"]
+    pub fn starvation(&self) -> Option<Starvation> {
+        self.scheduler.starvation()
+    }
+}
+impl<A: AlphabetLike, C: ClockLike> ProgramLike for PipelineZip2<A, C> {
+    const PRIORITY: u8 = <ProgramZip2<A, C> as ProgramLike>::PRIORITY;
+    fn has_pending_input(&self) -> bool {
+        !self.program.gateway_a.is_empty()
+            || !self.program.gateway_b.is_empty()
+            || !self.synced.exit_c.is_empty()
+            || !self.synced.exit_d.is_empty()
+            || self.program.has_pending_input()
+            || self.synced.has_pending_input()
+    }
+    fn close_gateways(&mut self) {
+        self.program.close_gateways();
+    }
+    fn propagate_close(&mut self) -> bool {
+        let _ = self.transfer();
+        let mut finished = true;
+        finished &= self.synced.propagate_close();
+        let _ = self.transfer();
+        self.program.propagate_close() && finished
+    }
+    fn start(&mut self) -> Result<(), StreamError> {
+        self.synced.start()?;
+        self.program.start()
+    }
+    fn run_step(&mut self) -> Result<bool, StreamError> {
+        let moved = self.transfer()?;
+        let stepped = self
+            .scheduler
+            .round(&mut [&mut self.program, &mut self.synced])?;
+        Ok(self.transfer()? || moved || stepped)
+    }
+}
+impl<A: AlphabetLike, C: ClockLike> ProgramGraph for PipelineZip2<A, C> {
+    fn spawn_all<'a, E: Executor<'a>>(&'a mut self, executor: &mut E) {
+        let (mut tx_0, mut rx_0) = executor.bridge::<A, C>();
+        let (mut tx_1, mut rx_1) = executor.bridge::<A, C>();
+        let (mut tx_2, mut rx_2) = executor.bridge::<A, C>();
+        let (mut tx_3, mut rx_3) = executor.bridge::<A, C>();
+        let Self {
+            program, synced, ..
+        } = self;
+        executor.spawn(program, move |program: &mut ProgramZip2<A, C>| {
+            rx_2.receive(&mut program.gateway_c)?;
+            rx_3.receive(&mut program.gateway_d)?;
+            Ok(tx_0.send(&mut program.gateway_a) & tx_1.send(&mut program.gateway_b))
+        });
+        executor.spawn(synced, move |synced: &mut ProgramSync2<A, C>| {
+            rx_0.receive(&mut synced.gateway_a)?;
+            rx_1.receive(&mut synced.gateway_b)?;
+            Ok(tx_2.send(&mut synced.exit_c) & tx_3.send(&mut synced.exit_d))
+        });
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    use std::io::Write;
+    let mut prog = ProgramHelloWorld::new();
+    if let Err(err) = prog.run() {
+        eprintln!("Program (hello_world) failed: {:?}", err);
+        std::process::exit(1);
+    }
+    let mut out = std::io::stdout().lock();
+    {
+        let mut line_open = false;
+        for item in prog.exit_a.drain() {
+            match item {
                 StreamItem::Character(chr) => {
-                    self.exit_d
-                        .push(chr)
-                        .expect("Failed to forward character from Gateway B to Exit D");
-                }
-                StreamItem::Moment(moment) => {
-                    self.exit_d
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway B to Exit D");
-                    break;
+                    let rep = <AlphabetAscii as AlphabetLike>::to_val(chr) as u32;
+                    write!(
+                        out,
+                        "{}",
+                        char::from_u32(rep).unwrap_or(char::REPLACEMENT_CHARACTER)
+                    )?;
+                    line_open = true;
                 }
-                StreamItem::Empty => continue,
-            }
-        }
-        if self.gateway_b.next_is_moment() {
-            match self.gateway_b.pop() {
-                StreamItem::Moment(moment) => {
-                    self.exit_d
-                        .push_moment(moment)
-                        .expect("Failed to forward moment from Gateway B to Exit D");
+                StreamItem::InvalidChar(_) => {
+                    write!(out, "{}", char::REPLACEMENT_CHARACTER)?;
+                    line_open = true;
                 }
-                _ => {
-                    panic ! ("Unreachable Code - unexpectedly popped a non-moment when calling forward_moment()");
+                StreamItem::Moment(_) => {
+                    if line_open {
+                        writeln!(out)?;
+                        line_open = false;
+                    }
                 }
+                StreamItem::Empty => (),
             }
-        } else {
-            panic ! ("Tried to forward_moment from {} to {} when the next item in the gateway, is not a Moment" , "B" , "D")
+        }
+        if line_open {
+            writeln!(out)?;
         }
     }
+    out.flush()
 }
-
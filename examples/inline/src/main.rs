@@ -0,0 +1,47 @@
+// The .tl source is transpiled while this crate compiles - no separate transpile step, and no transpiled.rs to keep in sync
+time_lang::program!(r#"
+defalphabet Digits;
+set_char_type       u8;
+def_char            0x30,ZERO;
+def_char            0x31,ONE;
+def_char            0x32,TWO;
+
+defclock CounterClock;
+set_moment_type     u32;
+set_clock_repr      QUANTITY;
+
+defprogram count_up;
+reg_exit            A,Digits,CounterClock,16;
+start_moment        0,A;
+push_char           ZERO,A;
+push_moment         1,A;
+push_char           ONE,A;
+push_moment         1,A;
+push_char           TWO,A;
+push_moment         1,A;
+"#);
+
+fn main() {
+    let mut prog = ProgramCountUp::new();
+    prog.label_root().expect("Program failed");
+
+    loop {
+        match prog.exit_a.pop() {
+            StreamItem::Character(chr) => {
+                println!("Character: {:?}", chr);
+            }
+
+            StreamItem::Moment(moment) => {
+                println!("Moment: {:?}", moment);
+            }
+
+            StreamItem::InvalidChar(rep) => {
+                println!("Invalid character: {:?}", rep);
+            }
+
+            StreamItem::Empty => {
+                break;
+            }
+        }
+    }
+}
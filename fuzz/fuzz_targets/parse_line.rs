@@ -0,0 +1,19 @@
+//! Arbitrary lines fed to the parser one at a time - carrying on past the ones it rejects, so whatever state they
+//! leave behind gets parsed on from too
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use parser::Parser;
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let mut parser = Parser::new("fuzz");
+    parser.set_codegen(false);
+
+    for line in source.lines() {
+        let _ = parser.parse_line(line.to_string());
+    }
+
+    let _ = parser.check();
+});
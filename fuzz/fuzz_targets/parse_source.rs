@@ -0,0 +1,34 @@
+//! Arbitrary sources run through every stage that takes one - and, differentially, through the formatter: formatting
+//! must settle after a single pass, and never change the code a source compiles to
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use parser::{format_source, tokenize, Compiler, Parser};
+
+/// Generated code, without the header naming the hash of the source it came from
+fn compile(source: &str) -> Option<String> {
+    Compiler::compile_str(source).ok().map(|code| code.lines().skip(1).collect::<Vec<_>>().join("\n"))
+}
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let _ = tokenize(&source);
+
+    let mut parser = Parser::new("fuzz");
+    parser.set_codegen(false);
+    if parser.parse_str(&source).is_ok() {
+        let _ = parser.check();
+        let _ = parser.lint();
+        let _ = parser.ast_json();
+        let _ = parser.to_ir().to_json();
+        let _ = parser.connect_dot();
+    }
+
+    let Ok(formatted) = format_source("fuzz", &source) else {
+        return;
+    };
+
+    assert_eq!(format_source("fuzz", &formatted).ok().as_ref(), Some(&formatted), "formatting the formatted source changed it");
+    assert_eq!(compile(&source), compile(&formatted), "formatting changed the generated code");
+});
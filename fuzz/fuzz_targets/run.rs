@@ -0,0 +1,125 @@
+//! Generated programs stepped one at a time with bounded fuel, as the REPL steps them, must end up with the same exits
+//! as their own `run()` - on whatever the fuzzer feeds their gateways, invalid characters included. Both sides run the
+//! same generated code (there is no separate interpreter), so this differentially checks stepping against `run()`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Every step of these only reads durations already buffered whole - forward_duration waits for the rest of one
+// within a step, which no fuel bounds
+time_lang::program!(r#"
+defalphabet ABC;
+set_char_type       u8;
+def_char            0x41,A;
+def_char            0x42,B;
+def_char            0x43,C;
+def_class           AB,A|B;
+
+defclock Counter;
+set_moment_type     u32;
+set_clock_repr      QUANTITY;
+
+defprogram drop_c;
+reg_gateway         G,ABC,Counter,32;
+reg_exit            E,ABC,Counter,32;
+
+label main;
+filter              G,E,0x43;
+
+defprogram piped;
+reg_gateway         G,ABC,Counter,8;
+reg_exit            E,ABC,Counter,32;
+
+connect             drop_c(G),DROPPED;
+reg_exit_gateway    DROPPED(E),D;
+
+label main;
+forward_duration    D,E;
+
+defprogram keep_ab;
+reg_gateway         G,ABC,Counter,8;
+reg_exit            E,ABC,Counter,32;
+reg_error_exit      ERR,ABC,Counter,32;
+
+label main;
+filter_keep         G,E,AB;
+"#);
+
+/// Steps a program takes at most - programs still running after as many are taken to never finish
+const FUEL: usize = 10_000;
+
+/// Values fed to gateways - the characters of ABC, and a value that is none of them
+const VALUES: [u8; 4] = [0x41, 0x42, 0x43, 0x7F];
+
+#[derive(Debug, Clone, Copy)]
+enum Feed {
+    Value(u8),
+    Moment(u32)
+}
+
+/// What each byte of the input feeds the gateway - a value, or (with the high bit set) how many moments pass before
+/// the current duration closes
+fn feeds(data: &[u8]) -> Vec<Feed> {
+    let mut moment = 0;
+
+    data.iter().map(|byte| match *byte & 0x80 {
+        0 => Feed::Value(VALUES[*byte as usize % VALUES.len()]),
+        _ => {
+            moment += (*byte & 0x0F) as u32 + 1;
+            Feed::Moment(moment)
+        }
+    }).collect()
+}
+
+/// Feeds a gateway - items a full gateway rejects are dropped, the same way for both runs
+fn feed(gateway: &mut Stream<AlphabetAbc, ClockCounter, 8>, feeds: &[Feed]) {
+    for feed in feeds.iter() {
+        let _ = match feed {
+            Feed::Value(value) => gateway.push_vals(&[*value]),
+            Feed::Moment(moment) => gateway.push_moment(*moment)
+        };
+    }
+}
+
+fn drain(exit: &mut Stream<AlphabetAbc, ClockCounter, 32>) -> Vec<String> {
+    Drain::new(exit).map(|item| format!("{:?}", item)).collect()
+}
+
+/// Runs a program both ways - stepping it until it has no input pending (or runs out of fuel), and with `run()` -
+/// and checks the outcomes match. Programs running out of fuel are left there, as `run()` would never return.
+fn differential<P: ProgramLike>(new: impl Fn() -> P, run: impl Fn(&mut P) -> String, exits: impl Fn(&mut P) -> Vec<String>) {
+    let mut stepped = new();
+    let mut fuel = FUEL;
+    let outcome = stepped.start().and_then(|_| {
+        while fuel > 0 && stepped.run_step()? {
+            fuel -= 1;
+        }
+
+        Ok(())
+    });
+
+    if fuel == 0 {
+        return;
+    }
+
+    let mut ran = new();
+    assert_eq!(format!("{:?}", outcome), run(&mut ran), "stepping and run() ended differently");
+    assert_eq!(exits(&mut stepped), exits(&mut ran), "stepping and run() pushed different items onto the exits");
+}
+
+fuzz_target!(|data: &[u8]| {
+    let feeds = feeds(data);
+
+    differential(|| {
+        let mut prog = PipelinePiped::new();
+        feed(&mut prog.program.gateway_g, &feeds);
+        prog
+    }, |prog| format!("{:?}", prog.run()), |prog| drain(&mut prog.program.exit_e));
+
+    differential(|| {
+        let mut prog = ProgramKeepAb::new();
+        feed(&mut prog.gateway_g, &feeds);
+        prog
+    }, |prog| format!("{:?}", prog.run()), |prog| [drain(&mut prog.exit_e), drain(&mut prog.exit_err)].concat());
+});
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use crate::{Diagnostic, Diagnostics, Ir, Parser};
+
+#[derive(Debug)]
+pub struct Manifest {
+    pub kind: &'static str,
+    pub source: String,
+    pub gateways: Vec<String>,
+    pub exits: Vec<String>,
+    pub connects: Vec<String>
+}
+
+#[derive(Debug)]
+pub struct Artifact {
+    pub code: String,
+
+    /// The definition on its own, as the versioned JSON IR `from-ir` reads
+    pub ir: String,
+    pub manifest: Manifest
+}
+
+/// Everything produced by a batch compilation - the shared runtime, plus one artifact per definition name
+#[derive(Debug)]
+pub struct Artifacts {
+    pub runtime: String,
+    pub definitions: BTreeMap<String, Artifact>
+}
+
+pub struct Compiler {}
+
+impl Compiler {
+    /// Transpiles a single source into self-contained Rust - in memory and unformatted, with the runtime inlined, so it
+    /// needs neither a filesystem nor rustfmt (what the wasm build runs in a browser)
+    pub fn compile_str(source: &str) -> Result<String, Diagnostic> {
+        let mut parser = Parser::new("program");
+        parser.set_codegen(false);
+        parser.set_format(false);
+        parser.set_inline_runtime(true);
+        parser.parse_str(source)?;
+        if let Some(diagnostic) = parser.check().into_iter().next() {
+            return Err(diagnostic);
+        }
+
+        parser.into_codegen().generate()
+    }
+
+    /// Compiles every `(name, source)` pair into in-memory artifacts, instead of printing generated code - run through
+    /// rustfmt with `format`. Sources are checked together, as they may use each other's definitions.
+    pub fn compile_all(sources: &[(&str, &str)], format: bool) -> Result<Artifacts, Diagnostics> {
+        let mut diagnostics = Diagnostics::default();
+        let mut definitions: BTreeMap<String, Artifact> = BTreeMap::new();
+        let mut dynamic_streams = false;
+
+        // Every source is parsed into the same definitions, so sources can use each other's alphabets, clocks and
+        // programs. Definitions are generated one by one below, so the parser only builds them.
+        let mut parser = Parser::new("");
+        parser.set_codegen(false);
+        let mut defined_in = vec![];
+        for (name, source) in sources {
+            let known = parser.definitions().count();
+            if let Err(diagnostic) = parser.begin_source(name).and_then(|_| parser.parse_str(source)) {
+                diagnostics.errors.push(diagnostic);
+            }
+
+            defined_in.extend(parser.definitions().skip(known).map(|_| *name));
+        }
+
+        diagnostics.errors.extend(parser.check());
+        if !diagnostics.errors.is_empty() {
+            return Err(diagnostics);
+        }
+
+        let all: Vec<_> = parser.definitions().collect();
+        for (definition, source) in all.iter().zip(defined_in) {
+            let manifest = Manifest{
+                kind: definition.kind(),
+                source: source.to_string(),
+                gateways: definition.as_program().map(|prog| prog.gateway_names().iter().map(|gateway| gateway.to_string()).collect()).unwrap_or_default(),
+                exits: definition.as_program().map(|prog| prog.exit_names().iter().map(|exit| exit.to_string()).collect()).unwrap_or_default(),
+                connects: definition.as_program().map(|prog| prog.connections().iter().map(|connection| connection.program.clone()).collect()).unwrap_or_default()
+            };
+
+            match definition.generate(format, &all) {
+                Ok(code) => {
+                    dynamic_streams |= definition.as_program().is_some_and(|prog| prog.uses_dynamic_streams());
+                    let ir = Ir::new(std::iter::once(*definition)).to_json();
+                    definitions.insert(definition.name().to_string(), Artifact{code, ir, manifest});
+                },
+
+                Err(diagnostic) => diagnostics.errors.push(diagnostic)
+            }
+        }
+
+        if !diagnostics.errors.is_empty() {
+            return Err(diagnostics);
+        }
+
+        // VecStream is only carried along for programs with `dynamic` streams, which need an allocator
+        let mut runtime = Parser::new("runtime");
+        runtime.set_format(format);
+        let runtime = if dynamic_streams {
+            format!("{}\n{}", runtime.runtime(), runtime.alloc_runtime(false))
+        } else {
+            runtime.runtime()
+        };
+
+        Ok(Artifacts{runtime, definitions})
+    }
+}
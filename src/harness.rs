@@ -0,0 +1,210 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use convert_case::{Case, Casing};
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestCaseError, TestError, TestRunner};
+use quote::{format_ident, quote};
+use crate::repl::{build, compile, parse_moment, Entry, MAX_RUN_STEPS};
+use crate::spec::alphabet_chars;
+use crate::Parser;
+
+/// Durations fed to a gateway at most, and characters within each of them - gateways smaller than that fail to be fed,
+/// the same way on both builds
+const MAX_DURATIONS: usize = 4;
+const MAX_CHARS: usize = 3;
+
+/// How long a build may run on an input - some programs wait forever within a step (like jumps on an empty gateway)
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A duration fed to a gateway - the values of its characters, and the moment closing it
+pub type FedDuration = (Vec<u64>, u64);
+
+/// What each gateway is fed, in order - moments only ever grow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inputs {
+    pub gateways: Vec<(String, Vec<FedDuration>)>
+}
+
+/// Checks that a program behaves the same stepped one `run_step()` at a time, as the REPL steps it, and run with its
+/// own `run()` - both builds are fed random timed inputs, and everything pushed onto their exits is compared. Both
+/// run the Rust parserbin generates (there is no separate interpreter), so this catches divergence between the two
+/// ways generated programs are driven, not codegen bugs both share. Programs are built with rustc (`$RUSTC`, or the
+/// one on the path) once, then run once per input.
+pub struct Harness {
+    entry: Entry,
+
+    /// The values of the characters of each gateway's alphabet - inputs only ever hold those
+    values: Vec<(String, Vec<u64>)>,
+    dir: PathBuf,
+    stepped: PathBuf,
+    ran: PathBuf
+}
+
+impl Harness {
+    /// Builds `program` of the source - or the last program it defines that can run on its own
+    pub fn new(source: &str, program: Option<&str>) -> Result<Self, String> {
+        let entry = Entry::find(source, program)?;
+
+        let mut parser = Parser::new("harness");
+        parser.set_codegen(false);
+        parser.parse_str(source).map_err(|diagnostic| diagnostic.render(source))?;
+
+        let prog = parser.definitions().filter_map(|state| state.as_program()).find(|prog| prog.name() == entry.name).unwrap();
+        let values = prog.gateway_types().into_iter().map(|(gateway, alphabet, _)| {
+            let values = alphabet_chars(&parser, alphabet).iter().filter_map(|(_, value)| parse_moment(value)).collect();
+            (gateway.to_string(), values)
+        }).collect();
+
+        let dir = std::env::temp_dir().join(format!("time_lang_harness_{}_{}", std::process::id(), entry.name));
+        let stepped = dir.join(format!("stepped{}", std::env::consts::EXE_SUFFIX));
+        let ran = dir.join(format!("ran{}", std::env::consts::EXE_SUFFIX));
+
+        let harness = Self{entry, values, dir, stepped, ran};
+        for (binary, format, stepped) in [(&harness.stepped, false, true), (&harness.ran, true, false)] {
+            let mut code = compile(source, format).map_err(|diagnostic| diagnostic.render(source))?;
+            code.push_str(&harness.driver(stepped));
+            build(&harness.dir, binary, &code)?;
+        }
+
+        Ok(harness)
+    }
+
+    /// Random inputs for every gateway of the program
+    pub fn inputs(&self) -> impl Strategy<Value = Inputs> {
+        let gateways: Vec<_> = self.values.iter().map(|(gateway, values)| {
+            let chars = match values.is_empty() {
+                true => Just(vec![]).boxed(),
+                false => prop::collection::vec(prop::sample::select(values.clone()), 0..=MAX_CHARS).boxed()
+            };
+
+            let durations = prop::collection::vec((chars, 1..4u64), 0..=MAX_DURATIONS).prop_map(|durations| {
+                let mut moment = 0;
+                durations.into_iter().map(|(chars, elapsed)| {
+                    moment += elapsed;
+                    (chars, moment)
+                }).collect::<Vec<_>>()
+            });
+
+            (Just(gateway.clone()), durations)
+        }).collect();
+
+        gateways.prop_map(|gateways| Inputs{gateways})
+    }
+
+    /// Runs both builds on the inputs - failing the test case unless their exits hold the same
+    pub fn check(&self, inputs: &Inputs) -> Result<(), TestCaseError> {
+        let stepped = self.run(&self.stepped, inputs).map_err(TestCaseError::fail)?;
+        let ran = self.run(&self.ran, inputs).map_err(TestCaseError::fail)?;
+
+        prop_assert_eq!(stepped, ran, "stepping the program (left) and its run() (right) diverged");
+        Ok(())
+    }
+
+    /// Checks the program on as many random inputs as `config` asks for - failures are shrunk to the smallest inputs
+    /// that still make the builds diverge
+    pub fn run_cases(&self, config: Config) -> Result<(), TestError<Inputs>> {
+        TestRunner::new(config).run(&self.inputs(), |inputs| self.check(&inputs))
+    }
+
+    /// What a build printed for the inputs - every exit's items, or how running failed
+    fn run(&self, binary: &PathBuf, inputs: &Inputs) -> Result<String, String> {
+        let mut child = Command::new(binary).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().map_err(|err| {
+            format!("Could not run {}: {}", binary.display(), err)
+        })?;
+
+        let mut stdin = child.stdin.take().unwrap();
+        for (gateway, durations) in inputs.gateways.iter() {
+            for (chars, moment) in durations.iter() {
+                let chars: Vec<String> = chars.iter().map(|value| value.to_string()).collect();
+                writeln!(stdin, "{} {} @{}", gateway, chars.join(" "), moment).map_err(|err| err.to_string())?;
+            }
+        }
+
+        drop(stdin);
+        let started = Instant::now();
+        while child.try_wait().map_err(|err| err.to_string())?.is_none() {
+            if started.elapsed() > TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("{} did not finish within {:?}", binary.display(), TIMEOUT));
+            }
+
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        let output = child.wait_with_output().map_err(|err| err.to_string())?;
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// A `fn main()` feeding the program what stdin holds (`GATEWAY VALUE ... @MOMENT` lines), then running it - step by
+    /// step like the REPL does, or with its own `run()` - and printing its exits
+    fn driver(&self, stepped: bool) -> String {
+        let (struct_name, streams) = (self.entry.struct_name(), self.entry.streams());
+        let gateway_names = self.entry.gateways.iter();
+        let gateway_fields = self.entry.gateways.iter().map(|gateway| format_ident!("gateway_{}", gateway.to_case(Case::Snake)));
+        let print_exits = self.entry.print_exits();
+
+        // Programs without propagated errors run without a Result
+        let run = match stepped {
+            true => quote! {
+                prog.start().map_err(|err| format!("Failed: {:?}", err))?;
+                let mut steps = 0;
+                while steps < #MAX_RUN_STEPS && prog.run_step().map_err(|err| format!("Failed: {:?}", err))? {
+                    steps += 1;
+                }
+            },
+
+            false => quote! {
+                trait Ran { fn ran(self) -> Result<(), String>; }
+                impl Ran for () { fn ran(self) -> Result<(), String> { Ok(()) } }
+                impl<E: std::fmt::Debug> Ran for Result<(), E> {
+                    fn ran(self) -> Result<(), String> { self.map_err(|err| format!("Failed: {:?}", err)) }
+                }
+
+                prog.run().ran()?;
+            }
+        };
+
+        quote! {
+            fn feed_and_run(prog: &mut #struct_name) -> Result<(), String> {
+                for line in std::io::stdin().lines() {
+                    let line = line.map_err(|err| err.to_string())?;
+                    let mut words = line.split_whitespace();
+                    let gateway = words.next().unwrap_or_default();
+                    let words: Vec<&str> = words.collect();
+                    let (moment, values) = words.split_last().ok_or_else(|| format!("Nothing fed to {}", gateway))?;
+                    let moment = moment.trim_start_matches('@');
+
+                    match gateway {
+                        #(#gateway_names => {
+                            let values: Vec<_> = values.iter().map(|value| value.parse().unwrap()).collect();
+                            #streams.#gateway_fields.push_vals(&values).map_err(|err| format!("Feeding {} failed: {:?}", gateway, err))?;
+                            #streams.#gateway_fields.push_moment(moment.parse().unwrap()).map_err(|err| format!("Feeding {} failed: {:?}", gateway, err))?;
+                        },)*
+                        _ => return Err(format!("No gateway {}", gateway))
+                    }
+                }
+
+                #run
+                Ok(())
+            }
+
+            fn main() {
+                let mut prog = #struct_name::new();
+                if let Err(err) = feed_and_run(&mut prog) {
+                    println!("{}", err);
+                }
+
+                #print_exits
+            }
+        }.to_string()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
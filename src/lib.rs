@@ -1,2 +1,18 @@
+mod compiler;
+#[cfg(feature = "proptest")]
+mod harness;
+mod lsp;
+mod model;
 mod parser;
-pub use parser::*;
\ No newline at end of file
+mod repl;
+mod spec;
+mod timeline;
+pub use compiler::*;
+#[cfg(feature = "proptest")]
+pub use harness::*;
+pub use lsp::*;
+pub use model::*;
+pub use parser::*;
+pub use repl::*;
+pub use spec::*;
+pub use timeline::*;
\ No newline at end of file
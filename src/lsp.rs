@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use serde_json::{json, Value};
+use crate::{tokenize, Diagnostic, Index, Parser, SemanticTokenKind, Severity, Symbol, SymbolKind};
+
+/// JSON-RPC's error code for requests of a method the server doesn't know
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// The kind of completion items naming characters - they are the members of their alphabet's enum
+const ENUM_MEMBER: u64 = 20;
+
+/// An open document - its text as of the latest change, and the names it defines and uses
+struct Document {
+    text: String,
+    index: Index
+}
+
+/// A language server for `.tl` sources, speaking the Language Server Protocol - diagnostics are published whenever
+/// a document is opened or saved, besides going to definitions, hovering names and completing characters of
+/// `push_char` and highlighting. Documents are synced whole, on every change.
+#[derive(Default)]
+pub struct LanguageServer {
+    documents: BTreeMap<String, Document>
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves messages from `input` until the client asks the server to exit
+    pub fn serve(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        while let Some(message) = read_message(&mut input)? {
+            let method = message["method"].as_str().unwrap_or_default();
+            if method == "exit" {
+                break;
+            }
+
+            let (result, notifications) = self.handle(method, &message["params"]);
+
+            // Notifications get no response
+            if let Some(id) = message.get("id") {
+                let response = match result {
+                    Some(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                    None => json!({"jsonrpc": "2.0", "id": id, "error": {"code": METHOD_NOT_FOUND, "message": format!("Unknown method: {}", method)}})
+                };
+
+                write_message(&mut output, &response)?;
+            }
+
+            for notification in notifications {
+                write_message(&mut output, &notification)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a message - returns the result of a request (None, for methods the server doesn't know), and the
+    /// notifications to send the client
+    fn handle(&mut self, method: &str, params: &Value) -> (Option<Value>, Vec<Value>) {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+        let position = &params["position"];
+        let (line, col) = (position["line"].as_u64().unwrap_or(0) as usize + 1, position["character"].as_u64().unwrap_or(0) as usize + 1);
+
+        match method {
+            "initialize" => (Some(json!({
+                "capabilities": {
+                    "textDocumentSync": {"openClose": true, "change": 1, "save": {"includeText": true}},
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "semanticTokensProvider": {
+                        "legend": {"tokenTypes": SemanticTokenKind::ALL.map(|kind| kind.lsp_type()), "tokenModifiers": []},
+                        "full": true
+                    }
+                },
+                "serverInfo": {"name": "time_lang", "version": env!("CARGO_PKG_VERSION")}
+            })), vec![]),
+
+            "shutdown" => (Some(Value::Null), vec![]),
+
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                let notification = self.update(&uri, text.to_string(), true);
+                (None, notification.into_iter().collect())
+            },
+
+            "textDocument/didChange" => {
+                if let Some(text) = params["contentChanges"].as_array().and_then(|changes| changes.last()).and_then(|change| change["text"].as_str()) {
+                    self.update(&uri, text.to_string(), false);
+                }
+
+                (None, vec![])
+            },
+
+            "textDocument/didSave" => {
+                let text = params["text"].as_str().map(|text| text.to_string()).or_else(|| self.documents.get(&uri).map(|document| document.text.clone()));
+                let notification = text.and_then(|text| self.update(&uri, text, true));
+                (None, notification.into_iter().collect())
+            },
+
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                (None, vec![publish_diagnostics(&uri, vec![])])
+            },
+
+            "textDocument/definition" => {
+                let symbol = self.documents.get(&uri).and_then(|document| document.index.symbol_at(line, col));
+                (Some(symbol.map_or(Value::Null, |symbol| json!({"uri": uri, "range": symbol_range(symbol)}))), vec![])
+            },
+
+            "textDocument/hover" => {
+                let document = self.documents.get(&uri);
+                let hover = document.and_then(|document| document.index.symbol_at(line, col).map(|symbol| hover(&document.index, symbol)));
+                (Some(hover.map_or(Value::Null, |hover| json!({"contents": {"kind": "markdown", "value": hover}}))), vec![])
+            },
+
+            "textDocument/completion" => {
+                let items = self.documents.get(&uri).map_or(vec![], |document| complete(document, line, col));
+                (Some(json!(items)), vec![])
+            },
+
+            "textDocument/semanticTokens/full" => {
+                let data = self.documents.get(&uri).map_or(vec![], |document| semantic_tokens(&document.text));
+                (Some(json!({"data": data})), vec![])
+            },
+
+            // Other notifications (like initialized) need no handling - other requests get an error
+            _ => (None, vec![])
+        }
+    }
+
+    /// Re-indexes a document - and returns its diagnostics to publish, if asked to
+    fn update(&mut self, uri: &str, text: String, diagnose: bool) -> Option<Value> {
+        let filename = uri.strip_prefix("file://").unwrap_or(uri);
+        let mut parser = Parser::new(filename);
+        parser.set_codegen(false);
+
+        let diagnostics = match parser.parse_str(&text) {
+            Ok(()) => {
+                let mut diagnostics = parser.check();
+                diagnostics.extend(parser.lint());
+                diagnostics
+            },
+
+            Err(diagnostic) => vec![diagnostic]
+        };
+
+        let diagnostics: Vec<Value> = diagnostics.iter().map(|diagnostic| lsp_diagnostic(&text, diagnostic)).collect();
+        self.documents.insert(uri.to_string(), Document{text, index: parser.into_index()});
+
+        diagnose.then(|| publish_diagnostics(uri, diagnostics))
+    }
+}
+
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    let mut header = String::new();
+
+    loop {
+        header.clear();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        match header.trim_end().split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("Content-Length") => length = value.trim().parse::<usize>().ok(),
+            Some(_) => (),
+            None if header.trim_end().is_empty() => break,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid header: {}", header.trim_end())))
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Message without a Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body).map(Some).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({"jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": {"uri": uri, "diagnostics": diagnostics}})
+}
+
+/// Positions of the protocol count lines and characters from 0 - spans count them from 1
+fn position(line: usize, col: usize) -> Value {
+    json!({"line": line.saturating_sub(1), "character": col.saturating_sub(1)})
+}
+
+fn symbol_range(symbol: &Symbol) -> Value {
+    json!({"start": position(symbol.span.line, symbol.span.col), "end": position(symbol.span.line, symbol.span.col + symbol.name.len())})
+}
+
+/// Diagnostics underline the name they point at - diagnostics about the whole source point at its start
+fn lsp_diagnostic(text: &str, diagnostic: &Diagnostic) -> Value {
+    let (line, col) = diagnostic.span.as_ref().map_or((1, 1), |span| (span.line, span.col));
+    let width = text.lines().nth(line.saturating_sub(1)).map_or(0, |line| {
+        line.chars().skip(col.saturating_sub(1)).take_while(|chr| chr.is_ascii_alphanumeric() || *chr == '_' || *chr == '$').count()
+    });
+
+    let message = match diagnostic.note.as_ref() {
+        Some(note) => format!("{}\nnote: {}", diagnostic.message, note),
+        None => diagnostic.message.clone()
+    };
+
+    json!({
+        "range": {"start": position(line, col), "end": position(line, col + width.max(1))},
+        "severity": if diagnostic.severity == Severity::Error { 1 } else { 2 },
+        "code": diagnostic.lint.map(|lint| lint.as_str()),
+        "source": "time_lang",
+        "message": message
+    })
+}
+
+/// The statements defining a symbol, then its documentation - alphabets also tell how many characters they have
+fn hover(index: &Index, symbol: &Symbol) -> String {
+    let mut detail = symbol.detail.clone();
+    if symbol.kind == SymbolKind::Alphabet {
+        detail.push(format!("{} characters", index.characters(Some(&symbol.name)).count()));
+    }
+
+    let mut hover = format!("```\n{}\n```", detail.join("\n"));
+    if !symbol.doc.is_empty() {
+        hover.push_str(&format!("\n\n{}", symbol.doc.join("\n")));
+    }
+
+    hover
+}
+
+/// Characters for the first argument of `push_char` - of the alphabet of the exit named after it, once that's typed
+fn complete(document: &Document, line: usize, col: usize) -> Vec<Value> {
+    let Some(text) = document.text.lines().nth(line - 1) else {
+        return vec![];
+    };
+
+    let before = &text[..text.len().min(col - 1)];
+    let Some(arg) = before.trim_start().strip_prefix("push_char").filter(|arg| arg.starts_with([' ', '\t']) && !arg.contains(',')) else {
+        return vec![];
+    };
+
+    let program = document.index.definition_at(line).filter(|definition| definition.kind == SymbolKind::Program).map(|program| program.name.as_str());
+    let exit = text[before.len()..].split_once(',').map(|(_, exit)| exit.split(';').next().unwrap_or(exit).trim());
+    let alphabet = exit.and_then(|exit| document.index.stream(program, exit)).and_then(|exit| exit.target.as_deref());
+
+    let typed = arg.trim_start();
+    document.index.characters(alphabet).filter(|chr| chr.name.starts_with(typed)).map(|chr| json!({
+        "label": chr.name,
+        "kind": ENUM_MEMBER,
+        "detail": match (chr.target.as_ref(), chr.scope.as_ref()) {
+            (Some(value), Some(alphabet)) => format!("{} ({})", value, alphabet),
+            _ => chr.detail[0].clone()
+        },
+        "documentation": chr.doc.join("\n")
+    })).collect()
+}
+
+/// Tokens of the whole document, encoded as the protocol expects - each relative to the one before it
+fn semantic_tokens(text: &str) -> Vec<usize> {
+    let mut data = vec![];
+    let (mut line, mut col) = (1, 1);
+
+    for token in tokenize(text) {
+        let delta_col = if token.line == line { token.col - col } else { token.col - 1 };
+        let kind = SemanticTokenKind::ALL.iter().position(|kind| *kind == token.kind).unwrap_or_default();
+        data.extend([token.line - line, delta_col, token.len, kind, 0]);
+        (line, col) = (token.line, token.col);
+    }
+
+    data
+}
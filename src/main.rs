@@ -1,5 +1,3 @@
-use std::io::{prelude::*, BufReader};
-
 static PROGRAM: &str = r#"
 # --- Overview ---
 # This is an Assembly Language for a Virtual Machine, that deals with *streams* of *characters* and contextual *time markers*.
@@ -8,14 +6,35 @@ static PROGRAM: &str = r#"
 # *Streams* are (potentially infinite) sources of information that correspond to a given *Alphabet* and *Clock* - They are stateful and once a character or time marker is read, it is forever removed from the stream.
 
 # This script first defines an Alphabet, a Clock, then a set of programs.
+# Statements end with a ; - until then, they may wrap onto as many lines as they need.
 # ---
 
+# Constants (defconst NAME,VALUE;) are defined before everything else - anywhere a number or moment is expected, they can be used in
+# integer expressions (BUF*2, 2*(TICK+1), ...)
+defconst BUF,0x50;
+
+# The generated code also gets a fn main() that runs this program and writes its exits to stdout (or use --bin=NAME)
+set_entry hello_world;
+
+# Macros are expanded before anything else sees their statements - $PARAMs are replaced by the invocation's arguments, and
+# labels defined inside get renamed per expansion. Bodies may also span several lines, closed by a lone }
+defmacro end_with(VAL, EXIT) {
+push_val            $VAL,$EXIT;
+push_moment         1,$EXIT;
+}
+
 defalphabet ASCII;
 
 # Defines the maximum number of bits a 'character' (atom of data) might take up
 set_char_type   u8;
 
-# Defines the 'characters' that can move through a stream
+# Derives for the generated character enum, on top of Copy, Clone and Debug - set_visibility (pub by default) works
+# the same way on alphabets, clocks and programs
+set_derives     PartialEq|Eq|Hash;
+
+# Defines the 'characters' that can move through a stream - a comment block right above one (or above a label, or
+# right below a def... header) becomes the documentation of the generated code
+
 def_char            0x0,NULL_BYTE;
 def_char            0x1,START_OF_HEADING;
 def_char            0x2,START_OF_TEXT;
@@ -145,25 +164,40 @@ def_char            0x7D,CLOSE_CURLY_BRACKET;
 def_char            0x7E,TILDE;
 def_char            0x7F,DELETE;
 
+# Defines named 'classes' of characters, which can be referenced by class-aware instructions
+def_class           WHITESPACE,SPACE|TAB|LINE_FEED|CARRIAGE_RETURN;
+
 defclock CounterClock;
 
 # Defines the maximum number of bits that a moment of time might take up
 set_moment_type      u32;
 
 # Defines what kind of thing the clock represents, could also be:
-    UNIX_TIMESTAMP
-    NATURAL_MILLISECONDS
-    NATURAL_SECONDS
-    NATURAL_MINUTES
-    NATURAL_HOURS
-    ...
+#   UNIX_TIMESTAMP
+#   NATURAL_MILLISECONDS
+#   NATURAL_SECONDS
+#   NATURAL_MINUTES
+#   NATURAL_HOURS
+#   ...
 set_clock_repr      QUANTITY;
 
 
 # --- Programs ---
+# Programs may be generic over alphabets and clocks (defprogram NAME<A: alphabet, C: clock>;) - streams then use $A and $C in their place
 # Quick explanation of functions:
+# expect_aligned    EXIT,EXIT                   - Expects every moment on the first EXIT to also be present on the second - checked by check_expectations()
+# set_priority      PRIORITY                    - Scheduling priority of the program (defaults to 0) - higher priorities are stepped first
+# set_fuel          FUEL                        - Units of work (labels, waits on empty gateways) a run of the label chain may take before failing with OutOfFuel
+# set_visibility    VISIBILITY                  - Visibility of the generated program struct - pub (the default), pub(crate), pub(super), ...
+# set_error_mode    MODE                        - What label functions do on errors - propagate (return a Result, the default), panic, or abort_program (record it in error() and stop)
+# set_on_full       POLICY                      - What forward_duration does when its exit has no room for the duration - error (the default), yield (wait for room before stepping), or drop_duration
 # reg_gateway       NAME,ALPHABET,CLOCK,BUF     - Register an input stream (Input of program) with BUF buffer size
 # reg_exit          NAME,ALPHABET,CLOCK,BUF     - Register an exit stream (Output of program) with BUF buffer size
+#                                               BUF may also be dynamic (or dynamic(CAP)) - a heap buffer growing as needed (up to CAP items), which needs the runtime's alloc feature
+#                                               Streams take an optional overflow policy last (NAME,ALPHABET,CLOCK,BUF,overwrite) - reject (the default) fails pushes onto a full buffer, overwrite evicts its oldest duration
+#                                               Streams of the entry program may also read from stdin (a single gateway, ...,from=stdin) or write to stdout (exits, ...,to=stdout) as it runs - which needs the runtime's std feature
+#                                               Durations read from stdin end with each line, unless set with moments=read (each read) or moments=N (every N bytes)
+# reg_error_exit    NAME,ALPHABET,CLOCK,BUF     - Register an exit receiving (instead of failing on) raw values popped off of gateways that are not characters of their alphabet
 # start_moment      INITIAL_MOMENT,EXIT         - Defines the "initial" moment that your exit clock will start at
 # push_char         CHAR,EXIT                   - Push a character onto the exit stream - can either directly be a character from the related alphabet or a hexadecimal representation of bits.
 # push_moment       INCREMENT_BY,EXIT           - Push a time marker onto the exit stream, representing INCREMENTED_BY moments passing
@@ -171,15 +205,33 @@ set_clock_repr      QUANTITY;
 # label             LABEL;                      - A nice label to make it easier to define jumps
 # jump_earlier      LABEL,GATEWAY,GATEWAY       - Jumps to a given label, if A is earlier than B - Can only jump *forward* in the program
 # jump_later        LABEL,GATEWAY,GATEWAY       - Jumps to a given label, if A is later than B - Can only jump *forward* in the program
+# jump_if_class     LABEL,GATEWAY,CLASS         - Jumps to a given label, if the next item in GATEWAY is a character within CLASS
+# jump_overflowed   LABEL,EXIT                  - Jumps to a given label, if EXIT rejected a push (due to being full) since the last jump_overflowed on it
 # forward_duration  GATEWAY,EXIT                - Pops characters off of GATEWAY until it hits the next duration, while PUSHing each of those characters to EXIT
+# filter            GATEWAY,EXIT,CLASS          - Like forward_duration, but drops characters within CLASS (or equal to a hexadecimal value)
+# filter_class      GATEWAY,EXIT,CLASS          - Like filter, but CLASS can only be a class
+# filter_keep       GATEWAY,EXIT,CLASS          - Like forward_duration, but only keeps characters within CLASS (or equal to a hexadecimal value)
+# tee               GATEWAY,EXIT,EXIT           - Like forward_duration, but PUSHes every character and the closing moment to both EXITs - stops early (leaving items in GATEWAY) while either EXIT is full
+# drop_duration     GATEWAY                     - Pops characters off of GATEWAY until (and including) the next moment, without PUSHing them anywhere
+# skip_until_moment GATEWAY,MOMENT              - Discards items of GATEWAY until its current moment reaches MOMENT - either a literal, or Time(STREAM)
+# sync              GATEWAY|...,EXIT|...        - Pushes the earliest pending moment of all GATEWAYs to every EXIT - GATEWAYs whose duration ends there also forward its characters to their EXIT
+# zip               GATEWAY|...,EXIT            - Forwards the characters of every GATEWAY whose duration ends at the earliest pending moment (in declaration order), then that moment, to EXIT
+# split             GATEWAY,EXIT|...,STRATEGY   - Routes each duration of GATEWAY to one EXIT - STRATEGY is round_robin, by_moment_parity (moment modulo the number of EXITs) or by_class(CLASS) (per character: in CLASS to the first EXIT, the rest to the second)
+# window            GATEWAY,EXIT,K              - Forwards K consecutive durations of GATEWAY to EXIT as a single duration, closed by the K-th moment
+# count_chars       GATEWAY,EXIT                - Pops a duration off of GATEWAY, PUSHing the number of its characters (as decimal digits) and its moment to EXIT
+# min_moment        GATEWAY,EXIT                - Pops a duration off of GATEWAY, PUSHing the moment it started at (as decimal digits) and its moment to EXIT
+# max_moment        GATEWAY,EXIT                - Pops a duration off of GATEWAY, PUSHing the moment it ended at (as decimal digits) and its moment to EXIT
+# delay             GATEWAY,EXIT,N              - Like forward_duration, but the moment PUSHed to EXIT is N moments later - needs an addable (integer) clock
+# throttle          GATEWAY,EXIT,N              - Forwards a duration, then drops durations from GATEWAY until at least N moments have elapsed on its clock
 # connect           PROGRAM(GATEWAY...),NAME    - Forwards GATEWAYs to PROGRAM. Exits of the program can be pulled from NAME
 # reg_exit_gateway  NAME(EXIT),NAME             - Registers a new Gateway, from the Exit of the connected program
+#                                               (programs that connect to others also get a PipelineNAME struct, which runs them together)
 
 defprogram hello_world;
 # Outputs "Hello, World!" in ASCII, within a single moment of time
 
 # Exits: Output stream for the program
-reg_exit            A,ASCII,CounterClock,0x50;
+reg_exit            A,ASCII,CounterClock,BUF;
 
 # All streams have clocks. What moment does this one start at?
 start_moment        0,A;
@@ -198,10 +250,10 @@ push_char           O_LOWERCASE,A;
 push_char           R_LOWERCASE,A;
 push_char           L_LOWERCASE,A;
 push_char           D_LOWERCASE,A;
-push_val            0x21,A;
-push_moment         1,A;
+end_with            0x21,A;                   # Trailing comments are fine too - this pushes "!" and ends the moment
 
-defprogram sync2;
+defprogram sync2<A: alphabet,
+                 C: clock>;
 # Ensures that two streams are in sync with each other, so that no time duration is missed.
 
 # Example:
@@ -210,10 +262,10 @@ defprogram sync2;
 #  Exit C:    |1 |2 B |3 D |4 |5
 #  Exit D:    |1 A |2 B |3 D |4 |5 E
 
-reg_gateway         A,ASCII,CounterClock,0x50;
-reg_gateway         B,ASCII,CounterClock,0x50;
-reg_exit            C,ASCII,CounterClock,0x50;
-reg_exit            D,ASCII,CounterClock,0x50;
+reg_gateway         A,$A,$C,BUF;
+reg_gateway         B,$A,$C,BUF;
+reg_exit            C,$A,$C,BUF;
+reg_exit            D,$A,$C,BUF;
 
 label main;
 jump_earlier        a_earlier,A,B;
@@ -233,7 +285,7 @@ forward_moment      B,C;
 forward_duration    B,D;
 forward_moment      B,D;
 
-defprogram zip2;
+defprogram zip2<A: alphabet, C: clock>;
 # Interleaves two streams of data - if both occurred in the same moment, the first stream's data comes first.
 
 # Example:
@@ -241,9 +293,9 @@ defprogram zip2;
 # Gateway B:    1| B 3| D
 # Exit C:       1| AB 3| CD 4| E
 
-reg_gateway         A,ASCII,CounterClock,0x50;
-reg_gateway         B,ASCII,CounterClock,0x50;
-reg_exit            E,ASCII,CounterClock,0x50;
+reg_gateway         A,$A,$C,BUF;
+reg_gateway         B,$A,$C,BUF;
+reg_exit            E,$A,$C,BUF;
 
 connect             sync2(A|B),SYNCED;
 reg_exit_gateway    SYNCED(C),C;
@@ -255,26 +307,439 @@ forward_duration    D,E;
 push_moment         Time(C),E;
 "#;
 
-mod parser;
-use parser::Parser;
+use std::io::Write;
+use parser::{format_source, textmate_grammar, tokenize, Codegen, Diagnostic, Ir, LanguageServer, Lint, Model, Parser, Repl, Reply, Severity, Spec, Timeline};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    Human,
+    Json
+}
+
+/// Prints the diagnostic - rendered against the source it points into
+fn print_diagnostic(diagnostic: &Diagnostic, source: &str, error_format: ErrorFormat) {
+    match error_format {
+        ErrorFormat::Human => eprint!("{}", diagnostic.render(source)),
+        ErrorFormat::Json => eprintln!("{}", diagnostic.to_json())
+    }
+}
+
+fn report(diagnostic: &Diagnostic, source: &str, error_format: ErrorFormat) -> ! {
+    print_diagnostic(diagnostic, source, error_format);
+    std::process::exit(1);
+}
+
+/// Parses a source with a configured parser - reporting the first error, and exiting on it
+fn parse<'a>(mut parser: Parser<'a>, source: &str, error_format: ErrorFormat) -> Parser<'a> {
+    if let Err(diagnostic) = parser.parse_str(source) {
+        report(&diagnostic, source, error_format);
+    }
+
+    parser
+}
+
+/// Reads lines from stdin into a session - starting from the file's definitions, if one is given
+fn repl(filename: Option<&str>) {
+    let mut repl = Repl::new();
+    if let Some(filename) = filename {
+        let source = std::fs::read_to_string(filename).unwrap_or_else(|err| panic!("Could not read {}: {}", filename, err));
+        if let Err(err) = repl.define(&source) {
+            eprint!("{}", err);
+            std::process::exit(1);
+        }
+    }
+
+    println!("time_lang repl - :help lists the commands");
+    let mut line = String::new();
+    loop {
+        print!("> ");
+        std::io::stdout().flush().expect("Could not write to stdout");
+
+        line.clear();
+        if std::io::stdin().read_line(&mut line).expect("Could not read from stdin") == 0 {
+            break;
+        }
+
+        match repl.eval(line.trim_end_matches(['\r', '\n'])) {
+            Ok(Reply::Output(output)) if output.is_empty() => (),
+            Ok(Reply::Output(output)) => println!("{}", output.trim_end()),
+            Ok(Reply::Quit) => break,
+            Err(err) => eprintln!("{}", err.trim_end())
+        }
+    }
+}
 
 fn main() {
-    let mut parser = Parser::new("program");
-    let reader = BufReader::new(PROGRAM.as_bytes());
+    let mut command = None;
+    let mut filename = None;
+    let mut only_cycles = false;
+    let mut allow_cycles = false;
+    let mut error_format = ErrorFormat::Human;
+    let mut emit = "rust";
+    let mut format = true;
+    let mut inline_runtime = false;
+    let mut trace = false;
+    let mut assert_monotonic = false;
+    let mut optimize = false;
+    let mut inline_connect = false;
+    let mut check_format = false;
+    let mut deny = vec![];
+    let mut strict = false;
+    let mut out_dir = None;
+    let mut entry = None;
+
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "graph" if command.is_none() && filename.is_none() => command = Some("graph"),
+            "check" if command.is_none() && filename.is_none() => command = Some("check"),
+            "runtime" if command.is_none() && filename.is_none() => command = Some("runtime"),
+            "from-ir" if command.is_none() && filename.is_none() => command = Some("from-ir"),
+            "repl" if command.is_none() && filename.is_none() => command = Some("repl"),
+            "fmt" if command.is_none() && filename.is_none() => command = Some("fmt"),
+            "lint" if command.is_none() && filename.is_none() => command = Some("lint"),
+            "lsp" if command.is_none() && filename.is_none() => command = Some("lsp"),
+            "grammar" if command.is_none() && filename.is_none() => command = Some("grammar"),
+            "timeline" if command.is_none() && filename.is_none() => command = Some("timeline"),
+            "test" if command.is_none() && filename.is_none() => command = Some("test"),
+            "verify" if command.is_none() && filename.is_none() => command = Some("verify"),
+            "chars" if command.is_none() && filename.is_none() => command = Some("chars"),
+            "--deny" => match args.next() {
+                Some(lint) if lint == "warnings" || Lint::ALL.iter().any(|known| known.as_str() == lint) => deny.push(lint),
+                Some(lint) => panic!("Unknown lint: {}", lint),
+                None => panic!("--deny needs a lint name, or warnings")
+            },
+            "--check" => check_format = true,
+            "--strict" => strict = true,
+            "--cycles" => only_cycles = true,
+            "--allow-cycles" => allow_cycles = true,
+            "--error-format=human" => error_format = ErrorFormat::Human,
+            "--error-format=json" => error_format = ErrorFormat::Json,
+            "--no-format" => format = false,
+            "--inline-runtime" => inline_runtime = true,
+            "--trace" => trace = true,
+            "--assert-monotonic" => assert_monotonic = true,
+            // -O inline-connect also splices connected programs into the programs connecting to them
+            "-O" | "--optimize" => {
+                optimize = true;
+                inline_connect |= args.next_if(|pass| pass == "inline-connect").is_some();
+            },
+            "--emit=rust" => emit = "rust",
+            "--emit=ast" => emit = "ast",
+            "--emit=ir" => emit = "ir",
+            "--emit=dot" => emit = "dot",
+            "--emit=tokens" => emit = "tokens",
+            "--emit=svg" => emit = "svg",
+            "--emit=html" => emit = "html",
+            flag if flag.starts_with("--bin=") => entry = Some(flag["--bin=".len()..].to_string()),
+            flag if flag.starts_with("--out-dir=") => out_dir = Some(flag["--out-dir=".len()..].to_string()),
+            flag if flag.starts_with("--") => panic!("Unknown flag: {}", flag),
+            file => filename = Some(file.to_string())
+        }
+    }
+
+    // The source of the time_lang_runtime crate - what --inline-runtime puts at the top of generated code
+    if command == Some("runtime") {
+        let mut parser = Parser::new("runtime");
+        parser.set_format(format);
+        println!("{}\n{}\n{}", parser.runtime(), parser.alloc_runtime(true), parser.std_runtime());
+        return;
+    }
+
+    // Speaks the Language Server Protocol over stdin and stdout, for editors
+    if command == Some("lsp") {
+        LanguageServer::new().serve(std::io::stdin().lock(), std::io::stdout().lock()).expect("Could not talk to the client");
+        return;
+    }
+
+    // A TextMate grammar of .tl sources, for editors without a language server
+    if command == Some("grammar") {
+        println!("{}", textmate_grammar());
+        return;
+    }
+
+    // Renders a trace (written by a program generated with --trace) as an SVG image, or an HTML page with
+    // --emit=html - the trace is read from stdin without a file
+    if command == Some("timeline") {
+        let trace = match filename.as_ref() {
+            Some(filename) => std::fs::read_to_string(filename),
+            None => std::io::read_to_string(std::io::stdin())
+        }.unwrap_or_else(|err| panic!("Could not read the trace: {}", err));
+
+        let timeline = Timeline::from_trace(filename.as_deref().unwrap_or("stdin"), &trace).unwrap_or_else(|diagnostic| {
+            report(&diagnostic, &trace, error_format)
+        });
+
+        match emit {
+            "html" => print!("{}", timeline.to_html()),
+            _ => print!("{}", timeline.to_svg())
+        }
+
+        return;
+    }
+
+    if command == Some("repl") {
+        repl(filename.as_deref());
+        return;
+    }
+
+    let source = match filename.as_ref() {
+        Some(filename) => std::fs::read_to_string(filename).unwrap_or_else(|err| {
+            panic!("Could not read {}: {}", filename, err);
+        }),
+        None => PROGRAM.to_string()
+    };
+
+    // Files are formatted in place - with --check, unformatted files are only reported. The sample program is printed.
+    if command == Some("fmt") {
+        let formatted = format_source(filename.as_deref().unwrap_or("program"), &source).unwrap_or_else(|diagnostic| {
+            report(&diagnostic, &source, error_format)
+        });
+
+        match filename.as_ref() {
+            Some(filename) if check_format && formatted != source => {
+                eprintln!("{} is not formatted", filename);
+                std::process::exit(1);
+            },
+
+            Some(filename) if !check_format && formatted != source => std::fs::write(filename, formatted).unwrap_or_else(|err| {
+                panic!("Could not write {}: {}", filename, err);
+            }),
+
+            Some(_) => (),
+            None => print!("{}", formatted)
+        }
+
+        return;
+    }
+
+    // Highlighting only needs the lexer - it works on sources the parser rejects, as JSON lines
+    if command.is_none() && emit == "tokens" {
+        for token in tokenize(&source) {
+            println!("{}", token.to_json());
+        }
+
+        return;
+    }
+
+    // Runs the tests of NAME.spec, next to the source - like cargo test, failures are listed after every test ran
+    if command == Some("test") {
+        let filename = filename.as_deref().unwrap_or_else(|| panic!("test needs the source to test"));
+        let spec_filename = std::path::Path::new(filename).with_extension("spec").display().to_string();
+        let spec_text = std::fs::read_to_string(&spec_filename).unwrap_or_else(|err| {
+            panic!("Could not read {}: {}", spec_filename, err);
+        });
+
+        let spec = Spec::parse(&spec_filename, &spec_text).unwrap_or_else(|diagnostic| report(&diagnostic, &spec_text, error_format));
+        println!("running {} tests", spec.cases().len());
+
+        let results = spec.run(filename, &source).unwrap_or_else(|diagnostic| report(&diagnostic, &source, error_format));
+        for result in results.iter() {
+            println!("test {} ... {}", result.name, if result.failure.is_some() { "FAILED" } else { "ok" });
+        }
 
-    for line in reader.lines() {
-        if line.is_ok() {
-            parser.parse_line(line.unwrap());
+        let failures: Vec<_> = results.iter().filter_map(|result| result.failure.as_ref().map(|failure| (result, failure))).collect();
+        if !failures.is_empty() {
+            println!("\nfailures:");
+            for (result, failure) in failures.iter() {
+                println!("\n---- {} ({}) ----\n{}", result.name, result.span, failure);
+            }
         }
+
+        let status = if failures.is_empty() { "ok" } else { "FAILED" };
+        println!("\ntest result: {}. {} passed; {} failed", status, results.len() - failures.len(), failures.len());
+        if !failures.is_empty() {
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    // Model-checks the source against the checks of NAME.model, next to it - exploring every schedule within each
+    // check's bounds, and reporting the first breaking one of its invariants
+    if command == Some("verify") {
+        let filename = filename.as_deref().unwrap_or_else(|| panic!("verify needs the source to check"));
+        let model_filename = std::path::Path::new(filename).with_extension("model").display().to_string();
+        let model_text = std::fs::read_to_string(&model_filename).unwrap_or_else(|err| {
+            panic!("Could not read {}: {}", model_filename, err);
+        });
+
+        let model = Model::parse(&model_filename, &model_text).unwrap_or_else(|diagnostic| report(&diagnostic, &model_text, error_format));
+        println!("running {} checks", model.checks().len());
+
+        let results = model.run(filename, &source).unwrap_or_else(|diagnostic| match diagnostic.source == model_filename {
+            true => report(&diagnostic, &model_text, error_format),
+            false => report(&diagnostic, &source, error_format)
+        });
+
+        for result in results.iter() {
+            println!("check {} ... {} ({} schedules)", result.name, if result.violation.is_some() { "FAILED" } else { "ok" }, result.schedules);
+        }
+
+        let failures: Vec<_> = results.iter().filter_map(|result| result.violation.as_ref().map(|violation| (result, violation))).collect();
+        if !failures.is_empty() {
+            println!("\nfailures:");
+            for (result, violation) in failures.iter() {
+                println!("\n---- {} ({}) ----\n{}", result.name, result.span, violation);
+            }
+        }
+
+        let status = if failures.is_empty() { "ok" } else { "FAILED" };
+        println!("\ncheck result: {}. {} passed; {} failed", status, results.len() - failures.len(), failures.len());
+        if !failures.is_empty() {
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    // IR from another frontend skips the parser entirely
+    if command == Some("from-ir") {
+        let generated = Ir::from_json(&source).and_then(|ir| Codegen::from_ir(&ir)).and_then(|mut codegen| {
+            codegen.set_format(format);
+            codegen.set_inline_runtime(inline_runtime);
+            codegen.set_trace(trace);
+            codegen.set_assert_monotonic(assert_monotonic);
+            codegen.set_optimize(optimize);
+            codegen.set_inline_connect(inline_connect);
+            codegen.set_entry(entry.clone());
+            codegen.generate()
+        });
+        match generated {
+            Ok(code) => println!("{}", code),
+            // Spans point into the IR's structure rather than its text, so there's no source line to show
+            Err(diagnostic) => report(&diagnostic, "", error_format)
+        }
+
+        return;
     }
 
-    match parser.generate() {
-        Ok(source) => {
-            println!("{}", source);
+    // Only emitting Rust needs the code generator
+    let codegen = !matches!(command, Some("check" | "lint" | "chars")) && emit == "rust" && out_dir.is_none();
+    let mut parser = Parser::new(filename.as_deref().unwrap_or("program"));
+    parser.set_codegen(codegen);
+    parser.set_format(format);
+    parser.set_trace(trace);
+    parser.set_assert_monotonic(assert_monotonic);
+    parser.set_optimize(optimize);
+    parser.set_inline_connect(inline_connect);
+
+    let mut parser = parse(parser, &source, error_format);
+    parser.set_allow_cycles(allow_cycles);
+    parser.set_inline_runtime(inline_runtime);
+    if let Some(entry) = entry.as_ref() {
+        parser.set_entry(entry);
+    }
+
+    for warning in parser.warnings() {
+        eprintln!("warning: {}", warning);
+    }
+
+    match command {
+        // --strict also fails on exits that may overflow their buffers
+        Some("check") => {
+            let mut diagnostics = parser.check();
+            if strict {
+                diagnostics.extend(parser.lint().into_iter().filter(|diagnostic| diagnostic.lint == Some(Lint::BufferOverflow)).map(Diagnostic::deny));
+            }
+
+            for diagnostic in diagnostics.iter() {
+                print_diagnostic(diagnostic, &source, error_format);
+            }
+
+            if !diagnostics.is_empty() {
+                std::process::exit(1);
+            }
         }
 
-        Err(err) => {
-            panic!("Parsing Error:\n{}", err);
+        // Lints warn, unless denied with --deny LINT (or --deny warnings, for all of them) - --strict denies buffer_overflow
+        Some("lint") => {
+            let mut diagnostics = parser.check();
+            diagnostics.extend(parser.lint().into_iter().map(|diagnostic| match diagnostic.lint {
+                Some(Lint::BufferOverflow) if strict => diagnostic.deny(),
+                Some(lint) if deny.iter().any(|denied| denied == "warnings" || denied == lint.as_str()) => diagnostic.deny(),
+                _ => diagnostic
+            }));
+
+            for diagnostic in diagnostics.iter() {
+                print_diagnostic(diagnostic, &source, error_format);
+            }
+
+            if diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error) {
+                std::process::exit(1);
+            }
+        }
+
+        // Which characters of their alphabet the exits of each program may be pushed - and which they never are
+        Some("chars") => {
+            let diagnostics = parser.check();
+            if let Some(diagnostic) = diagnostics.first() {
+                report(diagnostic, &source, error_format);
+            }
+
+            for exit in parser.reachable_chars() {
+                let total = exit.chars.len() + exit.never.len();
+                println!("Program ({}) - exit {} ({}) may be pushed {} of {} characters", exit.program, exit.exit, exit.alphabet, exit.chars.len(), total);
+                println!("  may push:     {}", if exit.chars.is_empty() { "nothing".to_string() } else { exit.chars.join(" ") });
+                println!("  never pushes: {}", if exit.never.is_empty() { "nothing".to_string() } else { exit.never.join(" ") });
+                if !exit.values.is_empty() {
+                    println!("  raw values:   {}", exit.values.join(" "));
+                }
+            }
+        }
+
+        Some("graph") => {
+            let graph = parser.connect_graph();
+            let cycles = graph.cycles();
+
+            if !only_cycles {
+                for edge in graph.edges() {
+                    println!("{} -> {} (as {})", edge.caller, edge.connection.program, edge.connection.name);
+                }
+            }
+
+            if cycles.is_empty() {
+                println!("No cycles found in the connect graph");
+            } else {
+                for cycle in cycles.iter() {
+                    println!("{}", cycle);
+                }
+
+                std::process::exit(1);
+            }
+        }
+
+        _ if out_dir.is_some() => {
+            if let Some(diagnostic) = parser.check().first() {
+                report(diagnostic, &source, error_format);
+            }
+
+            let out_dir = std::path::PathBuf::from(out_dir.unwrap());
+            let files = match parser.into_codegen().generate_modules() {
+                Ok(files) => files,
+                Err(diagnostic) => report(&diagnostic, &source, error_format)
+            };
+
+            for (path, code) in files {
+                let path = out_dir.join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).and_then(|_| std::fs::write(&path, code)).unwrap_or_else(|err| {
+                    panic!("Could not write {}: {}", path.display(), err);
+                });
+            }
+        }
+
+        _ => match emit {
+            "ast" => println!("{}", parser.ast_json()),
+            "ir" => println!("{}", parser.to_ir().to_json()),
+            "dot" => print!("{}", parser.connect_dot()),
+
+            _ => match parser.generate() {
+                Ok(source) => {
+                    println!("{}", source);
+                }
+
+                Err(diagnostic) => report(&diagnostic, &source, error_format)
+            }
         }
     }
-}
\ No newline at end of file
+}
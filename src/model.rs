@@ -0,0 +1,541 @@
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration as Elapsed, Instant};
+use convert_case::{Case, Casing};
+use quote::{format_ident, quote};
+use crate::repl::{build, compile, parse_moment, parse_quoted, Entry, Event};
+use crate::spec::{alphabet_chars, exit_durations, format_durations, Duration};
+use crate::{Diagnostic, Parser, Span};
+
+/// Schedules a check explores at most - checks with bounds allowing more are rejected, rather than left running for hours
+const MAX_SCHEDULES: usize = 200_000;
+
+/// Steps a run takes at most - inputs are small, so runs still stepping after as many never finish
+const MAX_STEPS: usize = 1_000;
+
+/// How long a schedule may take to run - schedules are tiny, so one running for longer waits forever within a step
+const TIMEOUT: Elapsed = Elapsed::from_secs(5);
+
+/// Characters of a gateway's alphabet fed to it, unless set with `values`
+const DEFAULT_VALUES: usize = 2;
+
+/// What must hold of every run of a check
+#[derive(Debug)]
+enum Invariant {
+    /// Every duration fed to the gateway shows up on one of the exits - its characters, in order, within a duration
+    /// of that exit closed at the same moment
+    Delivers { gateway: String, exits: Vec<String> },
+
+    /// Moments pushed onto the exit never go back
+    Monotonic(String),
+
+    /// Runs never fail, nor keep on stepping
+    Succeeds
+}
+
+/// How a schedule ran
+#[derive(Debug)]
+enum Status {
+    Ok,
+
+    /// A gateway had no room for what was fed to it - schedules like that are not explored
+    Full,
+    Failed(String)
+}
+
+/// How a schedule ran, and what each exit ended up holding - as the names of its characters and `@MOMENT`s
+type Outcome = (Status, Vec<(String, Vec<String>)>);
+
+/// A check of a program - it is fed every input within its bounds, in every order (running or not between feeds), and
+/// its invariants must hold once it ran on each of them
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    pub span: Span,
+    program: Option<String>,
+    durations: usize,
+    chars: usize,
+    moments: u64,
+    values: Option<Vec<u8>>,
+    invariants: Vec<(Span, String, Invariant)>
+}
+
+/// How a check went - the schedules explored, and the first of them breaking an invariant
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: String,
+    pub span: Span,
+    pub schedules: usize,
+    pub violation: Option<String>
+}
+
+/// The checks of a source, written next to it in a `.model` file - one statement per line:
+///
+/// ```text
+/// check NAME                      starts a check
+/// program NAME                    the program it explores (the last one defined, unless set)
+/// durations N                     durations fed to each gateway at most (2, unless set)
+/// chars N                         characters within each of them at most (1, unless set)
+/// moments N                       the latest moment closing them (3, unless set) - each gateway's moments grow from 1
+/// values "CHARS"                  the characters fed (the first two of each gateway's alphabet, unless set)
+/// delivers GATEWAY: EXIT|...      every duration fed to GATEWAY shows up on one of the EXITs, closed at the same moment
+/// monotonic EXIT                  moments pushed onto EXIT never go back
+/// succeeds                        runs never fail, nor keep on stepping
+/// ```
+#[derive(Debug, Default)]
+pub struct Model {
+    checks: Vec<Check>
+}
+
+impl Model {
+    pub fn parse(filename: &str, text: &str) -> Result<Self, Diagnostic> {
+        let mut model = Self::default();
+
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let span = Span::new(filename, lineno + 1, 1);
+            let (cmd, args) = line.split_once(char::is_whitespace).map_or((line, ""), |(cmd, args)| (cmd, args.trim()));
+
+            if cmd == "check" && !args.is_empty() {
+                model.checks.push(Check{
+                    name: args.to_string(),
+                    span,
+                    program: None,
+                    durations: 2,
+                    chars: 1,
+                    moments: 3,
+                    values: None,
+                    invariants: vec![]
+                });
+
+                continue;
+            }
+
+            let Some(check) = model.checks.last_mut() else {
+                return Err(Diagnostic::at(&span, format!("Expected a check NAME before {}", cmd)));
+            };
+
+            let bound = || args.parse().map_err(|_| Diagnostic::at(&span, format!("Expected {} N, found: {}", cmd, line)));
+            match cmd {
+                "program" if !args.is_empty() => check.program = Some(args.to_string()),
+                "durations" => check.durations = bound()?,
+                "chars" => check.chars = bound()?,
+                "moments" => check.moments = bound()? as u64,
+
+                "values" => {
+                    let (values, rest) = args.strip_prefix('"').ok_or_else(|| "Expected values \"CHARS\"".to_string()).and_then(parse_quoted)
+                        .map_err(|err| Diagnostic::at(&span, err))?;
+
+                    if !rest.trim().is_empty() || values.is_empty() {
+                        return Err(Diagnostic::at(&span, format!("Expected values \"CHARS\", found: {}", line)));
+                    }
+
+                    check.values = Some(values);
+                },
+
+                "delivers" => {
+                    let (gateway, exits) = args.split_once(':').ok_or_else(|| Diagnostic::at(&span, "Expected delivers GATEWAY: EXIT|..."))?;
+                    let exits = exits.split('|').map(|exit| exit.trim().to_string()).collect();
+                    check.invariants.push((span, line.to_string(), Invariant::Delivers{gateway: gateway.trim().to_string(), exits}));
+                },
+
+                "monotonic" if !args.is_empty() => check.invariants.push((span, line.to_string(), Invariant::Monotonic(args.to_string()))),
+                "succeeds" if args.is_empty() => check.invariants.push((span, line.to_string(), Invariant::Succeeds)),
+                _ => return Err(Diagnostic::at(&span, format!("Unknown check statement: {}", line)))
+            }
+        }
+
+        Ok(model)
+    }
+
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    /// Runs every check on the programs of `source` - each builds its program once, with rustc (`$RUSTC`, or the one
+    /// on the path), then runs it on all of its schedules
+    pub fn run(&self, filename: &str, source: &str) -> Result<Vec<CheckResult>, Diagnostic> {
+        let mut parser = Parser::new(filename);
+        parser.set_codegen(false);
+        parser.parse_str(source)?;
+
+        self.checks.iter().map(|check| check.run(&parser, source)).collect()
+    }
+}
+
+impl Check {
+    fn run(&self, parser: &Parser, source: &str) -> Result<CheckResult, Diagnostic> {
+        let entry = Entry::find(source, self.program.as_deref()).map_err(|err| Diagnostic::at(&self.span, err))?;
+        let prog = parser.definitions().filter_map(|state| state.as_program()).find(|prog| prog.name() == entry.name).unwrap();
+
+        for (span, _, invariant) in self.invariants.iter() {
+            let (gateways, exits) = match invariant {
+                Invariant::Delivers { gateway, exits } => (vec![gateway], exits.iter().collect()),
+                Invariant::Monotonic(exit) => (vec![], vec![exit]),
+                Invariant::Succeeds => (vec![], vec![])
+            };
+
+            if let Some(gateway) = gateways.into_iter().find(|gateway| !entry.gateways.contains(gateway)) {
+                return Err(Diagnostic::at(span, format!("Program ({}) has no gateway {}", entry.name, gateway)));
+            }
+
+            if let Some(exit) = exits.into_iter().find(|exit| !entry.exits.contains(exit)) {
+                return Err(Diagnostic::at(span, format!("Program ({}) has no exit {}", entry.name, exit)));
+            }
+        }
+
+        let schedules = self.schedules(parser, &prog.gateway_types()).map_err(|err| Diagnostic::at(&self.span, err))?;
+        let (outcomes, finished) = self.explore(&entry, source, &schedules).map_err(|err| Diagnostic::at(&self.span, err))?;
+
+        let result = |explored, violation| Ok(CheckResult{name: self.name.clone(), span: self.span.clone(), schedules: explored, violation});
+        let mut explored = 0;
+
+        for (schedule, (status, exits)) in schedules.iter().zip(outcomes.iter()) {
+            if let Status::Full = status {
+                continue;
+            }
+
+            explored += 1;
+            let exits = exits.iter().map(|(exit, items)| {
+                let alphabet = prog.exit_types().into_iter().find(|(name, _, _)| name == exit).map(|(_, alphabet, _)| alphabet);
+                exit_durations(parser, alphabet, items).map(|durations| (exit.clone(), durations))
+            }).collect::<Result<Vec<_>, _>>().map_err(|err| Diagnostic::at(&self.span, err))?;
+
+            for (span, statement, invariant) in self.invariants.iter() {
+                if let Some(reason) = invariant.violated(schedule, status, &exits) {
+                    let violation = format!("{} ({}) does not hold - {}\n{}", statement, span, reason, describe(schedule, status, &exits));
+                    return result(explored, Some(violation));
+                }
+            }
+        }
+
+        // Schedules are run in order, so the one after the last that finished never did
+        if !finished {
+            let schedule = &schedules[outcomes.len()];
+            let violation = format!("a run never finished - it was still going after {:?}\n{}", TIMEOUT, describe(schedule, &Status::Ok, &[]));
+            return result(explored, Some(violation));
+        }
+
+        result(explored, None)
+    }
+
+    /// Every way of feeding a gateway within the bounds - up to `durations` durations, each of up to `chars` of the
+    /// values and closed by a moment later than the one before
+    fn feeds(&self, values: &[u8]) -> Vec<Vec<Duration>> {
+        let mut texts = vec![vec![]];
+        let mut longest: Vec<Vec<u8>> = vec![vec![]];
+        for _ in 0..self.chars {
+            longest = longest.iter().flat_map(|text| values.iter().map(move |value| [text.as_slice(), &[*value]].concat())).collect();
+            texts.extend(longest.iter().cloned());
+        }
+
+        let mut feeds = vec![vec![]];
+        let mut longest: Vec<Vec<Duration>> = vec![vec![]];
+        for _ in 0..self.durations {
+            let mut next = vec![];
+            for feed in longest.iter() {
+                let after = feed.last().and_then(|duration: &Duration| duration.moment).unwrap_or(0);
+                for moment in after + 1..=self.moments {
+                    for text in texts.iter() {
+                        let mut feed = feed.clone();
+                        feed.push(Duration{moment: Some(moment), values: text.clone()});
+                        next.push(feed);
+                    }
+                }
+            }
+
+            feeds.extend(next.iter().cloned());
+            longest = next;
+        }
+
+        feeds
+    }
+
+    /// Every schedule within the bounds - the feeds of each gateway, interleaved every way, running the program after
+    /// each feed or not (and always once all of them are done). Schedules feeding less come first.
+    fn schedules(&self, parser: &Parser, gateways: &[(&str, &str, &str)]) -> Result<Vec<Vec<Event>>, String> {
+        let mut combinations: Vec<Vec<(&str, &[Duration])>> = vec![vec![]];
+        let gateway_feeds: Vec<_> = gateways.iter().map(|(gateway, alphabet, _)| {
+            let values = self.values.clone().unwrap_or_else(|| alphabet_chars(parser, alphabet).iter()
+                .filter_map(|(_, value)| parse_moment(value).and_then(|value| u8::try_from(value).ok())).take(DEFAULT_VALUES).collect());
+
+            (gateway, self.feeds(&values))
+        }).collect();
+
+        for (gateway, feeds) in gateway_feeds.iter() {
+            combinations = combinations.iter().flat_map(|combination| feeds.iter().map(move |feed| {
+                let mut combination = combination.clone();
+                combination.push((*gateway, feed.as_slice()));
+                combination
+            })).collect();
+
+            if combinations.len() > MAX_SCHEDULES {
+                return Err(format!("Exploring more than {} schedules - lower the check's bounds", MAX_SCHEDULES));
+            }
+        }
+
+        let mut schedules = vec![];
+        for combination in combinations.iter() {
+            interleave(combination, &mut vec![0; combination.len()], &mut vec![], &mut schedules)?;
+        }
+
+        schedules.sort_by_key(|schedule| schedule.iter().filter(|event| matches!(event, Event::Feed { .. })).count());
+        Ok(schedules)
+    }
+
+    /// Runs the program on every schedule - what each ended up with, and whether all of them finished
+    fn explore(&self, entry: &Entry, source: &str, schedules: &[Vec<Event>]) -> Result<(Vec<Outcome>, bool), String> {
+        let dir = std::env::temp_dir().join(format!("time_lang_model_{}_{}", std::process::id(), entry.name));
+        let binary = dir.join(format!("model{}", std::env::consts::EXE_SUFFIX));
+        let code = compile(source, false).map_err(|diagnostic| diagnostic.render(source))? + &driver(entry);
+
+        let ran = build(&dir, &binary, &code).and_then(|_| run(&dir, &binary, schedules));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (output, finished) = ran?;
+
+        // Each schedule's exits are followed by how it went - whatever follows the last of those was cut off
+        let mut outcomes = vec![];
+        let mut exits = vec![];
+        for line in output.lines() {
+            let status = match line.strip_prefix("= ") {
+                Some("ok") => Status::Ok,
+                Some(full) if full.starts_with("full: ") => Status::Full,
+                Some(failed) => Status::Failed(failed.trim_start_matches("failed: ").to_string()),
+
+                None => {
+                    if let Some((exit, items)) = line.split_once(':') {
+                        exits.push((exit.to_string(), items.split_whitespace().map(|item| item.to_string()).collect()));
+                    }
+
+                    continue;
+                }
+            };
+
+            outcomes.push((status, std::mem::take(&mut exits)));
+        }
+
+        Ok((outcomes, finished))
+    }
+}
+
+impl Invariant {
+    /// Why the invariant does not hold of a run - fed `schedule`, and leaving `exits` holding what they do
+    fn violated(&self, schedule: &[Event], status: &Status, exits: &[(String, Vec<Duration>)]) -> Option<String> {
+        match self {
+            Self::Delivers { gateway, exits: delivered_to } => schedule.iter().find_map(|event| match event {
+                Event::Feed { gateway: fed, values, moment: Some(moment) } if fed == gateway => {
+                    let delivered = exits.iter().filter(|(exit, _)| delivered_to.contains(exit)).flat_map(|(_, durations)| durations.iter()).any(|duration| {
+                        duration.moment == Some(*moment) && (values.is_empty() || duration.values.windows(values.len()).any(|window| window == values.as_slice()))
+                    });
+
+                    let fed = format_durations(&[Duration{moment: Some(*moment), values: values.clone()}]);
+                    (!delivered).then(|| format!("{} fed to {} is on none of {}", fed, gateway, delivered_to.join("|")))
+                },
+
+                _ => None
+            }),
+
+            Self::Monotonic(exit) => {
+                let moments: Vec<u64> = exits.iter().filter(|(name, _)| name == exit).flat_map(|(_, durations)| durations.iter().filter_map(|duration| duration.moment)).collect();
+                moments.windows(2).find(|pair| pair[1] < pair[0]).map(|pair| format!("moment {} was pushed onto {} after {}", pair[1], exit, pair[0]))
+            },
+
+            Self::Succeeds => match status {
+                Status::Failed(err) => Some(format!("the run failed: {}", err)),
+                _ => None
+            }
+        }
+    }
+}
+
+/// Extends `events` with every interleaving of the durations still to be fed (`taken` of each already were) - adding
+/// each schedule to `schedules`
+fn interleave(feeds: &[(&str, &[Duration])], taken: &mut Vec<usize>, events: &mut Vec<Event>, schedules: &mut Vec<Vec<Event>>) -> Result<(), String> {
+    let remaining = |taken: &[usize]| feeds.iter().zip(taken.iter()).any(|((_, durations), taken)| *taken < durations.len());
+    if !remaining(taken) {
+        let mut schedule = events.clone();
+        schedule.push(Event::Run);
+        schedules.push(schedule);
+
+        return match schedules.len() > MAX_SCHEDULES {
+            true => Err(format!("Exploring more than {} schedules - lower the check's bounds", MAX_SCHEDULES)),
+            false => Ok(())
+        };
+    }
+
+    for (idx, (gateway, durations)) in feeds.iter().enumerate() {
+        let Some(duration) = durations.get(taken[idx]) else {
+            continue;
+        };
+
+        taken[idx] += 1;
+        events.push(Event::Feed{gateway: gateway.to_string(), values: duration.values.clone(), moment: duration.moment});
+        interleave(feeds, taken, events, schedules)?;
+
+        // Running once everything was fed is what ends every schedule anyway
+        if remaining(taken) {
+            events.push(Event::Run);
+            interleave(feeds, taken, events, schedules)?;
+            events.pop();
+        }
+
+        events.pop();
+        taken[idx] -= 1;
+    }
+
+    Ok(())
+}
+
+/// Runs `binary` on the schedules (written into `dir`) - what it printed, and whether it finished, rather than going
+/// for longer than the timeout without finishing another schedule
+fn run(dir: &std::path::Path, binary: &std::path::Path, schedules: &[Vec<Event>]) -> Result<(String, bool), String> {
+    let mut text = String::new();
+    for schedule in schedules.iter() {
+        for event in schedule.iter() {
+            match event {
+                Event::Feed { gateway, values, moment: Some(moment) } => {
+                    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                    text.push_str(&format!("feed {} {} @{}\n", gateway, values.join(" "), moment));
+                },
+
+                _ => text.push_str("run\n")
+            }
+        }
+
+        text.push_str("end\n");
+    }
+
+    let file = dir.join("schedules");
+    std::fs::write(&file, text).map_err(|err| format!("Could not write {}: {}", file.display(), err))?;
+
+    let mut child = Command::new(binary).arg(&file).stdout(Stdio::piped()).spawn().map_err(|err| {
+        format!("Could not run {}: {}", binary.display(), err)
+    })?;
+
+    // Read as it's printed, so the pipe never fills up - and so runs making progress are told apart from stuck ones
+    let mut stdout = child.stdout.take().unwrap();
+    let output = Arc::new(Mutex::new(vec![]));
+    let reader = {
+        let output = output.clone();
+        std::thread::spawn(move || {
+            let mut chunk = [0; 4096];
+            loop {
+                match stdout.read(&mut chunk) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => output.lock().unwrap().extend_from_slice(&chunk[..read])
+                }
+            }
+        })
+    };
+
+    let (mut progressed, mut printed) = (Instant::now(), 0);
+    let mut finished = true;
+    while child.try_wait().map_err(|err| err.to_string())?.is_none() {
+        let len = output.lock().unwrap().len();
+        if len != printed {
+            (progressed, printed) = (Instant::now(), len);
+        } else if progressed.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            finished = false;
+            break;
+        }
+
+        std::thread::sleep(Elapsed::from_millis(5));
+    }
+
+    let status = child.wait().map_err(|err| err.to_string())?;
+    let _ = reader.join();
+    let output = String::from_utf8_lossy(&output.lock().unwrap()).to_string();
+    match finished && !status.success() {
+        true => Err(format!("{} failed: {}", binary.display(), status)),
+        false => Ok((output, finished))
+    }
+}
+
+/// A `fn main()` running the program on the schedules in the file it is given - `feed GATEWAY VALUE ... @MOMENT`,
+/// `run` and `end` lines. Once each schedule ends, it prints everything pushed onto each exit, then how it went (`= ok`,
+/// `= full: ...` or `= failed: ...`), and starts the next one on a new program.
+fn driver(entry: &Entry) -> String {
+    let (struct_name, streams) = (entry.struct_name(), entry.streams());
+    let gateway_names = entry.gateways.iter();
+    let gateway_fields = entry.gateways.iter().map(|gateway| format_ident!("gateway_{}", gateway.to_case(Case::Snake)));
+    let print_exits = entry.print_exits();
+
+    quote! {
+        fn play(prog: &mut #struct_name, line: &str) -> Result<(), String> {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["run"] => {
+                    let mut steps = 0;
+                    while prog.run_step().map_err(|err| format!("failed: step {} failed: {:?}", steps + 1, err))? {
+                        steps += 1;
+                        if steps >= #MAX_STEPS {
+                            return Err(format!("failed: still stepping after {} steps", steps));
+                        }
+                    }
+                },
+
+                ["feed", gateway, values @ .., moment] => {
+                    let moment = moment.trim_start_matches('@');
+
+                    match *gateway {
+                        #(#gateway_names => {
+                            let values: Vec<_> = values.iter().map(|value| value.parse().unwrap()).collect();
+                            #streams.#gateway_fields.push_vals(&values).map_err(|err| format!("full: feeding {} failed: {:?}", gateway, err))?;
+                            #streams.#gateway_fields.push_moment(moment.parse().unwrap()).map_err(|err| format!("full: feeding {} failed: {:?}", gateway, err))?;
+                        },)*
+                        _ => return Err(format!("failed: no gateway {}", gateway))
+                    }
+                },
+
+                _ => return Err(format!("failed: not a schedule line: {}", line))
+            }
+
+            Ok(())
+        }
+
+        fn main() {
+            let file = std::env::args().nth(1).expect("Expected the schedules file");
+            let schedules = std::fs::read_to_string(&file).expect("Could not read the schedules");
+            let start = |prog: &mut #struct_name| prog.start().map_err(|err| format!("failed: starting the program failed: {:?}", err));
+
+            let mut prog = #struct_name::new();
+            let mut status = start(&mut prog);
+            for line in schedules.lines() {
+                if line != "end" {
+                    if status.is_ok() {
+                        status = play(&mut prog, line);
+                    }
+
+                    continue;
+                }
+
+                #print_exits
+                println!("= {}", status.as_ref().err().map_or("ok", |err| err.as_str()));
+
+                prog = #struct_name::new();
+                status = start(&mut prog);
+            }
+        }
+    }.to_string()
+}
+
+/// A schedule as the REPL commands replaying it - and what the exits ended up holding
+fn describe(schedule: &[Event], status: &Status, exits: &[(String, Vec<Duration>)]) -> String {
+    let mut lines: Vec<String> = schedule.iter().map(|event| match event {
+        Event::Feed { gateway, values, moment: Some(moment) } => format!("  :feed {} {} @{}", gateway, format_durations(&[Duration{moment: None, values: values.clone()}]), moment),
+        _ => "  :run".to_string()
+    }).collect();
+
+    if let Status::Failed(err) = status {
+        lines.push(format!("  (failed: {})", err));
+    }
+
+    lines.extend(exits.iter().map(|(exit, durations)| format!("  {}: {}", exit, format_durations(durations))));
+    lines.join("\n")
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use super::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+impl Severity {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning"
+        }
+    }
+}
+
+/// What a lint warns about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// A gateway never read - by instructions, `Time(GATEWAY)` moments or connected programs
+    UnusedGateway,
+
+    /// An exit never pushed to
+    UnusedExit,
+
+    /// A label never jumped to, other than the root and main labels
+    UnreachableLabel,
+
+    /// An instruction that can never have any effect - like a jump that is never taken
+    DeadCode,
+
+    /// An exit that may be pushed a duration longer than its buffer holds
+    BufferOverflow,
+
+    /// A moment that may be pushed onto an exit earlier than the one before it - time going backwards
+    NonMonotonic,
+
+    /// A label chain that never makes progress without input - waiting on it forever, or repeated without reading any
+    NoProgress
+}
+
+impl Lint {
+    pub const ALL: [Self; 7] = [Self::UnusedGateway, Self::UnusedExit, Self::UnreachableLabel, Self::DeadCode, Self::BufferOverflow, Self::NonMonotonic, Self::NoProgress];
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnusedGateway => "unused_gateway",
+            Self::UnusedExit => "unused_exit",
+            Self::UnreachableLabel => "unreachable_label",
+            Self::DeadCode => "dead_code",
+            Self::BufferOverflow => "buffer_overflow",
+            Self::NonMonotonic => "non_monotonic",
+            Self::NoProgress => "no_progress"
+        }
+    }
+}
+
+/// An error found while compiling a source - located by its span, when it points at a specific statement. Lints
+/// report warnings, named after the lint that found them.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub source: String,
+    pub span: Option<Span>,
+    pub message: String,
+    pub note: Option<String>,
+    pub severity: Severity,
+    pub lint: Option<Lint>
+}
+
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub errors: Vec<Diagnostic>
+}
+
+impl Diagnostic {
+    /// A diagnostic about a whole source, rather than one of its statements
+    pub fn new(source: &str, message: impl Into<String>) -> Self {
+        Self{source: source.to_string(), span: None, message: message.into(), note: None, severity: Severity::Error, lint: None}
+    }
+
+    pub fn at(span: &Span, message: impl Into<String>) -> Self {
+        Self{source: span.file.clone(), span: Some(span.clone()), message: message.into(), note: None, severity: Severity::Error, lint: None}
+    }
+
+    /// A warning found by the named lint
+    pub fn lint(span: &Span, lint: Lint, message: impl Into<String>) -> Self {
+        Self{severity: Severity::Warning, lint: Some(lint), ..Self::at(span, message)}
+    }
+
+    /// Turns a warning into an error - like `--deny` does
+    pub fn deny(mut self) -> Self {
+        self.severity = Severity::Error;
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    /// Renders the diagnostic like rustc does - the offending line of `text` (the source the span points into),
+    /// with the token at the span underlined
+    pub fn render(&self, text: &str) -> String {
+        let mut rendered = match self.lint {
+            Some(lint) => format!("{}[{}]: {}\n", self.severity.as_str(), lint.as_str(), self.message),
+            None => format!("{}: {}\n", self.severity.as_str(), self.message)
+        };
+
+        let span = match self.span.as_ref() {
+            Some(span) => span,
+            None => {
+                rendered.push_str(&format!(" --> {}\n", self.source));
+                if let Some(note) = self.note.as_ref() {
+                    rendered.push_str(&format!("  = note: {}\n", note));
+                }
+
+                return rendered;
+            }
+        };
+
+        let gutter = " ".repeat(span.line.to_string().len());
+        rendered.push_str(&format!("{}--> {}\n", gutter, span));
+
+        if let Some(line) = text.lines().nth(span.line.saturating_sub(1)) {
+            let start = line.char_indices().nth(span.col.saturating_sub(1)).map_or(line.len(), |(idx, _)| idx);
+            let width = line[start..].chars().take_while(|chr| chr.is_ascii_alphanumeric() || *chr == '_' || *chr == '$').count().max(1);
+            let indent: String = line[..start].chars().map(|chr| if chr == '\t' { '\t' } else { ' ' }).collect();
+
+            rendered.push_str(&format!("{} |\n", gutter));
+            rendered.push_str(&format!("{} | {}\n", span.line, line));
+            rendered.push_str(&format!("{} | {}{}\n", gutter, indent, "^".repeat(width)));
+        }
+
+        if let Some(note) = self.note.as_ref() {
+            rendered.push_str(&format!("{} = note: {}\n", gutter, note));
+        }
+
+        rendered
+    }
+
+    /// A single-line JSON object, for editors and other tools consuming diagnostics
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "severity": self.severity.as_str(),
+            "lint": self.lint.map(|lint| lint.as_str()),
+            "file": self.source,
+            "line": self.span.as_ref().map(|span| span.line),
+            "col": self.span.as_ref().map(|span| span.col),
+            "message": self.message,
+            "note": self.note
+        }).to_string()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span.as_ref() {
+            Some(span) => write!(f, "{} {}", span, self.message),
+            None => write!(f, "{}: {}", self.source, self.message)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self.errors.iter().map(|error| error.to_string()).collect();
+        write!(f, "{}", errors.join("\n"))
+    }
+}
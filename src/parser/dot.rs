@@ -0,0 +1,62 @@
+use super::state::Program;
+
+/// A Graphviz graph of the programs - each a cluster of its gateways and exits, with an edge for every stream a
+/// `connect` forwards into another program (or reads back out of it with `reg_exit_gateway`)
+pub fn connect_dot(programs: &[&Program]) -> String {
+    let mut dot = String::from("digraph connections {\n    rankdir=LR;\n    node [fontname=\"monospace\"];\n");
+
+    for program in programs.iter() {
+        let gateways = program.gateway_types();
+        let exits = program.exit_types();
+
+        dot.push_str(&format!("\n    subgraph {:?} {{\n        label={:?};\n", format!("cluster_{}", program.name()), program.name()));
+
+        for (name, alphabet, clock) in gateways.iter() {
+            dot.push_str(&format!("        {:?} [label={:?}, shape=invhouse];\n", node(program.name(), name), format!("{}\n{}, {}", name, alphabet, clock)));
+        }
+
+        // Gateways fed by a connected program's exit aren't registered with reg_gateway
+        for connection in program.connections() {
+            for (_, gateway) in connection.exit_gateways.iter() {
+                dot.push_str(&format!("        {:?} [label={:?}, shape=invhouse, style=dashed];\n", node(program.name(), gateway), gateway));
+            }
+        }
+
+        for (name, alphabet, clock) in exits.iter() {
+            dot.push_str(&format!("        {:?} [label={:?}, shape=house];\n", node(program.name(), name), format!("{}\n{}, {}", name, alphabet, clock)));
+        }
+
+        dot.push_str("    }\n");
+    }
+
+    dot.push('\n');
+
+    for program in programs.iter() {
+        let gateways = program.gateway_types();
+
+        for connection in program.connections() {
+            let callee = programs.iter().find(|callee| callee.name() == connection.program);
+
+            // Forwarded gateways line up with the callee's gateways in declaration order
+            for (idx, gateway) in connection.gateways.iter().enumerate() {
+                let target = callee.and_then(|callee| callee.gateway_types().get(idx).map(|(name, _, _)| name.to_string())).unwrap_or_else(|| format!("#{}", idx));
+                let types = gateways.iter().find(|(name, _, _)| name == gateway).map(|(_, alphabet, clock)| format!("{}, {}", alphabet, clock)).unwrap_or_default();
+
+                dot.push_str(&format!("    {:?} -> {:?} [label={:?}];\n", node(program.name(), gateway), node(&connection.program, &target), format!("{}: {}", connection.name, types)));
+            }
+
+            for (exit, gateway) in connection.exit_gateways.iter() {
+                let types = callee.and_then(|callee| callee.exit_types().into_iter().find(|(name, _, _)| name == exit)).map(|(_, alphabet, clock)| format!("{}, {}", alphabet, clock)).unwrap_or_default();
+
+                dot.push_str(&format!("    {:?} -> {:?} [label={:?}, style=dashed];\n", node(&connection.program, exit), node(program.name(), gateway), format!("{}: {}", connection.name, types)));
+            }
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn node(program: &str, stream: &str) -> String {
+    format!("{}.{}", program, stream)
+}
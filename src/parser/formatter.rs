@@ -0,0 +1,219 @@
+use super::diagnostic::Diagnostic;
+use super::lexer::{self, Token, TokenKind};
+use super::span::Span;
+
+/// Arguments of instructions start at this column - one space after the command, if it is longer
+const ARGS_COLUMN: usize = 20;
+
+/// Trailing comments start at this column - one space after the code, if it is longer
+const COMMENT_COLUMN: usize = 46;
+
+/// Statements starting (or naming) a definition - their arguments follow a single space rather than the column of
+/// instruction arguments, and the headers among them get a blank line above
+const DEFINITIONS: [&str; 7] = ["defalphabet", "defclock", "defprogram", "defmacro", "defconst", "label", "set_entry"];
+const HEADERS: [&str; 5] = ["defalphabet", "defclock", "defprogram", "defmacro", "label"];
+
+/// A line of the source - its code tokens (without whitespace), and the `#` comment it ends with
+struct Line<'a> {
+    indent: &'a str,
+    code: Vec<&'a Token>,
+    comment: Option<&'a str>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Emitted {
+    Blank,
+    Comment,
+    Code
+}
+
+/// Formats `.tl` source - instruction arguments aligned to a column, comments unindented (or aligned, if trailing)
+/// and at most one blank line in a row, with one above every definition header and label. Only whitespace changes,
+/// so comments and anything the parser would reject are kept.
+pub fn format_source(filename: &str, source: &str) -> Result<String, Diagnostic> {
+    let tokens = lexer::tokenize_lossless(source).map_err(|err| {
+        let (line, col) = lexer::line_col(source, err.range.start);
+        Diagnostic::at(&Span::new(filename, line + 1, col), err.to_string())
+    })?;
+
+    let lines: Vec<Line> = tokens.split(|token| token.kind == TokenKind::Newline).map(|tokens| Line::new(source, tokens)).collect();
+    let mut formatter = Formatter{source, out: vec![], in_macro: false};
+    let mut pending: Vec<&Line> = vec![];
+
+    for line in lines.iter() {
+        if !pending.is_empty() {
+            pending.push(line);
+        } else if formatter.in_macro && line.text(source) == "}" {
+            formatter.in_macro = false;
+            formatter.emit(Emitted::Code, with_comment("}".to_string(), line.comment));
+            continue;
+        } else if line.code.is_empty() {
+            match line.comment {
+                Some(comment) => formatter.emit(Emitted::Comment, comment.to_string()),
+                None => formatter.emit(Emitted::Blank, String::new())
+            }
+
+            continue;
+        } else if line.command(source) == Some("defmacro") {
+            // Macro headers are kept as written - a body opened by their { is formatted like any other statements
+            formatter.header("defmacro");
+            formatter.in_macro = line.code.last().is_some_and(|token| token.kind == TokenKind::Punct('{'));
+            let header = line.join(source, None);
+            formatter.emit(Emitted::Code, with_comment(header, line.comment));
+            continue;
+        } else {
+            pending.push(line);
+        }
+
+        let code: Vec<Token> = pending.iter().flat_map(|line| line.code.iter().map(|token| (*token).clone())).collect();
+        if !lexer::is_unfinished_tokens(&code) {
+            formatter.statement(&pending);
+            pending.clear();
+        }
+    }
+
+    // A statement never closed is still written out - the parser reports it. Blank lines it swallowed at the end of
+    // the source are dropped, like any others there.
+    while pending.last().is_some_and(|line| line.code.is_empty() && line.comment.is_none()) {
+        pending.pop();
+    }
+
+    if !pending.is_empty() {
+        formatter.statement(&pending);
+    }
+
+    while formatter.out.last().is_some_and(|(emitted, _)| *emitted == Emitted::Blank) {
+        formatter.out.pop();
+    }
+
+    Ok(formatter.out.into_iter().map(|(_, line)| line + "\n").collect())
+}
+
+impl<'a> Line<'a> {
+    fn new(source: &'a str, tokens: &'a [Token]) -> Self {
+        let indent = match tokens.first() {
+            Some(token) if token.kind == TokenKind::Whitespace => &source[token.range.start..token.range.end],
+            _ => ""
+        };
+
+        let comment = tokens.iter().find(|token| token.kind == TokenKind::Comment);
+        Self{
+            indent,
+            code: tokens.iter().filter(|token| !matches!(token.kind, TokenKind::Whitespace | TokenKind::Comment)).collect(),
+            comment: comment.map(|token| source[token.range.start..token.range.end].trim_end())
+        }
+    }
+
+    fn text(&self, source: &'a str) -> &'a str {
+        match (self.code.first(), self.code.last()) {
+            (Some(first), Some(last)) => &source[first.range.start..last.range.end],
+            _ => ""
+        }
+    }
+
+    fn command(&self, source: &'a str) -> Option<&'a str> {
+        self.code.first().filter(|token| token.kind == TokenKind::Word).map(|token| &source[token.range.start..token.range.end])
+    }
+
+    /// The code of the line - a single space wherever it had whitespace, none around commas between arguments (at
+    /// `depth` 0), or everywhere as written, if it isn't part of a statement
+    fn join(&self, source: &str, mut depth: Option<&mut isize>) -> String {
+        let mut text = String::new();
+        let mut previous: Option<&Token> = None;
+
+        for token in self.code.iter() {
+            let top_level = depth.as_deref().is_some_and(|depth| *depth == 0);
+            if let Some(previous) = previous {
+                let spaced = previous.range.end < token.range.start;
+                let separator = match (&previous.kind, &token.kind) {
+                    (TokenKind::Comma, _) | (_, TokenKind::Comma) if top_level => false,
+                    (_, TokenKind::Semicolon) if depth.is_some() => false,
+                    _ => spaced
+                };
+
+                if separator {
+                    text.push(' ');
+                }
+            }
+
+            if let Some(depth) = depth.as_deref_mut() {
+                match token.kind {
+                    TokenKind::Open(_) => *depth += 1,
+                    TokenKind::Close(_) => *depth -= 1,
+                    _ => ()
+                }
+            }
+
+            text.push_str(&source[token.range.start..token.range.end]);
+            previous = Some(token);
+        }
+
+        text
+    }
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    out: Vec<(Emitted, String)>,
+    in_macro: bool
+}
+
+impl<'a> Formatter<'a> {
+    /// Blank lines never start the file or follow one another
+    fn emit(&mut self, emitted: Emitted, line: String) {
+        let previous = self.out.last().map(|(emitted, _)| *emitted);
+        if emitted == Emitted::Blank && matches!(previous, None | Some(Emitted::Blank)) {
+            return;
+        }
+
+        self.out.push((emitted, line));
+    }
+
+    /// Headers are separated from the statement above - unless comments sit right above them, which may be their
+    /// documentation. Macro bodies are left alone, as they are expanded line by line.
+    fn header(&mut self, command: &str) {
+        if HEADERS.contains(&command) && !self.in_macro && self.out.last().is_some_and(|(emitted, _)| *emitted == Emitted::Code) {
+            self.out.push((Emitted::Blank, String::new()));
+        }
+    }
+
+    /// A statement, wrapped across `lines` - lines after the first keep their indentation
+    fn statement(&mut self, lines: &[&Line]) {
+        let source = self.source;
+        let mut depth = 0;
+
+        for (idx, line) in lines.iter().enumerate() {
+            if idx > 0 {
+                // Comments on lines of their own lose their indentation, like anywhere else
+                let code = if line.code.is_empty() { String::new() } else { format!("{}{}", line.indent, line.join(source, Some(&mut depth))) };
+                self.out.push((Emitted::Code, with_comment(code, line.comment)));
+                continue;
+            }
+
+            let Some(command) = line.command(source) else {
+                self.out.push((Emitted::Code, with_comment(line.join(source, Some(&mut depth)), line.comment)));
+                continue;
+            };
+
+            self.header(command);
+            let rest = Line{indent: "", code: line.code[1..].to_vec(), comment: None};
+            let args = rest.join(source, Some(&mut depth));
+
+            let text = match args.as_str() {
+                "" | ";" => format!("{}{}", command, args),
+                _ if DEFINITIONS.contains(&command) => format!("{} {}", command, args),
+                _ => format!("{:<width$} {}", command, args, width = ARGS_COLUMN - 1)
+            };
+
+            self.out.push((Emitted::Code, with_comment(text, line.comment)));
+        }
+    }
+}
+
+fn with_comment(code: String, comment: Option<&str>) -> String {
+    match comment {
+        Some(comment) if code.is_empty() => comment.to_string(),
+        Some(comment) => format!("{:<width$} {}", code, comment, width = COMMENT_COLUMN - 1),
+        None => code
+    }
+}
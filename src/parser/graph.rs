@@ -0,0 +1,95 @@
+use std::fmt;
+use super::state::{Connection, Program};
+
+#[derive(Debug)]
+pub struct ConnectEdge {
+    pub caller: String,
+    pub connection: Connection
+}
+
+#[derive(Debug)]
+pub struct ConnectGraph {
+    programs: Vec<String>,
+    edges: Vec<ConnectEdge>
+}
+
+#[derive(Debug)]
+pub struct Cycle<'a> {
+    pub edges: Vec<&'a ConnectEdge>
+}
+
+impl ConnectGraph {
+    pub fn new<'a>(programs: impl Iterator<Item = &'a Program>) -> Self {
+        let mut graph = Self{programs: vec![], edges: vec![]};
+
+        for program in programs {
+            graph.programs.push(program.name().to_string());
+
+            for connection in program.connections() {
+                graph.edges.push(ConnectEdge{caller: program.name().to_string(), connection});
+            }
+        }
+
+        graph
+    }
+
+    pub fn edges(&self) -> &[ConnectEdge] {
+        &self.edges
+    }
+
+    /// Every elementary cycle of `connect`s, each reported once - starting from its earliest declared program
+    pub fn cycles(&self) -> Vec<Cycle<'_>> {
+        let mut cycles = vec![];
+
+        for start in 0..self.programs.len() {
+            self.walk_cycles(start, start, &mut vec![], &mut cycles);
+        }
+
+        cycles
+    }
+
+    fn walk_cycles<'a>(&'a self, start: usize, node: usize, path: &mut Vec<&'a ConnectEdge>, cycles: &mut Vec<Cycle<'a>>) {
+        for edge in self.edges.iter().filter(|edge| edge.caller == self.programs[node]) {
+            let target = match self.programs.iter().position(|program| *program == edge.connection.program) {
+                Some(target) => target,
+                None => continue
+            };
+
+            let revisits = target == node || path.iter().any(|visited| visited.caller == self.programs[target]);
+            path.push(edge);
+
+            if target == start {
+                cycles.push(Cycle{edges: path.clone()});
+            } else if target > start && !revisits {
+                self.walk_cycles(start, target, path, cycles);
+            }
+
+            path.pop();
+        }
+    }
+}
+
+impl fmt::Display for Cycle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let programs: Vec<&str> = self.edges.iter().map(|edge| edge.caller.as_str()).collect();
+        write!(f, "cycle: {}", programs.join(" -> "))?;
+        if let Some(first) = programs.first() {
+            write!(f, " -> {}", first)?;
+        }
+
+        for edge in self.edges.iter() {
+            let connection = &edge.connection;
+            let exit_gateways: Vec<String> = connection.exit_gateways.iter().map(|(exit, gateway)| format!("{}->{}", exit, gateway)).collect();
+
+            write!(f, "\n  {} connects {} as {} (gateways: {}; exit gateways: {})",
+                edge.caller,
+                connection.program,
+                connection.name,
+                if connection.gateways.is_empty() { "none".to_string() } else { connection.gateways.join(", ") },
+                if exit_gateways.is_empty() { "none".to_string() } else { exit_gateways.join(", ") }
+            )?;
+        }
+
+        Ok(())
+    }
+}
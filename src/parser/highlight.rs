@@ -0,0 +1,411 @@
+use serde_json::json;
+use super::index::{self, Role};
+use super::lexer::{self, TextRange, Token, TokenKind};
+use super::SymbolKind;
+
+/// Arguments holding moments - rather than plain numbers - by command and argument
+const MOMENTS: [(&str, usize); 5] = [("start_moment", 0), ("push_moment", 0), ("skip_until_moment", 1), ("delay", 2), ("throttle", 2)];
+
+/// Commands registering a stream - with the argument naming it
+const STREAMS: [(&str, usize); 4] = [("reg_gateway", 0), ("reg_exit", 0), ("reg_error_exit", 0), ("reg_exit_gateway", 1)];
+
+/// What a token of the source is, for editors to highlight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticTokenKind {
+    Comment,
+    String,
+    Number,
+
+    /// A moment of a clock - pushed, started at or skipped to, or read off a stream with `Time(STREAM)`
+    Moment,
+    Operator,
+
+    /// The command a statement starts with
+    Command,
+
+    /// Words with a fixed meaning to their command - generic parameter kinds, strategies, representations, ...
+    Keyword,
+    Macro,
+
+    /// A `$` reference - or a parameter of a macro or generic program, where it's declared
+    Parameter,
+
+    /// An alphabet, clock or program
+    Type,
+    Stream,
+    Connection,
+
+    /// A character or class of an alphabet
+    Character,
+    Label,
+    Constant
+}
+
+impl SemanticTokenKind {
+    pub const ALL: [Self; 15] = [
+        Self::Comment, Self::String, Self::Number, Self::Moment, Self::Operator, Self::Command, Self::Keyword, Self::Macro,
+        Self::Parameter, Self::Type, Self::Stream, Self::Connection, Self::Character, Self::Label, Self::Constant
+    ];
+
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Comment => "comment",
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Moment => "moment",
+            Self::Operator => "operator",
+            Self::Command => "command",
+            Self::Keyword => "keyword",
+            Self::Macro => "macro",
+            Self::Parameter => "parameter",
+            Self::Type => "type",
+            Self::Stream => "stream",
+            Self::Connection => "connection",
+            Self::Character => "character",
+            Self::Label => "label",
+            Self::Constant => "constant"
+        }
+    }
+
+    /// The closest of the token types predefined by the Language Server Protocol - themes only color those
+    pub const fn lsp_type(&self) -> &'static str {
+        match self {
+            Self::Comment => "comment",
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Moment => "event",
+            Self::Operator => "operator",
+            Self::Command => "keyword",
+            Self::Keyword => "modifier",
+            Self::Macro => "macro",
+            Self::Parameter => "typeParameter",
+            Self::Type => "type",
+            Self::Stream => "variable",
+            Self::Connection => "namespace",
+            Self::Character => "enumMember",
+            Self::Label => "function",
+            Self::Constant => "property"
+        }
+    }
+
+    /// The TextMate scope of the kind, within the grammar of textmate_grammar
+    pub const fn scope(&self) -> &'static str {
+        match self {
+            Self::Comment => "comment.line.number-sign.tl",
+            Self::String => "string.quoted.double.tl",
+            Self::Number => "constant.numeric.tl",
+            Self::Moment => "constant.numeric.moment.tl",
+            Self::Operator => "keyword.operator.tl",
+            Self::Command => "keyword.other.command.tl",
+            Self::Keyword => "storage.modifier.tl",
+            Self::Macro => "entity.name.function.macro.tl",
+            Self::Parameter => "variable.parameter.tl",
+            Self::Type => "entity.name.type.tl",
+            Self::Stream => "variable.other.stream.tl",
+            Self::Connection => "entity.name.namespace.tl",
+            Self::Character => "variable.other.enummember.tl",
+            Self::Label => "entity.name.label.tl",
+            Self::Constant => "variable.other.constant.tl"
+        }
+    }
+}
+
+/// A highlighted piece of the source - its line and column (counting from 1, like spans) and length, in bytes. Tokens
+/// never span lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SemanticToken {
+    pub kind: SemanticTokenKind,
+    pub line: usize,
+    pub col: usize,
+    pub len: usize
+}
+
+impl SemanticToken {
+    pub fn to_json(&self) -> String {
+        json!({"kind": self.kind.as_str(), "line": self.line, "col": self.col, "len": self.len}).to_string()
+    }
+}
+
+/// Highlights `.tl` source, in order - names are told apart by where they appear in their statement, the way the
+/// parser reads them, so this works on sources the parser would reject (or that are still being typed)
+pub fn tokenize(source: &str) -> Vec<SemanticToken> {
+    // An unterminated string literal runs to the end of the source
+    let (tokens, unterminated) = match lexer::tokenize_lossless(source) {
+        Ok(tokens) => (tokens, None),
+        Err(err) => (lexer::tokenize_lossless(&source[..err.range.start]).unwrap_or_default(), Some(err.range))
+    };
+
+    let mut highlighter = Highlighter{source, streams: vec![], constants: vec![], macros: vec![], out: vec![]};
+    let mut statements: Vec<Vec<Token>> = vec![];
+    let mut statement: Vec<Token> = vec![];
+
+    for token in tokens {
+        match token.kind {
+            TokenKind::Whitespace => continue,
+            TokenKind::Comment => highlighter.push(SemanticTokenKind::Comment, token.range),
+
+            // Statements continue across lines until their ;
+            TokenKind::Newline => (),
+
+            // The } closing a macro body belongs to no statement
+            TokenKind::Punct('}') if statement.is_empty() => (),
+
+            // So does the { opening one - the statements inside it are highlighted like any others
+            TokenKind::Punct('{') if statement.first().is_some_and(|cmd| highlighter.text(cmd) == "defmacro") => statements.push(std::mem::take(&mut statement)),
+
+            TokenKind::Semicolon => {
+                statement.push(token);
+                statements.push(std::mem::take(&mut statement));
+            },
+
+            _ => statement.push(token)
+        }
+    }
+
+    statements.push(statement);
+    if let Some(range) = unterminated {
+        highlighter.push(SemanticTokenKind::String, range);
+    }
+
+    for statement in statements.iter() {
+        highlighter.declare(statement);
+    }
+
+    for statement in statements.iter() {
+        highlighter.statement(statement);
+    }
+
+    highlighter.out.sort_by_key(|token| (token.line, token.col));
+    highlighter.out
+}
+
+struct Highlighter<'a> {
+    source: &'a str,
+
+    /// Names declared anywhere in the source - names of streams are only highlighted as such where one is declared
+    streams: Vec<&'a str>,
+    constants: Vec<&'a str>,
+    macros: Vec<&'a str>,
+    out: Vec<SemanticToken>
+}
+
+impl<'a> Highlighter<'a> {
+    fn text(&self, token: &Token) -> &'a str {
+        &self.source[token.range.start..token.range.end]
+    }
+
+    /// Pushes a token - split at line ends, for string literals spanning lines
+    fn push(&mut self, kind: SemanticTokenKind, range: TextRange) {
+        let mut start = range.start;
+        for piece in self.source[range.start..range.end].split('\n') {
+            if !piece.is_empty() {
+                let (line, col) = lexer::line_col(self.source, start);
+                self.out.push(SemanticToken{kind, line: line + 1, col, len: piece.len()});
+            }
+
+            start += piece.len() + 1;
+        }
+    }
+
+    /// The arguments of a statement, split at top-level commas - without its command and ;
+    fn args<'t>(&self, statement: &'t [Token]) -> Vec<&'t [Token]> {
+        let body = match statement.get(1..).unwrap_or_default().split_last() {
+            Some((last, body)) if last.kind == TokenKind::Semicolon => body,
+            _ => statement.get(1..).unwrap_or_default()
+        };
+
+        let mut depth = 0;
+        body.split(|token| {
+            match token.kind {
+                TokenKind::Open(_) => depth += 1,
+                TokenKind::Close(_) => depth -= 1,
+                _ => ()
+            }
+
+            depth == 0 && token.kind == TokenKind::Comma
+        }).collect()
+    }
+
+    fn declare(&mut self, statement: &[Token]) {
+        let Some(cmd) = statement.first().map(|cmd| self.text(cmd)) else {
+            return;
+        };
+
+        let args = self.args(statement);
+        let name = |idx: usize| args.get(idx).and_then(|arg| arg.iter().find(|token| token.kind == TokenKind::Word));
+
+        match cmd {
+            "defconst" => self.constants.extend(name(0).map(|token| self.text(token))),
+            "defmacro" => self.macros.extend(statement.get(1).map(|token| self.text(token))),
+            cmd => if let Some((_, idx)) = STREAMS.iter().find(|(streams, _)| *streams == cmd) {
+                self.streams.extend(name(*idx).map(|token| self.text(token)));
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &[Token]) {
+        let Some(first) = statement.first() else {
+            return;
+        };
+
+        if first.kind != TokenKind::Word {
+            for token in statement.iter() {
+                self.token(token, SemanticTokenKind::Keyword);
+            }
+
+            return;
+        }
+
+        let cmd = self.text(first);
+        if cmd == "defmacro" {
+            self.push(SemanticTokenKind::Command, first.range);
+            for (idx, token) in statement[1..].iter().enumerate() {
+                self.token(token, if idx == 0 { SemanticTokenKind::Macro } else { SemanticTokenKind::Parameter });
+            }
+
+            return;
+        }
+
+        self.push(if self.macros.contains(&cmd) { SemanticTokenKind::Macro } else { SemanticTokenKind::Command }, first.range);
+
+        let args = self.args(statement);
+        let arg_texts: Vec<&str> = args.iter().map(|arg| match (arg.first(), arg.last()) {
+            (Some(first), Some(last)) => &self.source[first.range.start..last.range.end],
+            _ => ""
+        }).collect();
+
+        for (idx, arg) in args.iter().enumerate() {
+            let mut word_idx = 0;
+            for (token_idx, token) in arg.iter().enumerate() {
+                if token.kind != TokenKind::Word {
+                    self.token(token, SemanticTokenKind::Keyword);
+                    continue;
+                }
+
+                let called = arg.get(token_idx + 1).is_some_and(|next| next.kind == TokenKind::Open('('));
+                let kind = self.word(cmd, &arg_texts, idx, word_idx, self.text(token), called);
+                self.push(kind, token.range);
+                word_idx += 1;
+            }
+        }
+    }
+
+    /// Highlights anything but a name - words get `word` (unless they are numbers or $ references)
+    fn token(&mut self, token: &Token, word: SemanticTokenKind) {
+        let kind = match token.kind {
+            TokenKind::Word => match self.text(token) {
+                text if text.starts_with('$') => SemanticTokenKind::Parameter,
+                text if text.starts_with(|chr: char| chr.is_ascii_digit()) => SemanticTokenKind::Number,
+                _ => word
+            },
+
+            TokenKind::Str => SemanticTokenKind::String,
+            TokenKind::Punct('{' | '}') => return,
+            TokenKind::Punct(_) => SemanticTokenKind::Operator,
+            _ => return
+        };
+
+        self.push(kind, token.range);
+    }
+
+    /// What the `word_idx`-th name within argument `idx` of a statement is - `called` if it's followed by brackets
+    fn word(&self, cmd: &str, args: &[&str], idx: usize, word_idx: usize, word: &str, called: bool) -> SemanticTokenKind {
+        if word.starts_with('$') {
+            return SemanticTokenKind::Parameter;
+        }
+
+        if word.starts_with(|chr: char| chr.is_ascii_digit()) || (word == "Time" && called) {
+            let moment = word == "Time" || MOMENTS.contains(&(cmd, idx));
+            return if moment { SemanticTokenKind::Moment } else { SemanticTokenKind::Number };
+        }
+
+        let role = match (cmd, idx, word_idx) {
+            ("reg_exit_gateway", 0, 1..) => return SemanticTokenKind::Stream,
+            ("set_char_type" | "set_moment_type", 0, 0) => return SemanticTokenKind::Type,
+            (cmd, idx, word_idx) => index::role(cmd, args, idx, word_idx)
+        };
+
+        match role {
+            Some(Role::Defines(kind)) => symbol_kind(kind),
+            Some(Role::Refers(kinds) | Role::Character(kinds, _)) if kinds.contains(&SymbolKind::Constant) && self.constants.contains(&word) => SemanticTokenKind::Constant,
+            Some(Role::Character(..)) => SemanticTokenKind::Character,
+
+            // Arguments of commands without names of their own (set_clock_repr, set_derives, ...) aren't streams
+            Some(Role::Refers(kinds)) if kinds.contains(&SymbolKind::Gateway) => match self.streams.contains(&word) {
+                true => SemanticTokenKind::Stream,
+                false => SemanticTokenKind::Keyword
+            },
+
+            Some(Role::Refers(kinds)) => kinds.first().map_or(SemanticTokenKind::Keyword, |kind| symbol_kind(*kind)),
+            Some(Role::ConnectedExit(_)) => SemanticTokenKind::Stream,
+
+            // Generic parameters of programs are declared along with their kind
+            None if cmd == "defprogram" && !matches!(word, "alphabet" | "clock") => SemanticTokenKind::Parameter,
+            None => SemanticTokenKind::Keyword
+        }
+    }
+}
+
+const fn symbol_kind(kind: SymbolKind) -> SemanticTokenKind {
+    match kind {
+        SymbolKind::Alphabet | SymbolKind::Clock | SymbolKind::Program => SemanticTokenKind::Type,
+        SymbolKind::Character | SymbolKind::Class => SemanticTokenKind::Character,
+        SymbolKind::Gateway | SymbolKind::Exit => SemanticTokenKind::Stream,
+        SymbolKind::Connection => SemanticTokenKind::Connection,
+        SymbolKind::Label => SemanticTokenKind::Label,
+        SymbolKind::Constant => SemanticTokenKind::Constant,
+        SymbolKind::Macro => SemanticTokenKind::Macro
+    }
+}
+
+/// A TextMate grammar for `.tl` sources, as JSON - line by line, it highlights what tokenize does where a line shows
+/// enough of its statement, using the scopes of SemanticTokenKind
+pub fn textmate_grammar() -> String {
+    use SemanticTokenKind as Kind;
+
+    const NAME: &str = r"[A-Za-z_][A-Za-z0-9_]*";
+    let named = |commands: &str, kind: Kind| json!({
+        "match": format!(r"^\s*({})\s+({})", commands, NAME),
+        "captures": {"1": {"name": Kind::Command.scope()}, "2": {"name": kind.scope()}}
+    });
+
+    let grammar = json!({
+        "$schema": "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json",
+        "name": "time_lang",
+        "scopeName": "source.tl",
+        "fileTypes": ["tl"],
+        "patterns": [
+            {"match": "#.*$", "name": Kind::Comment.scope()},
+            {"begin": "\"", "end": "\"", "name": Kind::String.scope(), "patterns": [{"match": r"\\.", "name": "constant.character.escape.tl"}]},
+            named("defalphabet|defclock|defprogram|set_entry", Kind::Type),
+            named("defmacro", Kind::Macro),
+            named("defconst", Kind::Constant),
+            named("label|jump_earlier|jump_later|jump_if_class|jump_overflowed", Kind::Label),
+            named("reg_gateway|reg_exit|reg_error_exit", Kind::Stream),
+            named("connect", Kind::Type),
+            named("def_class", Kind::Character),
+            {
+                "match": format!(r"^\s*(def_char)\s+([^,;#]+),\s*({})", NAME),
+                "captures": {"1": {"name": Kind::Command.scope()}, "2": {"name": Kind::Number.scope()}, "3": {"name": Kind::Character.scope()}}
+            },
+            {
+                "match": format!(r"^\s*(push_char)\s+({})", NAME),
+                "captures": {"1": {"name": Kind::Command.scope()}, "2": {"name": Kind::Character.scope()}}
+            },
+            {
+                "match": r"^\s*(start_moment|push_moment)\s+([0-9][0-9A-Fa-fx_]*)\b",
+                "captures": {"1": {"name": Kind::Command.scope()}, "2": {"name": Kind::Moment.scope()}}
+            },
+            {"match": r"\bTime(?=\s*\()", "name": Kind::Moment.scope()},
+
+            // Any other statement starts with its command - unlike `NAME: KIND` parameters wrapped onto their own line
+            {"match": format!(r"^\s*({})\b(?!\s*:)", NAME), "captures": {"1": {"name": Kind::Command.scope()}}},
+            {"match": r"\$[A-Za-z0-9_]+", "name": Kind::Parameter.scope()},
+            {"match": r"\b(alphabet|clock)\b", "name": Kind::Keyword.scope()},
+            {"match": r"\b(0x[0-9A-Fa-f]+|[0-9]+)\b", "name": Kind::Number.scope()},
+            {"match": r"[-+*/%|:&^]", "name": Kind::Operator.scope()}
+        ]
+    });
+
+    serde_json::to_string_pretty(&grammar).unwrap()
+}
@@ -0,0 +1,287 @@
+use super::lexer::{self, Statement, TokenKind};
+use super::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Alphabet,
+    Clock,
+    Program,
+    Character,
+    Class,
+    Gateway,
+    Exit,
+    Label,
+    Connection,
+    Constant,
+    Macro
+}
+
+impl SymbolKind {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Alphabet => "alphabet",
+            Self::Clock => "clock",
+            Self::Program => "program",
+            Self::Character => "character",
+            Self::Class => "class",
+            Self::Gateway => "gateway",
+            Self::Exit => "exit",
+            Self::Label => "label",
+            Self::Connection => "connection",
+            Self::Constant => "constant",
+            Self::Macro => "macro"
+        }
+    }
+
+    /// Characters and classes belong to their alphabet - streams, labels and connections to their program
+    const fn is_scoped(&self) -> bool {
+        matches!(self, Self::Character | Self::Class | Self::Gateway | Self::Exit | Self::Label | Self::Connection)
+    }
+}
+
+/// A name the source defines, where it defines it
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub name: String,
+
+    /// The alphabet or program the symbol belongs to, if it is scoped
+    pub scope: Option<String>,
+
+    /// What the symbol stands for - the alphabet of a stream, the program of a connection, the value of a character
+    pub target: Option<String>,
+    pub span: Span,
+
+    /// The statements defining the symbol - its definition, then the settings of an alphabet or clock
+    pub detail: Vec<String>,
+    pub doc: Vec<String>
+}
+
+/// What a name appearing in the source is - resolved to a symbol once every definition is known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Role {
+    Defines(SymbolKind),
+    Refers(&'static [SymbolKind]),
+
+    /// A character (or class) of the alphabet of the named stream
+    Character(&'static [SymbolKind], String),
+
+    /// An exit of the program behind the named connection
+    ConnectedExit(String)
+}
+
+#[derive(Debug, Clone)]
+struct Occurrence {
+    name: String,
+    span: Span,
+    scope: Option<String>,
+    role: Role
+}
+
+const STREAMS_OR_CONSTANTS: &[SymbolKind] = &[SymbolKind::Gateway, SymbolKind::Exit, SymbolKind::Constant];
+const CLASSES_OR_CHARACTERS: &[SymbolKind] = &[SymbolKind::Class, SymbolKind::Character, SymbolKind::Constant];
+
+/// Every name of a source - where it's defined, and everywhere it appears - for editors to navigate through
+#[derive(Debug, Default)]
+pub struct Index {
+    symbols: Vec<Symbol>,
+    occurrences: Vec<Occurrence>,
+
+    /// The alphabet, clock or program statements belong to - as an index into `symbols`
+    scope: Option<usize>,
+
+    /// The symbol defined by the latest statement, which its comment block documents
+    latest: Option<usize>
+}
+
+impl Index {
+    pub const fn new() -> Self {
+        Self{symbols: vec![], occurrences: vec![], scope: None, latest: None}
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Records what a statement defines and refers to - `text` is the statement as written, starting on line `lineno`
+    pub fn record(&mut self, filename: &str, lineno: usize, text: &str, statement: &Statement) {
+        let Ok(tokens) = lexer::tokenize(text) else {
+            return;
+        };
+
+        let span_at = |offset: usize| {
+            let (line, col) = lexer::line_col(text, offset);
+            Span::new(filename, lineno + line, col)
+        };
+
+        let args: Vec<&str> = statement.args.iter().map(|(arg, _)| *arg).collect();
+        let words: Vec<Vec<(&str, Span)>> = statement.args.iter().map(|(_, range)| {
+            tokens.iter().filter(|token| token.kind == TokenKind::Word && token.range.start >= range.start && token.range.end <= range.end)
+                .map(|token| (&text[token.range.start..token.range.end], span_at(token.range.start))).collect()
+        }).collect();
+
+        let definition = format!("{} {}", statement.cmd, args.join(","));
+        self.latest = None;
+
+        match statement.cmd {
+            "defalphabet" | "defclock" | "defprogram" => self.scope = None,
+            "set_char_type" | "set_moment_type" | "set_clock_repr" => {
+                if let Some(scope) = self.scope {
+                    self.symbols[scope].detail.push(definition.clone());
+                }
+            },
+            _ => ()
+        }
+
+        let scope = self.scope.map(|scope| self.symbols[scope].name.clone());
+        self.occurrences.push(Occurrence{name: statement.cmd.to_string(), span: span_at(statement.cmd_range.start), scope: scope.clone(), role: Role::Refers(&[SymbolKind::Macro])});
+
+        for (idx, arg_words) in words.iter().enumerate() {
+            for (word_idx, (word, span)) in arg_words.iter().enumerate() {
+                let role = match (statement.cmd, idx, word_idx) {
+                    ("reg_exit_gateway", 0, 1..) => Some(Role::ConnectedExit(arg_words[0].0.to_string())),
+                    (cmd, idx, word_idx) => role(cmd, &args, idx, word_idx)
+                };
+
+                let Some(role) = role else {
+                    continue;
+                };
+
+                if let Role::Defines(kind) = role {
+                    let target = match (kind, statement.cmd) {
+                        (SymbolKind::Gateway | SymbolKind::Exit, "reg_gateway" | "reg_exit" | "reg_error_exit") => args.get(1),
+                        (SymbolKind::Connection, _) => words[0].first().map(|(program, _)| program),
+                        (SymbolKind::Character | SymbolKind::Constant, _) => args.first(),
+                        _ => None
+                    };
+
+                    self.define(kind, word, span.clone(), target.map(|target| target.to_string()), definition.clone());
+                }
+
+                self.occurrences.push(Occurrence{name: word.to_string(), span: span.clone(), scope: scope.clone(), role});
+            }
+        }
+    }
+
+    /// Defines a symbol - alphabets, clocks and programs scope the statements that follow them
+    pub fn define(&mut self, kind: SymbolKind, name: &str, span: Span, target: Option<String>, definition: String) {
+        let scope = self.scope.filter(|_| kind.is_scoped()).map(|scope| self.symbols[scope].name.clone());
+
+        let mut detail = vec![definition];
+        if let Some(scope) = scope.as_ref() {
+            let owner = if matches!(kind, SymbolKind::Character | SymbolKind::Class) { "alphabet" } else { "program" };
+            detail.push(format!("in {} {}", owner, scope));
+        }
+
+        self.symbols.push(Symbol{kind, name: name.to_string(), scope, target, span, detail, doc: vec![]});
+        self.latest = Some(self.symbols.len() - 1);
+
+        if matches!(kind, SymbolKind::Alphabet | SymbolKind::Clock | SymbolKind::Program) {
+            self.scope = self.latest;
+        }
+    }
+
+    /// Documents the alphabet, clock or program being defined
+    pub fn document_definition(&mut self, doc: Vec<String>) {
+        if let Some(scope) = self.scope {
+            self.symbols[scope].doc = doc;
+        }
+    }
+
+    /// Documents what the latest statement defined
+    pub fn document_latest(&mut self, doc: Vec<String>) {
+        if let Some(latest) = self.latest {
+            self.symbols[latest].doc = doc;
+        }
+    }
+
+    /// The symbol the name at a position (line and column counting from 1) stands for - a name being defined stands
+    /// for itself
+    pub fn symbol_at(&self, line: usize, col: usize) -> Option<&Symbol> {
+        let occurrence = self.occurrences.iter().find(|occurrence| {
+            occurrence.span.line == line && (occurrence.span.col..=occurrence.span.col + occurrence.name.len()).contains(&col)
+        })?;
+
+        match &occurrence.role {
+            Role::Defines(_) => self.symbols.iter().find(|symbol| symbol.span == occurrence.span),
+            Role::Refers(kinds) => kinds.iter().find_map(|kind| self.lookup(*kind, &occurrence.name, occurrence.scope.as_deref())),
+
+            Role::Character(kinds, stream) => {
+                let alphabet = self.stream(occurrence.scope.as_deref(), stream).and_then(|stream| stream.target.as_deref());
+                kinds.iter().find_map(|kind| match kind {
+                    SymbolKind::Character | SymbolKind::Class => self.symbols.iter().find(|symbol| {
+                        symbol.kind == *kind && symbol.name == occurrence.name && (alphabet.is_none() || symbol.scope.as_deref() == alphabet)
+                    }),
+                    kind => self.lookup(*kind, &occurrence.name, occurrence.scope.as_deref())
+                })
+            },
+
+            Role::ConnectedExit(connection) => {
+                let program = self.lookup(SymbolKind::Connection, connection, occurrence.scope.as_deref())?.target.as_deref();
+                self.lookup(SymbolKind::Exit, &occurrence.name, program)
+            }
+        }
+    }
+
+    /// A symbol by its name - scoped symbols are only found within `scope`
+    pub fn lookup(&self, kind: SymbolKind, name: &str, scope: Option<&str>) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.kind == kind && symbol.name == name && (!kind.is_scoped() || symbol.scope.as_deref() == scope))
+    }
+
+    /// A gateway or exit of a program
+    pub fn stream(&self, program: Option<&str>, name: &str) -> Option<&Symbol> {
+        self.lookup(SymbolKind::Gateway, name, program).or_else(|| self.lookup(SymbolKind::Exit, name, program))
+    }
+
+    /// The alphabet, clock or program a line belongs to
+    pub fn definition_at(&self, line: usize) -> Option<&Symbol> {
+        self.symbols.iter().filter(|symbol| matches!(symbol.kind, SymbolKind::Alphabet | SymbolKind::Clock | SymbolKind::Program))
+            .take_while(|symbol| symbol.span.line <= line).last()
+    }
+
+    /// The characters of an alphabet - or of every alphabet, without one
+    pub fn characters<'a>(&'a self, alphabet: Option<&'a str>) -> impl Iterator<Item = &'a Symbol> {
+        self.symbols.iter().filter(move |symbol| symbol.kind == SymbolKind::Character && (alphabet.is_none() || symbol.scope.as_deref() == alphabet))
+    }
+}
+
+/// What the `word_idx`-th name within argument `idx` of a statement is
+pub(super) fn role(cmd: &str, args: &[&str], idx: usize, word_idx: usize) -> Option<Role> {
+    use SymbolKind::*;
+    let stream = |idx: usize| args.get(idx).map_or(String::new(), |stream| stream.to_string());
+
+    let role = match (cmd, idx, word_idx) {
+        ("defalphabet", 0, 0) => Role::Defines(Alphabet),
+        ("defclock", 0, 0) => Role::Defines(Clock),
+        ("defprogram", 0, 0) => Role::Defines(Program),
+        ("defconst", 0, 0) => Role::Defines(Constant),
+        ("set_entry", 0, 0) => Role::Refers(&[Program]),
+        ("def_char", 1, 0) => Role::Defines(Character),
+        ("def_class", 0, 0) => Role::Defines(Class),
+        ("def_class", 1, _) => Role::Refers(&[Character]),
+        ("label", 0, 0) => Role::Defines(Label),
+
+        ("reg_gateway", 0, 0) => Role::Defines(Gateway),
+        ("reg_exit" | "reg_error_exit", 0, 0) => Role::Defines(Exit),
+        ("reg_gateway" | "reg_exit" | "reg_error_exit", 1, 0) => Role::Refers(&[Alphabet]),
+        ("reg_gateway" | "reg_exit" | "reg_error_exit", 2, 0) => Role::Refers(&[Clock]),
+        ("reg_gateway" | "reg_exit" | "reg_error_exit", 3, _) => Role::Refers(&[Constant]),
+
+        ("connect", 0, 0) => Role::Refers(&[Program]),
+        ("connect", 1, 0) => Role::Defines(Connection),
+        ("reg_exit_gateway", 0, 0) => Role::Refers(&[Connection]),
+        ("reg_exit_gateway", 1, 0) => Role::Defines(Gateway),
+
+        ("jump_earlier" | "jump_later" | "jump_if_class" | "jump_overflowed", 0, 0) => Role::Refers(&[Label]),
+        ("jump_if_class", 2, _) => Role::Character(CLASSES_OR_CHARACTERS, stream(1)),
+        ("push_char", 0, _) => Role::Character(&[Character], stream(1)),
+        ("filter" | "filter_keep", 2, _) | ("split", 2, 1) => Role::Character(CLASSES_OR_CHARACTERS, stream(0)),
+        ("filter_class", 2, _) => Role::Character(&[Class], stream(0)),
+
+        // Generic parameters, values of characters and the options of streams name nothing of the source
+        ("defprogram" | "def_char" | "reg_gateway" | "reg_exit" | "reg_error_exit" | "split", _, _) => return None,
+        _ => Role::Refers(STREAMS_OR_CONSTANTS)
+    };
+
+    Some(role)
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use super::state::State;
+use super::Diagnostic;
+
+/// Bumped whenever the shape or meaning of the IR changes - `Codegen::from_ir` only accepts its own version
+pub const IR_VERSION: u32 = 1;
+
+/// A flat form of the parsed definitions - each one is its `def...` header and body statements, with macros
+/// already expanded and constants resolved, so it stands on its own without the original source
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ir {
+    pub version: u32,
+    pub definitions: Vec<IrDefinition>
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IrDefinition {
+    /// `alphabet`, `clock` or `program`
+    pub kind: String,
+    pub definition: String,
+    pub statements: Vec<IrStatement>
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IrStatement {
+    pub cmd: String,
+    pub args: Vec<String>
+}
+
+impl Ir {
+    pub fn new<'a>(definitions: impl Iterator<Item = &'a State>) -> Self {
+        let definitions = definitions.map(|state| IrDefinition{
+            kind: state.kind().to_string(),
+            definition: state.definition(),
+            statements: state.statements().into_iter().map(|(cmd, args)| IrStatement{cmd, args}).collect()
+        }).collect();
+
+        Self{version: IR_VERSION, definitions}
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Diagnostic> {
+        serde_json::from_str(json).map_err(|err| Diagnostic::new("ir", format!("Invalid IR: {}", err)))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+
+/// Byte offsets of a token within the text it was lexed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRange {
+    pub start: usize,
+    pub end: usize
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Identifiers, numbers and `$` references - anything made of letters, digits, `_` and `$`
+    Word,
+
+    /// A double-quoted literal - `\` escapes the next character
+    Str,
+
+    Comma,
+    Semicolon,
+    Open(char),
+    Close(char),
+
+    /// Any other single character (operators, `|`, `:`, ...)
+    Punct(char),
+
+    /// Spaces and tabs - only kept by tokenize_lossless, like the newlines and comments below
+    Whitespace,
+    Newline,
+
+    /// A `#` comment, up to (but not including) the end of its line
+    Comment
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub range: TextRange
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub range: TextRange,
+    pub message: String
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// A `COMMAND ARG,ARG,...;` statement - arguments are the source text between top-level commas
+#[derive(Debug)]
+pub struct Statement<'a> {
+    pub cmd: &'a str,
+    pub cmd_range: TextRange,
+    pub args: Vec<(&'a str, TextRange)>
+}
+
+fn is_word_char(chr: char) -> bool {
+    chr.is_ascii_alphanumeric() || chr == '_' || chr == '$'
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, LexError> {
+    lex(source, false)
+}
+
+/// Tokenizes without dropping anything - whitespace, newlines and `#` comments are tokens too, so the source is
+/// exactly the concatenation of its tokens
+pub fn tokenize_lossless(source: &str) -> Result<Vec<Token>, LexError> {
+    lex(source, true)
+}
+
+fn lex(source: &str, lossless: bool) -> Result<Vec<Token>, LexError> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((start, chr)) = chars.next() {
+        let kind = match chr {
+            '\n' if lossless => TokenKind::Newline,
+
+            _ if chr.is_whitespace() && lossless => {
+                while chars.next_if(|(_, chr)| chr.is_whitespace() && *chr != '\n').is_some() {}
+                TokenKind::Whitespace
+            },
+
+            _ if chr.is_whitespace() => continue,
+
+            '#' if lossless => {
+                while chars.next_if(|(_, chr)| *chr != '\n').is_some() {}
+                TokenKind::Comment
+            },
+
+            _ if is_word_char(chr) => {
+                while chars.next_if(|(_, chr)| is_word_char(*chr)).is_some() {}
+                TokenKind::Word
+            },
+
+            '"' => {
+                let mut escaped = false;
+                let closed = chars.by_ref().any(|(_, chr)| match chr {
+                    _ if escaped => { escaped = false; false },
+                    '\\' => { escaped = true; false },
+                    '"' => true,
+                    _ => false
+                });
+
+                if !closed {
+                    return Err(LexError{range: TextRange{start, end: source.len()}, message: "unterminated string literal".to_string()});
+                }
+
+                TokenKind::Str
+            },
+
+            ',' => TokenKind::Comma,
+            ';' => TokenKind::Semicolon,
+            '(' | '<' => TokenKind::Open(chr),
+            ')' | '>' => TokenKind::Close(chr),
+            _ => TokenKind::Punct(chr)
+        };
+
+        let end = chars.peek().map_or(source.len(), |(idx, _)| *idx);
+        tokens.push(Token{kind, range: TextRange{start, end}});
+    }
+
+    Ok(tokens)
+}
+
+/// Whether the text is cut short of its statement - statements continue across lines until they end with `;`, which
+/// string literals and comments never do
+pub fn is_unfinished(source: &str) -> bool {
+    match tokenize(source) {
+        Ok(tokens) => is_unfinished_tokens(&tokens),
+        // The only lexing error is an unterminated string literal
+        Err(_) => true
+    }
+}
+
+/// Like is_unfinished, on the tokens of a statement - without whitespace or comments
+pub fn is_unfinished_tokens(tokens: &[Token]) -> bool {
+    tokens.last().is_some_and(|token| token.kind != TokenKind::Semicolon)
+}
+
+/// The line (counting from 0) and column (counting from 1) of a byte offset within multi-line text
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count();
+    let col = before.rfind('\n').map_or(offset, |newline| offset - newline - 1);
+
+    (line, col + 1)
+}
+
+/// Lexes a single statement - `Ok(None)` if the text holds no tokens at all
+pub fn parse_statement(source: &str) -> Result<Option<Statement<'_>>, LexError> {
+    let tokens = tokenize(source)?;
+
+    let (cmd, rest) = match tokens.split_first() {
+        None => return Ok(None),
+        Some((cmd, rest)) if cmd.kind == TokenKind::Word => (cmd, rest),
+        Some((token, _)) => return Err(LexError{range: token.range, message: format!("expected a command, found: {}", &source[token.range.start..token.range.end])})
+    };
+
+    let (terminator, body) = match rest.split_last() {
+        Some((terminator, body)) if terminator.kind == TokenKind::Semicolon => (terminator, body),
+        _ => return Err(LexError{range: TextRange{start: cmd.range.start, end: source.len()}, message: "missing semicolon - the statement starting here never ends with ;".to_string()})
+    };
+
+    let mut args = vec![];
+    let mut depth: Vec<&Token> = vec![];
+    let mut arg_start: Option<TextRange> = None;
+    let mut arg_end = 0;
+
+    for token in body {
+        match token.kind {
+            TokenKind::Semicolon => return Err(LexError{range: token.range, message: "unexpected ; before the end of the statement".to_string()}),
+
+            TokenKind::Comma if depth.is_empty() => {
+                match arg_start.take() {
+                    Some(start) => args.push((&source[start.start..arg_end], TextRange{start: start.start, end: arg_end})),
+                    None => return Err(LexError{range: token.range, message: "empty argument".to_string()})
+                }
+
+                continue;
+            },
+
+            TokenKind::Open(_) => depth.push(token),
+
+            TokenKind::Close(chr) => match depth.pop() {
+                Some(Token{kind: TokenKind::Open(open), ..}) if matches!((open, chr), ('(', ')') | ('<', '>')) => (),
+                _ => return Err(LexError{range: token.range, message: format!("unmatched {}", chr)})
+            },
+
+            _ => ()
+        }
+
+        arg_start.get_or_insert(token.range);
+        arg_end = token.range.end;
+    }
+
+    if let Some(open) = depth.last() {
+        return Err(LexError{range: open.range, message: "unclosed bracket".to_string()});
+    }
+
+    match arg_start {
+        Some(start) => args.push((&source[start.start..arg_end], TextRange{start: start.start, end: arg_end})),
+        None if !body.is_empty() => return Err(LexError{range: terminator.range, message: "empty argument".to_string()}),
+        None => ()
+    }
+
+    Ok(Some(Statement{cmd: &source[cmd.range.start..cmd.range.end], cmd_range: cmd.range, args}))
+}
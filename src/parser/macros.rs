@@ -0,0 +1,143 @@
+use std::sync::OnceLock;
+use regex::Regex;
+use super::lexer::{self, TokenKind};
+
+static DEFMACRO_REGEX: &str = r"^defmacro\s+(?<name>[a-zA-Z0-9_]+)\s*\((?<params>[^)]*)\)\s*\{(?<body>.*)$";
+static DEFMACRO_RE: OnceLock<Regex> = OnceLock::new();
+
+/// Nested expansions deeper than this are assumed to be a macro (indirectly) invoking itself
+pub const MAX_EXPANSION_DEPTH: usize = 32;
+
+/// A `defmacro NAME(PARAM, ...) { STATEMENT; ... }` definition - its body may span multiple lines, up to a lone `}`
+#[derive(Debug)]
+pub struct Macro {
+    name: String,
+    params: Vec<String>,
+    body: Vec<String>,
+
+    /// The start of a statement wrapped onto the next line of the body
+    unterminated: String
+}
+
+impl Macro {
+    /// Parses the `defmacro` line - returns the macro, and whether its body is still open (continuing on the next lines)
+    pub fn parse_header(line: &str) -> Option<(Self, bool)> {
+        let defmacro_re = DEFMACRO_RE.get_or_init(|| Regex::new(DEFMACRO_REGEX).unwrap());
+        let header = defmacro_re.captures(line.trim())?;
+
+        let params: Vec<String> = header["params"].split(',').map(|param| param.trim().to_string()).filter(|param| !param.is_empty()).collect();
+        let mut mac = Self{name: header["name"].to_string(), params, body: vec![], unterminated: String::new()};
+
+        let body = header["body"].trim();
+        let (body, open) = match body.strip_suffix('}') {
+            Some(body) => (body, false),
+            None => (body, true)
+        };
+
+        mac.push_body(body);
+        Some((mac, open))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adds the statements of a line of the macro's body - split at the `;`s outside of string literals. A statement
+    /// without its `;` yet continues on the next line.
+    pub fn push_body(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+
+        let text = match self.unterminated.is_empty() {
+            true => line.to_string(),
+            false => format!("{}\n{}", self.unterminated, line)
+        };
+
+        // The only lexing error is an unterminated string literal, which continues on the next line too
+        let Ok(tokens) = lexer::tokenize(&text) else {
+            self.unterminated = text;
+            return;
+        };
+
+        let mut start = 0;
+        for semicolon in tokens.iter().filter(|token| token.kind == TokenKind::Semicolon) {
+            let statement = text[start..semicolon.range.end].trim();
+            if statement != ";" {
+                self.body.push(statement.to_string());
+            }
+
+            start = semicolon.range.end;
+        }
+
+        self.unterminated = text[start..].trim().to_string();
+    }
+
+    /// Whether every statement of the body so far ended with its `;`
+    pub fn is_terminated(&self) -> bool {
+        self.unterminated.is_empty()
+    }
+
+    /// The statements of one expansion - `$PARAM` words are substituted, and labels defined by the macro are renamed
+    /// (along with the jumps to them) so that every expansion gets its own. `$` references that aren't parameters of
+    /// the macro (like a generic program's `$A`) and string literals are left alone.
+    pub fn expand(&self, args: &[&str], expansion: usize) -> Result<Vec<String>, String> {
+        if args.len() != self.params.len() {
+            return Err(format!("Macro ({}) takes {} arguments, got {}", self.name, self.params.len(), args.len()));
+        }
+
+        let statements: Vec<String> = self.body.iter().map(|statement| map_words(statement, |_, word| {
+            let param = self.params.iter().position(|param| word.strip_prefix('$') == Some(param.as_str()));
+            param.map(|idx| args[idx].trim().to_string())
+        })).collect();
+
+        // Labels are the first argument of the statements defining and jumping to them
+        let mut labels: Vec<String> = vec![];
+        for statement in statements.iter() {
+            let mut defines = false;
+            map_words(statement, |idx, word| {
+                defines |= idx == 0 && word == "label";
+                if defines && idx == 1 {
+                    labels.push(word.to_string());
+                }
+
+                None
+            });
+        }
+
+        Ok(statements.iter().map(|statement| {
+            let mut labelled = false;
+            map_words(statement, |idx, word| {
+                labelled |= idx == 0 && (word == "label" || word.starts_with("jump_"));
+                match labelled && idx == 1 && labels.iter().any(|label| label == word) {
+                    true => Some(format!("{}__{}_{}", word, self.name, expansion)),
+                    false => None
+                }
+            })
+        }).collect())
+    }
+}
+
+/// The statement with the words `replace` returns something for replaced - `replace` is given each word's position
+/// among the statement's tokens. Everything else (string literals, punctuation and whitespace) is kept as it is.
+fn map_words(statement: &str, mut replace: impl FnMut(usize, &str) -> Option<String>) -> String {
+    // Body statements were lexed when they were added, so they lex fine
+    let tokens = lexer::tokenize(statement).unwrap_or_default();
+
+    let mut mapped = String::new();
+    let mut end = 0;
+    for (idx, token) in tokens.iter().enumerate() {
+        let text = &statement[token.range.start..token.range.end];
+        mapped.push_str(&statement[end..token.range.start]);
+        end = token.range.end;
+
+        match token.kind {
+            TokenKind::Word => mapped.push_str(&replace(idx, text).unwrap_or_else(|| text.to_string())),
+            _ => mapped.push_str(text)
+        }
+    }
+
+    mapped.push_str(&statement[end..]);
+    mapped
+}
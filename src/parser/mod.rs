@@ -1,303 +1,593 @@
+mod codegen;
+mod diagnostic;
+mod dot;
+mod formatter;
+mod graph;
+mod highlight;
+mod index;
+mod ir;
+mod lexer;
+mod macros;
+mod span;
 mod state;
-use state::State;
-use regex::Regex;
-use quote::quote;
-
-static COMMENT_REGEX: &str = r"^(#+)(?<comment>.*)(#*)$";
-static CMD_REGEX: &str = r"^(?<cmd>[a-zA-Z0-9_]+)([\s]+(?<args>.+))?;$";
+use convert_case::{Case, Casing};
+use macros::{Macro, MAX_EXPANSION_DEPTH};
+use state::{check_name, Constants, State};
+pub use codegen::Codegen;
+pub use diagnostic::{Diagnostic, Diagnostics, Lint, Severity};
+pub use formatter::format_source;
+pub use graph::{ConnectEdge, ConnectGraph, Cycle};
+pub use highlight::{textmate_grammar, tokenize, SemanticToken, SemanticTokenKind};
+pub use index::{Index, Symbol, SymbolKind};
+pub use ir::{Ir, IrDefinition, IrStatement, IR_VERSION};
+pub use span::Span;
+pub use state::ExitChars;
 
 pub struct Parser<'a> {
     filename: &'a str,
     state: State,
+    definitions: Vec<State>,
     source: String,
-    lineno: usize
+    lineno: usize,
+    source_hash: u64,
+    entry: Option<String>,
+    allow_cycles: bool,
+    codegen: bool,
+    format: bool,
+    inline_runtime: bool,
+    trace: bool,
+    assert_monotonic: bool,
+    optimize: bool,
+    inline_connect: bool,
+    constants: Constants,
+    macros: Vec<Macro>,
+    open_macro: Option<Macro>,
+    expansions: usize,
+    expansion_depth: usize,
+
+    /// A statement wrapped across lines - the line it started on, and its text so far
+    pending: Option<(usize, String)>,
+
+    /// The `#` comments read since the last statement - they document the statement right below them, or the
+    /// definition whose `def...` header they directly follow
+    comments: Vec<String>,
+    after_header: bool,
+
+    /// Every alphabet, clock and program defined so far - its kind, name and where it was defined
+    defined: Vec<(&'static str, String, Span)>,
+
+    /// Where every name of the source is defined and used - statements expanded from macros are left out
+    index: Index
 }
 
 impl<'a> Parser<'a> {
     pub const fn new(filename: &'a str) -> Self {
         Self{
-            filename: filename,
+            filename,
             state: State::General,
+            definitions: vec![],
             source: String::new(),
-            lineno: 0
+            lineno: 0,
+            source_hash: codegen::SOURCE_HASH_INIT,
+            entry: None,
+            allow_cycles: false,
+            codegen: true,
+            format: true,
+            inline_runtime: false,
+            trace: false,
+            assert_monotonic: false,
+            optimize: false,
+            inline_connect: false,
+            constants: Constants::new(),
+            macros: vec![],
+            open_macro: None,
+            expansions: 0,
+            expansion_depth: 0,
+            pending: None,
+            comments: vec![],
+            after_header: false,
+            defined: vec![],
+            index: Index::new()
         }
     }
 
-    pub fn parse_line(&mut self, line: String) {
-        self.lineno += 1;
-        let cmd_re = Regex::new(CMD_REGEX).unwrap();
-        let comment_re = Regex::new(COMMENT_REGEX).unwrap();
-
-        if let Some(cmd) = cmd_re.captures(&line) {
-            let args: Vec<&str> = cmd["args"].split(",").collect();
-
-            match (&cmd["cmd"], &args[..]) {
-                ("defalphabet", [name]) => self.start_state(State::alphabet(name.to_string())),
-                ("defclock", [name]) => self.start_state(State::clock(name.to_string())),
-                ("defprogram", [name]) => self.start_state(State::program(name.to_string())),
-                (cmd, args) => {
-                    self.state.process_command(self.filename, self.lineno, cmd, args);
-                }
-            }
-        } else if let Some(_comment) = comment_re.captures(&line) {
-            ()
+    /// Cyclic `connect` topologies are rejected by `generate` unless explicitly allowed (they then rely on the scheduler)
+    pub fn set_allow_cycles(&mut self, allow_cycles: bool) {
+        self.allow_cycles = allow_cycles;
+    }
+
+    /// Without codegen, parsing only builds the definitions - nothing is generated (or run through rustfmt), for `check`
+    pub fn set_codegen(&mut self, codegen: bool) {
+        self.codegen = codegen;
+    }
+
+    /// Without formatting, generated code skips rustfmt - it's emitted unformatted, which needs no rustfmt install and is much faster
+    pub fn set_format(&mut self, format: bool) {
+        self.format = format;
+    }
+
+    /// Generated code imports the shared runtime from the time_lang_runtime crate, unless it is inlined - for single-file
+    /// output without any dependencies
+    pub fn set_inline_runtime(&mut self, inline_runtime: bool) {
+        self.inline_runtime = inline_runtime;
+    }
+
+    /// Programs generated from now on trace their streams - every push and pop is recorded, along with every jump, by the
+    /// time_lang_runtime crate's `trace` feature (as JSON lines) or its `tracing` feature (as `tracing` events, within a
+    /// span per label function run)
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Programs generated from now on have debug builds panic on any moment pushed onto an exit earlier than the one
+    /// pushed before it - the runtime check the `non_monotonic` lint can only warn of
+    pub fn set_assert_monotonic(&mut self, assert_monotonic: bool) {
+        self.assert_monotonic = assert_monotonic;
+    }
+
+    /// Programs generated from now on are optimized - labels that never run aren't generated, jumps that are never taken
+    /// are dropped, and consecutive characters pushed onto an exit are pushed in one go (all of them, or none if they
+    /// don't fit). Label functions that are never jumped to are gone from the generated struct, and a constant moment
+    /// pushed onto an exit several times in a row is pushed once.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    /// Programs generated from now on splice the programs they connect to into themselves, where they can - their labels
+    /// run as the caller's, straight off of the caller's streams, with no pipeline (or buffers) in between. Programs
+    /// connecting to others, or with stream options or settings of their own, are still run by the pipeline.
+    pub fn set_inline_connect(&mut self, inline_connect: bool) {
+        self.inline_connect = inline_connect;
+    }
+
+    /// Generated code also gets a `fn main()` running the named program - like a `set_entry NAME;` statement
+    pub fn set_entry(&mut self, entry: &str) {
+        self.entry = Some(entry.to_string());
+    }
+
+    pub fn connect_graph(&self) -> ConnectGraph {
+        let programs = self.definitions().filter_map(|state| state.as_program());
+        ConnectGraph::new(programs)
+    }
+
+    /// The `connect` topology of the programs as a Graphviz digraph
+    pub fn connect_dot(&self) -> String {
+        let programs: Vec<_> = self.definitions().filter_map(|state| state.as_program()).collect();
+        dot::connect_dot(&programs)
+    }
+
+    /// Every definition parsed so far, in source order
+    pub fn definitions(&self) -> impl Iterator<Item = &State> {
+        self.definitions.iter().chain(std::iter::once(&self.state)).filter(|state| !matches!(state, State::General))
+    }
+
+    /// The parsed definitions as they are held internally (including source spans), serialized to JSON
+    pub fn ast_json(&self) -> String {
+        let definitions: Vec<&State> = self.definitions().collect();
+        serde_json::to_string_pretty(&definitions).unwrap()
+    }
+
+    pub fn to_ir(&self) -> Ir {
+        Ir::new(self.definitions())
+    }
+
+    /// Hands every parsed definition - including the one still open - over to a code generator
+    pub fn into_codegen(self) -> Codegen {
+        let mut definitions = self.definitions;
+        if !matches!(self.state, State::General) {
+            definitions.push(self.state);
         }
+
+        let mut codegen = Codegen::new(definitions, self.source_hash);
+        codegen.set_format(self.format);
+        codegen.set_inline_runtime(self.inline_runtime);
+        codegen.set_trace(self.trace);
+        codegen.set_assert_monotonic(self.assert_monotonic);
+        codegen.set_optimize(self.optimize);
+        codegen.set_inline_connect(self.inline_connect);
+        codegen.set_entry(self.entry);
+        codegen
     }
 
-    pub fn generate(&self) -> Result<String, String> {
-        let header_code = rustfmt_wrapper::rustfmt(quote! {
-            use core::default::Default;
-            use core::fmt::Debug;
-        }).unwrap_or_else(|val| {
-            panic!("Error writing Header base code:\n{}", val);
-        });
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
 
-        let alphabet_code = rustfmt_wrapper::rustfmt(quote! {
-            #[derive(Debug)]
-            pub enum AlphabetError<CharRep: Debug> {
-                UnknownCharacter(CharRep),
-                UnexpectedError(&'static str),
-                NameNotFound()
-            }
+    pub fn into_index(self) -> Index {
+        self.index
+    }
 
-            pub trait AlphabetLike {
-                type CharRep: Copy + Clone + Debug;
-                type CharEnum: Copy + Clone + Debug;
+    pub fn warnings(&self) -> Vec<&str> {
+        self.definitions().flat_map(|state| state.warnings().iter().map(|warning| warning.as_str())).collect()
+    }
 
-                fn char_with_name(rep: &str) -> Result<Self::CharEnum, AlphabetError<&str>>;
-                fn to_char(rep: Self::CharRep) -> Result<Self::CharEnum, AlphabetError<Self::CharRep>>;
-                fn to_val(rep: Self::CharEnum) -> Self::CharRep;
-            }
-        }).unwrap_or_else(|val| {
-            panic!("Error writing Alphabet base code:\n{}", val);
-        });
-
-        let clock_code = rustfmt_wrapper::rustfmt(quote! {
-            pub enum ClockMoment<MomentRep> {
-                UnixSeconds(MomentRep),
-                UnixMilliseconds(MomentRep),
-                Quantity(MomentRep)
-            }
+    /// Parses a whole source - stops at the first error
+    pub fn parse_str(&mut self, source: &str) -> Result<(), Diagnostic> {
+        for line in source.lines() {
+            self.parse_line(line.to_string())?;
+        }
 
-            pub trait ClockLike {
-                type MomentRep: Copy + Clone + Debug;
+        if let Some(mac) = self.open_macro.as_ref() {
+            return Err(Diagnostic::at(&self.line_span(self.lineno), format!("Macro ({}) - body is never closed with a }}", mac.name())));
+        }
 
-                fn represents(&self) -> &str;
-                fn to_moment(rep: Self::MomentRep) -> ClockMoment<Self::MomentRep>;
-            }
+        if let Some((lineno, text)) = self.pending.take() {
+            self.parse_statement(lineno, &text)?;
+        }
 
-            pub trait AddableClockLike<MomentRep: core::ops::Add<Output = MomentRep>> {
-                fn add(moment: ClockMoment<MomentRep>, rep: MomentRep) -> ClockMoment<MomentRep> {
-                    match moment {
-                        ClockMoment::Quantity(orig_rep) => ClockMoment::Quantity(orig_rep + rep),
-                        ClockMoment::UnixMilliseconds(orig_rep) => ClockMoment::UnixMilliseconds(orig_rep + rep),
-                        ClockMoment::UnixSeconds(orig_rep) => ClockMoment::UnixSeconds(orig_rep + rep)
-                    }
-                }
-            }
-            
-        }).unwrap_or_else(|val| {
-            panic!("Error writing Clock base code:\n{}", val);
-        });
-
-        let stream_code = rustfmt_wrapper::rustfmt(quote! {
-            #[derive(Debug)]
-            pub enum ExitError {
-                BufferFull
+        Ok(())
+    }
+
+    pub fn parse_line(&mut self, line: String) -> Result<(), Diagnostic> {
+        self.lineno += 1;
+        self.source_hash = codegen::hash_source(self.source_hash, line.as_bytes());
+        self.source_hash = codegen::hash_source(self.source_hash, b"\n");
+        let line = strip_trailing_comment(&line);
+
+        if let Some(mut mac) = self.open_macro.take() {
+            if line.trim() == "}" {
+                self.define_macro(mac)
+            } else {
+                mac.push_body(line);
+                self.open_macro = Some(mac);
+                Ok(())
             }
-            
-            pub trait ExitLike<Alphabet: AlphabetLike, Clock: ClockLike> {
-                type InternalItem;
-                type Item;
-
-                fn set_initial_moment(&mut self, monent: Clock::MomentRep);
-                fn accepting_pushes(&mut self) -> bool;
-                fn push(&mut self, chr: Alphabet::CharEnum) -> Result<(), ExitError>;
-                fn push_moment(&mut self, moment: Clock::MomentRep) -> Result<(), ExitError>;
-
-                fn push_with_name(&mut self, chr_name: &str) -> Result<(), ExitError> {
-                    self.push(Alphabet::char_with_name(chr_name).unwrap_or_else(|_| { panic!("Unknown char name: {}", chr_name)}))
-                }
+        } else if line.trim_start().starts_with("defmacro") {
+            if let Some((lineno, _)) = self.pending.as_ref() {
+                return Err(Diagnostic::at(&self.line_span(*lineno), "missing semicolon - the statement starting here never ends with ;"));
             }
 
-            pub trait GatewayLike<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
-                type InternalItem;
-                type Item;
-                
-                fn pop(&mut self) -> Self::Item;
-                fn forward_duration<Exit: ExitLike<Alphabet, Clock>>(&mut self, exit: &mut Exit) -> Result<(), ExitError>;
-                fn current_moment(&self) -> Option<Clock::MomentRep>;
-                fn is_empty(&self) -> bool;
-                fn next_is_character(&self) -> bool;
-                fn next_is_moment(&self) -> bool;
+            if let Some((mac, _)) = Macro::parse_header(line) {
+                let col = line.find(mac.name()).map_or(1, |idx| idx + 1);
+                let header = line.split('{').next().unwrap_or(line).trim();
+                self.index.define(SymbolKind::Macro, mac.name(), Span::new(self.filename, self.lineno, col), None, header.to_string());
             }
 
-            #[derive(Copy, Clone, Debug)]
-            pub enum StreamItem<CharacterRep, Moment> {
-                Empty,
-                Character(CharacterRep),
-                Moment(Moment)
-            }
+            match Macro::parse_header(line) {
+                Some((mac, true)) => {
+                    self.open_macro = Some(mac);
+                    Ok(())
+                },
 
-            impl<CharacterRep, Moment> Default for StreamItem<CharacterRep, Moment> {
-                fn default() -> Self { Self::Empty }
+                Some((mac, false)) => self.define_macro(mac),
+                None => Err(Diagnostic::at(&self.line_span(self.lineno), "Invalid macro definition - expected defmacro NAME(PARAM, ...) { ... }"))
             }
+        } else {
+            self.continue_statement(line)
+        }
+    }
 
-            pub struct Stream<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
-                buffer: [StreamItem<Alphabet::CharRep, Clock::MomentRep>; BUFFER_SIZE],
-                idx: usize,
-                buffered_total: usize,
-                buffered_moments: usize,
-                buffered_characters: usize,
-                last_seen_moment: Option<Clock::MomentRep>
-            }
+    /// Statements may wrap across lines anywhere - they are parsed once they end with `;`
+    fn continue_statement(&mut self, line: &str) -> Result<(), Diagnostic> {
+        let comment = line.trim_start().starts_with('#');
+        if self.pending.is_none() && comment {
+            let text = line.trim_start()[1..].strip_prefix(' ').unwrap_or(&line.trim_start()[1..]);
+            self.comments.push(text.trim_end().to_string());
+            return Ok(());
+        }
 
-            impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> Stream<Alphabet, Clock, BUFFER_SIZE> {
-                pub const fn new() -> Self {
-                    Self {
-                        buffer: [StreamItem::Empty; BUFFER_SIZE],
-                        idx: 0,
-                        buffered_total: 0,
-                        buffered_moments: 0,
-                        buffered_characters: 0,
-                        last_seen_moment: None
-                    }
-                }
+        if self.pending.is_none() && line.trim().is_empty() {
+            self.end_comment_block();
+            return Ok(());
+        }
 
-                fn inc_index(&mut self) {
-                    self.idx = (self.idx + 1) % BUFFER_SIZE;
-                }
+        let (lineno, mut text) = self.pending.take().unwrap_or((self.lineno, String::new()));
+        if !text.is_empty() {
+            text.push('\n');
+        }
+
+        // Comments inside a wrapped statement still take up their line, so positions within it stay right
+        if !comment {
+            text.push_str(line);
+        }
+
+        if lexer::is_unfinished(&text) {
+            self.pending = Some((lineno, text));
+            Ok(())
+        } else {
+            self.parse_statement(lineno, &text)
+        }
+    }
+
+    /// Below a header, blank lines separate the paragraphs of the definition's doc - anywhere else, they detach
+    /// comments from the statement below them
+    fn end_comment_block(&mut self) {
+        if self.after_header && !self.comments.is_empty() {
+            if self.comments.last().is_some_and(|line| !line.is_empty()) {
+                self.comments.push(String::new());
             }
+        } else {
+            self.comments.clear();
+            self.after_header = false;
+        }
+    }
 
-            impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> ExitLike<Alphabet, Clock> for Stream<Alphabet, Clock, BUFFER_SIZE> {
-                type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
-                type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep>;
+    /// Errors that aren't about a single statement (like macro definitions) point at the start of their line
+    fn line_span(&self, lineno: usize) -> Span {
+        Span::new(self.filename, lineno, 1)
+    }
 
-                fn set_initial_moment(&mut self, moment: Clock::MomentRep) {
-                    self.last_seen_moment = Some(moment);
-                }
+    fn define_macro(&mut self, mac: Macro) -> Result<(), Diagnostic> {
+        if self.macros.iter().any(|defined| defined.name() == mac.name()) {
+            return Err(Diagnostic::at(&self.line_span(self.lineno), format!("Macro ({}) - already defined", mac.name())));
+        }
 
-                fn accepting_pushes(&mut self) -> bool { self.buffered_total < BUFFER_SIZE }
-
-                fn push(&mut self, chr: Alphabet::CharEnum) -> Result<(), ExitError> {
-                    if self.accepting_pushes() {
-                        self.buffer[self.idx + self.buffered_total % BUFFER_SIZE] = Self::InternalItem::Character(Alphabet::to_val(chr));
-                        self.buffered_characters += 1;
-                        self.buffered_total += 1;
-                        Ok(())
-                    } else {
-                        Err(ExitError::BufferFull)
-                    }
-                }
+        if !mac.is_terminated() {
+            return Err(Diagnostic::at(&self.line_span(self.lineno), format!("Macro ({}) - missing semicolon at the end of its body", mac.name())));
+        }
 
-                fn push_moment(&mut self, moment: Clock::MomentRep) -> Result<(), ExitError> {
-                    if self.accepting_pushes() {
-                        self.buffer[self.idx + self.buffered_total % BUFFER_SIZE] = Self::InternalItem::Moment(moment);
-                        self.buffered_moments += 1;
-                        self.buffered_total += 1;
-                        Ok(())
-                    } else {
-                        Err(ExitError::BufferFull)
-                    }
-                }
-            }
+        self.macros.push(mac);
+        Ok(())
+    }
 
-            impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> GatewayLike<Alphabet, Clock, BUFFER_SIZE> for Stream<Alphabet, Clock, BUFFER_SIZE> {
-                type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
-                type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep>;
-
-                fn pop(&mut self) -> Self::Item {
-                    let last = core::mem::take(&mut self.buffer[self.idx]);
-
-                    match last {
-                        Self::InternalItem::Character(chr) => {
-                            self.inc_index();
-                            self.buffered_characters -= 1;
-                            self.buffered_total -= 1;
-                            Self::Item::Character(Alphabet::to_char(chr).unwrap_or_else(|err| {
-                                panic!("Unexpected character received in stream: {:?}", err);
-                            }))
-                        },
-
-                        Self::InternalItem::Moment(moment) => {
-                            self.inc_index();
-                            self.buffered_moments -= 1;
-                            self.buffered_total -= 1;
-                            self.last_seen_moment = Some(moment);
-                            Self::Item::Moment(moment)
-                        },
-                        
-                        Self::InternalItem::Empty => Self::Item::Empty
-                    }
-                }
-                
-                fn forward_duration<Exit: ExitLike<Alphabet, Clock>>(&mut self, exit: &mut Exit) -> Result<(), ExitError> {
-                    while self.next_is_character() {
-                        match self.pop() {
-                            Self::Item::Character(chr) => {
-                                let result = exit.push(chr);
-                                match result {
-                                    Ok(_) => (),
-                                    Err(err) => {
-                                        return Err(err)
-                                    }
-                                }
-                            },
-
-                            item => panic!("Expected to pop Character off Gateway. Popped something else:\n{:?}", item)
-                        }
-                    };
-                    
-                    Ok(())
-                }
+    /// Macro invocations are expanded here, before the state machine sees any of their statements
+    fn expand_macro(&mut self, span: &Span, name: &str, args: &[&str]) -> Result<bool, Diagnostic> {
+        let statements = match self.macros.iter().find(|mac| mac.name() == name) {
+            Some(mac) => mac.expand(args, self.expansions).map_err(|err| Diagnostic::at(span, err))?,
+            None => return Ok(false)
+        };
 
-                fn current_moment(&self) -> Option<Clock::MomentRep> {
-                    self.last_seen_moment
-                }
+        if self.expansion_depth >= MAX_EXPANSION_DEPTH {
+            return Err(Diagnostic::at(span, format!("Macro ({}) - expansion is nested too deeply (recursive macro?)", name)));
+        }
 
-                fn is_empty(&self) -> bool {
-                    self.buffered_total == 0
-                }
+        self.expansions += 1;
+        self.expansion_depth += 1;
+        for statement in statements {
+            self.parse_statement(span.line, &statement).map_err(|err| err.with_note(format!("in this expansion of macro ({})", name)))?;
+        }
+        self.expansion_depth -= 1;
+
+        Ok(true)
+    }
+
+    /// Parses a complete statement - `lineno` is the line it starts on
+    fn parse_statement(&mut self, lineno: usize, text: &str) -> Result<(), Diagnostic> {
+        let statement = lexer::parse_statement(text).map_err(|err| {
+            let (line, col) = lexer::line_col(text, err.range.start);
+            Diagnostic::at(&Span::new(self.filename, lineno + line, col), err.to_string())
+        })?;
+
+        let Some(statement) = statement else {
+            return Ok(());
+        };
+
+        let (line, col) = lexer::line_col(text, statement.cmd_range.start);
+        let span = Span::new(self.filename, lineno + line, col);
+        let args: Vec<&str> = statement.args.iter().map(|(arg, _)| *arg).collect();
+
+        // A comment block documents the statement right below it (definitions, def_char and label) - unless it directly
+        // follows a definition's header. Statements expanded from a macro leave the comments alone.
+        let mut doc = if self.expansion_depth == 0 { std::mem::take(&mut self.comments) } else { vec![] };
+        while doc.last().is_some_and(|line| line.is_empty()) {
+            doc.pop();
+        }
+
+        if self.after_header && !doc.is_empty() {
+            self.index.document_definition(doc.clone());
+            self.state.set_doc(std::mem::take(&mut doc));
+        }
 
-                fn next_is_character(&self) -> bool {
-                    match self.buffer[self.idx] {
-                        Self::InternalItem::Character(_) => true,
-                        _ => false
-                    }
+        if self.expansion_depth == 0 {
+            self.index.record(self.filename, lineno, text, &statement);
+        }
+
+        let is_header = matches!(statement.cmd, "defalphabet" | "defclock" | "defprogram");
+        if self.expansion_depth == 0 {
+            self.after_header = is_header;
+        }
+
+        let result = match (statement.cmd, &args[..]) {
+            ("defalphabet", [name]) => check_name(&span, "Alphabet", "name", name).and_then(|_| self.start_state(&span, State::alphabet(span.clone(), name.to_string()))),
+            ("defclock", [name]) => check_name(&span, "Clock", "name", name).and_then(|_| self.start_state(&span, State::clock(span.clone(), name.to_string()))),
+            ("defprogram", [definition]) => self.start_state(&span, State::program(span.clone(), definition)?),
+
+            // Constants are global, so they're defined up front - before any alphabet, clock or program
+            ("defconst", [name, value]) => match self.state {
+                State::General => self.constants.define(&span, name, value),
+                _ => Err(Diagnostic::at(&span, format!("Constant ({}) - defconst must come before the first definition", name)))
+            },
+
+            // The entry program may be defined anywhere in the source, so it's only looked up once generating
+            ("set_entry", [name]) => match self.entry.as_ref() {
+                Some(entry) => Err(Diagnostic::at(&span, format!("Entry program ({}) - the entry is already set to {}", name, entry))),
+                None => {
+                    self.set_entry(name);
+                    Ok(())
                 }
+            },
 
-                fn next_is_moment(&self) -> bool {
-                    match self.buffer[self.idx] {
-                        Self::InternalItem::Moment(_) => true,
-                        _ => false
-                    }
+            (cmd, args) => {
+                if self.expand_macro(&span, cmd, args)? {
+                    Ok(())
+                } else {
+                    self.state.process_command(&span, cmd, args, &self.constants)
                 }
             }
-        }).unwrap_or_else(|val| {
-            panic!("Error writing Stream base code:\n{}", val);
-        });
-
-        let mut code = header_code.to_string();
-        code.push_str(format!("\n{}", alphabet_code).as_str());
-        code.push_str(format!("\n{}", clock_code).as_str());
-        code.push_str(format!("\n{}", stream_code).as_str());
+        };
+
+        if result.is_ok() && !doc.is_empty() {
+            if is_header {
+                self.index.document_definition(doc.clone());
+                self.state.set_doc(doc);
+            } else {
+                self.index.document_latest(doc.clone());
+                self.state.document(statement.cmd, &args, doc);
+            }
+        }
+
+        result
+    }
+
+    pub fn check_cycles(&self) -> Result<(), String> {
+        let graph = self.connect_graph();
+        let cycles = graph.cycles();
+        if !self.allow_cycles && !cycles.is_empty() {
+            let report: Vec<String> = cycles.iter().map(|cycle| cycle.to_string()).collect();
+            return Err(format!("Cyclic connect topology (allow it with set_allow_cycles):\n{}", report.join("\n")));
+        }
+
+        Ok(())
+    }
+
+    /// Every semantic problem of the parsed definitions (streams, labels, alphabets, clocks and connects), without generating code
+    pub fn check(&self) -> Vec<Diagnostic> {
+        let definitions: Vec<&State> = self.definitions().collect();
+        let mut diagnostics: Vec<Diagnostic> = definitions.iter().flat_map(|state| state.validate(&definitions)).collect();
+
+        if let Err(err) = self.check_cycles() {
+            diagnostics.push(Diagnostic::new(self.filename, err));
+        }
+
+        if let Some(Err(diagnostic)) = self.entry.as_ref().map(|entry| codegen::entry_program(self.definitions(), self.filename, entry)) {
+            diagnostics.push(diagnostic);
+        }
+
+        diagnostics
+    }
+
+    /// Warnings about what never does anything - unused streams, labels that never run and jumps never taken - and
+    /// about exits that may be pushed durations longer than their buffers hold
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        let definitions: Vec<&State> = self.definitions().collect();
+        definitions.iter().flat_map(|state| state.lint(&definitions)).collect()
+    }
+
+    /// The characters of its alphabet each exit of every program may be pushed, fed anything on its gateways - from
+    /// `push_char`, `push_val`, and what its instructions (and the programs it connects to) forward
+    pub fn reachable_chars(&self) -> Vec<ExitChars> {
+        let definitions: Vec<&State> = self.definitions().collect();
+        definitions.iter().flat_map(|state| state.reachable_chars(&definitions)).collect()
+    }
+
+    /// Generates the parsed source - failing with the first problem `check` finds, so nothing invalid is generated
+    pub fn generate(&self) -> Result<String, Diagnostic> {
+        if let Some(diagnostic) = self.check().into_iter().next() {
+            return Err(diagnostic);
+        }
+
+        codegen::check_trace(self.filename, self.trace, self.inline_runtime)?;
+
+        let mut code = codegen::generated_header(self.source_hash);
+        code.push_str(&codegen::runtime_header(self.inline_runtime, codegen::uses_dynamic_streams(self.definitions()), self.format));
         code.push_str(format!("\n{}", self.source).as_str());
-        
+
+        // Definitions are generated as the next one starts - the one still open at the end of the source isn't yet
+        if self.codegen {
+            let definitions: Vec<&State> = self.definitions.iter().collect();
+            let generated_code = self.state.generate(self.format, &definitions)?;
+            if !generated_code.is_empty() {
+                code.push_str(generated_code.as_str());
+                code.push('\n');
+            }
+        }
+
+        if let Some(entry) = self.entry.as_ref() {
+            let programs: Vec<_> = self.definitions().filter_map(|state| state.as_program()).collect();
+            code.push_str(&codegen::entry_program(self.definitions(), self.filename, entry)?.generate_main(self.format, &programs)?);
+        }
+
         Ok(code)
     }
 
-    fn start_state(&mut self, state: State) {
-        match self.state.generate() {
-            Ok(generated_code) => {
-                self.source.push_str(generated_code.as_str());
-                self.source.push_str("\n");
-            },
+    /// The shared runtime (alphabet/clock/stream traits and the Stream type) that all generated definitions build upon
+    pub fn runtime(&self) -> String {
+        codegen::runtime(self.format)
+    }
 
-            Err(err) => {
-                panic!("Error generating code:\n{}\n\n{:?}", err, state);
-            }
+    /// The heap-backed parts of the runtime (VecStream) - `gated` behind the `alloc` feature, as the time_lang_runtime
+    /// crate carries them, or not, as inlined runtimes of programs with `dynamic` streams do
+    pub fn alloc_runtime(&self, gated: bool) -> String {
+        codegen::alloc_runtime(self.format, gated)
+    }
+
+    /// The std-only parts of the runtime (the executors, io adapters, serde support and tracing), its dates and times of
+    /// moments and its embedded-hal adapters - never inlined, the time_lang_runtime crate carries them behind its `std`,
+    /// `async`, `serde`, `json`, `mqtt`, `kafka`, `parquet`, `trace`, `tracing`, `time` and `embedded-hal` features
+    pub fn std_runtime(&self) -> String {
+        codegen::std_runtime(self.format)
+    }
+
+    /// Parses the sources that follow into the same definitions, as `filename` - they may use everything defined before
+    /// them, and the definition left open by the previous source is closed
+    pub fn begin_source(&mut self, filename: &'a str) -> Result<(), Diagnostic> {
+        self.close_state(State::General)?;
+        self.filename = filename;
+        self.lineno = 0;
+        self.comments.clear();
+        self.after_header = false;
+
+        Ok(())
+    }
+
+    fn start_state(&mut self, span: &Span, mut state: State) -> Result<(), Diagnostic> {
+        self.define(span, &state)?;
+        if let Some(prog) = state.as_program_mut() {
+            prog.set_trace(self.trace);
+            prog.set_assert_monotonic(self.assert_monotonic);
+            prog.set_optimize(self.optimize);
+            prog.set_inline_connect(self.inline_connect);
+        }
+
+        self.close_state(state)
+    }
+
+    /// Closes the open definition (generating it, unless the parser only builds definitions), and opens `state`
+    fn close_state(&mut self, state: State) -> Result<(), Diagnostic> {
+        if self.codegen {
+            let definitions: Vec<&State> = self.definitions.iter().collect();
+            let generated_code = self.state.generate(self.format, &definitions)?;
+            self.source.push_str(generated_code.as_str());
+            self.source.push('\n');
         }
 
-        self.state = state;
+        let previous = std::mem::replace(&mut self.state, state);
+        if !matches!(previous, State::General) {
+            self.definitions.push(previous);
+        }
+
+        Ok(())
     }
+
+    /// Definitions of a kind generate items of the same namespace - names only differing in case (`sync2` and
+    /// `Sync2`) would generate the same ones
+    fn define(&mut self, span: &Span, state: &State) -> Result<(), Diagnostic> {
+        let kind = match state {
+            State::Alphabet(_) => "Alphabet",
+            State::Clock(_) => "Clock",
+            _ => "Program"
+        };
+
+        let ident = state.name().to_case(Case::Pascal);
+        if let Some((_, name, first)) = self.defined.iter().find(|(defined, name, _)| *defined == kind && name.to_case(Case::Pascal) == ident) {
+            let diagnostic = Diagnostic::at(span, format!("{} ({}) - already defined", kind, state.name()));
+            return Err(diagnostic.with_note(format!("{} ({}) is first defined at {}", kind, name, first)));
+        }
+
+        self.defined.push((kind, state.name().to_string(), span.clone()));
+        Ok(())
+    }
+}
+
+/// Cuts a trailing `# comment` off of a statement - `#`s inside double-quoted literals don't start comments.
+/// Lines that are entirely a comment are kept, for the comment handling of the line parser.
+fn strip_trailing_comment(line: &str) -> &str {
+    if line.trim_start().starts_with('#') {
+        return line;
+    }
+
+    let mut in_literal = false;
+    let mut escaped = false;
+
+    for (idx, chr) in line.char_indices() {
+        match chr {
+            _ if escaped => escaped = false,
+            '\\' if in_literal => escaped = true,
+            '"' => in_literal = !in_literal,
+            '#' if !in_literal => return line[..idx].trim_end(),
+            _ => ()
+        }
+    }
+
+    line
 }
\ No newline at end of file
@@ -0,0 +1,22 @@
+use std::fmt;
+use serde::Serialize;
+
+/// Where something appears in the source - displayed as `file:line:col`, the prefix of every semantic error
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,
+    pub col: usize
+}
+
+impl Span {
+    pub fn new(file: &str, line: usize, col: usize) -> Self {
+        Self{file: file.to_string(), line, col}
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
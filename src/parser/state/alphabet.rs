@@ -1,51 +1,183 @@
+use std::collections::BTreeMap;
 use quote::{format_ident, quote};
 use convert_case::{Case, Casing};
+use serde::Serialize;
+use super::{check_name, doc_attrs, format_tokens, hash_source, parse_number, parse_visibility, visibility_tokens, Constants, Diagnostic, FormatError, Span, SOURCE_HASH_INIT};
 
-#[derive(Debug)]
+/// Derives every char enum has - `set_derives` adds to these
+const DEFAULT_DERIVES: [&str; 3] = ["Copy", "Clone", "Debug"];
+
+#[derive(Debug, Serialize)]
 pub struct Alphabet {
+    span: Span,
     name: String,
     char_type: Option<String>,
-    chars: Vec<(String, String)>
+    chars: Vec<(String, String, Span)>,
+    classes: Vec<(String, Vec<String>, Span)>,
+    derives: Vec<String>,
+    visibility: Option<String>,
+    doc: Vec<String>,
+    char_docs: BTreeMap<String, Vec<String>>
 }
 
 impl Alphabet {
-    pub const fn new(name: String) -> Self {
-        Self{name: name, char_type: None, chars: vec![]}
+    pub const fn new(span: Span, name: String) -> Self {
+        Self{span, name, char_type: None, chars: vec![], classes: vec![], derives: vec![], visibility: None, doc: vec![], char_docs: BTreeMap::new()}
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_doc(&mut self, doc: Vec<String>) {
+        self.doc = doc;
     }
 
-    pub fn process_command(&mut self, filename: &str, lineno: usize, cmd: &str, args: &[&str]) {
+    pub fn document_char(&mut self, name: &str, doc: Vec<String>) {
+        self.char_docs.insert(name.to_string(), doc);
+    }
+
+    pub fn process_command(&mut self, span: &Span, cmd: &str, args: &[&str], constants: &Constants) -> Result<(), Diagnostic> {
         match (cmd, args) {
             ("set_char_type", [char_type]) => {
+                check_name(span, &format!("Alphabet ({})", self.name), "character type", char_type)?;
                 self.char_type = Some(char_type.to_string());
             },
 
             ("def_char", [hex_rep, name]) => {
-                self.chars.push((hex_rep.to_string(), name.to_string()));
+                let hex_rep = &constants.resolve(span, hex_rep)?;
+                if syn::parse_str::<syn::Lit>(hex_rep).is_err() {
+                    return Err(Diagnostic::at(span, format!("Alphabet ({}) - invalid character value: {}", self.name, hex_rep)));
+                }
+
+                check_name(span, &format!("Alphabet ({})", self.name), "character name", name)?;
+                self.chars.push((hex_rep.to_string(), name.to_string(), span.clone()));
+            },
+
+            ("set_derives", [derives]) => {
+                for derive in derives.split('|') {
+                    if syn::parse_str::<syn::Path>(derive).is_err() {
+                        return Err(Diagnostic::at(span, format!("Alphabet ({}) - invalid derive: {}", self.name, derive)));
+                    }
+
+                    if !DEFAULT_DERIVES.contains(&derive) && !self.derives.iter().any(|existing| existing == derive) {
+                        self.derives.push(derive.to_string());
+                    }
+                }
+            },
+
+            ("set_visibility", [visibility]) => {
+                self.visibility = Some(parse_visibility(span, &format!("Alphabet ({})", self.name), visibility)?);
+            },
+
+            ("def_class", [name, members]) => {
+                check_name(span, &format!("Alphabet ({})", self.name), "class name", name)?;
+                self.classes.push((name.to_string(), members.split('|').map(|member| member.to_string()).collect(), span.clone()));
             },
             
             _ => {
-                panic!("{}:{} Alphabet ({}) - unknown command: {} ({:?})", filename, lineno, self.name, cmd, args);
+                return Err(Diagnostic::at(span, format!("Alphabet ({}) - unknown command: {} ({:?})", self.name, cmd, args)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The statements of the alphabet's body
+    pub fn statements(&self) -> Vec<(String, Vec<String>)> {
+        let mut statements = vec![];
+
+        if let Some(char_type) = self.char_type.as_ref() {
+            statements.push(("set_char_type".to_string(), vec![char_type.clone()]));
+        }
+
+        if !self.derives.is_empty() {
+            statements.push(("set_derives".to_string(), vec![self.derives.join("|")]));
+        }
+
+        if let Some(visibility) = self.visibility.as_ref() {
+            statements.push(("set_visibility".to_string(), vec![visibility.clone()]));
+        }
+
+        statements.extend(self.chars.iter().map(|(hex_rep, name, _)| ("def_char".to_string(), vec![hex_rep.clone(), name.clone()])));
+        statements.extend(self.classes.iter().map(|(name, members, _)| ("def_class".to_string(), vec![name.clone(), members.join("|")])));
+        statements
+    }
+
+    pub fn has_char(&self, name: &str) -> bool {
+        self.chars.iter().any(|(_, char_name, _)| char_name == name)
+    }
+
+    pub fn has_class(&self, name: &str) -> bool {
+        self.classes.iter().any(|(class_name, _, _)| class_name == name)
+    }
+
+    /// The names of the alphabet's characters, in the order they were defined
+    pub fn char_names(&self) -> impl Iterator<Item = &str> {
+        self.chars.iter().map(|(_, name, _)| name.as_str())
+    }
+
+    /// The name of the character with a raw value, if there is one
+    pub fn char_with_value(&self, value: u64) -> Option<&str> {
+        self.chars.iter().find(|(hex_rep, _, _)| parse_number(hex_rep) == Some(value)).map(|(_, name, _)| name.as_str())
+    }
+
+    pub fn class_members(&self, name: &str) -> Option<&[String]> {
+        self.classes.iter().find(|(class_name, _, _)| class_name == name).map(|(_, members, _)| members.as_slice())
+    }
+
+    /// Every problem generating the alphabet would run into
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+
+        if self.char_type.is_none() {
+            diagnostics.push(Diagnostic::at(&self.span, format!("Never called set_char_type on Alphabet ({})", self.name)));
+        }
+
+        for (class_name, members, class_span) in self.classes.iter() {
+            for member in members.iter().filter(|member| !self.has_char(member)) {
+                diagnostics.push(Diagnostic::at(class_span, format!("Class ({}) references unknown character ({}) in Alphabet ({})", class_name, member, self.name)));
             }
         }
+
+        diagnostics
     }
 
-    pub fn generate(&self) -> Result<String, String> {
+    /// Identifies what the alphabet defines - its character type, and each character's value and name - so streams
+    /// exchanged between processes built from different sources can be told apart
+    fn definition_hash(&self) -> u64 {
+        let mut hash = hash_source(SOURCE_HASH_INIT, self.char_type.as_deref().unwrap_or_default().as_bytes());
+
+        for (char_rep_val, char_name, _) in self.chars.iter() {
+            let val = parse_number(char_rep_val).map_or_else(|| char_rep_val.clone(), |val| val.to_string());
+            hash = hash_source(hash, format!("\0{}={}", val, char_name).as_bytes());
+        }
+
+        hash
+    }
+
+    pub fn generate(&self, format: bool) -> Result<String, Diagnostic> {
         let char_rep = format_ident!("{}", if let Some(ct) = self.char_type.as_ref() { ct.clone() } else {
-            return Err(format!("Never called set_char_type on Alphabet ({})", self.name).to_string())
+            return Err(Diagnostic::at(&self.span, format!("Never called set_char_type on Alphabet ({})", self.name)))
         });
 
         let char_enum_name = format_ident!("Char{}", self.name.to_case(Case::Pascal));
         let struct_name = format_ident!("Alphabet{}", self.name.to_case(Case::Pascal));
+        let visibility = visibility_tokens(self.visibility.as_ref());
+        let derives: Vec<syn::Path> = DEFAULT_DERIVES.iter().copied().chain(self.derives.iter().map(|derive| derive.as_str())).map(|derive| syn::parse_str(derive).unwrap()).collect();
 
-        let char_enums: Vec<_> = self.chars.iter().map(|(_, char_name)| {
+        let char_enums: Vec<_> = self.chars.iter().map(|(_, char_name, _)| {
             let rep_enum = format_ident!("{}", char_name.to_case(Case::Pascal));
+            let doc = doc_attrs(self.char_docs.get(char_name).map_or(&[], |doc| doc.as_slice()));
 
             quote!{
+                #doc
                 #rep_enum(),
             }
         }).collect();
+        let doc = doc_attrs(&self.doc);
 
-        let char_name_matches: Vec<_> = self.chars.iter().map(|(_char_rep_val, char_name)| {
+        let char_name_matches: Vec<_> = self.chars.iter().map(|(_char_rep_val, char_name, _)| {
             let rep_enum = format_ident!("{}", char_name.to_case(Case::Pascal));
 
             quote!{
@@ -53,7 +185,7 @@ impl Alphabet {
             }
         }).collect();
 
-        let char_matches: Vec<_> = self.chars.iter().map(|(char_rep_val, char_name)| {
+        let char_matches: Vec<_> = self.chars.iter().map(|(char_rep_val, char_name, _)| {
             let rep_enum = format_ident!("{}", char_name.to_case(Case::Pascal));
             let lit_rep: proc_macro2::TokenStream = char_rep_val.parse().unwrap();
 
@@ -62,7 +194,7 @@ impl Alphabet {
             }
         }).collect();
 
-        let char_to_val_matches: Vec<_> = self.chars.iter().map(|(char_rep_val, char_name)| {
+        let char_to_val_matches: Vec<_> = self.chars.iter().map(|(char_rep_val, char_name, _)| {
             let rep_enum = format_ident!("{}", char_name.to_case(Case::Pascal));
             let lit_rep: proc_macro2::TokenStream = char_rep_val.parse().unwrap();
 
@@ -71,15 +203,54 @@ impl Alphabet {
             }
         }).collect();
 
-        let formatted = rustfmt_wrapper::rustfmt(quote! {
-            #[derive(Copy, Clone, Debug)]
-            pub enum #char_enum_name {
+        let mut class_predicates = vec![];
+        let mut class_sets = vec![];
+        for (class_name, members, class_span) in self.classes.iter() {
+            let member_enums: Vec<_> = members.iter().map(|member| {
+                if !self.chars.iter().any(|(_, char_name, _)| char_name == member) {
+                    return Err(Diagnostic::at(class_span, format!("Class ({}) references unknown character ({}) in Alphabet ({})", class_name, member, self.name)))
+                }
+
+                let rep_enum = format_ident!("{}", member.to_case(Case::Pascal));
+                Ok(quote!{ #char_enum_name::#rep_enum() })
+            }).collect::<Result<_, _>>()?;
+
+            let predicate_name = format_ident!("is_{}", class_name.to_case(Case::Snake));
+            let set_name = format_ident!("Class{}", class_name.to_case(Case::Pascal));
+
+            class_predicates.push(quote!{
+                pub const fn #predicate_name(&self) -> bool {
+                    matches!(self, #(#member_enums)|*)
+                }
+            });
+
+            // Named like the types generated for the source's definitions (ClassWhitespace), not as a usual constant
+            class_sets.push(quote!{
+                #[allow(non_upper_case_globals)]
+                pub const #set_name: &'static [#char_enum_name] = &[#(#member_enums),*];
+            });
+        }
+
+        let name = &self.name;
+        let hash: proc_macro2::TokenStream = format!("0x{:016x}", self.definition_hash()).parse().unwrap();
+
+        let formatted = format_tokens(quote! {
+            #doc
+            #[derive(#(#derives),*)]
+            #visibility enum #char_enum_name {
                 #(#char_enums)*
             }
 
-            pub struct #struct_name {}
+            impl #char_enum_name {
+                #(#class_predicates)*
+            }
+
+            #doc
+            #visibility struct #struct_name {}
             
             impl #struct_name {
+                #(#class_sets)*
+
                 fn char_with_name(name: &str) -> Result<#char_enum_name, AlphabetError<&str>> {
                     use #char_enum_name::*;
                     match name {
@@ -108,6 +279,9 @@ impl Alphabet {
                 type CharRep = #char_rep;
                 type CharEnum = #char_enum_name;
 
+                const NAME: &'static str = #name;
+                const HASH: u64 = #hash;
+
                 fn char_with_name(name: &str) -> Result<#char_enum_name, AlphabetError<&str>> {
                     <#struct_name>::char_with_name(name)
                 }
@@ -120,12 +294,12 @@ impl Alphabet {
                     <#struct_name>::to_val(chr)
                 }
             }
-        });
+        }, format);
 
         match formatted {
             Ok(formatted_str) => Ok(formatted_str),
-            Err(rustfmt_wrapper::Error::Rustfmt(err)) => Err(format!("Error formatting Alphabet({}):\n{}", self.name, err)),
-            Err(err) => Err(format!("Error generating Alphabet({}):\n{}", self.name, err))
+            Err(FormatError::Rustfmt(err)) => Err(Diagnostic::at(&self.span, format!("Error formatting Alphabet({}):\n{}", self.name, err))),
+            Err(FormatError::Generate(err)) => Err(Diagnostic::at(&self.span, format!("Error generating Alphabet({}):\n{}", self.name, err)))
         }
     }
 }
\ No newline at end of file
@@ -1,48 +1,142 @@
 use quote::{format_ident, quote};
 use convert_case::{Case, Casing};
+use serde::Serialize;
+use super::{check_name, doc_attrs, format_tokens, parse_visibility, visibility_tokens, Diagnostic, FormatError, Span};
 
-#[derive(Debug)]
+/// Moment types that moments can be added onto - clocks with any other moment type are not `AddableClockLike`
+const ADDABLE_MOMENT_TYPES: [&str; 12] = ["u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize"];
+
+#[derive(Debug, Serialize)]
 pub struct Clock {
+    span: Span,
     name: String,
     moment_type: Option<String>,
-    repr: Option<String>
+    repr: Option<String>,
+    visibility: Option<String>,
+    doc: Vec<String>
 }
 
 impl Clock {
-    pub const fn new(name: String) -> Self {
-        Self{name: name, moment_type: None, repr: None}
+    pub const fn new(span: Span, name: String) -> Self {
+        Self{span, name, moment_type: None, repr: None, visibility: None, doc: vec![]}
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Decimal digits of the largest moment, once cast to a u64 - moments of other (or signed) types may take all 20
+    pub fn moment_digits(&self) -> u64 {
+        match self.moment_type.as_deref() {
+            Some("u8") => 3,
+            Some("u16") => 5,
+            Some("u32") => 10,
+            _ => 20
+        }
+    }
+
+    pub fn set_doc(&mut self, doc: Vec<String>) {
+        self.doc = doc;
     }
 
-    pub fn process_command(&mut self, filename: &str, lineno: usize, cmd: &str, args: &[&str]) {
+    pub fn process_command(&mut self, span: &Span, cmd: &str, args: &[&str]) -> Result<(), Diagnostic> {
         match (cmd, args) {
             ("set_moment_type", [moment_type]) => {
+                check_name(span, &format!("Clock ({})", self.name), "moment type", moment_type)?;
                 self.moment_type = Some(moment_type.to_string());
             },
 
             ("set_clock_repr", [repr]) => {
+                check_name(span, &format!("Clock ({})", self.name), "clock representation", repr)?;
                 self.repr = Some(repr.to_string());
             },
 
+            ("set_visibility", [visibility]) => {
+                self.visibility = Some(parse_visibility(span, &format!("Clock ({})", self.name), visibility)?);
+            },
+
             _ => {
-                panic!("{}:{} Clock ({}) - unknown command: {} ({:?})", filename, lineno, self.name, cmd, args);
+                return Err(Diagnostic::at(span, format!("Clock ({}) - unknown command: {} ({:?})", self.name, cmd, args)));
             }
         }
+
+        Ok(())
+    }
+
+    /// The statements of the clock's body
+    pub fn statements(&self) -> Vec<(String, Vec<String>)> {
+        let mut statements = vec![];
+
+        if let Some(moment_type) = self.moment_type.as_ref() {
+            statements.push(("set_moment_type".to_string(), vec![moment_type.clone()]));
+        }
+
+        if let Some(repr) = self.repr.as_ref() {
+            statements.push(("set_clock_repr".to_string(), vec![repr.clone()]));
+        }
+
+        if let Some(visibility) = self.visibility.as_ref() {
+            statements.push(("set_visibility".to_string(), vec![visibility.clone()]));
+        }
+
+        statements
+    }
+
+    /// Every problem generating the clock would run into
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+
+        if self.repr.is_none() {
+            diagnostics.push(Diagnostic::at(&self.span, format!("Never called set_clock_repr on Clock ({})", self.name)));
+        }
+
+        if self.moment_type.is_none() {
+            diagnostics.push(Diagnostic::at(&self.span, format!("Never called set_moment_type on Clock ({})", self.name)));
+        }
+
+        diagnostics
     }
 
-    pub fn generate(&self) -> Result<String, String> {
+    pub fn generate(&self, format: bool) -> Result<String, Diagnostic> {
         let moment_enum = format_ident!("{}", if let Some(repr) = self.repr.as_ref() { repr.clone() } else {
-            return Err(format!("Never called set_clock_repr on Clock ({})", self.name).to_string())
+            return Err(Diagnostic::at(&self.span, format!("Never called set_clock_repr on Clock ({})", self.name)))
         }.to_case(Case::Pascal));
         let repr_name = self.repr.as_ref().unwrap();
 
         let struct_name = format_ident!("Clock{}", self.name.to_case(Case::Pascal));
+        let visibility = visibility_tokens(self.visibility.as_ref());
+        let doc = doc_attrs(&self.doc);
 
         let moment_rep = format_ident!("{}", if let Some(ct) = self.moment_type.as_ref() { ct.clone() } else {
-            return Err(format!("Never called set_moment_type on Clock ({})", self.name).to_string())
+            return Err(Diagnostic::at(&self.span, format!("Never called set_moment_type on Clock ({})", self.name)))
         });
 
-        let formatted = rustfmt_wrapper::rustfmt(quote! {
-            pub struct #struct_name {}
+        let addable_impl = if ADDABLE_MOMENT_TYPES.contains(&moment_rep.to_string().as_str()) {
+            quote! { impl AddableClockLike<#moment_rep> for #struct_name {} }
+        } else {
+            quote! {}
+        };
+
+        // Clocks counting unix time in integers convert to and from real timestamps
+        let unix_micros: Option<u64> = match moment_enum.to_string().as_str() {
+            "UnixSeconds" => Some(1_000_000),
+            "UnixMilliseconds" => Some(1000),
+            "UnixMicroseconds" => Some(1),
+            _ => None
+        };
+
+        let unix_impl = match unix_micros {
+            Some(micros) if ADDABLE_MOMENT_TYPES.contains(&moment_rep.to_string().as_str()) => quote! {
+                impl UnixClockLike for #struct_name {
+                    const MICROS_PER_MOMENT: u64 = #micros;
+                }
+            },
+            _ => quote! {}
+        };
+
+        let formatted = format_tokens(quote! {
+            #doc
+            #visibility struct #struct_name {}
 
             impl #struct_name {
                 const fn to_moment(rep: #moment_rep) -> ClockMoment<#moment_rep> {
@@ -55,6 +149,8 @@ impl Clock {
             impl ClockLike for #struct_name {
                 type MomentRep = #moment_rep;
 
+                const REPRESENTS: &'static str = #repr_name;
+
                 fn represents(&self) -> &str { <#struct_name>::represents() }
 
                 fn to_moment(rep: #moment_rep) -> ClockMoment<#moment_rep> {
@@ -62,13 +158,14 @@ impl Clock {
                 }
             }
 
-            impl AddableClockLike<#moment_rep> for #struct_name {}
-        });
+            #addable_impl
+            #unix_impl
+        }, format);
 
         match formatted {
             Ok(formatted_str) => Ok(formatted_str),
-            Err(rustfmt_wrapper::Error::Rustfmt(err)) => Err(format!("Error formatting Clock({}):\n{}", self.name, err)),
-            Err(err) => Err(format!("Error generating Clock({}):\n{}", self.name, err))
+            Err(FormatError::Rustfmt(err)) => Err(Diagnostic::at(&self.span, format!("Error formatting Clock({}):\n{}", self.name, err))),
+            Err(FormatError::Generate(err)) => Err(Diagnostic::at(&self.span, format!("Error generating Clock({}):\n{}", self.name, err)))
         }
     }
 }
\ No newline at end of file
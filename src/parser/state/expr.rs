@@ -0,0 +1,106 @@
+use std::iter::Peekable;
+use std::str::Chars;
+use super::parse_number;
+
+/// Evaluates a compile-time integer expression - numeric literals, named constants, `+ - * /` and parentheses
+pub fn evaluate(expr: &str, constant: &dyn Fn(&str) -> Option<i128>) -> Result<i128, String> {
+    let mut evaluator = Evaluator{chars: expr.chars().peekable(), constant};
+    let value = evaluator.sum()?;
+
+    evaluator.skip_whitespace();
+    match evaluator.chars.next() {
+        None => Ok(value),
+        Some(chr) => Err(format!("unexpected character: {}", chr))
+    }
+}
+
+struct Evaluator<'a> {
+    chars: Peekable<Chars<'a>>,
+    constant: &'a dyn Fn(&str) -> Option<i128>
+}
+
+impl Evaluator<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|chr| chr.is_whitespace()).is_some() {}
+    }
+
+    fn sum(&mut self) -> Result<i128, String> {
+        let mut value = self.product()?;
+
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                Some('+') => {
+                    self.chars.next();
+                    value = value.checked_add(self.product()?).ok_or("overflow")?;
+                },
+
+                Some('-') => {
+                    self.chars.next();
+                    value = value.checked_sub(self.product()?).ok_or("overflow")?;
+                },
+
+                _ => return Ok(value)
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<i128, String> {
+        let mut value = self.factor()?;
+
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                Some('*') => {
+                    self.chars.next();
+                    value = value.checked_mul(self.factor()?).ok_or("overflow")?;
+                },
+
+                Some('/') => {
+                    self.chars.next();
+                    value = value.checked_div(self.factor()?).ok_or("division by zero")?;
+                },
+
+                _ => return Ok(value)
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<i128, String> {
+        self.skip_whitespace();
+
+        match self.chars.peek().copied() {
+            Some('-') => {
+                self.chars.next();
+                self.factor()?.checked_neg().ok_or_else(|| "overflow".to_string())
+            },
+
+            Some('(') => {
+                self.chars.next();
+                let value = self.sum()?;
+
+                self.skip_whitespace();
+                match self.chars.next() {
+                    Some(')') => Ok(value),
+                    _ => Err("missing closing parenthesis".to_string())
+                }
+            },
+
+            Some(chr) if chr.is_ascii_alphanumeric() || chr == '_' => {
+                let mut word = String::new();
+                while let Some(chr) = self.chars.next_if(|chr| chr.is_ascii_alphanumeric() || *chr == '_') {
+                    word.push(chr);
+                }
+
+                if chr.is_ascii_digit() {
+                    parse_number(&word).map(i128::from).ok_or_else(|| format!("invalid number: {}", word))
+                } else {
+                    (self.constant)(&word).ok_or_else(|| format!("undefined constant: {}", word))
+                }
+            },
+
+            Some(chr) => Err(format!("unexpected character: {}", chr)),
+            None => Err("unexpected end of expression".to_string())
+        }
+    }
+}
@@ -1,8 +1,113 @@
 mod alphabet;
 mod clock;
+mod expr;
 mod program;
 
+use std::collections::BTreeMap;
+use quote::quote;
+use serde::Serialize;
+use super::codegen::{format_tokens, hash_source, FormatError, SOURCE_HASH_INIT};
+use super::{Diagnostic, Lint, Span};
+
+pub use program::{Connection, ExitChars, Program};
+
+
+/// Parses a numeric literal of the language - hexadecimal (`0x50`), binary (`0b101`) or decimal (`80`)
+pub fn parse_number(literal: &str) -> Option<u64> {
+    let literal = literal.trim();
+
+    // from_str_radix and parse take a leading +, which no Rust literal has
+    if literal.contains('+') {
+        return None;
+    }
+
+    if let Some(hex) = literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = literal.strip_prefix("0b").or_else(|| literal.strip_prefix("0B")) {
+        u64::from_str_radix(bin, 2).ok()
+    } else {
+        literal.parse().ok()
+    }
+}
+
+/// Checks that something named in the source can become (part of) a Rust identifier - letters, digits and `_`, not
+/// starting with a digit
+pub fn check_name(span: &Span, owner: &str, what: &str, name: &str) -> Result<(), Diagnostic> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|chr| chr.is_ascii_alphanumeric() || chr == '_')
+        && name.chars().any(|chr| chr.is_ascii_alphanumeric());
+
+    match valid {
+        true => Ok(()),
+        false => Err(Diagnostic::at(span, format!("{} - invalid {}: {} (expected letters, digits and _, not starting with a digit)", owner, what, name)))
+    }
+}
+
+/// Checks a `set_visibility` argument is a Rust visibility (`pub`, `pub(crate)`, `pub(super)`, ...)
+pub fn parse_visibility(span: &Span, owner: &str, visibility: &str) -> Result<String, Diagnostic> {
+    match syn::parse_str::<syn::Visibility>(visibility) {
+        Ok(syn::Visibility::Inherited) | Err(_) => Err(Diagnostic::at(span, format!("{} - invalid visibility: {} (expected pub, pub(crate), pub(super), ...)", owner, visibility))),
+        Ok(_) => Ok(visibility.to_string())
+    }
+}
+
+/// The visibility generated items are declared with - `pub` unless set with `set_visibility`
+pub fn visibility_tokens(visibility: Option<&String>) -> proc_macro2::TokenStream {
+    visibility.map_or("pub", |visibility| visibility.as_str()).parse().unwrap()
+}
+
+/// `#[doc]` attributes for a documented item - one per line of its source comments
+pub fn doc_attrs(doc: &[String]) -> proc_macro2::TokenStream {
+    let lines = doc.iter().map(|line| format!(" {}", line));
+    quote! { #(#[doc = #lines])* }
+}
+
+/// Constants defined with `defconst NAME,VALUE;` - usable wherever a number or moment literal is accepted
 #[derive(Debug)]
+pub struct Constants {
+    values: BTreeMap<String, i128>
+}
+
+impl Constants {
+    pub const fn new() -> Self {
+        Self{values: BTreeMap::new()}
+    }
+
+    /// Defines a constant - its value may itself be an expression over previously defined constants
+    pub fn define(&mut self, span: &Span, name: &str, value: &str) -> Result<(), Diagnostic> {
+        let value = expr::evaluate(value, &|name| self.get(name)).map_err(|err| {
+            Diagnostic::at(span, format!("Constant ({}) - invalid value ({}): {}", name, value, err))
+        })?;
+
+        if self.values.insert(name.to_string(), value).is_some() {
+            return Err(Diagnostic::at(span, format!("Constant ({}) - already defined", name)));
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<i128> {
+        self.values.get(name).copied()
+    }
+
+    /// Resolves an argument that takes a number or moment - plain literals (and `Time(STREAM)`) are kept as is,
+    /// anything else is evaluated as an expression over constants (`2*TICK+1`)
+    pub fn resolve(&self, span: &Span, arg: &str) -> Result<String, Diagnostic> {
+        if (arg.starts_with("Time(") && arg.ends_with(')')) || parse_number(arg).is_some() {
+            Ok(arg.to_string())
+        } else {
+            expr::evaluate(arg, &|name| self.get(name)).map(|value| value.to_string()).map_err(|err| {
+                Diagnostic::at(span, format!("Invalid expression ({}): {}", arg, err))
+            })
+        }
+    }
+}
+
+// Definitions are few and long-lived - boxing programs would only add indirection
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "definition", rename_all = "snake_case")]
 pub enum State {
     General,
     Alphabet(alphabet::Alphabet),
@@ -11,29 +116,147 @@ pub enum State {
 }
 
 impl State {
-    pub const fn alphabet(name: String) -> Self { Self::Alphabet(alphabet::Alphabet::new(name)) }
-    pub const fn clock(name: String) -> Self { Self::Clock(clock::Clock::new(name)) }
-    pub const fn program(name: String) -> Self { Self::Program(program::Program::new(name)) }
+    pub const fn alphabet(span: Span, name: String) -> Self { Self::Alphabet(alphabet::Alphabet::new(span, name)) }
+    pub const fn clock(span: Span, name: String) -> Self { Self::Clock(clock::Clock::new(span, name)) }
+    pub fn program(span: Span, definition: &str) -> Result<Self, Diagnostic> { program::Program::from_definition(span, definition).map(Self::Program) }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::General => "",
+            Self::Alphabet(alphabet) => alphabet.name(),
+            Self::Clock(clock) => clock.name(),
+            Self::Program(prog) => prog.name(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::General => "general",
+            Self::Alphabet(_) => "alphabet",
+            Self::Clock(_) => "clock",
+            Self::Program(_) => "program",
+        }
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        match self {
+            Self::Program(prog) => prog.warnings(),
+            _ => &[]
+        }
+    }
+
+    pub fn as_program(&self) -> Option<&program::Program> {
+        match self {
+            Self::Program(prog) => Some(prog),
+            _ => None
+        }
+    }
+
+    pub fn as_program_mut(&mut self) -> Option<&mut program::Program> {
+        match self {
+            Self::Program(prog) => Some(prog),
+            _ => None
+        }
+    }
+
+    /// Documents the definition - from the comment block right above or below its header
+    pub fn set_doc(&mut self, doc: Vec<String>) {
+        match self {
+            Self::General => (),
+            Self::Alphabet(alphabet) => alphabet.set_doc(doc),
+            Self::Clock(clock) => clock.set_doc(doc),
+            Self::Program(prog) => prog.set_doc(doc)
+        }
+    }
+
+    /// Documents what a statement defined, from the comment block right above it - a `def_char`'s character, or a label
+    pub fn document(&mut self, cmd: &str, args: &[&str], doc: Vec<String>) {
+        match (self, cmd, args) {
+            (Self::Alphabet(alphabet), "def_char", [_, name]) => alphabet.document_char(name, doc),
+            (Self::Program(prog), "label", [name]) => prog.document_label(name, doc),
+            _ => ()
+        }
+    }
+
+    /// The argument of the definition's `def...` statement - its name, plus any generic parameters of a program
+    pub fn definition(&self) -> String {
+        match self {
+            Self::Program(prog) => prog.definition(),
+            other => other.name().to_string()
+        }
+    }
+
+    /// The statements of the definition's body
+    pub fn statements(&self) -> Vec<(String, Vec<String>)> {
+        match self {
+            Self::General => vec![],
+            Self::Alphabet(alphabet) => alphabet.statements(),
+            Self::Clock(clock) => clock.statements(),
+            Self::Program(prog) => prog.statements()
+        }
+    }
+
+    /// Semantic checks of the definition, against every definition of the compilation unit
+    pub fn validate(&self, definitions: &[&State]) -> Vec<Diagnostic> {
+        let alphabets: Vec<_> = definitions.iter().filter_map(|state| match state { Self::Alphabet(alphabet) => Some(alphabet), _ => None }).collect();
+        let clocks: Vec<_> = definitions.iter().filter_map(|state| match state { Self::Clock(clock) => Some(clock), _ => None }).collect();
+        let programs: Vec<_> = definitions.iter().filter_map(|state| state.as_program()).collect();
+
+        match self {
+            Self::General => vec![],
+            Self::Alphabet(alphabet) => alphabet.validate(),
+            Self::Clock(clock) => clock.validate(),
+            Self::Program(prog) => prog.validate(&alphabets, &clocks, &programs)
+        }
+    }
+
+    /// Warnings about parts of the definition that never do anything, or may overflow - programs look up the clocks
+    /// and programs they use among `definitions`
+    pub fn lint(&self, definitions: &[&State]) -> Vec<Diagnostic> {
+        let clocks: Vec<_> = definitions.iter().filter_map(|state| match state { Self::Clock(clock) => Some(clock), _ => None }).collect();
+        let programs: Vec<_> = definitions.iter().filter_map(|state| state.as_program()).collect();
+
+        match self {
+            Self::Program(prog) => prog.lint(&clocks, &programs),
+            _ => vec![]
+        }
+    }
+
+    /// The characters each exit of a (non-generic) program may be pushed - programs look up the alphabets of their
+    /// streams, and the programs they connect to, among `definitions`
+    pub fn reachable_chars(&self, definitions: &[&State]) -> Vec<ExitChars> {
+        let alphabets: Vec<_> = definitions.iter().filter_map(|state| match state { Self::Alphabet(alphabet) => Some(alphabet), _ => None }).collect();
+        let programs: Vec<_> = definitions.iter().filter_map(|state| state.as_program()).collect();
+
+        match self {
+            Self::Program(prog) if !prog.is_generic() => prog.reachable_chars(&alphabets, &programs),
+            _ => vec![]
+        }
+    }
 
-    pub fn generate(&self) -> Result<String, String> {
+    /// Generates the definition - programs look up the programs they connect to among `definitions`
+    pub fn generate(&self, format: bool, definitions: &[&State]) -> Result<String, Diagnostic> {
         use State::*;
 
         match self {
             General => Ok("".to_string()),
-            Alphabet(alphabet) => alphabet.generate(),
-            Clock(clock) => clock.generate(),
-            Program(prog) => prog.generate(),
+            Alphabet(alphabet) => alphabet.generate(format),
+            Clock(clock) => clock.generate(format),
+            Program(prog) => {
+                let programs: Vec<_> = definitions.iter().filter_map(|state| state.as_program()).collect();
+                prog.generate(format, &programs)
+            },
         }
     }
 
-    pub fn process_command(&mut self, filename: &str, lineno: usize, cmd: &str, args: &[&str]) {
+    pub fn process_command(&mut self, span: &Span, cmd: &str, args: &[&str], constants: &Constants) -> Result<(), Diagnostic> {
         use State::*;
 
         match self {
-            General => panic!("General - Unknown command: {} ({:?})", cmd, args),
-            Alphabet(alphabet) => alphabet.process_command(filename, lineno, cmd, args),
-            Clock(clock) => clock.process_command(filename, lineno, cmd, args),
-            Program(prog) => prog.process_command(filename, lineno, cmd, args),
+            General => Err(Diagnostic::at(span, format!("General - Unknown command: {} ({:?})", cmd, args))),
+            Alphabet(alphabet) => alphabet.process_command(span, cmd, args, constants),
+            Clock(clock) => clock.process_command(span, cmd, args),
+            Program(prog) => prog.process_command(span, cmd, args, constants),
         }
     }
 }
\ No newline at end of file
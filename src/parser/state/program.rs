@@ -1,7 +1,41 @@
+use std::collections::{BTreeMap, BTreeSet};
 use quote::{format_ident, quote};
 use convert_case::{Case, Casing};
+use serde::Serialize;
+use super::{check_name, doc_attrs, format_tokens, parse_number, parse_visibility, visibility_tokens, Constants, Diagnostic, FormatError, Lint, Span};
+use super::alphabet::Alphabet;
+use super::clock::Clock;
 
-#[derive(Debug)]
+/// Stream buffers live on the stack - anything above this many items is likely a mistake
+const LARGE_BUFFER_WARNING: u64 = 0x10000;
+
+/// How many characters `forward_duration` moves across at once, between streams of the same alphabet
+const FORWARD_BATCH: usize = 32;
+
+/// The names of the characters counts and moments are pushed as, digit by digit - like the runtime's `push_decimal`
+const DIGIT_NAMES: [&str; 10] = ["ZERO", "ONE", "TWO", "THREE", "FOUR", "FIVE", "SIX", "SEVEN", "EIGHT", "NINE"];
+
+/// Arguments (by command and position) taking a number or moment literal - these may also be expressions over `defconst` constants
+const NUMERIC_ARGS: [(&str, usize); 12] = [
+    ("start_moment", 0), ("set_priority", 0), ("set_fuel", 0), ("reg_gateway", 3), ("reg_exit", 3), ("reg_error_exit", 3), ("push_moment", 0),
+    ("push_val", 0), ("skip_until_moment", 1), ("window", 2), ("delay", 2), ("throttle", 2)
+];
+
+/// Arguments (by command and position) naming a stream, label or connection - these end up in identifiers of the
+/// generated code. The lists (`A|B`) and references (`PROGRAM(A|B)`) some commands take are checked name by name.
+const NAME_ARGS: [(&str, usize); 51] = [
+    ("start_moment", 1), ("expect_aligned", 0), ("expect_aligned", 1), ("reg_gateway", 0), ("reg_exit", 0),
+    ("reg_error_exit", 0), ("reg_exit_gateway", 1), ("label", 0), ("jump_earlier", 0), ("jump_earlier", 1),
+    ("jump_earlier", 2), ("jump_later", 0), ("jump_later", 1), ("jump_later", 2), ("jump_if_class", 0),
+    ("jump_if_class", 1), ("jump_overflowed", 0), ("jump_overflowed", 1), ("push_moment", 1), ("forward_moment", 0),
+    ("forward_moment", 1), ("push_char", 1), ("push_val", 1), ("forward_duration", 0), ("forward_duration", 1),
+    ("filter", 0), ("filter", 1), ("filter_class", 0), ("filter_class", 1), ("filter_keep", 0), ("filter_keep", 1),
+    ("tee", 0), ("tee", 1), ("tee", 2), ("drop_duration", 0), ("skip_until_moment", 0), ("zip", 1), ("split", 0),
+    ("window", 0), ("window", 1), ("count_chars", 0), ("count_chars", 1), ("min_moment", 0), ("min_moment", 1),
+    ("max_moment", 0), ("max_moment", 1), ("delay", 0), ("delay", 1), ("throttle", 0), ("throttle", 1), ("connect", 1)
+];
+
+#[derive(Debug, Clone, Serialize)]
 pub enum ArgType {
     Name(String),
     Label(String),
@@ -12,10 +46,11 @@ pub enum ArgType {
     Moment(String),
     Character(String),
     Number(String),
-    Program(String)
+    Program(String),
+    Class(String)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Instruction {
     StartMoment(ArgType, ArgType),
     PushMoment(ArgType, ArgType),
@@ -24,431 +59,4121 @@ pub enum Instruction {
     PushVal(ArgType, ArgType),
     JumpEarlier(ArgType, ArgType, ArgType),
     JumpLater(ArgType, ArgType, ArgType),
+    JumpIfClass(ArgType, ArgType, ArgType),
+    JumpOverflowed(ArgType, ArgType),
     ForwardDuration(ArgType, ArgType),
+    Filter(ArgType, ArgType, ArgType),
+    Tee(ArgType, ArgType, ArgType),
+    DropDuration(ArgType),
+    SkipUntilMoment(ArgType, ArgType),
+    Sync(Vec<ArgType>, Vec<ArgType>),
+    Zip(Vec<ArgType>, ArgType),
+    Split(ArgType, Vec<ArgType>, SplitStrategy),
+    Window(ArgType, ArgType, ArgType),
+    CountChars(ArgType, ArgType),
+    Delay(ArgType, ArgType, ArgType),
+    Throttle(ArgType, ArgType, ArgType),
+    MinMoment(ArgType, ArgType),
+    MaxMoment(ArgType, ArgType),
+    FilterKeep(ArgType, ArgType, ArgType),
     Connect(ArgType, ArgType),
     ExitGateway(ArgType, ArgType)
 }
 
-#[derive(Debug)]
-pub struct Program {
-    name: String,
-    instructions: Vec<(ArgType, Vec<Instruction>)>,
-    gateways: Vec<(ArgType, ArgType, ArgType, ArgType)>,
-    exits: Vec<(ArgType, ArgType, ArgType, ArgType)>
-}
+impl ArgType {
+    pub fn as_str(&self) -> &str {
+        use ArgType::*;
 
-impl Program {
-    pub const fn new(name: String) -> Self {
-        Self{
-            name: name,
-            instructions: vec![],
-            gateways: vec![],
-            exits: vec![]
+        match self {
+            Name(val) | Label(val) | Gateway(val) | Exit(val) | Alphabet(val) | Clock(val) | Moment(val) | Character(val) |
+            Number(val) | Program(val) | Class(val) => val
         }
     }
+}
 
-    pub fn process_command(&mut self, filename: &str, lineno: usize, cmd: &str, args: &[&str]) {
-        if self.instructions.len() == 0 {
-            self.instructions.push((ArgType::Name("root".to_string()), vec![]));
-        }
-
-        let latest_func = self.instructions.last_mut().unwrap();
-
-        match (cmd, args) {
-            ("start_moment", [moment, exit]) => {
-                latest_func.1.push(Instruction::StartMoment(ArgType::Moment(moment.to_string()), ArgType::Exit(exit.to_string())));
-            },
-
-            ("reg_gateway", [name, alphabet, clock, buf_size]) => {
-                self.gateways.push((ArgType::Name(name.to_string()), ArgType::Alphabet(alphabet.to_string()), ArgType::Clock(clock.to_string()), ArgType::Number(buf_size.to_string())));
-            },
+/// Joins stream lists back into their `A|B|C` form
+fn joined(args: &[ArgType]) -> String {
+    args.iter().map(|arg| arg.as_str()).collect::<Vec<_>>().join("|")
+}
 
-            ("reg_exit", [name, alphabet, clock, buf_size]) => {
-                self.exits.push((ArgType::Name(name.to_string()), ArgType::Alphabet(alphabet.to_string()), ArgType::Clock(clock.to_string()), ArgType::Number(buf_size.to_string())));
-            },
+impl Instruction {
+    /// The statement the instruction was parsed from - with constants and macros already resolved
+    pub fn statement(&self) -> (&'static str, Vec<String>) {
+        use Instruction::*;
 
-            ("reg_exit_gateway", [connected_name, gateway]) => {
-                latest_func.1.push(Instruction::ExitGateway(ArgType::Exit(connected_name.to_string()), ArgType::Gateway(gateway.to_string())));
-            },
+        let (cmd, args): (&'static str, Vec<&ArgType>) = match self {
+            StartMoment(a, b) => ("start_moment", vec![a, b]),
+            PushMoment(a, b) => ("push_moment", vec![a, b]),
+            ForwardMoment(a, b) => ("forward_moment", vec![a, b]),
+            PushChar(a, b) => ("push_char", vec![a, b]),
+            PushVal(a, b) => ("push_val", vec![a, b]),
+            JumpEarlier(a, b, c) => ("jump_earlier", vec![a, b, c]),
+            JumpLater(a, b, c) => ("jump_later", vec![a, b, c]),
+            JumpIfClass(a, b, c) => ("jump_if_class", vec![a, b, c]),
+            JumpOverflowed(a, b) => ("jump_overflowed", vec![a, b]),
+            ForwardDuration(a, b) => ("forward_duration", vec![a, b]),
+            Filter(a, b, c) => ("filter", vec![a, b, c]),
+            Tee(a, b, c) => ("tee", vec![a, b, c]),
+            DropDuration(a) => ("drop_duration", vec![a]),
+            SkipUntilMoment(a, b) => ("skip_until_moment", vec![a, b]),
+            Window(a, b, c) => ("window", vec![a, b, c]),
+            CountChars(a, b) => ("count_chars", vec![a, b]),
+            Delay(a, b, c) => ("delay", vec![a, b, c]),
+            Throttle(a, b, c) => ("throttle", vec![a, b, c]),
+            MinMoment(a, b) => ("min_moment", vec![a, b]),
+            MaxMoment(a, b) => ("max_moment", vec![a, b]),
+            FilterKeep(a, b, c) => ("filter_keep", vec![a, b, c]),
+            Connect(a, b) => ("connect", vec![a, b]),
+            ExitGateway(a, b) => ("reg_exit_gateway", vec![a, b]),
 
-            ("label", [name]) => {
-                self.instructions.push((ArgType::Name(name.to_string()), vec![]));
-            },
+            Sync(gateways, exits) => return ("sync", vec![joined(gateways), joined(exits)]),
+            Zip(gateways, exit) => return ("zip", vec![joined(gateways), exit.as_str().to_string()]),
+            Split(gateway, exits, strategy) => {
+                let strategy = match strategy {
+                    SplitStrategy::RoundRobin(_) => "round_robin".to_string(),
+                    SplitStrategy::ByMomentParity => "by_moment_parity".to_string(),
+                    SplitStrategy::ByClass(class) => format!("by_class({})", class.as_str())
+                };
 
-            ("jump_earlier", [label_name, a, b]) => {
-                latest_func.1.push(Instruction::JumpEarlier(ArgType::Label(label_name.to_string()), ArgType::Gateway(a.to_string()), ArgType::Gateway(b.to_string())));
-            },
+                return ("split", vec![gateway.as_str().to_string(), joined(exits), strategy]);
+            }
+        };
 
-            ("jump_later", [label_name, a, b]) => {
-                latest_func.1.push(Instruction::JumpLater(ArgType::Label(label_name.to_string()), ArgType::Gateway(a.to_string()), ArgType::Gateway(b.to_string())));
-            },
+        (cmd, args.iter().map(|arg| arg.as_str().to_string()).collect())
+    }
 
-            ("push_moment", [moment_incr, exit]) => {
-                latest_func.1.push(Instruction::PushMoment(ArgType::Moment(moment_incr.to_string()), ArgType::Exit(exit.to_string())));
-            },
+    /// The arguments naming streams, labels and moments of the program itself - `connect` and `reg_exit_gateway`
+    /// refer to other programs' streams, so they have none
+    pub fn args(&self) -> Vec<&ArgType> {
+        use Instruction::*;
 
-            ("forward_moment", [gateway, exit]) => {
-                latest_func.1.push(Instruction::ForwardMoment(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string())));
-            },
+        match self {
+            DropDuration(a) => vec![a],
 
-            ("push_char", [chr, exit]) => {
-                latest_func.1.push(Instruction::PushChar(ArgType::Character(chr.to_string()), ArgType::Exit(exit.to_string())));
-            },
+            StartMoment(a, b) | PushMoment(a, b) | ForwardMoment(a, b) | PushChar(a, b) | PushVal(a, b) | JumpOverflowed(a, b) |
+            ForwardDuration(a, b) | SkipUntilMoment(a, b) | CountChars(a, b) | MinMoment(a, b) | MaxMoment(a, b) => vec![a, b],
 
-            ("push_val", [chr, exit]) => {
-                latest_func.1.push(Instruction::PushVal(ArgType::Number(chr.to_string()), ArgType::Exit(exit.to_string())));
-            },
+            JumpEarlier(a, b, c) | JumpLater(a, b, c) | JumpIfClass(a, b, c) | Filter(a, b, c) | Tee(a, b, c) | Window(a, b, c) |
+            Delay(a, b, c) | Throttle(a, b, c) | FilterKeep(a, b, c) => vec![a, b, c],
 
-            ("forward_duration", [gateway, exit]) => {
-                latest_func.1.push(Instruction::ForwardDuration(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string())));
-            },
+            Sync(gateways, exits) => gateways.iter().chain(exits.iter()).collect(),
+            Zip(gateways, exit) => gateways.iter().chain(std::iter::once(exit)).collect(),
+            Split(gateway, exits, _) => std::iter::once(gateway).chain(exits.iter()).collect(),
+            Connect(_, _) | ExitGateway(_, _) => vec![]
+        }
+    }
 
-            ("connect", [program, name]) => {
-                latest_func.1.push(Instruction::Connect(ArgType::Program(program.to_string()), ArgType::Name(name.to_string())));
-            },
+    /// The gateway the instruction pops off of until a duration (or a moment) is whole - waiting on it for as long as
+    /// it is empty
+    fn awaited(&self) -> Option<&ArgType> {
+        use Instruction::*;
 
-            _ => {
-                panic!("{}:{} Program ({}) - unknown command: {} ({:?})", filename, lineno, self.name, cmd, args);
-            }
+        match self {
+            ForwardDuration(gateway, _) | Filter(gateway, _, _) | FilterKeep(gateway, _, _) | Tee(gateway, _, _) | DropDuration(gateway) |
+            SkipUntilMoment(gateway, _) | Split(gateway, _, _) | Window(gateway, _, _) | CountChars(gateway, _) | MinMoment(gateway, _) |
+            MaxMoment(gateway, _) | Delay(gateway, _, _) | Throttle(gateway, _, _) => Some(gateway),
+            _ => None
         }
     }
 
-    pub fn gateway_field(&self, name: &String, alphabet: &String, clock: &String, buf_size: &String) -> proc_macro2::TokenStream {
-        let field_name = format_ident!("gateway_{}", name.to_case(Case::Snake));
-        let alphabet_name = format_ident!("Alphabet{}", alphabet.to_case(Case::Pascal));
-        let clock_name = format_ident!("Clock{}", clock.to_case(Case::Pascal));
-        let buf_size_lit: proc_macro2::TokenStream = buf_size.parse().unwrap();
+    /// The gateways the instruction reads whole durations off of, and how many it needs buffered on each to run
+    /// without waiting - windows merge several, jumps pick which of two gateways is read next, and sync and zip line up
+    /// every gateway
+    fn needed_durations(&self) -> Vec<(&ArgType, usize)> {
+        use Instruction::*;
 
-        quote! {
-            pub #field_name: Stream<#alphabet_name, #clock_name, #buf_size_lit>,
+        match self {
+            Window(gateway, _, ArgType::Number(size)) => vec![(gateway, parse_number(size).map_or(1, |size| size as usize))],
+            JumpEarlier(_, a, b) | JumpLater(_, a, b) => vec![(a, 1), (b, 1)],
+            Sync(gateways, _) | Zip(gateways, _) => gateways.iter().map(|gateway| (gateway, 1)).collect(),
+            ForwardMoment(gateway, _) => vec![(gateway, 1)],
+            _ => self.awaited().map(|gateway| (gateway, 1)).into_iter().collect()
         }
     }
 
-    pub fn initialize_gateway_field(&self, name: &String, alphabet: &String, clock: &String, buf_size: &String) -> proc_macro2::TokenStream {
-        let field_name = format_ident!("gateway_{}", name.to_case(Case::Snake));
-        let alphabet_name = format_ident!("Alphabet{}", alphabet.to_case(Case::Pascal));
-        let clock_name = format_ident!("Clock{}", clock.to_case(Case::Pascal));
-        let buf_size_lit: proc_macro2::TokenStream = buf_size.parse().unwrap();
-
-        quote! {
-            #field_name: <Stream<#alphabet_name, #clock_name, #buf_size_lit>>::new(),
+    /// Whether the instruction always takes something off of a gateway, or fails - sync and zip only read once every
+    /// gateway has a moment, and skip_until_moment nothing once the gateway is past the moment
+    fn consumes(&self) -> bool {
+        match self {
+            Instruction::ForwardMoment(_, _) => true,
+            Instruction::SkipUntilMoment(_, _) => false,
+            _ => self.awaited().is_some()
         }
     }
 
-    pub fn exit_field(&self, name: &String, alphabet: &String, clock: &String, buf_size: &String) -> proc_macro2::TokenStream {
-        let field_name = format_ident!("exit_{}", name.to_case(Case::Snake));
-        let alphabet_name = format_ident!("Alphabet{}", alphabet.to_case(Case::Pascal));
-        let clock_name = format_ident!("Clock{}", clock.to_case(Case::Pascal));
-        let buf_size_lit: proc_macro2::TokenStream = buf_size.parse().unwrap();
+    /// The label the instruction may jump to
+    fn jump_target(&self) -> Option<&str> {
+        use Instruction::*;
 
-        quote! {
-            pub #field_name: Stream<#alphabet_name, #clock_name, #buf_size_lit>,
+        match self {
+            JumpEarlier(label, _, _) | JumpLater(label, _, _) | JumpIfClass(label, _, _) | JumpOverflowed(label, _) => Some(label.as_str()),
+            _ => None
         }
     }
 
-    pub fn initialize_exit_field(&self, name: &String, alphabet: &String, clock: &String, buf_size: &String) -> proc_macro2::TokenStream {
-        let field_name = format_ident!("exit_{}", name.to_case(Case::Snake));
-        let alphabet_name = format_ident!("Alphabet{}", alphabet.to_case(Case::Pascal));
-        let clock_name = format_ident!("Clock{}", clock.to_case(Case::Pascal));
-        let buf_size_lit: proc_macro2::TokenStream = buf_size.parse().unwrap();
+    /// Whether the instruction is a jump that is never taken - comparing a gateway with itself, it is never earlier (or later)
+    fn never_taken(&self) -> bool {
+        match self {
+            Instruction::JumpEarlier(_, a, b) | Instruction::JumpLater(_, a, b) => a.as_str() == b.as_str(),
+            _ => false
+        }
+    }
 
-        quote! {
-            #field_name: <Stream<#alphabet_name, #clock_name, #buf_size_lit>>::new(),
+    /// The exit a single character is pushed onto - by push_char or push_val
+    fn pushed_char_exit(&self) -> Option<&str> {
+        match self {
+            Instruction::PushChar(_, ArgType::Exit(exit)) | Instruction::PushVal(_, ArgType::Exit(exit)) => Some(exit.as_str()),
+            _ => None
         }
     }
 
-    pub fn instruction_call(&self, instruction: &Instruction) -> proc_macro2::TokenStream {
+    /// The same instruction, with each of its arguments mapped - split strategies included
+    fn map_args(&self, map: &dyn Fn(&ArgType) -> ArgType) -> Self {
         use Instruction::*;
+        let all = |args: &[ArgType]| args.iter().map(map).collect();
 
-        match instruction {
-            StartMoment(ArgType::Moment(moment), ArgType::Exit(exit_name)) => {
-                let exit_field = format_ident!("exit_{}", exit_name.to_case(Case::Snake));
-                let moment_lit: proc_macro2::TokenStream = moment.parse().unwrap();
+        match self {
+            StartMoment(a, b) => StartMoment(map(a), map(b)),
+            PushMoment(a, b) => PushMoment(map(a), map(b)),
+            ForwardMoment(a, b) => ForwardMoment(map(a), map(b)),
+            PushChar(a, b) => PushChar(map(a), map(b)),
+            PushVal(a, b) => PushVal(map(a), map(b)),
+            JumpEarlier(a, b, c) => JumpEarlier(map(a), map(b), map(c)),
+            JumpLater(a, b, c) => JumpLater(map(a), map(b), map(c)),
+            JumpIfClass(a, b, c) => JumpIfClass(map(a), map(b), map(c)),
+            JumpOverflowed(a, b) => JumpOverflowed(map(a), map(b)),
+            ForwardDuration(a, b) => ForwardDuration(map(a), map(b)),
+            Filter(a, b, c) => Filter(map(a), map(b), map(c)),
+            Tee(a, b, c) => Tee(map(a), map(b), map(c)),
+            DropDuration(a) => DropDuration(map(a)),
+            SkipUntilMoment(a, b) => SkipUntilMoment(map(a), map(b)),
+            Sync(gateways, exits) => Sync(all(gateways), all(exits)),
+            Zip(gateways, exit) => Zip(all(gateways), map(exit)),
+            Split(gateway, exits, strategy) => Split(map(gateway), all(exits), match strategy {
+                SplitStrategy::RoundRobin(counter) => SplitStrategy::RoundRobin(map(counter)),
+                SplitStrategy::ByMomentParity => SplitStrategy::ByMomentParity,
+                SplitStrategy::ByClass(class) => SplitStrategy::ByClass(map(class))
+            }),
+            Window(a, b, c) => Window(map(a), map(b), map(c)),
+            CountChars(a, b) => CountChars(map(a), map(b)),
+            Delay(a, b, c) => Delay(map(a), map(b), map(c)),
+            Throttle(a, b, c) => Throttle(map(a), map(b), map(c)),
+            MinMoment(a, b) => MinMoment(map(a), map(b)),
+            MaxMoment(a, b) => MaxMoment(map(a), map(b)),
+            FilterKeep(a, b, c) => FilterKeep(map(a), map(b), map(c)),
+            Connect(a, b) => Connect(map(a), map(b)),
+            ExitGateway(a, b) => ExitGateway(map(a), map(b))
+        }
+    }
+}
 
-                quote! {
-                    self.#exit_field.set_initial_moment(#moment_lit);
-                }
-            }
-            
-            PushMoment(ArgType::Moment(moment), ArgType::Exit(exit_name)) => {
-                let exit_field = format_ident!("exit_{}", exit_name.to_case(Case::Snake));
-                let moment_lit: proc_macro2::TokenStream = moment.parse().unwrap();
-                let push_error = format!("Could not push_moment to Exit ({})", exit_name);
+#[derive(Debug, Clone, Serialize)]
+pub enum SplitStrategy {
+    RoundRobin(ArgType),
+    ByMomentParity,
+    ByClass(ArgType)
+}
 
-                quote! {
-                    self.#exit_field.push_moment(#moment_lit).expect(#push_error);
-                }
-            }
-            
-            ForwardMoment(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name)) => {
-                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
-                let exit_field = format_ident!("exit_{}", exit_name.to_case(Case::Snake));
-                let push_moment_fail_msg = format!("Failed to forward moment from Gateway {} to Exit {}", gateway_name, exit_name);
+/// What generated label functions do with a StreamError
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ErrorMode {
+    /// Label functions panic (via `.expect(...)`)
+    Panic,
 
-                quote! {
-                    if self.#gateway_field.next_is_moment() {
-                        match self.#gateway_field.pop() {
-                            StreamItem::Moment(moment) => {
-                                self.#exit_field.push_moment(moment).expect(#push_moment_fail_msg);
-                            }
-                            _ => {
-                                panic!("Unreachable Code - unexpectedly popped a non-moment when calling forward_moment()");
-                            }
-                        }
-                    } else {
-                        panic!("Tried to forward_moment from {} to {} when the next item in the gateway, is not a Moment", #gateway_name, #exit_name)
-                    }
-                }
-            }
+    /// Label functions record the error on the program, which stops running from then on
+    AbortProgram,
 
-            PushVal(ArgType::Number(val), ArgType::Exit(exit_name)) => {
-                let exit_field = format_ident!("exit_{}", exit_name.to_case(Case::Snake));
-                let val_lit: proc_macro2::TokenStream = val.parse().unwrap();
+    /// Label functions return `Result<(), StreamError>`
+    Propagate
+}
 
-                let alphabet = self.exits.iter().find_map(|(name, alphabet, _, _)| {
-                    match (name, alphabet) {
-                        (ArgType::Name(name), ArgType::Alphabet(alphabet)) if name == exit_name => Some(alphabet),
-                        _ => None
-                    }
-                }).unwrap_or_else(|| {
-                    panic!("Could not find Exit ({}) for Program ({})", exit_name, self.name);
-                });
-                let alphabet_name = format_ident!("Alphabet{}", alphabet.to_case(Case::Pascal));
-                let error_message = format!("No character found in Alphabet ({}): {:?}", alphabet, val);
-                let push_error = format!("Could not push_val to Exit ({})", exit_name);
-                
-                quote! {
-                    self.#exit_field.push(#alphabet_name::to_char(#val_lit).expect(#error_message)).expect(#push_error);
-                }
-            }
+/// What a program does when an exit has no room for a duration it forwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum OnFull {
+    /// The push fails with ExitError::BufferFull, part way through the duration
+    Error,
 
-            PushChar(ArgType::Character(chr), ArgType::Exit(exit_name)) => {
-                let alphabet = self.exits.iter().find_map(|(name, alphabet, _, _)| {
-                    match (name, alphabet) {
-                        (ArgType::Name(name), ArgType::Alphabet(alphabet)) if name == exit_name => Some(alphabet),
-                        _ => None
-                    }
-                }).unwrap_or_else(|| {
-                    panic!("Could not find Exit ({}) for Program ({})", exit_name, self.name);
-                });
+    /// The program has no input pending until there is room - executors resume it once its exits were emptied
+    Yield,
 
-                let alphabet_name = format_ident!("Alphabet{}", alphabet.to_case(Case::Pascal));
-                let enum_name = format_ident!("{}", chr.to_case(Case::Pascal));
-                let exit_field = format_ident!("exit_{}", exit_name.to_case(Case::Snake));
-                let error_message = format!("Could not push_char ({:?})", chr);
+    /// The duration is popped off of the gateway and dropped
+    DropDuration
+}
 
-                quote!{
-                    self.#exit_field.push(<#alphabet_name as AlphabetLike>::CharEnum::#enum_name()).expect(#error_message);
-                }
-            },
+/// A standard stream an entry program's gateway reads from (`from=stdin`), or its exit writes to (`to=stdout`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StdioConnector {
+    Stdin(StdinMoments),
+    Stdout
+}
 
-            ForwardDuration(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name)) => {
-                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
-                let exit_field = format_ident!("exit_{}", exit_name.to_case(Case::Snake));
+/// Where durations read from stdin end (`moments=...`) - each line (the default), each read, or every so many bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StdinMoments {
+    Line,
+    Read,
+    Bytes(u64)
+}
+
+#[derive(Debug)]
+pub struct Connection {
+    pub span: Span,
+    pub program: String,
+    pub name: String,
+    pub gateways: Vec<String>,
+    pub exit_gateways: Vec<(String, String)>
+}
 
-                let push_fail_msg = format!("Failed to forward character from Gateway {} to Exit {}", gateway_name, exit_name);
-                let push_moment_fail_msg = format!("Failed to forward moment from Gateway {} to Exit {}", gateway_name, exit_name);
+/// The characters of its alphabet an exit of a program may be pushed - and those it never is. Raw values pushed with
+/// `push_val` that are no character of the alphabet are listed apart.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitChars {
+    pub program: String,
+    pub exit: String,
+    pub alphabet: String,
+    pub chars: Vec<String>,
+    pub never: Vec<String>,
+    pub values: Vec<String>
+}
 
-                quote!{
-                    loop {
-                        match self.#gateway_field.pop() {
-                            StreamItem::Character(chr) => {
-                                self.#exit_field.push(chr).expect(#push_fail_msg);
-                            }
+/// The alphabet of a stream (unless generic, and unbound), and the characters it may hold - by name, or the raw value
+/// for values that are no character of the alphabet
+type Reach = (Option<String>, BTreeSet<String>);
 
-                            StreamItem::Moment(moment) => {
-                                self.#exit_field.push_moment(moment).expect(#push_moment_fail_msg);
-                                break;
-                            }
+/// A label's name, where it is defined, and the instructions it runs
+type Label = (ArgType, Span, Vec<(Span, Instruction)>);
 
-                            StreamItem::Empty => {
-                                continue
-                            }
-                        }
-                    }
-                }
-            },
+/// A registered stream - its name, alphabet, clock and buffer size, and where it was registered
+type StreamDef = (ArgType, ArgType, ArgType, ArgType, Span);
 
-            JumpEarlier(ArgType::Label(label), ArgType::Gateway(gateway_a), ArgType::Gateway(gateway_b)) => {
-                let label_func = format_ident!("label_{}", label.to_case(Case::Snake));
-                let gateway_a_field = format_ident!("gateway_{}", gateway_a.to_case(Case::Snake));
-                let gateway_b_field = format_ident!("gateway_{}", gateway_b.to_case(Case::Snake));
+/// A `connect`ed program, resolved against its definition - what the caller's pipeline instantiates and wires up
+#[derive(Debug, Clone)]
+struct Link {
+    name: String,
+    program: String,
 
-                let clock_a = self.gateways.iter().find_map(|(name, _, clock, _)| {
-                    match (name, clock) {
-                        (ArgType::Name(name), ArgType::Clock(clock)) if name == gateway_a => Some(format_ident!("Clock{}", clock)),
-                        _ => None
-                    }
-                }).unwrap_or_else(|| {
-                    panic!("Could not find Gateway ({}) for Program ({})", gateway_a, self.name);
-                });
+    /// The connected program connects to others itself, so it's driven through its own pipeline
+    pipeline: bool,
 
-                let clock_b = self.gateways.iter().find_map(|(name, _, clock, _)| {
-                    match (name, clock) {
-                        (ArgType::Name(name), ArgType::Clock(clock)) if name == gateway_b => Some(format_ident!("Clock{}", clock)),
-                        _ => None
-                    }
-                }).unwrap_or_else(|| {
-                    panic!("Could not find Gateway ({}) for Program ({})", gateway_b, self.name);
-                });
+    /// The caller's alphabet or clock each generic parameter of the connected program takes on
+    type_args: Vec<ArgType>,
 
-                let clock_repr_error = format!("(Clock of) Gateway {} and (Clock of) Gateway {} being compared while not representing the same thing", gateway_a, gateway_b);
+    /// The caller's gateways feeding the connected program's gateways
+    feeds: Vec<(String, String)>,
 
-                quote! {
-                    if #clock_a::represents() != #clock_b::represents() {
-                        panic!(#clock_repr_error);
-                    }
+    /// The connected program's exits feeding the caller's (exit) gateways
+    exits: Vec<(String, String)>
+}
 
-                    match (self.#gateway_a_field.current_moment(), self.#gateway_b_field.current_moment()) {
-                        (None, Some(_)) => {
-                            return self.#label_func();
-                        }
+/// A `connect`ed program spliced into the caller by `-O inline-connect` - its labels run as part of the caller's own
+#[derive(Debug, Clone)]
+struct Inlined {
+    /// The (renamed) labels its chain starts at, and keeps on going from
+    root: String,
+    main: String,
 
-                        (Some(a), Some(b)) if a < b => {
-                            return self.#label_func();
-                        }
+    /// The caller's gateways it reads, and those its exits push straight onto
+    gateways: Vec<String>,
+    exits: Vec<String>
+}
 
-                        _ => ()
-                    }
-                }
-            },
+#[derive(Debug, Clone, Serialize)]
+pub struct Program {
+    span: Span,
+    name: String,
+    generics: Vec<ArgType>,
+    instructions: Vec<Label>,
+    gateways: Vec<StreamDef>,
+    exits: Vec<StreamDef>,
+    error_exit: Option<ArgType>,
 
-            JumpLater(ArgType::Label(label), ArgType::Gateway(gateway_a), ArgType::Gateway(gateway_b)) => {
-                let label_func = format_ident!("label_{}", label.to_case(Case::Snake));
-                let gateway_a_field = format_ident!("gateway_{}", gateway_a.to_case(Case::Snake));
-                let gateway_b_field = format_ident!("gateway_{}", gateway_b.to_case(Case::Snake));
+    /// Streams evicting their oldest duration when full, instead of rejecting pushes
+    overwriting: Vec<ArgType>,
 
-                let clock_a = self.gateways.iter().find_map(|(name, _, clock, _)| {
-                    match (name, clock) {
-                        (ArgType::Name(name), ArgType::Clock(clock)) if name == gateway_a => Some(format_ident!("Clock{}", clock)),
-                        _ => None
-                    }
-                }).unwrap_or_else(|| {
-                    panic!("Could not find Gateway ({}) for Program ({})", gateway_a, self.name);
-                });
+    /// Streams an entry program connects to stdin or stdout
+    connectors: Vec<(ArgType, StdioConnector)>,
+    error_mode: ErrorMode,
+    on_full: OnFull,
+    priority: Option<ArgType>,
 
-                let clock_b = self.gateways.iter().find_map(|(name, _, clock, _)| {
-                    match (name, clock) {
-                        (ArgType::Name(name), ArgType::Clock(clock)) if name == gateway_b => Some(format_ident!("Clock{}", clock)),
-                        _ => None
-                    }
-                }).unwrap_or_else(|| {
-                    panic!("Could not find Gateway ({}) for Program ({})", gateway_b, self.name);
-                });
+    /// Units of work a single run may take before failing - see `set_fuel`
+    fuel: Option<ArgType>,
+    visibility: Option<String>,
+    doc: Vec<String>,
+    label_docs: BTreeMap<String, Vec<String>>,
+    expectations: Vec<(ArgType, ArgType, Span)>,
+    counters: Vec<ArgType>,
 
-                let clock_repr_error = format!("(Clock of) Gateway {} and (Clock of) Gateway {} being compared while not representing the same thing", gateway_a, gateway_b);
+    #[serde(skip)]
+    warnings: Vec<String>,
 
-                quote! {
-                    if #clock_a::represents() != #clock_b::represents() {
-                        panic!(#clock_repr_error);
-                    }
+    /// Connected programs and the gateways their exits feed, once resolved for code generation
+    #[serde(skip)]
+    links: Vec<Link>,
+    #[serde(skip)]
+    linked_gateways: Vec<StreamDef>,
 
-                    match (self.#gateway_a_field.current_moment(), self.#gateway_b_field.current_moment()) {
-                        (Some(_), None) => {
-                            return self.#label_func();
-                        }
+    /// Streams are traced, and jumps and label functions recorded - see `Parser::set_trace`
+    #[serde(skip)]
+    trace: bool,
 
-                        (Some(a), Some(b)) if a > b => {
-                            return self.#label_func();
-                        }
+    /// Exits check that moments pushed onto them never go back - see `Parser::set_assert_monotonic`
+    #[serde(skip)]
+    assert_monotonic: bool,
 
-                        _ => ()
-                    }
-                }
-            }
+    /// Labels are optimized as they are generated - see `Parser::set_optimize`
+    #[serde(skip)]
+    optimize: bool,
 
-            instr => {
-                let error_message = format!("Not implemented: {:?}", instr);
+    /// Connected programs are spliced in where they can be, rather than run by the pipeline - see `Parser::set_inline_connect`
+    #[serde(skip)]
+    inline_connect: bool,
+    #[serde(skip)]
+    inlined: Vec<Inlined>
+}
 
-                quote!{
-                    compile_error!(#error_message);
-                }
-            }
+impl Program {
+    pub const fn new(span: Span, name: String) -> Self {
+        Self{
+            span,
+            name,
+            generics: vec![],
+            instructions: vec![],
+            gateways: vec![],
+            exits: vec![],
+            error_exit: None,
+            overwriting: vec![],
+            connectors: vec![],
+            error_mode: ErrorMode::Propagate,
+            on_full: OnFull::Error,
+            priority: None,
+            fuel: None,
+            visibility: None,
+            doc: vec![],
+            label_docs: BTreeMap::new(),
+            expectations: vec![],
+            counters: vec![],
+            warnings: vec![],
+            links: vec![],
+            linked_gateways: vec![],
+            trace: false,
+            assert_monotonic: false,
+            optimize: false,
+            inline_connect: false,
+            inlined: vec![]
         }
     }
 
-    pub fn func_def(&self, name: &String, instructions: &Vec<Instruction>) -> proc_macro2::TokenStream {
-        let func_name = format_ident!("label_{}", name.to_case(Case::Snake));
-        let instructions: Vec<_> = instructions.iter().map(|instruction| self.instruction_call(instruction)).collect();
+    /// Creates a program from its definition - a name, optionally followed by generic parameters (`sync2<A: alphabet, C: clock>`)
+    pub fn from_definition(span: Span, definition: &str) -> Result<Self, Diagnostic> {
+        let (name, params) = match definition.split_once('<') {
+            Some((name, params)) => match params.trim_end().strip_suffix('>') {
+                Some(params) => (name.trim(), params),
+                None => return Err(Diagnostic::at(&span, format!("Program ({}) - unclosed generic parameters: {}", name.trim(), definition)))
+            },
 
-        quote! {
-            pub fn #func_name(&mut self) {
-                #(#instructions)*
+            None => (definition.trim(), "")
+        };
+
+        check_name(&span, "Program", "name", name)?;
+        let mut program = Self::new(span.clone(), name.to_string());
+        for param in params.split(',').filter(|param| !param.trim().is_empty()) {
+            if let Some((param, _)) = param.split_once(':') {
+                check_name(&span, &format!("Program ({})", name), "generic parameter", param.trim())?;
             }
+
+            let generic = match param.split_once(':').map(|(param, kind)| (param.trim(), kind.trim())) {
+                Some((param, "alphabet")) => ArgType::Alphabet(param.to_string()),
+                Some((param, "clock")) => ArgType::Clock(param.to_string()),
+                _ => return Err(Diagnostic::at(&span, format!("Program ({}) - generic parameters are either `NAME: alphabet` or `NAME: clock`, got: {}", name, param.trim())))
+            };
+
+            program.generics.push(generic);
         }
+
+        Ok(program)
     }
 
-    pub fn generate(&self) -> Result<String, String> {
-        let struct_name = format_ident!("Program{}", self.name.to_case(Case::Pascal));
-        let gateways: Vec<_> = self.gateways.iter().map(|gateway_data| {
-            match gateway_data {
-                (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock), ArgType::Number(buf_size)) => {
-                    self.gateway_field(name, alphabet, clock, buf_size)
-                },
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 
-                _ => panic!("Unexpected reg_gateway params: {:?}", gateway_data)
-            }
-        }).collect();
+    pub fn set_doc(&mut self, doc: Vec<String>) {
+        self.doc = doc;
+    }
 
-        let initialize_gateways: Vec<_> = self.gateways.iter().map(|gateway_data| {
-            match gateway_data {
-                (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock), ArgType::Number(buf_size)) => {
-                    self.initialize_gateway_field(name, alphabet, clock, buf_size)
-                },
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
 
-                _ => panic!("Unexpected reg_gateway params: {:?}", gateway_data)
-            }
+    pub fn set_assert_monotonic(&mut self, assert_monotonic: bool) {
+        self.assert_monotonic = assert_monotonic;
+    }
+
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    pub fn set_inline_connect(&mut self, inline_connect: bool) {
+        self.inline_connect = inline_connect;
+    }
+
+    /// Whether the program has generic parameters - it can then only be run through the programs connecting to it
+    pub fn is_generic(&self) -> bool {
+        !self.generics.is_empty()
+    }
+
+    pub fn document_label(&mut self, name: &str, doc: Vec<String>) {
+        self.label_docs.insert(name.to_string(), doc);
+    }
+
+    /// The `defprogram` definition - the name, and any generic parameters
+    pub fn definition(&self) -> String {
+        let params: Vec<String> = self.generics.iter().map(|generic| match generic {
+            ArgType::Clock(name) => format!("{}: clock", name),
+            other => format!("{}: alphabet", other.as_str())
         }).collect();
 
-        let exits: Vec<_> = self.exits.iter().map(|exit_data| {
-            match exit_data {
-                (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock), ArgType::Number(buf_size)) => {
-                    self.exit_field(name, alphabet, clock, buf_size)
-                },
+        match params.is_empty() {
+            true => self.name.clone(),
+            false => format!("{}<{}>", self.name, params.join(", "))
+        }
+    }
 
-                _ => panic!("Unexpected reg_exit params: {:?}", exit_data)
+    /// The statements of the program's body - registrations and settings first, then every label's instructions
+    pub fn statements(&self) -> Vec<(String, Vec<String>)> {
+        let mut statements = vec![];
+        let stream = |cmd: &str, (name, alphabet, clock, buf_size, _): &(ArgType, ArgType, ArgType, ArgType, Span)| {
+            let mut args = vec![name.as_str().to_string(), alphabet.as_str().to_string(), clock.as_str().to_string(), buf_size.as_str().to_string()];
+            let kind = if cmd == "reg_gateway" { ArgType::Gateway(name.as_str().to_string()) } else { ArgType::Exit(name.as_str().to_string()) };
+            if self.overwrites(&kind) {
+                args.push("overwrite".to_string());
             }
-        }).collect();
 
-        let initialize_exits: Vec<_> = self.exits.iter().map(|exit_data| {
-            match exit_data {
-                (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock), ArgType::Number(buf_size)) => {
-                    self.initialize_exit_field(name, alphabet, clock, buf_size)
+            match self.connector(&kind) {
+                Some(StdioConnector::Stdin(moments)) => {
+                    args.push("from=stdin".to_string());
+                    match moments {
+                        StdinMoments::Line => (),
+                        StdinMoments::Read => args.push("moments=read".to_string()),
+                        StdinMoments::Bytes(bytes) => args.push(format!("moments={}", bytes))
+                    }
                 },
 
-                _ => panic!("Unexpected reg_exit params: {:?}", exit_data)
+                Some(StdioConnector::Stdout) => args.push("to=stdout".to_string()),
+                None => ()
             }
-        }).collect();
 
-        let funcs: Vec<_> = self.instructions.iter().map(|func_data| {
-            match func_data {
-                (ArgType::Name(name), instructions) => self.func_def(name, instructions),
-                _ => panic!("Unexpected label data: {:?}", func_data)
-            }
-        }).collect();
+            (cmd.to_string(), args)
+        };
 
-        let formatted = rustfmt_wrapper::rustfmt(quote! {
-            pub struct #struct_name {
-                #(#gateways)*
-                #(#exits)*
+        if let Some(priority) = self.priority.as_ref() {
+            statements.push(("set_priority".to_string(), vec![priority.as_str().to_string()]));
+        }
+
+        if let Some(fuel) = self.fuel.as_ref() {
+            statements.push(("set_fuel".to_string(), vec![fuel.as_str().to_string()]));
+        }
+
+        if let Some(visibility) = self.visibility.as_ref() {
+            statements.push(("set_visibility".to_string(), vec![visibility.clone()]));
+        }
+
+        let error_mode = match self.error_mode {
+            ErrorMode::Panic => Some("panic"),
+            ErrorMode::AbortProgram => Some("abort_program"),
+            ErrorMode::Propagate => None
+        };
+
+        if let Some(error_mode) = error_mode {
+            statements.push(("set_error_mode".to_string(), vec![error_mode.to_string()]));
+        }
+
+        let on_full = match self.on_full {
+            OnFull::Yield => Some("yield"),
+            OnFull::DropDuration => Some("drop_duration"),
+            OnFull::Error => None
+        };
+
+        if let Some(on_full) = on_full {
+            statements.push(("set_on_full".to_string(), vec![on_full.to_string()]));
+        }
+
+        statements.extend(self.gateways.iter().map(|gateway| stream("reg_gateway", gateway)));
+        statements.extend(self.exits.iter().map(|exit| match self.error_exit.as_ref() {
+            Some(error_exit) if error_exit.as_str() == exit.0.as_str() => stream("reg_error_exit", exit),
+            _ => stream("reg_exit", exit)
+        }));
+
+        for (exit_a, exit_b, _) in self.expectations.iter() {
+            statements.push(("expect_aligned".to_string(), vec![exit_a.as_str().to_string(), exit_b.as_str().to_string()]));
+        }
+
+        for (idx, (label, _, instructions)) in self.instructions.iter().enumerate() {
+            // The first label is the implicit root
+            if idx > 0 {
+                statements.push(("label".to_string(), vec![label.as_str().to_string()]));
+            }
+
+            statements.extend(instructions.iter().map(|(_, instruction)| {
+                let (cmd, args) = instruction.statement();
+                (cmd.to_string(), args)
+            }));
+        }
+
+        statements
+    }
+
+    /// Streams may use the program's generic parameters (`$A`) in place of an alphabet or clock - these must be declared
+    fn check_generics(&self, span: &Span, stream: &str, alphabet: &str, clock: &str) -> Result<(), Diagnostic> {
+        if let Some(param) = alphabet.strip_prefix('$') {
+            if !self.generics.iter().any(|generic| matches!(generic, ArgType::Alphabet(name) if name == param)) {
+                return Err(Diagnostic::at(span, format!("Program ({}) - stream {} uses undeclared alphabet parameter: {}", self.name, stream, alphabet)));
+            }
+        }
+
+        if let Some(param) = clock.strip_prefix('$') {
+            if !self.generics.iter().any(|generic| matches!(generic, ArgType::Clock(name) if name == param)) {
+                return Err(Diagnostic::at(span, format!("Program ({}) - stream {} uses undeclared clock parameter: {}", self.name, stream, clock)));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    fn check_buffer_size(&mut self, span: &Span, stream: &str, buf_size: &str) -> Result<(), Diagnostic> {
+        let (size, dynamic) = match parse_dynamic(buf_size) {
+            Some(None) => return Ok(()),
+            Some(Some(cap)) => (cap, true),
+            None => (buf_size, false)
+        };
+
+        match parse_number(size) {
+            Some(0) => return Err(Diagnostic::at(span, format!("Program ({}) - stream {} must have a buffer size of at least 1", self.name, stream))),
+            Some(size) if size > LARGE_BUFFER_WARNING && !dynamic => {
+                self.warnings.push(format!("{} Program ({}) - stream {} has a very large stack buffer ({} items)", span, self.name, stream, size));
+            },
+            Some(_) => (),
+            None => return Err(Diagnostic::at(span, format!("Program ({}) - stream {} has an invalid buffer size: {}", self.name, stream, buf_size)))
+        }
+
+        Ok(())
+    }
+
+    /// Records the overflow policy given as the last argument of a `reg_gateway`/`reg_exit` - `reject` (the default) or
+    /// `overwrite`
+    fn set_overflow_policy(&mut self, span: &Span, stream: ArgType, policy: Option<&&str>) -> Result<(), Diagnostic> {
+        match policy {
+            None | Some(&"reject") => Ok(()),
+            Some(&"overwrite") => {
+                self.overwriting.push(stream);
+                Ok(())
+            },
+
+            Some(policy) => Err(Diagnostic::at(span, format!("Program ({}) - stream {} has an unknown overflow policy: {} (expected reject or overwrite)", self.name, stream.as_str(), policy)))
+        }
+    }
+
+    /// Applies the options following a `reg_gateway`/`reg_exit`'s buffer size - its overflow policy, and which standard
+    /// stream it connects to (`from=stdin`, optionally with `moments=line|read|N`, or `to=stdout`)
+    fn stream_options(&mut self, span: &Span, stream: ArgType, options: &[&str], constants: &Constants) -> Result<(), Diagnostic> {
+        let (settings, policies): (Vec<&str>, Vec<&str>) = options.iter().partition(|option| option.contains('='));
+        if policies.len() > 1 {
+            return Err(Diagnostic::at(span, format!("Program ({}) - stream {} has more than one overflow policy", self.name, stream.as_str())));
+        }
+
+        self.set_overflow_policy(span, stream.clone(), policies.first())?;
+
+        let mut connector = None;
+        let mut moments = None;
+        for setting in settings {
+            let (key, value) = setting.split_once('=').map(|(key, value)| (key.trim(), value.trim())).unwrap();
+
+            match (key, value, &stream) {
+                ("from", "stdin", ArgType::Gateway(_)) => {
+                    if self.connectors.iter().any(|(_, connector)| matches!(connector, StdioConnector::Stdin(_))) {
+                        return Err(Diagnostic::at(span, format!("Program ({}) - only one gateway can read from stdin", self.name)));
+                    }
+
+                    connector = Some(StdioConnector::Stdin(StdinMoments::Line));
+                },
+
+                ("to", "stdout", ArgType::Exit(_)) => connector = Some(StdioConnector::Stdout),
+
+                ("moments", moment, ArgType::Gateway(_)) => moments = Some(match moment {
+                    "line" => StdinMoments::Line,
+                    "read" => StdinMoments::Read,
+                    bytes => match parse_number(&constants.resolve(span, bytes)?) {
+                        Some(0) | None => return Err(Diagnostic::at(span, format!("Program ({}) - stream {} has an invalid moments setting: {} (expected line, read or a byte count of at least 1)", self.name, stream.as_str(), bytes))),
+                        Some(bytes) => StdinMoments::Bytes(bytes)
+                    }
+                }),
+
+                _ => return Err(Diagnostic::at(span, format!("Program ({}) - stream {} has an unknown option: {} (gateways take from=stdin and moments=..., exits to=stdout)", self.name, stream.as_str(), setting)))
+            }
+        }
+
+        match (connector, moments) {
+            (Some(StdioConnector::Stdin(_)), Some(moments)) => self.connectors.push((stream, StdioConnector::Stdin(moments))),
+            (_, Some(_)) => return Err(Diagnostic::at(span, format!("Program ({}) - stream {} sets moments, but doesn't read from stdin", self.name, stream.as_str()))),
+            (Some(connector), None) => self.connectors.push((stream, connector)),
+            (None, None) => ()
+        }
+
+        Ok(())
+    }
+
+    /// The standard stream a gateway or exit is connected to, if any
+    fn connector(&self, stream: &ArgType) -> Option<StdioConnector> {
+        self.connectors.iter().find_map(|(connected, connector)| match (connected, stream) {
+            (ArgType::Gateway(a), ArgType::Gateway(b)) | (ArgType::Exit(a), ArgType::Exit(b)) if a == b => Some(*connector),
+            _ => None
+        })
+    }
+
+    fn overwrites(&self, stream: &ArgType) -> bool {
+        self.overwriting.iter().any(|overwriting| match (overwriting, stream) {
+            (ArgType::Gateway(a), ArgType::Gateway(b)) | (ArgType::Exit(a), ArgType::Exit(b)) => a == b,
+            _ => false
+        })
+    }
+
+    /// Whether any stream of the program buffers on the heap - generated code then needs the runtime's VecStream
+    pub fn uses_dynamic_streams(&self) -> bool {
+        self.gateways.iter().chain(self.exits.iter()).any(|(_, _, _, buf_size, _)| parse_dynamic(buf_size.as_str()).is_some())
+    }
+
+    pub fn gateway_names(&self) -> Vec<&str> {
+        self.gateways.iter().filter_map(|(name, _, _, _, _)| match name {
+            ArgType::Name(name) => Some(name.as_str()),
+            _ => None
+        }).collect()
+    }
+
+    pub fn exit_names(&self) -> Vec<&str> {
+        self.exits.iter().filter_map(|(name, _, _, _, _)| match name {
+            ArgType::Name(name) => Some(name.as_str()),
+            _ => None
+        }).collect()
+    }
+
+    /// Every gateway as `(name, alphabet, clock)`
+    pub fn gateway_types(&self) -> Vec<(&str, &str, &str)> {
+        self.gateways.iter().map(|(name, alphabet, clock, _, _)| (name.as_str(), alphabet.as_str(), clock.as_str())).collect()
+    }
+
+    /// Every exit as `(name, alphabet, clock)`
+    pub fn exit_types(&self) -> Vec<(&str, &str, &str)> {
+        self.exits.iter().map(|(name, alphabet, clock, _, _)| (name.as_str(), alphabet.as_str(), clock.as_str())).collect()
+    }
+
+    pub fn connections(&self) -> Vec<Connection> {
+        let mut connections: Vec<Connection> = self.instructions.iter().flat_map(|(_, _, instructions)| instructions.iter()).filter_map(|instruction| {
+            match instruction {
+                (span, Instruction::Connect(ArgType::Program(target), ArgType::Name(name))) => {
+                    let (program, gateways) = split_stream_ref(target);
+                    Some(Connection{span: span.clone(), program: program.to_string(), name: name.to_string(), gateways, exit_gateways: vec![]})
+                },
+
+                _ => None
+            }
+        }).collect();
+
+        for instruction in self.instructions.iter().flat_map(|(_, _, instructions)| instructions.iter()) {
+            if let (_, Instruction::ExitGateway(ArgType::Exit(connected_exit), ArgType::Gateway(gateway))) = instruction {
+                let (name, exits) = split_stream_ref(connected_exit);
+                if let Some(connection) = connections.iter_mut().find(|connection| connection.name == name) {
+                    for exit in exits {
+                        connection.exit_gateways.push((exit, gateway.to_string()));
+                    }
+                }
+            }
+        }
+
+        connections
+    }
+
+    /// The alphabet one of the program's gateways or exits was registered with
+    fn stream_alphabet(&self, stream: &str) -> Option<&str> {
+        self.gateways.iter().chain(self.exits.iter()).find_map(|(name, alphabet, _, _, _)| match (name, alphabet) {
+            (ArgType::Name(name), ArgType::Alphabet(alphabet)) if name == stream => Some(alphabet.as_str()),
+            _ => None
+        })
+    }
+
+    /// What each exit of the program may be pushed, when its gateways may be fed any character of their alphabets -
+    /// error exits, receiving raw values rather than characters, are left out
+    pub fn reachable_chars(&self, alphabets: &[&Alphabet], programs: &[&Program]) -> Vec<ExitChars> {
+        let exits = self.exit_chars(alphabets, programs, &BTreeMap::new(), &BTreeMap::new(), &mut vec![]);
+
+        self.exits.iter().filter_map(|(name, _, _, _, _)| {
+            let (Some(alphabet), chars) = exits.get(name.as_str())? else {
+                return None;
+            };
+
+            let alphabet = alphabets.iter().find(|defined| defined.name() == alphabet)?;
+            let (reached, never): (Vec<&str>, Vec<&str>) = alphabet.char_names().partition(|chr| chars.contains(*chr));
+
+            Some(ExitChars{
+                program: self.name.clone(),
+                exit: name.as_str().to_string(),
+                alphabet: alphabet.name().to_string(),
+                chars: reached.into_iter().map(String::from).collect(),
+                never: never.into_iter().map(String::from).collect(),
+                values: chars.iter().filter(|chr| !alphabet.has_char(chr)).cloned().collect()
+            })
+        }).collect()
+    }
+
+    /// The characters each exit may be pushed - gateways may hold any character of their alphabet, unless `fed` says
+    /// otherwise. `params` binds the program's generic parameters to alphabets, and `connecting` lists the programs
+    /// whose connections led to this one, so connect cycles come to an end.
+    fn exit_chars(&self, alphabets: &[&Alphabet], programs: &[&Program], fed: &BTreeMap<&str, Reach>, params: &BTreeMap<String, String>, connecting: &mut Vec<String>) -> BTreeMap<String, Reach> {
+        use Instruction::*;
+        let find_alphabet = |name: &Option<String>| name.as_ref().and_then(|name| alphabets.iter().find(|alphabet| alphabet.name() == name).copied());
+        let resolve = |alphabet: &ArgType| match alphabet.as_str().strip_prefix('$') {
+            Some(param) => params.get(param).cloned(),
+            None => Some(alphabet.as_str().to_string())
+        };
+
+        let mut streams: BTreeMap<String, Reach> = self.gateways.iter().map(|(name, alphabet, _, _, _)| {
+            let reach = fed.get(name.as_str()).cloned().unwrap_or_else(|| {
+                let alphabet = resolve(alphabet);
+                let chars = find_alphabet(&alphabet).map_or(BTreeSet::new(), |defined| defined.char_names().map(String::from).collect());
+                (alphabet, chars)
+            });
+
+            (name.as_str().to_string(), reach)
+        }).collect();
+
+        // Exit gateways hold whatever the connected program may push onto the exits feeding them
+        connecting.push(self.name.clone());
+        for connection in self.connections() {
+            let Some(connected) = programs.iter().find(|prog| prog.name() == connection.program).filter(|prog| !connecting.contains(&prog.name)) else {
+                continue;
+            };
+
+            let mut connected_fed = BTreeMap::new();
+            let mut connected_params = BTreeMap::new();
+            for ((gateway, alphabet, _, _, _), feeding) in connected.gateways.iter().zip(connection.gateways.iter()) {
+                let Some(reach) = streams.get(feeding) else {
+                    continue;
+                };
+
+                if let (Some(param), Some(bound)) = (alphabet.as_str().strip_prefix('$'), reach.0.as_ref()) {
+                    connected_params.entry(param.to_string()).or_insert_with(|| bound.clone());
+                }
+
+                connected_fed.insert(gateway.as_str(), reach.clone());
+            }
+
+            let connected_exits = connected.exit_chars(alphabets, programs, &connected_fed, &connected_params, connecting);
+            for (exit, gateway) in connection.exit_gateways.iter() {
+                if let Some(reach) = connected_exits.get(exit) {
+                    streams.insert(gateway.clone(), reach.clone());
+                }
+            }
+        }
+        connecting.pop();
+
+        let mut exits: BTreeMap<String, Reach> = self.exits.iter().filter(|(name, _, _, _, _)| {
+            self.error_exit.as_ref().is_none_or(|error_exit| error_exit.as_str() != name.as_str())
+        }).map(|(name, alphabet, _, _, _)| (name.as_str().to_string(), (resolve(alphabet), BTreeSet::new()))).collect();
+
+        let exit_alphabet = |exit: &ArgType| self.exits.iter().find(|(name, _, _, _, _)| name.as_str() == exit.as_str()).and_then(|(_, alphabet, _, _, _)| resolve(alphabet));
+        let held = |gateway: &ArgType| streams.get(gateway.as_str()).map_or(BTreeSet::new(), |(_, chars)| chars.clone());
+
+        // A character of a stream's alphabet by name or raw value - or the value itself, when it is none
+        let named = |stream: &Option<String>, chr: &str| match (parse_number(chr), find_alphabet(stream)) {
+            (Some(value), Some(alphabet)) => alphabet.char_with_value(value).map_or(chr.to_string(), String::from),
+            _ => chr.to_string()
+        };
+
+        // The characters within a class of the gateway's alphabet - or equal to a raw value
+        let within = |gateway: &ArgType, class: &ArgType| -> BTreeSet<String> {
+            let alphabet = streams.get(gateway.as_str()).and_then(|(alphabet, _)| alphabet.clone());
+            match find_alphabet(&alphabet).and_then(|defined| defined.class_members(class.as_str())) {
+                Some(members) => members.iter().cloned().collect(),
+                None => BTreeSet::from([named(&alphabet, class.as_str())])
+            }
+        };
+
+        let mut push = |exit: &ArgType, chars: BTreeSet<String>| {
+            if let Some((_, reached)) = exits.get_mut(exit.as_str()) {
+                reached.extend(chars);
+            }
+        };
+
+        for (_, instruction) in self.instructions.iter().flat_map(|(_, _, instructions)| instructions.iter()) {
+            match instruction {
+                PushChar(chr, exit) | PushVal(chr, exit) => {
+                    push(exit, BTreeSet::from([named(&exit_alphabet(exit), chr.as_str())]));
+                },
+
+                ForwardDuration(gateway, exit) | Window(gateway, exit, _) | Delay(gateway, exit, _) | Throttle(gateway, exit, _) => push(exit, held(gateway)),
+                Filter(gateway, exit, class) => push(exit, held(gateway).difference(&within(gateway, class)).cloned().collect()),
+                FilterKeep(gateway, exit, class) => push(exit, held(gateway).intersection(&within(gateway, class)).cloned().collect()),
+
+                Tee(gateway, exit_a, exit_b) => {
+                    push(exit_a, held(gateway));
+                    push(exit_b, held(gateway));
+                },
+
+                Sync(gateways, sync_exits) => {
+                    for (gateway, exit) in gateways.iter().zip(sync_exits.iter()) {
+                        push(exit, held(gateway));
+                    }
+                },
+
+                Zip(gateways, exit) => {
+                    for gateway in gateways.iter() {
+                        push(exit, held(gateway));
+                    }
+                },
+
+                Split(gateway, split_exits, SplitStrategy::ByClass(class)) => {
+                    let (chars, class) = (held(gateway), within(gateway, class));
+                    if let [in_class, out_of_class] = split_exits.as_slice() {
+                        push(in_class, chars.intersection(&class).cloned().collect());
+                        push(out_of_class, chars.difference(&class).cloned().collect());
+                    }
+                },
+
+                Split(gateway, split_exits, _) => {
+                    for exit in split_exits.iter() {
+                        push(exit, held(gateway));
+                    }
+                },
+
+                // Counts and moments are pushed as the digits of the exit's alphabet
+                CountChars(_, exit) | MinMoment(_, exit) | MaxMoment(_, exit) => {
+                    let digits = find_alphabet(&exit_alphabet(exit)).map_or(BTreeSet::new(), |alphabet| {
+                        DIGIT_NAMES.iter().filter(|digit| alphabet.has_char(digit)).map(|digit| digit.to_string()).collect()
+                    });
+
+                    push(exit, digits);
+                },
+
+                _ => ()
+            }
+        }
+
+        exits
+    }
+
+    /// Semantic checks that need no code generation - every problem found is reported, rather than just the first
+    pub fn validate(&self, alphabets: &[&Alphabet], clocks: &[&Clock], programs: &[&Program]) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let find_alphabet = |name: &str| alphabets.iter().find(|alphabet| alphabet.name() == name).copied();
+        let find_program = |name: &str| programs.iter().find(|program| program.name() == name).copied();
+
+        for (name, alphabet, clock, _, span) in self.gateways.iter().chain(self.exits.iter()) {
+            if let (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock)) = (name, alphabet, clock) {
+                if !alphabet.starts_with('$') && find_alphabet(alphabet).is_none() {
+                    diagnostics.push(Diagnostic::at(span, format!("Program ({}) - stream {} uses undefined Alphabet ({})", self.name, name, alphabet)));
+                }
+
+                if !clock.starts_with('$') && !clocks.iter().any(|defined| defined.name() == clock) {
+                    diagnostics.push(Diagnostic::at(span, format!("Program ({}) - stream {} uses undefined Clock ({})", self.name, name, clock)));
+                }
+            }
+        }
+
+        // Gateways registered with reg_exit_gateway read from a connected program, but are used like any other gateway
+        let connections = self.connections();
+        let mut gateways = self.gateway_names();
+        gateways.extend(connections.iter().flat_map(|connection| connection.exit_gateways.iter().map(|(_, gateway)| gateway.as_str())));
+        let exits = self.exit_names();
+
+        let labels: Vec<&str> = self.instructions.iter().filter_map(|(label, _, _)| match label {
+            ArgType::Name(name) => Some(name.as_str()),
+            _ => None
+        }).collect();
+
+        for (idx, (_, label_span, instructions)) in self.instructions.iter().enumerate() {
+            if labels[..idx].contains(&labels[idx]) {
+                diagnostics.push(Diagnostic::at(label_span, format!("Program ({}) - label {} is already defined", self.name, labels[idx])));
+            }
+
+            for (span, instruction) in instructions.iter() {
+                for arg in instruction.args() {
+                    match arg {
+                        ArgType::Gateway(name) if !gateways.contains(&name.as_str()) => {
+                            diagnostics.push(Diagnostic::at(span, format!("Could not find Gateway ({}) for Program ({})", name, self.name)));
+                        },
+
+                        ArgType::Exit(name) if !exits.contains(&name.as_str()) => {
+                            diagnostics.push(Diagnostic::at(span, format!("Could not find Exit ({}) for Program ({})", name, self.name)));
+                        },
+
+                        ArgType::Label(name) => match labels.iter().position(|label| label == name) {
+                            None => diagnostics.push(Diagnostic::at(span, format!("Program ({}) - jump to undefined label: {}", self.name, name))),
+                            Some(target) if target <= idx => {
+                                diagnostics.push(Diagnostic::at(span, format!("Program ({}) - jumps can only go forward, but label {} is not after label {}", self.name, name, labels[idx])));
+                            },
+                            Some(_) => ()
+                        },
+
+                        ArgType::Moment(moment) => {
+                            if let Some(stream) = moment.strip_prefix("Time(").and_then(|stream| stream.strip_suffix(')')) {
+                                if !gateways.contains(&stream) && !exits.contains(&stream) {
+                                    diagnostics.push(Diagnostic::at(span, format!("Could not find stream ({}) for Program ({})", stream, self.name)));
+                                }
+                            }
+                        },
+
+                        _ => ()
+                    }
+                }
+
+                diagnostics.extend(self.validate_references(span, instruction, &gateways, &connections, &find_alphabet, &find_program));
+            }
+        }
+
+        // Connects are resolved once everything they refer to exists - which checks the types of the streams they wire up
+        if diagnostics.is_empty() {
+            diagnostics.extend(self.resolve_links(programs).err());
+        }
+
+        diagnostics
+    }
+
+    /// Checks what an instruction refers to outside of the program's own streams - characters and classes of
+    /// alphabets, and connected programs
+    fn validate_references<'a>(
+        &self,
+        span: &Span,
+        instruction: &Instruction,
+        gateways: &[&str],
+        connections: &[Connection],
+        find_alphabet: &dyn Fn(&str) -> Option<&'a Alphabet>,
+        find_program: &dyn Fn(&str) -> Option<&'a Program>
+    ) -> Vec<Diagnostic> {
+        use Instruction::*;
+        let mut diagnostics = vec![];
+
+        match instruction {
+            PushChar(ArgType::Character(chr), ArgType::Exit(exit)) if parse_number(chr).is_none() => {
+                if let Some(alphabet) = self.stream_alphabet(exit).and_then(find_alphabet) {
+                    if !alphabet.has_char(chr) {
+                        diagnostics.push(Diagnostic::at(span, format!("Alphabet ({}) has no character named {}", alphabet.name(), chr)));
+                    }
+                }
+            },
+
+            JumpIfClass(_, ArgType::Gateway(gateway), ArgType::Class(class)) | Filter(ArgType::Gateway(gateway), _, ArgType::Class(class)) |
+            FilterKeep(ArgType::Gateway(gateway), _, ArgType::Class(class)) | Split(ArgType::Gateway(gateway), _, SplitStrategy::ByClass(ArgType::Class(class))) => {
+                if let Some(alphabet) = self.stream_alphabet(gateway).and_then(find_alphabet) {
+                    if !alphabet.has_class(class) {
+                        diagnostics.push(Diagnostic::at(span, format!("Alphabet ({}) has no class named {}", alphabet.name(), class)));
+                    }
+                }
+            },
+
+            Connect(ArgType::Program(target), _) => {
+                let (program, forwarded) = split_stream_ref(target);
+                match find_program(program) {
+                    None => diagnostics.push(Diagnostic::at(span, format!("Program ({}) - connects to undefined Program ({})", self.name, program))),
+                    Some(callee) if callee.gateway_names().len() != forwarded.len() => {
+                        diagnostics.push(Diagnostic::at(span, format!("Program ({}) - forwards {} gateways to Program ({}), which has {}", self.name, forwarded.len(), program, callee.gateway_names().len())));
+                    },
+                    Some(_) => ()
+                }
+
+                for gateway in forwarded.iter().filter(|gateway| !gateways.contains(&gateway.as_str())) {
+                    diagnostics.push(Diagnostic::at(span, format!("Could not find Gateway ({}) for Program ({})", gateway, self.name)));
+                }
+            },
+
+            ExitGateway(ArgType::Exit(connected_exit), _) => {
+                let (name, exits) = split_stream_ref(connected_exit);
+                match connections.iter().find(|connection| connection.name == name) {
+                    None => diagnostics.push(Diagnostic::at(span, format!("Program ({}) - no connect is named {}", self.name, name))),
+                    Some(connection) => if let Some(callee) = find_program(&connection.program) {
+                        for exit in exits.iter().filter(|exit| !callee.exit_names().contains(&exit.as_str())) {
+                            diagnostics.push(Diagnostic::at(span, format!("Could not find Exit ({}) for Program ({})", exit, callee.name())));
+                        }
+                    }
+                }
+            },
+
+            _ => ()
+        }
+
+        diagnostics
+    }
+
+    /// Warnings about what never does anything - gateways never read, exits never pushed to, labels that never run
+    /// and jumps that are never taken - and about exits that may overflow. Every jump of the language is conditional,
+    /// so the instructions a jump leaves dead are those of labels it alone would run.
+    pub fn lint(&self, clocks: &[&Clock], programs: &[&Program]) -> Vec<Diagnostic> {
+        use Instruction::*;
+        let mut diagnostics = vec![];
+        let instructions = || self.instructions.iter().flat_map(|(_, _, instructions)| instructions.iter());
+
+        // Gateways are also read by the programs they are forwarded to, and their clocks by Time(GATEWAY) moments
+        let connections = self.connections();
+        let mut read: Vec<&str> = connections.iter().flat_map(|connection| connection.gateways.iter().map(|gateway| gateway.as_str())).collect();
+        let mut pushed: Vec<&str> = vec![];
+
+        for (_, instruction) in instructions() {
+            for arg in instruction.args() {
+                match (instruction, arg) {
+                    (_, ArgType::Gateway(name)) => read.push(name),
+                    (StartMoment(_, _) | JumpOverflowed(_, _), ArgType::Exit(_)) => (),
+                    (_, ArgType::Exit(name)) => pushed.push(name),
+                    (_, ArgType::Moment(moment)) => read.extend(moment.strip_prefix("Time(").and_then(|stream| stream.strip_suffix(')'))),
+                    _ => ()
+                }
+            }
+        }
+
+        let exit_gateways = instructions().filter_map(|(span, instruction)| match instruction {
+            ExitGateway(_, ArgType::Gateway(gateway)) => Some((gateway.as_str(), span)),
+            _ => None
+        });
+
+        for (gateway, span) in self.gateways.iter().map(|(name, _, _, _, span)| (name.as_str(), span)).chain(exit_gateways) {
+            if !read.contains(&gateway) {
+                diagnostics.push(Diagnostic::lint(span, Lint::UnusedGateway, format!("Program ({}) - gateway {} is never read", self.name, gateway)));
+            }
+        }
+
+        // Error exits are pushed to by the runtime, with whatever wasn't a character
+        for (exit, _, _, _, span) in self.exits.iter() {
+            let error_exit = self.error_exit.as_ref().is_some_and(|error_exit| error_exit.as_str() == exit.as_str());
+            if !error_exit && !pushed.contains(&exit.as_str()) {
+                diagnostics.push(Diagnostic::lint(span, Lint::UnusedExit, format!("Program ({}) - exit {} is never pushed to", self.name, exit.as_str())));
+            }
+        }
+
+        for (span, instruction) in instructions().filter(|(_, instruction)| instruction.never_taken()) {
+            let (cmd, args) = instruction.statement();
+            diagnostics.push(Diagnostic::lint(span, Lint::DeadCode, format!("Program ({}) - {} compares gateway {} with itself, so it never jumps", self.name, cmd, args[1])));
+        }
+
+        let reachable = self.reachable_labels();
+        for (name, span, instructions) in self.instructions.iter().filter(|(name, _, _)| !reachable.contains(&name.as_str())) {
+            let message = match instructions.len() {
+                0 => format!("Program ({}) - label {} is never jumped to", self.name, name.as_str()),
+                1 => format!("Program ({}) - label {} is never jumped to, so its instruction never runs", self.name, name.as_str()),
+                len => format!("Program ({}) - label {} is never jumped to, so its {} instructions never run", self.name, name.as_str(), len)
+            };
+
+            diagnostics.push(Diagnostic::lint(span, Lint::UnreachableLabel, message));
+        }
+
+        diagnostics.extend(self.buffer_overflows(clocks, programs));
+        diagnostics.extend(self.backwards_moments());
+        diagnostics.extend(self.stalls());
+        diagnostics
+    }
+
+    /// The labels that may ever run - the root label runs first, then main (or the root again), and anything else
+    /// only once jumped to. Spliced programs start their own chains.
+    fn reachable_labels(&self) -> Vec<&str> {
+        let mut reachable = vec!["root", "main"];
+        reachable.extend(self.inlined.iter().flat_map(|inlined| [inlined.root.as_str(), inlined.main.as_str()]));
+        let mut idx = 0;
+        while idx < reachable.len() {
+            let label = self.instructions.iter().find(|(name, _, _)| name.as_str() == reachable[idx]);
+            for (_, instruction) in label.into_iter().flat_map(|(_, _, instructions)| instructions.iter()) {
+                match instruction.jump_target() {
+                    Some(target) if !instruction.never_taken() && !reachable.contains(&target) => reachable.push(target),
+                    _ => ()
+                }
+            }
+
+            idx += 1;
+        }
+
+        reachable
+    }
+
+    /// The labels to generate, with their instructions - with `-O`, labels that never run and jumps that are never
+    /// taken are left out, along with start_moments overridden by the one right after them and push_moments repeating
+    /// the constant moment pushed onto the same exit right before them
+    fn generated_labels(&self) -> Vec<(&str, Vec<(Span, Instruction)>)> {
+        if !self.optimize {
+            return self.instructions.iter().map(|(name, _, instructions)| (name.as_str(), instructions.clone())).collect();
+        }
+
+        let reachable = self.reachable_labels();
+        self.instructions.iter().filter(|(name, _, _)| reachable.contains(&name.as_str())).map(|(name, _, instructions)| {
+            let kept = instructions.iter().enumerate().filter(|(idx, (_, instruction))| {
+                let overridden = match (instruction, instructions.get(idx + 1)) {
+                    (Instruction::StartMoment(_, exit), Some((_, Instruction::StartMoment(_, next_exit)))) => exit.as_str() == next_exit.as_str(),
+                    _ => false
+                };
+
+                // The repeated moment would only end an empty duration, ending where it starts
+                let repeated = match (idx.checked_sub(1).map(|prev| &instructions[prev].1), instruction) {
+                    (Some(Instruction::PushMoment(ArgType::Moment(prev), prev_exit)), Instruction::PushMoment(ArgType::Moment(moment), exit)) => {
+                        !moment.starts_with("Time(") && prev == moment && prev_exit.as_str() == exit.as_str()
+                    },
+                    _ => false
+                };
+
+                !overridden && !repeated && !instruction.never_taken()
+            });
+
+            (name.as_str(), kept.map(|(_, instruction)| instruction.clone()).collect())
+        }).collect()
+    }
+
+    /// Warnings about label chains never making progress when no input comes in - the root label (which runs before
+    /// any input is buffered) waiting on a gateway, and paths through main (or the root again, repeated for as long as
+    /// a gateway has a duration buffered) reading none, which leave that duration there for the next step to find
+    fn stalls(&self) -> Vec<Diagnostic> {
+        use Instruction::*;
+        let labels: Vec<&str> = self.instructions.iter().map(|(name, _, _)| name.as_str()).collect();
+        let mut progress = vec![Progress::END; labels.len()];
+
+        // Like buffer loads, labels are summed up from the last
+        for (idx, (name, _, instructions)) in self.instructions.iter().enumerate().rev() {
+            let mut rest = Progress::END;
+            for (span, instruction) in instructions.iter().rev() {
+                match instruction {
+                    JumpEarlier(label, _, _) | JumpLater(label, _, _) | JumpIfClass(label, _, _) | JumpOverflowed(label, _) => {
+                        if let Some(target) = labels.iter().position(|name| *name == label.as_str()).filter(|target| *target > idx) {
+                            rest = rest.or(progress[target].clone());
+                        }
+                    },
+
+                    _ => {
+                        if let Some(gateway) = instruction.awaited() {
+                            rest.waits = Some((vec![], span, gateway.as_str()));
+                        }
+
+                        if instruction.consumes() {
+                            rest.idle = None;
+                        }
+                    }
+                }
+            }
+
+            progress[idx] = rest.from(name.as_str());
+        }
+
+        let mut diagnostics = vec![];
+        let Some(root) = progress.first() else {
+            return diagnostics;
+        };
+
+        if let Some((chain, span, gateway)) = root.waits.as_ref() {
+            let message = format!("Program ({}) - label chain {} waits on gateway {} before any input is buffered, forever if none comes in", self.name, chain.join(" -> "), gateway);
+            diagnostics.push(Diagnostic::lint(span, Lint::NoProgress, message));
+        }
+
+        let main = labels.iter().position(|label| *label == "main");
+        let reads_input = !self.gateways.is_empty() || !self.connections().is_empty();
+        if let (true, Some(chain)) = (reads_input, main.map_or(root, |main| &progress[main]).idle.as_ref()) {
+            let message = format!("Program ({}) - label chain {} may end without reading a gateway, so it is repeated for as long as input is pending", self.name, chain.join(" -> "));
+            diagnostics.push(Diagnostic::lint(&self.instructions[main.unwrap_or(0)].1, Lint::NoProgress, message));
+        }
+
+        diagnostics
+    }
+
+    /// Warnings about moments that may be pushed onto an exit earlier than the one before - comparing every two
+    /// moments pushed in a row, along every path through the labels and across steps. Each stream's moments never go
+    /// back, but how those of two streams relate is only known for streams the program orders itself: comparing them
+    /// with jump_earlier or jump_later, reading them with sync or zip, or reading them off of the same connected program.
+    fn backwards_moments(&self) -> Vec<Diagnostic> {
+        use Instruction::*;
+        let instructions = || self.instructions.iter().flat_map(|(_, _, instructions)| instructions.iter());
+
+        let mut ordered: Vec<Vec<String>> = vec![];
+        let mut order = |streams: Vec<String>| {
+            let (joined, rest): (Vec<_>, Vec<_>) = ordered.drain(..).partition(|group| group.iter().any(|stream| streams.contains(stream)));
+            let mut group: Vec<String> = joined.into_iter().flatten().chain(streams).collect();
+            group.sort_unstable();
+            group.dedup();
+            ordered = rest;
+            ordered.push(group);
+        };
+
+        for (_, instruction) in instructions() {
+            match instruction {
+                JumpEarlier(_, a, b) | JumpLater(_, a, b) => order(vec![a.as_str().to_string(), b.as_str().to_string()]),
+                Sync(gateways, _) | Zip(gateways, _) => order(gateways.iter().map(|gateway| gateway.as_str().to_string()).collect()),
+                _ => ()
+            }
+        }
+
+        for connection in self.connections() {
+            order(connection.exit_gateways.into_iter().map(|(_, gateway)| gateway).collect());
+        }
+
+        let same_order = |a: &str, b: &str| a == b || ordered.iter().any(|group| group.iter().any(|stream| stream == a) && group.iter().any(|stream| stream == b));
+        let may_go_back = |previous: &MomentSource, next: &MomentSource| match (previous, next) {
+            (MomentSource::Literal(previous), MomentSource::Literal(next)) => next < previous,
+            (MomentSource::Literal(previous), MomentSource::Stream(_, later_by)) => previous > later_by,
+            (MomentSource::Stream(_, _), MomentSource::Literal(_)) => true,
+            (MomentSource::Stream(previous, previous_by), MomentSource::Stream(next, next_by)) => next_by < previous_by || !same_order(previous, next)
+        };
+
+        let labels: Vec<&str> = self.instructions.iter().map(|(name, _, _)| name.as_str()).collect();
+        let main = labels.iter().position(|label| *label == "main");
+        let mut diagnostics = vec![];
+
+        for (exit, _, _, _, _) in self.exits.iter() {
+            let mut backwards: Vec<(&Span, MomentSource, MomentSource)> = vec![];
+            let mut pushes = vec![Pushes::NONE; labels.len()];
+
+            // Like buffer loads, labels are summed up from the last
+            for (idx, (_, _, instructions)) in self.instructions.iter().enumerate().rev() {
+                let mut rest = Pushes::NONE;
+                for (span, instruction) in instructions.iter().rev() {
+                    rest = match instruction {
+                        JumpEarlier(label, _, _) | JumpLater(label, _, _) | JumpIfClass(label, _, _) | JumpOverflowed(label, _) => {
+                            match labels.iter().position(|name| *name == label.as_str()).filter(|target| *target > idx) {
+                                Some(target) => pushes[target].clone().or(rest),
+                                None => rest
+                            }
+                        },
+
+                        _ => match MomentSource::of(instruction, exit.as_str()) {
+                            Some(source) => Pushes::push(span, source).then(rest, &may_go_back, &mut backwards),
+                            None => rest
+                        }
+                    };
+                }
+
+                pushes[idx] = rest;
+            }
+
+            // The root label runs once, after the initial moment set by start_moment - then main (or the root again)
+            // runs for as long as there is input, the moments a step may end on followed by those the next one may
+            // start with
+            let Some(root) = pushes.first() else {
+                continue;
+            };
+
+            let initial: Vec<MomentSource> = instructions().filter_map(|(_, instruction)| match instruction {
+                StartMoment(moment, to) if to.as_str() == exit.as_str() => parse_number(moment.as_str()).map(MomentSource::Literal),
+                _ => None
+            }).collect();
+
+            let mut last = Pushes{first: vec![], last: initial, through: true}.then(root.clone(), &may_go_back, &mut backwards).last;
+            let step = main.map_or(root, |main| &pushes[main]);
+            let reads_input = !self.gateways.is_empty() || !self.connections().is_empty();
+
+            let mut checked = None;
+            while reads_input && checked != Some(last.len()) {
+                checked = Some(last.len());
+                for previous in last.iter() {
+                    for (span, next) in step.first.iter().filter(|(_, next)| may_go_back(previous, next)) {
+                        backwards.push((span, previous.clone(), next.clone()));
+                    }
+                }
+
+                extend_unique(&mut last, step.last.clone());
+            }
+
+            let mut reported: Vec<&Span> = vec![];
+            for (span, previous, next) in backwards {
+                if reported.contains(&span) {
+                    continue;
+                }
+
+                reported.push(span);
+                diagnostics.push(Diagnostic::lint(span, Lint::NonMonotonic, format!("Program ({}) - may push {} onto exit {} after {}, going back in time", self.name, next, exit.as_str(), previous)));
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Warnings about exits that may be pushed a duration (its characters, then the moment closing it) longer than
+    /// their buffers hold - bounding the characters pushed between two moments along every path through the labels,
+    /// and across steps. Gateways are taken to hold whole durations, so theirs are shorter than their buffers (or
+    /// than the buffers of the exits feeding them, for exit gateways).
+    fn buffer_overflows(&self, clocks: &[&Clock], programs: &[&Program]) -> Vec<Diagnostic> {
+        use Instruction::*;
+        let connections = self.connections();
+
+        // Exit gateways are registered like the connected program's exits feeding them
+        let gateway = |gateway: &str| {
+            let connected = connections.iter().find_map(|connection| {
+                connection.exit_gateways.iter().find(|(_, exit_gateway)| exit_gateway == gateway).map(|(exit, _)| (connection.program.as_str(), exit.as_str()))
+            });
+
+            match connected {
+                Some((program, exit)) => programs.iter().find(|prog| prog.name() == program).and_then(|prog| prog.exits.iter().find(|(name, _, _, _, _)| name.as_str() == exit)),
+                None => self.gateways.iter().find(|(name, _, _, _, _)| name.as_str() == gateway)
+            }
+        };
+
+        let bounds = Bounds{
+            longest_duration: &|name| gateway(name).and_then(|(_, _, _, buf_size, _)| buffer_items(buf_size.as_str())).map_or(UNBOUNDED_LOAD, |items| items.saturating_sub(1)),
+            moment_digits: &|name| {
+                let clock = gateway(name).and_then(|(_, _, clock, _, _)| clocks.iter().find(|defined| defined.name() == clock.as_str()));
+                clock.map_or(20, |clock| clock.moment_digits())
+            }
+        };
+
+        let labels: Vec<&str> = self.instructions.iter().map(|(name, _, _)| name.as_str()).collect();
+        let main = labels.iter().position(|label| *label == "main");
+        let mut diagnostics = vec![];
+
+        for (exit, _, _, buf_size, span) in self.exits.iter() {
+            let error_exit = self.error_exit.as_ref().is_some_and(|error_exit| error_exit.as_str() == exit.as_str());
+            let Some(items) = buffer_items(buf_size.as_str()).filter(|_| !error_exit) else {
+                continue;
+            };
+
+            // Jumps only go forward, so labels are summed up from the last - a jump either runs its label, or the
+            // rest of the one it's in
+            let mut loads = vec![Load::NONE; labels.len()];
+            for (idx, (_, _, instructions)) in self.instructions.iter().enumerate().rev() {
+                loads[idx] = instructions.iter().rev().fold(Load::NONE, |rest, (_, instruction)| match instruction {
+                    JumpEarlier(label, _, _) | JumpLater(label, _, _) | JumpIfClass(label, _, _) | JumpOverflowed(label, _) => {
+                        match labels.iter().position(|name| *name == label.as_str()).filter(|target| *target > idx) {
+                            Some(target) => loads[target].or(rest),
+                            None => rest
+                        }
+                    },
+
+                    _ => Load::of(instruction, exit.as_str(), &bounds).then(rest)
+                });
+            }
+
+            // The root label runs first, then main (or the root again) on every step - a step that may push
+            // characters without closing the duration lets it grow for as long as the program runs
+            let (root, step) = match loads.first() {
+                Some(root) => (*root, main.map_or(*root, |main| loads[main])),
+                None => continue
+            };
+
+            let mut open = root.through.unwrap_or(0).max(root.last.unwrap_or(0));
+            let mut longest = root.within.max(root.first.unwrap_or(0));
+            if step.through.is_some_and(|chars| chars > 0) {
+                open = UNBOUNDED_LOAD;
+            }
+
+            for _ in 0..2 {
+                longest = longest.max(open).max(step.within).max(step.first.map_or(0, |first| open.saturating_add(first)));
+                open = open.max(step.last.unwrap_or(0));
+            }
+
+            let message = match longest {
+                UNBOUNDED_LOAD => format!("Program ({}) - exit {} may be pushed durations of any length, which its buffer of {} items can't hold", self.name, exit.as_str(), items),
+                longest if longest >= items => {
+                    format!("Program ({}) - exit {} may be pushed {} characters between moments, which (with the moment closing them) its buffer of {} items can't hold", self.name, exit.as_str(), longest, items)
+                },
+                _ => continue
+            };
+
+            diagnostics.push(Diagnostic::lint(span, Lint::BufferOverflow, message));
+        }
+
+        diagnostics
+    }
+
+    pub fn process_command(&mut self, span: &Span, cmd: &str, args: &[&str], constants: &Constants) -> Result<(), Diagnostic> {
+        if self.instructions.is_empty() {
+            self.instructions.push((ArgType::Name("root".to_string()), self.span.clone(), vec![]));
+        }
+
+        let resolved: Vec<String> = args.iter().enumerate().map(|(idx, arg)| match cmd {
+            "filter" | "filter_keep" if idx == 2 => Ok(constants.get(arg).map_or(arg.to_string(), |value| value.to_string())),
+            "reg_gateway" | "reg_exit" | "reg_error_exit" if idx == 3 && parse_dynamic(arg).is_some() => match parse_dynamic(arg) {
+                Some(Some(cap)) => constants.resolve(span, cap).map(|cap| format!("dynamic({})", cap)),
+                _ => Ok(arg.to_string())
+            },
+            _ if NUMERIC_ARGS.contains(&(cmd, idx)) => constants.resolve(span, arg),
+            _ => Ok(arg.to_string())
+        }).collect::<Result<_, _>>()?;
+        let resolved: Vec<&str> = resolved.iter().map(|arg| arg.as_str()).collect();
+        let args = &resolved[..];
+
+        for (idx, arg) in args.iter().enumerate() {
+            let names: Vec<&str> = match (cmd, idx) {
+                ("sync", _) | ("zip", 0) | ("split", 1) => arg.split('|').collect(),
+                ("connect", 0) | ("reg_exit_gateway", 0) => match arg.split_once('(') {
+                    Some((owner, streams)) => std::iter::once(owner).chain(streams.strip_suffix(')').unwrap_or(streams).split('|')).collect(),
+                    None => vec![arg]
+                },
+
+                // Streams of generic programs may use the program's `$PARAM` alphabets and clocks
+                ("reg_gateway" | "reg_exit" | "reg_error_exit", 1 | 2) => vec![arg.strip_prefix('$').unwrap_or(arg)],
+
+                // Classes are named like streams - characters may also be given by their value
+                ("filter_class", 2) => vec![arg],
+                ("filter" | "filter_keep" | "jump_if_class", 2) | ("push_char", 0) if parse_number(arg).is_some() => vec![],
+                ("filter" | "filter_keep" | "jump_if_class", 2) | ("push_char", 0) => vec![arg],
+                ("split", 2) => arg.strip_prefix("by_class(").and_then(|class| class.strip_suffix(')')).into_iter().collect(),
+
+                _ if NAME_ARGS.contains(&(cmd, idx)) => vec![arg],
+                _ => vec![]
+            };
+
+            for name in names {
+                check_name(span, &format!("Program ({})", self.name), "name", name)?;
+            }
+        }
+
+        let latest_func = self.instructions.last_mut().unwrap();
+
+        match (cmd, args) {
+            ("start_moment", [moment, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::StartMoment(ArgType::Moment(moment.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("set_priority", [priority]) => {
+                self.priority = Some(ArgType::Number(priority.to_string()));
+            },
+
+            ("set_fuel", [fuel]) => {
+                if parse_number(fuel).is_none() {
+                    return Err(Diagnostic::at(span, format!("Program ({}) - invalid fuel: {} (expected a number)", self.name, fuel)));
+                }
+
+                self.fuel = Some(ArgType::Number(fuel.to_string()));
+            },
+
+            ("set_visibility", [visibility]) => {
+                self.visibility = Some(parse_visibility(span, &format!("Program ({})", self.name), visibility)?);
+            },
+
+            ("set_error_mode", [mode]) => {
+                self.error_mode = match *mode {
+                    "panic" => ErrorMode::Panic,
+                    "abort_program" => ErrorMode::AbortProgram,
+                    "propagate" => ErrorMode::Propagate,
+                    mode => return Err(Diagnostic::at(span, format!("Program ({}) - unknown error mode: {} (expected panic, abort_program or propagate)", self.name, mode)))
+                };
+            },
+
+            ("set_on_full", [policy]) => {
+                self.on_full = match *policy {
+                    "error" => OnFull::Error,
+                    "yield" => OnFull::Yield,
+                    "drop_duration" => OnFull::DropDuration,
+                    policy => return Err(Diagnostic::at(span, format!("Program ({}) - unknown on_full policy: {} (expected error, yield or drop_duration)", self.name, policy)))
+                };
+            },
+
+            ("expect_aligned", [exit_a, exit_b]) => {
+                self.expectations.push((ArgType::Exit(exit_a.to_string()), ArgType::Exit(exit_b.to_string()), span.clone()));
+            },
+
+            ("reg_gateway", [name, alphabet, clock, buf_size, options @ ..]) => {
+                self.check_generics(span, name, alphabet, clock)?;
+                self.check_buffer_size(span, name, buf_size)?;
+                self.stream_options(span, ArgType::Gateway(name.to_string()), options, constants)?;
+                self.gateways.push((ArgType::Name(name.to_string()), ArgType::Alphabet(alphabet.to_string()), ArgType::Clock(clock.to_string()), ArgType::Number(buf_size.to_string()), span.clone()));
+            },
+
+            ("reg_exit", [name, alphabet, clock, buf_size, options @ ..]) => {
+                self.check_generics(span, name, alphabet, clock)?;
+                self.check_buffer_size(span, name, buf_size)?;
+                self.stream_options(span, ArgType::Exit(name.to_string()), options, constants)?;
+                self.exits.push((ArgType::Name(name.to_string()), ArgType::Alphabet(alphabet.to_string()), ArgType::Clock(clock.to_string()), ArgType::Number(buf_size.to_string()), span.clone()));
+            },
+
+            ("reg_error_exit", [name, alphabet, clock, buf_size, options @ ..]) => {
+                if let Some(ArgType::Exit(existing)) = &self.error_exit {
+                    return Err(Diagnostic::at(span, format!("Program ({}) - already has an error exit: {}", self.name, existing)));
+                }
+
+                self.check_generics(span, name, alphabet, clock)?;
+                self.check_buffer_size(span, name, buf_size)?;
+                self.stream_options(span, ArgType::Exit(name.to_string()), options, constants)?;
+                self.exits.push((ArgType::Name(name.to_string()), ArgType::Alphabet(alphabet.to_string()), ArgType::Clock(clock.to_string()), ArgType::Number(buf_size.to_string()), span.clone()));
+                self.error_exit = Some(ArgType::Exit(name.to_string()));
+            },
+
+            ("reg_exit_gateway", [connected_name, gateway]) => {
+                latest_func.2.push((span.clone(), Instruction::ExitGateway(ArgType::Exit(connected_name.to_string()), ArgType::Gateway(gateway.to_string()))));
+            },
+
+            ("label", [name]) => {
+                self.instructions.push((ArgType::Name(name.to_string()), span.clone(), vec![]));
+            },
+
+            ("jump_earlier", [label_name, a, b]) => {
+                latest_func.2.push((span.clone(), Instruction::JumpEarlier(ArgType::Label(label_name.to_string()), ArgType::Gateway(a.to_string()), ArgType::Gateway(b.to_string()))));
+            },
+
+            ("jump_later", [label_name, a, b]) => {
+                latest_func.2.push((span.clone(), Instruction::JumpLater(ArgType::Label(label_name.to_string()), ArgType::Gateway(a.to_string()), ArgType::Gateway(b.to_string()))));
+            },
+
+            ("jump_if_class", [label_name, gateway, class]) => {
+                latest_func.2.push((span.clone(), Instruction::JumpIfClass(ArgType::Label(label_name.to_string()), ArgType::Gateway(gateway.to_string()), ArgType::Class(class.to_string()))));
+            },
+
+            ("jump_overflowed", [label_name, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::JumpOverflowed(ArgType::Label(label_name.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("push_moment", [moment_incr, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::PushMoment(ArgType::Moment(moment_incr.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("forward_moment", [gateway, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::ForwardMoment(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            // A character given by its value is pushed the way push_val pushes one
+            ("push_char", [chr, exit]) if parse_number(chr).is_some() => {
+                latest_func.2.push((span.clone(), Instruction::PushVal(ArgType::Number(chr.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("push_char", [chr, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::PushChar(ArgType::Character(chr.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("push_val", [chr, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::PushVal(ArgType::Number(chr.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("forward_duration", [gateway, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::ForwardDuration(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("filter", [gateway, exit, matching]) => {
+                latest_func.2.push((span.clone(), Instruction::Filter(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()), class_or_value(matching))));
+            },
+
+            // filter, with only a class to match
+            ("filter_class", [gateway, exit, class]) => {
+                latest_func.2.push((span.clone(), Instruction::Filter(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()), ArgType::Class(class.to_string()))));
+            },
+
+            ("filter_keep", [gateway, exit, matching]) => {
+                latest_func.2.push((span.clone(), Instruction::FilterKeep(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()), class_or_value(matching))));
+            },
+
+            ("tee", [gateway, exit_a, exit_b]) => {
+                latest_func.2.push((span.clone(), Instruction::Tee(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit_a.to_string()), ArgType::Exit(exit_b.to_string()))));
+            },
+
+            ("drop_duration", [gateway]) => {
+                latest_func.2.push((span.clone(), Instruction::DropDuration(ArgType::Gateway(gateway.to_string()))));
+            },
+
+            ("skip_until_moment", [gateway, moment]) => {
+                latest_func.2.push((span.clone(), Instruction::SkipUntilMoment(ArgType::Gateway(gateway.to_string()), ArgType::Moment(moment.to_string()))));
+            },
+
+            ("sync", [gateways, exits]) => {
+                let gateways: Vec<_> = gateways.split('|').map(|gateway| ArgType::Gateway(gateway.to_string())).collect();
+                let exits: Vec<_> = exits.split('|').map(|exit| ArgType::Exit(exit.to_string())).collect();
+                if gateways.len() != exits.len() {
+                    return Err(Diagnostic::at(span, format!("Program ({}) - sync needs as many exits as gateways ({} vs {})", self.name, gateways.len(), exits.len())));
+                }
+
+                latest_func.2.push((span.clone(), Instruction::Sync(gateways, exits)));
+            },
+
+            ("zip", [gateways, exit]) => {
+                let gateways: Vec<_> = gateways.split('|').map(|gateway| ArgType::Gateway(gateway.to_string())).collect();
+                latest_func.2.push((span.clone(), Instruction::Zip(gateways, ArgType::Exit(exit.to_string()))));
+            },
+
+            ("split", [gateway, exits, strategy]) => {
+                let exits: Vec<_> = exits.split('|').map(|exit| ArgType::Exit(exit.to_string())).collect();
+                let strategy = match *strategy {
+                    "round_robin" => {
+                        let counter = format!("split_{}", self.counters.len());
+                        self.counters.push(ArgType::Name(counter.clone()));
+                        SplitStrategy::RoundRobin(ArgType::Name(counter))
+                    },
+
+                    "by_moment_parity" => SplitStrategy::ByMomentParity,
+
+                    strategy => match strategy.strip_prefix("by_class(").and_then(|class| class.strip_suffix(')')) {
+                        Some(class) if exits.len() == 2 => SplitStrategy::ByClass(ArgType::Class(class.to_string())),
+                        Some(_) => return Err(Diagnostic::at(span, format!("Program ({}) - split by_class needs exactly two exits (in class, not in class)", self.name))),
+                        None => return Err(Diagnostic::at(span, format!("Program ({}) - unknown split strategy: {}", self.name, strategy)))
+                    }
+                };
+
+                latest_func.2.push((span.clone(), Instruction::Split(ArgType::Gateway(gateway.to_string()), exits, strategy)));
+            },
+
+            ("window", [gateway, exit, size]) => {
+                if !matches!(parse_number(size), Some(size) if size > 0) {
+                    return Err(Diagnostic::at(span, format!("Program ({}) - window size must be a positive number, got: {}", self.name, size)));
+                }
+
+                latest_func.2.push((span.clone(), Instruction::Window(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()), ArgType::Number(size.to_string()))));
+            },
+
+            ("count_chars", [gateway, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::CountChars(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("min_moment", [gateway, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::MinMoment(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("max_moment", [gateway, exit]) => {
+                latest_func.2.push((span.clone(), Instruction::MaxMoment(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()))));
+            },
+
+            ("delay", [gateway, exit, amount]) => {
+                latest_func.2.push((span.clone(), Instruction::Delay(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()), ArgType::Moment(amount.to_string()))));
+            },
+
+            ("throttle", [gateway, exit, moments]) => {
+                if !matches!(parse_number(moments), Some(moments) if moments > 0) {
+                    return Err(Diagnostic::at(span, format!("Program ({}) - throttle needs a positive number of moments, got: {}", self.name, moments)));
+                }
+
+                latest_func.2.push((span.clone(), Instruction::Throttle(ArgType::Gateway(gateway.to_string()), ArgType::Exit(exit.to_string()), ArgType::Number(moments.to_string()))));
+            },
+
+            ("connect", [program, name]) => {
+                latest_func.2.push((span.clone(), Instruction::Connect(ArgType::Program(program.to_string()), ArgType::Name(name.to_string()))));
+            },
+
+            _ => {
+                return Err(Diagnostic::at(span, format!("Program ({}) - unknown command: {} ({:?})", self.name, cmd, args)));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn gateway_field(&self, name: &str, alphabet: &str, clock: &str, buf_size: &str) -> proc_macro2::TokenStream {
+        let field_name = format_ident!("gateway_{}", name.to_case(Case::Snake));
+        let stream = self.field_type(alphabet, clock, buf_size);
+
+        quote! {
+            pub #field_name: #stream,
+        }
+    }
+
+    pub fn initialize_gateway_field(&self, name: &str, alphabet: &str, clock: &str, buf_size: &str) -> proc_macro2::TokenStream {
+        let field_name = format_ident!("gateway_{}", name.to_case(Case::Snake));
+        let stream = stream_type(alphabet, clock, buf_size);
+
+        let initialize = if self.overwrites(&ArgType::Gateway(name.to_string())) {
+            quote! { <#stream>::with_policy(OverflowPolicy::OverwriteOldest) }
+        } else {
+            quote! { <#stream>::new() }
+        };
+
+        let initialize = self.traced(name, initialize);
+        quote! {
+            #field_name: #initialize,
+        }
+    }
+
+    pub fn exit_field(&self, name: &str, alphabet: &str, clock: &str, buf_size: &str) -> proc_macro2::TokenStream {
+        let field_name = format_ident!("exit_{}", name.to_case(Case::Snake));
+        let stream = self.field_type(alphabet, clock, buf_size);
+
+        quote! {
+            pub #field_name: #stream,
+        }
+    }
+
+    pub fn initialize_exit_field(&self, name: &str, alphabet: &str, clock: &str, buf_size: &str) -> proc_macro2::TokenStream {
+        let field_name = format_ident!("exit_{}", name.to_case(Case::Snake));
+        let stream = stream_type(alphabet, clock, buf_size);
+
+        let initialize = if self.overwrites(&ArgType::Exit(name.to_string())) {
+            quote! { <#stream>::with_policy(OverflowPolicy::OverwriteOldest) }
+        } else {
+            quote! { <#stream>::new() }
+        };
+
+        let initialize = match self.assert_monotonic {
+            true => quote! { #initialize.asserting_monotonic() },
+            false => initialize
+        };
+
+        let initialize = self.traced(name, initialize);
+        quote! {
+            #field_name: #initialize,
+        }
+    }
+
+    /// The type of a stream's field - wrapped in the runtime's `trace::Traced` when tracing
+    fn field_type(&self, alphabet: &str, clock: &str, buf_size: &str) -> proc_macro2::TokenStream {
+        let stream = stream_type(alphabet, clock, buf_size);
+        if !self.trace {
+            return stream;
+        }
+
+        let alphabet_name = alphabet_type(alphabet);
+        let clock_name = clock_type(clock);
+        let size = buffer_size(buf_size);
+        quote! { trace::Traced<#alphabet_name, #clock_name, #stream, #size> }
+    }
+
+    fn traced(&self, name: &str, stream: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        if !self.trace {
+            return stream;
+        }
+
+        let program = &self.name;
+        quote! { trace::Traced::new(#stream, #program, #name) }
+    }
+
+    /// Returns whatever the label returns - recording the jump first, when tracing
+    fn jump(&self, span: &Span, label: &str) -> proc_macro2::TokenStream {
+        let label_func = self.label_func(label);
+        if !self.trace {
+            return quote! { return self.#label_func(); };
+        }
+
+        let program = &self.name;
+        let line = span.line;
+        quote! {
+            trace::jump(#program, #label, #line);
+            return self.#label_func();
+        }
+    }
+
+    pub fn instruction_call(&self, span: &Span, instruction: &Instruction) -> Result<proc_macro2::TokenStream, Diagnostic> {
+        use Instruction::*;
+
+        Ok(match instruction {
+            StartMoment(ArgType::Moment(moment), ArgType::Exit(exit_name)) => {
+                let exit_field = self.exit_ident(exit_name);
+                let moment_lit: proc_macro2::TokenStream = moment.parse().unwrap();
+
+                quote! {
+                    self.#exit_field.set_initial_moment(#moment_lit);
+                }
+            }
+            
+            PushMoment(ArgType::Moment(moment), ArgType::Exit(exit_name)) => {
+                let exit_field = self.exit_ident(exit_name);
+                let moment_lit = self.moment_expr(span, moment)?;
+
+                quote! {
+                    self.#exit_field.push_moment(#moment_lit)?;
+                }
+            }
+            
+            ForwardMoment(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name)) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let exit_field = self.exit_ident(exit_name);
+
+                quote! {
+                    forward_moment(&mut self.#gateway_field, &mut self.#exit_field)?;
+                }
+            }
+
+            PushVal(ArgType::Number(val), ArgType::Exit(exit_name)) => {
+                let exit_field = self.exit_ident(exit_name);
+                let val_lit: proc_macro2::TokenStream = val.parse().unwrap();
+
+                let alphabet = self.exit_defs().find_map(|(name, alphabet, _, _, _)| {
+                    match (name, alphabet) {
+                        (ArgType::Name(name), ArgType::Alphabet(alphabet)) if name == exit_name => Some(alphabet),
+                        _ => None
+                    }
+                }).ok_or_else(|| Diagnostic::at(span, format!("Could not find Exit ({}) for Program ({})", exit_name, self.name)))?;
+                let alphabet_name = alphabet_type(alphabet);
+                
+                quote! {
+                    self.#exit_field.push(#alphabet_name::to_char(#val_lit).map_err(|_| StreamError::UnknownChar)?)?;
+                }
+            }
+
+            PushChar(ArgType::Character(chr), ArgType::Exit(exit_name)) => {
+                let alphabet = self.exit_defs().find_map(|(name, alphabet, _, _, _)| {
+                    match (name, alphabet) {
+                        (ArgType::Name(name), ArgType::Alphabet(alphabet)) if name == exit_name => Some(alphabet),
+                        _ => None
+                    }
+                }).ok_or_else(|| Diagnostic::at(span, format!("Could not find Exit ({}) for Program ({})", exit_name, self.name)))?;
+
+                let alphabet_name = alphabet_type(alphabet);
+                let enum_name = format_ident!("{}", chr.to_case(Case::Pascal));
+                let exit_field = self.exit_ident(exit_name);
+
+                quote!{
+                    self.#exit_field.push(<#alphabet_name as AlphabetLike>::CharEnum::#enum_name())?;
+                }
+            },
+
+            ForwardDuration(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name)) => {
+                self.forward_duration(gateway_name, exit_name)
+            },
+
+            Sync(gateways, exits) => {
+                let gateway_fields: Vec<_> = gateways.iter().map(|gateway| match gateway {
+                    ArgType::Gateway(gateway_name) => format_ident!("gateway_{}", gateway_name.to_case(Case::Snake)),
+                    other => panic!("{} Expected a Gateway to sync, got: {:?}", span, other)
+                }).collect();
+
+                let steps: Vec<_> = gateways.iter().zip(exits.iter()).enumerate().map(|(idx, streams)| {
+                    match streams {
+                        (ArgType::Gateway(gateway_name), ArgType::Exit(exit_name)) => {
+                            let exit_field = self.exit_ident(exit_name);
+                            let forward = self.forward_duration(gateway_name, exit_name);
+
+                            quote! {
+                                if next_moments[#idx] == Some(target) {
+                                    #forward
+                                } else {
+                                    self.#exit_field.push_moment(target)?;
+                                }
+                            }
+                        },
+
+                        other => panic!("{} Unexpected sync streams: {:?}", span, other)
+                    }
+                }).collect();
+
+                // Every exit receives the earliest pending moment - gateways whose duration ends there forward it, the others only get the moment
+                quote! {
+                    let next_moments = [#(self.#gateway_fields.moments().next()),*];
+                    let earliest = next_moments.iter().flatten().copied().reduce(|a, b| if b < a { b } else { a });
+                    if let (true, Some(target)) = (next_moments.iter().all(Option::is_some), earliest) {
+                        #(#steps)*
+                    }
+                }
+            },
+
+            Zip(gateways, ArgType::Exit(exit_name)) => {
+                let exit_field = self.exit_ident(exit_name);
+
+                let mut gateway_names: Vec<&str> = gateways.iter().map(|gateway| match gateway {
+                    ArgType::Gateway(gateway_name) => gateway_name.as_str(),
+                    other => panic!("{} Expected a Gateway to zip, got: {:?}", span, other)
+                }).collect();
+
+                let declared = self.gateway_names();
+                gateway_names.sort_by_key(|gateway_name| declared.iter().position(|declared_name| declared_name == gateway_name));
+
+                let gateway_fields: Vec<_> = gateway_names.iter().map(|gateway_name| format_ident!("gateway_{}", gateway_name.to_case(Case::Snake))).collect();
+                let steps: Vec<_> = gateway_names.iter().enumerate().map(|(idx, gateway_name)| {
+                    let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                    let invalid_char = self.invalid_char_arm();
+                    let empty = self.empty_arm();
+
+                    quote! {
+                        if next_moments[#idx] == Some(target) {
+                            loop {
+                                match self.#gateway_field.pop() {
+                                    StreamItem::Character(chr) => {
+                                        self.#exit_field.push(chr)?;
+                                    }
+
+                                    StreamItem::Moment(_) => {
+                                        break;
+                                    }
+
+                                    #invalid_char
+
+                                    #empty
+                                }
+                            }
+                        }
+                    }
+                }).collect();
+
+                // Same-moment data is interleaved in gateway declaration order, then closed by the shared moment
+                quote! {
+                    let next_moments = [#(self.#gateway_fields.moments().next()),*];
+                    let earliest = next_moments.iter().flatten().copied().reduce(|a, b| if b < a { b } else { a });
+                    if let (true, Some(target)) = (next_moments.iter().all(Option::is_some), earliest) {
+                        #(#steps)*
+                        self.#exit_field.push_moment(target)?;
+                    }
+                }
+            },
+
+            Split(ArgType::Gateway(gateway_name), exits, strategy) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let exit_names: Vec<&str> = exits.iter().map(|exit| match exit {
+                    ArgType::Exit(exit_name) => exit_name.as_str(),
+                    other => panic!("{} Expected an Exit to split into, got: {:?}", span, other)
+                }).collect();
+                let exit_count = exit_names.len();
+
+                // Durations are only handed to an exit once whole - with just part of one buffered, nothing is split yet
+                match strategy {
+                    SplitStrategy::RoundRobin(ArgType::Name(counter)) => {
+                        let counter_field = format_ident!("{}", counter);
+                        let routes: Vec<_> = exit_names.iter().enumerate().map(|(idx, exit_name)| {
+                            let forward = self.forward_duration(gateway_name, exit_name);
+                            match idx + 1 == exit_count {
+                                true => quote! { _ => { #forward } },
+                                false => quote! { #idx => { #forward } }
+                            }
+                        }).collect();
+
+                        // The last exit takes whatever the others don't, so the match is exhaustive
+                        quote! {
+                            if self.#gateway_field.has_duration() {
+                                match self.#counter_field % #exit_count {
+                                    #(#routes)*
+                                }
+                                self.#counter_field = (self.#counter_field + 1) % #exit_count;
+                            }
+                        }
+                    },
+
+                    SplitStrategy::ByMomentParity => {
+                        let routes: Vec<_> = exit_names.iter().enumerate().map(|(idx, exit_name)| {
+                            let forward = self.forward_duration(gateway_name, exit_name);
+                            let idx_lit = proc_macro2::Literal::usize_unsuffixed(idx);
+                            let count_lit = proc_macro2::Literal::usize_unsuffixed(exit_count);
+                            quote! { if moment % #count_lit == #idx_lit { #forward } }
+                        }).collect();
+
+                        // The duration is routed by the moment closing it, so it can only be split once that moment is buffered
+                        quote! {
+                            let next_moment = self.#gateway_field.moments().next();
+                            if let Some(moment) = next_moment {
+                                #(#routes)*
+                            }
+                        }
+                    },
+
+                    SplitStrategy::ByClass(ArgType::Class(class)) => {
+                        let class_predicate = format_ident!("is_{}", class.to_case(Case::Snake));
+                        let in_class_field = self.exit_ident(exit_names[0]);
+                        let out_class_field = self.exit_ident(exit_names[1]);
+                        let invalid_char = self.invalid_char_arm();
+                        let empty = self.empty_arm();
+
+                        quote! {
+                            if self.#gateway_field.has_duration() {
+                                loop {
+                                    match self.#gateway_field.pop() {
+                                        StreamItem::Character(chr) if chr.#class_predicate() => {
+                                            self.#in_class_field.push(chr)?;
+                                        }
+
+                                        StreamItem::Character(chr) => {
+                                            self.#out_class_field.push(chr)?;
+                                        }
+
+                                        StreamItem::Moment(moment) => {
+                                            self.#in_class_field.push_moment(moment)?;
+                                            self.#out_class_field.push_moment(moment)?;
+                                            break;
+                                        }
+
+                                        #invalid_char
+
+                                        #empty
+                                    }
+                                }
+                            }
+                        }
+                    },
+
+                    other => panic!("{} Unexpected split strategy: {:?}", span, other)
+                }
+            },
+
+            Window(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name), ArgType::Number(size)) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let exit_field = self.exit_ident(exit_name);
+                let size_lit: proc_macro2::TokenStream = size.parse().unwrap();
+                let invalid_char = self.invalid_char_arm();
+                let empty = self.empty_arm();
+                let forward = self.forward_duration(gateway_name, exit_name);
+                let window_field = window_field(gateway_name, exit_name);
+
+                // Durations are merged as they become whole, so a window builds up over as many runs as it takes - the first
+                // K-1 durations lose their closing moment, and the K-th moment closes the merged duration
+                quote! {
+                    while self.#gateway_field.has_duration() {
+                        if self.#window_field + 1 < #size_lit {
+                            loop {
+                                match self.#gateway_field.pop() {
+                                    StreamItem::Character(chr) => {
+                                        self.#exit_field.push(chr)?;
+                                    }
+
+                                    StreamItem::Moment(_) => {
+                                        break;
+                                    }
+
+                                    #invalid_char
+
+                                    #empty
+                                }
+                            }
+
+                            self.#window_field += 1;
+                        } else {
+                            #forward
+                            self.#window_field = 0;
+                            break;
+                        }
+                    }
+                }
+            },
+
+            CountChars(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name)) | MinMoment(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name)) | MaxMoment(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name)) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let exit_field = self.exit_ident(exit_name);
+                let (alphabet_name, clock_name) = self.stream_types(span, exit_name)?;
+                let invalid_char = self.invalid_char_arm();
+                let empty = self.empty_arm();
+
+                // The duration spans from the gateway's current moment (its minimum) up to the moment closing it (its maximum)
+                let (setup, on_character, value) = match instruction {
+                    CountChars(..) => (quote! { let mut count: u64 = 0; }, quote! { count += 1; }, quote! { count }),
+                    MinMoment(..) => (quote! { let start = self.#gateway_field.current_moment(); }, quote! {}, quote! { start.unwrap_or(moment) as u64 }),
+                    _ => (quote! {}, quote! {}, quote! { moment as u64 })
+                };
+
+                quote! {
+                    #setup
+                    loop {
+                        match self.#gateway_field.pop() {
+                            StreamItem::Character(_) => {
+                                #on_character
+                            }
+
+                            StreamItem::Moment(moment) => {
+                                push_decimal::<#alphabet_name, #clock_name, _>(&mut self.#exit_field, #value)?;
+                                self.#exit_field.push_moment(moment)?;
+                                break;
+                            }
+
+                            #invalid_char
+
+                            #empty
+                        }
+                    }
+                }
+            },
+
+            Delay(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name), ArgType::Moment(amount)) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let exit_field = self.exit_ident(exit_name);
+                let (_, clock_name) = self.stream_types(span, gateway_name)?;
+                let amount_lit = self.moment_expr(span, amount)?;
+
+                let invalid_char = self.invalid_char_arm();
+                let empty = self.empty_arm();
+
+                // Delays rely on the clock being AddableClockLike, so clocks without addable moments fail to compile here
+                quote! {
+                    loop {
+                        match self.#gateway_field.pop() {
+                            StreamItem::Character(chr) => {
+                                self.#exit_field.push(chr)?;
+                            }
+
+                            StreamItem::Moment(moment) => {
+                                let delayed = <#clock_name as AddableClockLike<<#clock_name as ClockLike>::MomentRep>>::add(#clock_name::to_moment(moment), #amount_lit);
+                                self.#exit_field.push_moment(delayed.into_rep())?;
+                                break;
+                            }
+
+                            #invalid_char
+
+                            #empty
+                        }
+                    }
+                }
+            },
+
+            Throttle(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name), ArgType::Number(moments)) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let threshold_field = throttle_field(gateway_name, exit_name);
+                let (_, clock_name) = self.stream_types(span, gateway_name)?;
+                let moments_lit: proc_macro2::TokenStream = moments.parse().unwrap();
+                let forward = self.forward_duration(gateway_name, exit_name);
+                let invalid_char = self.invalid_char_arm();
+
+                // Whole durations closing before the threshold of the last one forwarded are dropped - the threshold is
+                // kept by the program, so durations still coming in are dropped by later runs rather than waited for
+                quote! {
+                    loop {
+                        let next_moment = self.#gateway_field.moments().next();
+                        match (self.#threshold_field, next_moment) {
+                            (Some(threshold), Some(moment)) if moment < threshold => {
+                                loop {
+                                    match self.#gateway_field.pop() {
+                                        StreamItem::Moment(_) | StreamItem::Empty => break,
+                                        StreamItem::Character(_) => continue,
+                                        #invalid_char
+                                    }
+                                }
+                            }
+
+                            _ => {
+                                break;
+                            }
+                        }
+                    }
+
+                    if self.#gateway_field.has_duration() {
+                        #forward
+
+                        let forwarded = self.#gateway_field.current_moment().ok_or(StreamError::NoMoment)?;
+                        self.#threshold_field = Some(<#clock_name as AddableClockLike<<#clock_name as ClockLike>::MomentRep>>::add(#clock_name::to_moment(forwarded), #moments_lit).into_rep());
+                    }
+                }
+            },
+
+            Filter(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name), matching) | FilterKeep(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name), matching) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let exit_field = self.exit_ident(exit_name);
+
+                let invalid_char = self.invalid_char_arm();
+                let empty = self.empty_arm();
+
+                let matches = self.char_matches(span, gateway_name, matching)?;
+                let dropped = match instruction {
+                    FilterKeep(..) => quote! { !(#matches) },
+                    _ => matches
+                };
+
+                quote!{
+                    loop {
+                        match self.#gateway_field.pop() {
+                            StreamItem::Character(chr) if #dropped => {
+                                continue
+                            }
+
+                            StreamItem::Character(chr) => {
+                                self.#exit_field.push(chr)?;
+                            }
+
+                            StreamItem::Moment(moment) => {
+                                self.#exit_field.push_moment(moment)?;
+                                break;
+                            }
+
+                            #invalid_char
+
+                            #empty
+                        }
+                    }
+                }
+            },
+
+            Tee(ArgType::Gateway(gateway_name), ArgType::Exit(exit_a), ArgType::Exit(exit_b)) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let exit_a_field = self.exit_ident(exit_a);
+                let exit_b_field = self.exit_ident(exit_b);
+
+                let invalid_char = self.invalid_char_arm();
+                let empty = self.empty_arm();
+
+                // Items are only popped once both exits can take them, so neither exit ever gets ahead of the other
+                quote!{
+                    loop {
+                        if !self.#exit_a_field.accepting_pushes() || !self.#exit_b_field.accepting_pushes() {
+                            break;
+                        }
+
+                        match self.#gateway_field.pop() {
+                            StreamItem::Character(chr) => {
+                                self.#exit_a_field.push(chr)?;
+                                self.#exit_b_field.push(chr)?;
+                            }
+
+                            StreamItem::Moment(moment) => {
+                                self.#exit_a_field.push_moment(moment)?;
+                                self.#exit_b_field.push_moment(moment)?;
+                                break;
+                            }
+
+                            #invalid_char
+
+                            #empty
+                        }
+                    }
+                }
+            },
+
+            DropDuration(ArgType::Gateway(gateway_name)) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let invalid_char = self.invalid_char_arm();
+                let empty = self.empty_arm();
+
+                quote!{
+                    loop {
+                        match self.#gateway_field.pop() {
+                            StreamItem::Character(_) => {
+                                continue
+                            }
+
+                            StreamItem::Moment(_) => {
+                                break;
+                            }
+
+                            #invalid_char
+
+                            #empty
+                        }
+                    }
+                }
+            },
+
+            SkipUntilMoment(ArgType::Gateway(gateway_name), ArgType::Moment(moment)) => {
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let target = self.moment_expr(span, moment)?;
+                let burn = self.burn_fuel();
+
+                quote!{
+                    let target = #target;
+                    loop {
+                        match self.#gateway_field.current_moment() {
+                            Some(moment) if moment >= target => {
+                                break;
+                            }
+
+                            _ => {
+                                if let StreamItem::Empty = self.#gateway_field.pop() {
+                                    #burn
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+
+            JumpEarlier(ArgType::Label(label), ArgType::Gateway(gateway_a), ArgType::Gateway(gateway_b)) => {
+                let jump = self.jump(span, label);
+                let gateway_a_field = format_ident!("gateway_{}", gateway_a.to_case(Case::Snake));
+                let gateway_b_field = format_ident!("gateway_{}", gateway_b.to_case(Case::Snake));
+
+                let clock_a = self.gateways.iter().find_map(|(name, _, clock, _, _)| {
+                    match (name, clock) {
+                        (ArgType::Name(name), ArgType::Clock(clock)) if name == gateway_a => Some(clock_type(clock)),
+                        _ => None
+                    }
+                }).ok_or_else(|| Diagnostic::at(span, format!("Could not find Gateway ({}) for Program ({})", gateway_a, self.name)))?;
+
+                let clock_b = self.gateways.iter().find_map(|(name, _, clock, _, _)| {
+                    match (name, clock) {
+                        (ArgType::Name(name), ArgType::Clock(clock)) if name == gateway_b => Some(clock_type(clock)),
+                        _ => None
+                    }
+                }).ok_or_else(|| Diagnostic::at(span, format!("Could not find Gateway ({}) for Program ({})", gateway_b, self.name)))?;
+
+                quote! {
+                    if <#clock_a as ClockLike>::REPRESENTS != <#clock_b as ClockLike>::REPRESENTS {
+                        return Err(StreamError::ClockMismatch);
+                    }
+
+                    match (self.#gateway_a_field.current_moment(), self.#gateway_b_field.current_moment()) {
+                        (None, Some(_)) => {
+                            #jump
+                        }
+
+                        (Some(a), Some(b)) if a < b => {
+                            #jump
+                        }
+
+                        _ => ()
+                    }
+                }
+            },
+
+            JumpLater(ArgType::Label(label), ArgType::Gateway(gateway_a), ArgType::Gateway(gateway_b)) => {
+                let jump = self.jump(span, label);
+                let gateway_a_field = format_ident!("gateway_{}", gateway_a.to_case(Case::Snake));
+                let gateway_b_field = format_ident!("gateway_{}", gateway_b.to_case(Case::Snake));
+
+                let clock_a = self.gateways.iter().find_map(|(name, _, clock, _, _)| {
+                    match (name, clock) {
+                        (ArgType::Name(name), ArgType::Clock(clock)) if name == gateway_a => Some(clock_type(clock)),
+                        _ => None
+                    }
+                }).ok_or_else(|| Diagnostic::at(span, format!("Could not find Gateway ({}) for Program ({})", gateway_a, self.name)))?;
+
+                let clock_b = self.gateways.iter().find_map(|(name, _, clock, _, _)| {
+                    match (name, clock) {
+                        (ArgType::Name(name), ArgType::Clock(clock)) if name == gateway_b => Some(clock_type(clock)),
+                        _ => None
+                    }
+                }).ok_or_else(|| Diagnostic::at(span, format!("Could not find Gateway ({}) for Program ({})", gateway_b, self.name)))?;
+
+                quote! {
+                    if <#clock_a as ClockLike>::REPRESENTS != <#clock_b as ClockLike>::REPRESENTS {
+                        return Err(StreamError::ClockMismatch);
+                    }
+
+                    match (self.#gateway_a_field.current_moment(), self.#gateway_b_field.current_moment()) {
+                        (Some(_), None) => {
+                            #jump
+                        }
+
+                        (Some(a), Some(b)) if a > b => {
+                            #jump
+                        }
+
+                        _ => ()
+                    }
+                }
+            }
+
+            JumpIfClass(ArgType::Label(label), ArgType::Gateway(gateway_name), ArgType::Class(class)) => {
+                let jump = self.jump(span, label);
+                let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                let class_predicate = format_ident!("is_{}", class.to_case(Case::Snake));
+
+                quote! {
+                    if let StreamItem::Character(chr) = self.#gateway_field.peek() {
+                        if chr.#class_predicate() {
+                            #jump
+                        }
+                    }
+                }
+            }
+
+            JumpOverflowed(ArgType::Label(label), ArgType::Exit(exit_name)) => {
+                let jump = self.jump(span, label);
+                let exit_field = self.exit_ident(exit_name);
+
+                quote! {
+                    if self.#exit_field.take_overflowed() {
+                        #jump
+                    }
+                }
+            }
+
+            // Connected programs run alongside the program, wired up by its pipeline
+            Connect(_, _) | ExitGateway(_, _) => quote! {},
+
+            instr => {
+                let error_message = format!("Not implemented: {:?}", instr);
+
+                quote!{
+                    compile_error!(#error_message);
+                }
+            }
+        })
+    }
+
+    pub fn forward_duration(&self, gateway_name: &str, exit_name: &str) -> proc_macro2::TokenStream {
+        let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+        let exit_field = self.exit_ident(exit_name);
+
+        let invalid_char = self.invalid_char_arm();
+        let empty = self.empty_arm();
+
+        // Between streams of the same alphabet and clock, buffered items move across as they are - between streams
+        // of the same alphabet only, characters move across in batches of raw values
+        let gateway = self.gateways.iter().chain(self.linked_gateways.iter()).find(|(name, _, _, _, _)| name.as_str() == gateway_name);
+        let exit = self.exit_defs().find(|(name, _, _, _, _)| name.as_str() == exit_name);
+        let batch = match (gateway, exit) {
+            (Some((_, gateway_alphabet, gateway_clock, _, _)), Some((_, exit_alphabet, exit_clock, _, _))) if gateway_alphabet.as_str() == exit_alphabet.as_str() && gateway_clock.as_str() == exit_clock.as_str() => quote! {
+                if forward_raw(&mut self.#gateway_field, &mut self.#exit_field)? {
+                    break;
+                }
+            },
+
+            (Some((_, ArgType::Alphabet(gateway_alphabet), _, _, _)), Some((_, ArgType::Alphabet(exit_alphabet), _, _, _))) if gateway_alphabet == exit_alphabet => {
+                let alphabet_name = alphabet_type(gateway_alphabet);
+
+                quote! {
+                    if let StreamItem::Character(chr) = self.#gateway_field.peek() {
+                        let mut vals = [<#alphabet_name as AlphabetLike>::to_val(chr); #FORWARD_BATCH];
+                        let len = self.#gateway_field.pop_duration_into(&mut vals);
+                        self.#exit_field.push_vals(&vals[..len])?;
+                        continue;
+                    }
+                }
+            },
+
+            _ => quote! {}
+        };
+
+        let forward = quote!{
+            loop {
+                #batch
+
+                // forward_chars only stops short of the moment ending the duration at items it couldn't forward
+                match forward_chars(&mut self.#gateway_field, &mut self.#exit_field)? {
+                    #invalid_char
+
+                    #empty
+
+                    _ => break
+                }
+            }
+        };
+
+        match self.on_full {
+            OnFull::DropDuration => quote! {
+                if self.#gateway_field.fits_duration(&self.#exit_field) == PushOutcome::Fits {
+                    #forward
+                } else {
+                    self.#gateway_field.skip_duration();
+                }
+            },
+
+            _ => forward
+        }
+    }
+
+    /// For programs yielding on full exits - the first duration their step label forwards which its exit has no room for
+    fn backpressure(&self, label: &str) -> proc_macro2::TokenStream {
+        if self.on_full != OnFull::Yield {
+            return quote! {};
+        }
+
+        let checks: Vec<_> = self.instructions.iter().filter(|(name, _, _)| name.as_str() == label).flat_map(|(_, _, instructions)| {
+            instructions.iter().filter_map(|(_, instruction)| match instruction {
+                Instruction::ForwardDuration(ArgType::Gateway(gateway_name), ArgType::Exit(exit_name)) => {
+                    let gateway_field = format_ident!("gateway_{}", gateway_name.to_case(Case::Snake));
+                    let exit_field = self.exit_ident(exit_name);
+
+                    Some(quote! {
+                        if let full @ PushOutcome::Full { .. } = self.#gateway_field.fits_duration(&self.#exit_field) {
+                            return full;
+                        }
+                    })
+                },
+
+                _ => None
+            })
+        }).collect();
+
+        quote! {
+            fn backpressure(&self) -> PushOutcome {
+                #(#checks)*
+                PushOutcome::Fits
+            }
+        }
+    }
+
+    /// The arm of a pop waiting for more to be pushed - burning fuel, with a fuel limit set
+    fn empty_arm(&self) -> proc_macro2::TokenStream {
+        let burn = self.burn_fuel();
+
+        quote! {
+            StreamItem::Empty => {
+                #burn
+                continue
+            }
+        }
+    }
+
+    /// Takes a unit of the program's fuel - failing with `StreamError::OutOfFuel` once there is none left
+    fn burn_fuel(&self) -> proc_macro2::TokenStream {
+        match self.fuel {
+            Some(_) => quote! { self.fuel = self.fuel.checked_sub(1).ok_or(StreamError::OutOfFuel)?; },
+            None => quote! {}
+        }
+    }
+
+    /// Match arm for raw values popped off of a gateway that aren't characters of its alphabet - dead-lettered
+    /// to the program's error exit if it registered one, and failing with StreamError::InvalidChar otherwise
+    fn invalid_char_arm(&self) -> proc_macro2::TokenStream {
+        match &self.error_exit {
+            Some(ArgType::Exit(error_exit)) => {
+                let error_exit_field = self.exit_ident(error_exit);
+
+                quote! {
+                    StreamItem::InvalidChar(rep) => {
+                        self.#error_exit_field.push_invalid(rep)?;
+                    }
+                }
+            },
+
+            _ => {
+                quote! {
+                    StreamItem::InvalidChar(_) => {
+                        return Err(StreamError::InvalidChar);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The generated Alphabet and Clock types of one of the program's gateways or exits
+    fn stream_types(&self, span: &Span, stream: &str) -> Result<(proc_macro2::Ident, proc_macro2::Ident), Diagnostic> {
+        self.gateways.iter().chain(self.linked_gateways.iter()).chain(self.exits.iter()).find_map(|(name, alphabet, clock, _, _)| {
+            match (name, alphabet, clock) {
+                (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock)) if name == stream => {
+                    Some((alphabet_type(alphabet), clock_type(clock)))
+                },
+                _ => None
+            }
+        }).ok_or_else(|| Diagnostic::at(span, format!("Could not find stream ({}) for Program ({})", stream, self.name)))
+    }
+
+    /// A moment is either a literal, or `Time(STREAM)` - the current moment of another of the program's streams
+    fn moment_expr(&self, span: &Span, moment: &str) -> Result<proc_macro2::TokenStream, Diagnostic> {
+        match moment.strip_prefix("Time(").and_then(|stream| stream.strip_suffix(')')) {
+            Some(stream) => {
+                let is_gateway = self.gateway_names().contains(&stream) || self.linked_gateways.iter().any(|(name, _, _, _, _)| name.as_str() == stream);
+                let is_exit = self.exit_names().contains(&stream);
+                let field = match (is_gateway, is_exit) {
+                    (true, _) => format_ident!("gateway_{}", stream.to_case(Case::Snake)),
+                    (false, true) => self.exit_ident(stream),
+                    _ => return Err(Diagnostic::at(span, format!("Could not find stream ({}) for Program ({})", stream, self.name)))
+                };
+
+                Ok(quote! { self.#field.current_moment().ok_or(StreamError::NoMoment)? })
+            },
+
+            None => Ok(moment.parse().unwrap())
+        }
+    }
+
+    /// A boolean expression testing whether `chr` (popped off of the gateway) is within a class, or equal to a raw value
+    fn char_matches(&self, span: &Span, gateway_name: &str, matching: &ArgType) -> Result<proc_macro2::TokenStream, Diagnostic> {
+        match matching {
+            ArgType::Class(class) => {
+                let class_predicate = format_ident!("is_{}", class.to_case(Case::Snake));
+                Ok(quote! { chr.#class_predicate() })
+            },
+
+            ArgType::Number(val) => {
+                let alphabet = self.gateways.iter().find_map(|(name, alphabet, _, _, _)| {
+                    match (name, alphabet) {
+                        (ArgType::Name(name), ArgType::Alphabet(alphabet)) if name == gateway_name => Some(alphabet),
+                        _ => None
+                    }
+                }).ok_or_else(|| Diagnostic::at(span, format!("Could not find Gateway ({}) for Program ({})", gateway_name, self.name)))?;
+
+                let alphabet_name = alphabet_type(alphabet);
+                let val_lit: proc_macro2::TokenStream = val.parse().unwrap();
+                Ok(quote! { #alphabet_name::to_val(chr) == #val_lit })
+            },
+
+            other => panic!("{} Expected a class or value to match characters against, got: {:?}", span, other)
+        }
+    }
+
+    /// The field instructions push onto for an exit - spliced programs push straight onto the gateways reading their exits
+    fn exit_ident(&self, name: &str) -> proc_macro2::Ident {
+        match self.inlined.iter().any(|inlined| inlined.exits.iter().any(|exit| exit == name)) {
+            true => format_ident!("gateway_{}", name.to_case(Case::Snake)),
+            false => format_ident!("exit_{}", name.to_case(Case::Snake))
+        }
+    }
+
+    /// Every stream instructions may push onto - the exits, and the gateways spliced programs push onto
+    fn exit_defs(&self) -> impl Iterator<Item = &StreamDef> + Clone {
+        let spliced = |name: &ArgType| self.inlined.iter().any(|inlined| inlined.exits.iter().any(|exit| exit == name.as_str()));
+        self.exits.iter().chain(self.linked_gateways.iter().filter(move |(name, _, _, _, _)| spliced(name)))
+    }
+
+    /// The function jumps call - it returns a Result, and is wrapped by the public label function unless errors are propagated
+    /// Whether the label has the input its own instructions need - as many whole durations as they read on each of the
+    /// `reads` gateways. Labels reading none of them run once any has a whole duration.
+    fn ready(&self, label: &str, reads: &[&str]) -> proc_macro2::TokenStream {
+        let mut needed: Vec<(&str, usize)> = vec![];
+        let instructions = self.instructions.iter().filter(|(name, _, _)| name.as_str() == label).flat_map(|(_, _, instructions)| instructions.iter());
+        for (gateway, count) in instructions.flat_map(|(_, instruction)| instruction.needed_durations()) {
+            match needed.iter_mut().find(|(name, _)| *name == gateway.as_str()) {
+                Some((_, most)) => *most = (*most).max(count),
+                None if reads.contains(&gateway.as_str()) => needed.push((gateway.as_str(), count)),
+                None => ()
+            }
+        }
+
+        let fields = reads.iter().map(|gateway| format_ident!("gateway_{}", gateway.to_case(Case::Snake)));
+        if needed.is_empty() {
+            return quote! { #(self.#fields.has_duration())||* };
+        }
+
+        let checks = needed.iter().map(|(gateway, count)| {
+            let field = format_ident!("gateway_{}", gateway.to_case(Case::Snake));
+            match count {
+                1 => quote! { self.#field.has_duration() },
+                _ => {
+                    let count = proc_macro2::Literal::usize_unsuffixed(*count);
+                    quote! { self.#field.buffered_moments() >= #count }
+                }
+            }
+        });
+
+        quote! { #(#checks)&&* }
+    }
+
+    fn label_func(&self, name: &str) -> proc_macro2::Ident {
+        match self.error_mode {
+            ErrorMode::Propagate => format_ident!("label_{}", name.to_case(Case::Snake)),
+            _ => format_ident!("try_label_{}", name.to_case(Case::Snake))
+        }
+    }
+
+    /// The code of a label's instructions - with `-O`, consecutive characters pushed onto the same exit are pushed in
+    /// one go, all of them or none
+    fn instruction_calls(&self, instructions: &[(Span, Instruction)]) -> Result<Vec<proc_macro2::TokenStream>, Diagnostic> {
+        let mut calls = vec![];
+        let mut idx = 0;
+        while idx < instructions.len() {
+            let (span, instruction) = &instructions[idx];
+            let batched = match (self.optimize, instruction.pushed_char_exit()) {
+                (true, Some(exit)) => instructions[idx..].iter().take_while(|(_, next)| next.pushed_char_exit() == Some(exit)).count(),
+                _ => 1
+            };
+
+            calls.push(match batched {
+                1 => self.instruction_call(span, instruction)?,
+                _ => self.push_batch(span, &instructions[idx..idx + batched])?
+            });
+
+            idx += batched;
+        }
+
+        Ok(calls)
+    }
+
+    /// Pushes the characters of consecutive push_char and push_val instructions onto their exit with a single push_vals
+    fn push_batch(&self, span: &Span, instructions: &[(Span, Instruction)]) -> Result<proc_macro2::TokenStream, Diagnostic> {
+        let exit_name = instructions[0].1.pushed_char_exit().unwrap_or_default();
+        let alphabet = self.exit_defs().find_map(|(name, alphabet, _, _, _)| {
+            match (name, alphabet) {
+                (ArgType::Name(name), ArgType::Alphabet(alphabet)) if name == exit_name => Some(alphabet),
+                _ => None
+            }
+        }).ok_or_else(|| Diagnostic::at(span, format!("Could not find Exit ({}) for Program ({})", exit_name, self.name)))?;
+
+        let alphabet_name = alphabet_type(alphabet);
+        let exit_field = self.exit_ident(exit_name);
+        let vals: Vec<_> = instructions.iter().map(|(_, instruction)| match instruction {
+            Instruction::PushVal(ArgType::Number(val), _) => {
+                let val_lit: proc_macro2::TokenStream = val.parse().unwrap();
+                quote! { <#alphabet_name as AlphabetLike>::to_val(#alphabet_name::to_char(#val_lit).map_err(|_| StreamError::UnknownChar)?) }
+            },
+
+            Instruction::PushChar(ArgType::Character(chr), _) => {
+                let enum_name = format_ident!("{}", chr.to_case(Case::Pascal));
+                quote! { <#alphabet_name as AlphabetLike>::to_val(<#alphabet_name as AlphabetLike>::CharEnum::#enum_name()) }
+            },
+
+            other => panic!("{} Expected a character push, got: {:?}", span, other)
+        }).collect();
+
+        Ok(quote! {
+            self.#exit_field.push_vals(&[#(#vals),*])?;
+        })
+    }
+
+    pub fn func_def(&self, name: &str, instructions: &[(Span, Instruction)]) -> Result<proc_macro2::TokenStream, Diagnostic> {
+        let func_name = format_ident!("label_{}", name.to_case(Case::Snake));
+        let try_func_name = self.label_func(name);
+        let instructions = self.instruction_calls(instructions)?;
+        let doc = doc_attrs(self.label_docs.get(name).map_or(&[], |doc| doc.as_slice()));
+
+        let program = &self.name;
+        let span = match self.trace {
+            true => quote! { let _span = trace::label(#program, #name); },
+            false => quote! {}
+        };
+
+        let wrapper = match self.error_mode {
+            ErrorMode::Propagate => quote! {},
+
+            ErrorMode::Panic => {
+                let error_message = format!("Program ({}) failed in label {}", self.name, name);
+
+                quote! {
+                    #doc
+                    pub fn #func_name(&mut self) {
+                        self.#try_func_name().expect(#error_message);
+                    }
+                }
+            },
+
+            ErrorMode::AbortProgram => quote! {
+                #doc
+                pub fn #func_name(&mut self) {
+                    if self.error.is_some() {
+                        return;
+                    }
+
+                    if let Err(err) = self.#try_func_name() {
+                        self.error = Some(err);
+                    }
+                }
+            }
+        };
+
+        let visibility = match self.error_mode {
+            ErrorMode::Propagate => quote! { pub },
+            _ => quote! {}
+        };
+
+        let burn = self.burn_fuel();
+
+        Ok(quote! {
+            #doc
+            #visibility fn #try_func_name(&mut self) -> Result<(), StreamError> {
+                #span
+                #burn
+                #(#instructions)*
+                Ok(())
+            }
+
+            #wrapper
+        })
+    }
+
+    /// Resolves every `connect` against the connected program's definition - its generic parameters take on the
+    /// types of the gateways feeding it, and each `reg_exit_gateway` becomes a gateway typed like the exit it reads.
+    /// Gateways must be fed streams of the alphabet and clock they were registered with, or of the one their generic
+    /// parameter took on.
+    fn resolve_links(&self, programs: &[&Program]) -> Result<(Vec<Link>, Vec<StreamDef>), Diagnostic> {
+        let mut links = vec![];
+        let mut linked_gateways: Vec<StreamDef> = vec![];
+
+        for connection in self.connections() {
+            let span = &connection.span;
+            let callee = programs.iter().find(|program| program.name() == connection.program).ok_or_else(|| {
+                Diagnostic::at(span, format!("Program ({}) - connects to undefined Program ({})", self.name, connection.program))
+            })?;
+
+            if ["program", "scheduler"].contains(&connection.name.to_case(Case::Snake).as_str()) {
+                return Err(Diagnostic::at(span, format!("Program ({}) - a connect can't be named {}, which its pipeline uses itself", self.name, connection.name)));
+            }
+
+            let mut params: BTreeMap<&str, String> = BTreeMap::new();
+            let mut feeds = vec![];
+            for (gateway, (callee_gateway, alphabet, clock, _, _)) in connection.gateways.iter().zip(callee.gateways.iter()) {
+                let (_, caller_alphabet, caller_clock, _, _) = self.gateways.iter().chain(linked_gateways.iter()).find(|(name, _, _, _, _)| name.as_str() == gateway).ok_or_else(|| {
+                    Diagnostic::at(span, format!("Could not find Gateway ({}) for Program ({})", gateway, self.name))
+                })?;
+
+                for (kind, callee_type, caller_type) in [("Alphabet", alphabet, caller_alphabet), ("Clock", clock, caller_clock)] {
+                    let expected = match callee_type.as_str().strip_prefix('$') {
+                        Some(param) => params.entry(param).or_insert_with(|| caller_type.as_str().to_string()).clone(),
+                        None => callee_type.as_str().to_string()
+                    };
+
+                    if expected != caller_type.as_str() {
+                        return Err(Diagnostic::at(span, format!(
+                            "Program ({}) - connects Gateway ({}) of {} ({}) to Gateway ({}) of Program ({}), which takes {} ({})",
+                            self.name, gateway, kind, caller_type.as_str(), callee_gateway.as_str(), callee.name(), kind, expected
+                        )));
+                    }
+                }
+
+                feeds.push((gateway.clone(), callee_gateway.as_str().to_string()));
+            }
+
+            let type_args = callee.generics.iter().map(|generic| match (generic, params.get(generic.as_str())) {
+                (ArgType::Alphabet(_), Some(alphabet)) => Ok(ArgType::Alphabet(alphabet.to_string())),
+                (_, Some(clock)) => Ok(ArgType::Clock(clock.to_string())),
+                (_, None) => Err(Diagnostic::at(span, format!("Program ({}) - can't tell the generic parameter {} of Program ({}) from the gateways connected to it", self.name, generic.as_str(), callee.name())))
+            }).collect::<Result<Vec<_>, _>>()?;
+
+            let substitute = |stream_type: &str| match stream_type.strip_prefix('$').and_then(|param| params.get(param)) {
+                Some(caller_type) => caller_type.clone(),
+                None => stream_type.to_string()
+            };
+
+            let mut exits = vec![];
+            for (exit, gateway) in connection.exit_gateways.iter() {
+                let (_, alphabet, clock, buf_size, _) = callee.exits.iter().find(|(name, _, _, _, _)| name.as_str() == exit).ok_or_else(|| {
+                    Diagnostic::at(span, format!("Could not find Exit ({}) for Program ({})", exit, callee.name()))
+                })?;
+
+                let (alphabet, clock) = (substitute(alphabet.as_str()), substitute(clock.as_str()));
+                match linked_gateways.iter().find(|(name, _, _, _, _)| name.as_str() == gateway) {
+                    // Exits read by the same gateway must all be of its alphabet and clock
+                    Some((_, linked_alphabet, linked_clock, _, _)) => {
+                        for (kind, linked_type, exit_type) in [("Alphabet", linked_alphabet, &alphabet), ("Clock", linked_clock, &clock)] {
+                            if linked_type.as_str() != exit_type {
+                                return Err(Diagnostic::at(span, format!(
+                                    "Program ({}) - Gateway ({}) of {} ({}) reads Exit ({}) of Program ({}), which is of {} ({})",
+                                    self.name, gateway, kind, linked_type.as_str(), exit, callee.name(), kind, exit_type
+                                )));
+                            }
+                        }
+                    },
+
+                    None => linked_gateways.push((
+                        ArgType::Name(gateway.clone()),
+                        ArgType::Alphabet(alphabet),
+                        ArgType::Clock(clock),
+                        ArgType::Number(buf_size.as_str().to_string()),
+                        self.span.clone()
+                    ))
+                }
+
+                exits.push((exit.clone(), gateway.clone()));
+            }
+
+            links.push(Link{
+                name: connection.name,
+                program: callee.name().to_string(),
+                pipeline: !callee.connections().is_empty(),
+                type_args,
+                feeds,
+                exits
+            });
+        }
+
+        Ok((links, linked_gateways))
+    }
+
+    /// Whether a connected program can be spliced into this one - it must connect to none itself, take on the caller's
+    /// error mode, full exit policy and fuel, and have no streams of its own left once spliced: every gateway fed by a
+    /// gateway of the caller that nothing else reads, and every exit read by a gateway of the caller that nothing else feeds
+    fn can_inline(&self, link: &Link, callee: &Program) -> bool {
+        let fuel = |prog: &Program| prog.fuel.as_ref().map(|fuel| fuel.as_str().to_string());
+        let settings = !link.pipeline && callee.error_exit.is_none() && callee.connectors.is_empty() && callee.expectations.is_empty() &&
+            callee.overwriting.is_empty() && callee.error_mode == self.error_mode && callee.on_full == self.on_full && fuel(callee) == fuel(self);
+
+        let read_by_caller = |gateway: &str| self.instructions.iter().flat_map(|(_, _, instructions)| instructions.iter()).any(|(_, instruction)| {
+            instruction.args().iter().any(|arg| match arg {
+                ArgType::Gateway(name) => name == gateway,
+                ArgType::Moment(moment) => moment.strip_prefix("Time(").and_then(|stream| stream.strip_suffix(')')) == Some(gateway),
+                _ => false
+            })
+        });
+
+        let fed_once = |gateway: &str| self.links.iter().flat_map(|link| link.feeds.iter()).filter(|(fed, _)| fed == gateway).count() == 1;
+        let feeds = !link.feeds.is_empty() && callee.gateways.len() == link.feeds.len() && link.feeds.iter().all(|(gateway, _)| {
+            fed_once(gateway) && !read_by_caller(gateway) && !link.exits.iter().any(|(_, read)| read == gateway)
+        });
+
+        let read_once = |gateway: &str| self.links.iter().flat_map(|link| link.exits.iter()).filter(|(_, read)| read == gateway).count() == 1;
+        let exits = callee.exits.iter().all(|(exit, _, _, _, _)| {
+            link.exits.iter().filter(|(read, gateway)| read == exit.as_str() && read_once(gateway)).count() == 1
+        });
+
+        let labels = callee.instructions.iter().any(|(label, _, _)| label.as_str() == "root") && callee.instructions.iter().all(|(label, _, _)| {
+            let renamed = format!("{}_{}", link.name, label.as_str());
+            !self.instructions.iter().any(|(own, _, _)| own.as_str() == renamed)
+        });
+
+        settings && feeds && exits && labels
+    }
+
+    /// Splices the connected programs that can be into this one (`-O inline-connect`) - their labels and counters are
+    /// renamed after the connect, their gateways become the caller's gateways feeding them, and their exits the gateways
+    /// reading them. What they would have buffered in between, they no longer do. The others stay in the pipeline.
+    fn inline_links(&mut self, programs: &[&Program]) {
+        let mut kept = vec![];
+        for link in self.links.clone() {
+            let callee = match programs.iter().find(|program| program.name() == link.program) {
+                Some(callee) if self.can_inline(&link, callee) => callee,
+                _ => {
+                    kept.push(link);
+                    continue
+                }
+            };
+
+            let rename_stream = |stream: &str| {
+                let fed = link.feeds.iter().find(|(_, gateway)| gateway == stream).map(|(fed, _)| fed);
+                let read = link.exits.iter().find(|(exit, _)| exit == stream).map(|(_, read)| read);
+                fed.or(read).map_or_else(|| stream.to_string(), |renamed| renamed.clone())
+            };
+
+            let rename = |arg: &ArgType| match arg {
+                ArgType::Gateway(gateway) => ArgType::Gateway(rename_stream(gateway)),
+                ArgType::Exit(exit) => ArgType::Exit(rename_stream(exit)),
+                ArgType::Label(label) => ArgType::Label(format!("{}_{}", link.name, label)),
+                ArgType::Name(counter) => ArgType::Name(format!("{}_{}", link.name.to_case(Case::Snake), counter)),
+                ArgType::Moment(moment) => match moment.strip_prefix("Time(").and_then(|stream| stream.strip_suffix(')')) {
+                    Some(stream) => ArgType::Moment(format!("Time({})", rename_stream(stream))),
+                    None => arg.clone()
+                },
+                other => other.clone()
+            };
+
+            for (label, span, instructions) in callee.instructions.iter() {
+                let instructions = instructions.iter().map(|(span, instruction)| (span.clone(), instruction.map_args(&rename))).collect();
+                self.instructions.push((ArgType::Name(format!("{}_{}", link.name, label.as_str())), span.clone(), instructions));
+            }
+
+            self.counters.extend(callee.counters.iter().map(rename));
+
+            let has_main = callee.instructions.iter().any(|(name, _, _)| name.as_str() == "main");
+            self.inlined.push(Inlined{
+                root: format!("{}_root", link.name),
+                main: format!("{}_{}", link.name, if has_main { "main" } else { "root" }),
+                gateways: link.feeds.iter().map(|(gateway, _)| gateway.clone()).collect(),
+                exits: link.exits.iter().map(|(_, gateway)| gateway.clone()).collect()
+            });
+        }
+
+        self.links = kept;
+    }
+
+    /// The program with the programs it connects to resolved - and spliced in, where they can be with `-O inline-connect`
+    fn linked(&self, programs: &[&Program]) -> Result<Program, Diagnostic> {
+        let mut linked = self.clone();
+        (linked.links, linked.linked_gateways) = self.resolve_links(programs)?;
+        if self.inline_connect {
+            linked.inline_links(programs);
+        }
+
+        Ok(linked)
+    }
+
+    /// Generates the program - programs it connects to are looked up among `programs`, and wired up by a pipeline
+    pub fn generate(&self, format: bool, programs: &[&Program]) -> Result<String, Diagnostic> {
+        if self.connections().is_empty() {
+            return self.generate_linked(format);
+        }
+
+        self.linked(programs)?.generate_linked(format)
+    }
+
+    fn generate_linked(&self, format: bool) -> Result<String, Diagnostic> {
+        let struct_name = format_ident!("Program{}", self.name.to_case(Case::Pascal));
+        let gateways: Vec<_> = self.gateways.iter().chain(self.linked_gateways.iter()).map(|gateway_data| {
+            match gateway_data {
+                (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock), ArgType::Number(buf_size), _) => {
+                    self.gateway_field(name, alphabet, clock, buf_size)
+                },
+
+                _ => panic!("Unexpected reg_gateway params: {:?}", gateway_data)
+            }
+        }).collect();
+
+        let initialize_gateways: Vec<_> = self.gateways.iter().chain(self.linked_gateways.iter()).map(|gateway_data| {
+            match gateway_data {
+                (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock), ArgType::Number(buf_size), _) => {
+                    self.initialize_gateway_field(name, alphabet, clock, buf_size)
+                },
+
+                _ => panic!("Unexpected reg_gateway params: {:?}", gateway_data)
+            }
+        }).collect();
+
+        let exits: Vec<_> = self.exits.iter().map(|exit_data| {
+            match exit_data {
+                (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock), ArgType::Number(buf_size), _) => {
+                    self.exit_field(name, alphabet, clock, buf_size)
+                },
+
+                _ => panic!("Unexpected reg_exit params: {:?}", exit_data)
+            }
+        }).collect();
+
+        let initialize_exits: Vec<_> = self.exits.iter().map(|exit_data| {
+            match exit_data {
+                (ArgType::Name(name), ArgType::Alphabet(alphabet), ArgType::Clock(clock), ArgType::Number(buf_size), _) => {
+                    self.initialize_exit_field(name, alphabet, clock, buf_size)
+                },
+
+                _ => panic!("Unexpected reg_exit params: {:?}", exit_data)
+            }
+        }).collect();
+
+        let counters: Vec<_> = self.counters.iter().map(|counter| {
+            match counter {
+                ArgType::Name(name) => format_ident!("{}", name),
+                _ => panic!("Unexpected counter: {:?}", counter)
+            }
+        }).collect();
+
+        // Throttles keep the moment before which they drop durations, and windows how many durations they merged so far
+        let mut state_fields: Vec<(proc_macro2::Ident, proc_macro2::TokenStream, proc_macro2::TokenStream)> = vec![];
+        for (_, instruction) in self.instructions.iter().flat_map(|(_, _, instructions)| instructions.iter()) {
+            let field = match instruction {
+                Instruction::Throttle(ArgType::Gateway(gateway), ArgType::Exit(exit), _) => {
+                    let (_, clock_name) = self.stream_types(&self.span, gateway)?;
+                    (throttle_field(gateway, exit), quote! { Option<<#clock_name as ClockLike>::MomentRep> }, quote! { None })
+                },
+
+                Instruction::Window(ArgType::Gateway(gateway), ArgType::Exit(exit), _) => (window_field(gateway, exit), quote! { usize }, quote! { 0 }),
+                _ => continue
+            };
+
+            if !state_fields.iter().any(|(name, _, _)| *name == field.0) {
+                state_fields.push(field);
+            }
+        }
+
+        let state_names: Vec<_> = state_fields.iter().map(|(name, _, _)| name).collect();
+        let state_types = state_fields.iter().map(|(_, field_type, _)| field_type);
+        let state_inits = state_fields.iter().map(|(_, _, init)| init);
+
+        let funcs: Vec<_> = self.generated_labels().iter().map(|(name, instructions)| self.func_def(name, instructions)).collect::<Result<_, _>>()?;
+
+        let visibility = visibility_tokens(self.visibility.as_ref());
+        let doc = doc_attrs(&self.doc);
+
+        // Running starts at the root label, then keeps on going from `main` (or the root again, without one) for as long
+        // as there's input - aborted programs have none pending, so they stop
+        let has_main = self.instructions.iter().any(|(name, _, _)| name.as_str() == "main");
+        let root_func = self.label_func("root");
+
+        // With a fuel limit, each run of the root or main label chain starts with a full tank
+        let (fuel_field, initialize_fuel, fuel_const, refuel) = match self.fuel.as_ref() {
+            Some(ArgType::Number(fuel)) => {
+                let fuel: proc_macro2::TokenStream = fuel.parse().unwrap();
+
+                (
+                    quote! { fuel: usize, },
+                    quote! { fuel: Self::FUEL, },
+                    quote! {
+                        /// Units of work each run of the root or main label chain may take - a label, or a wait on an empty gateway
+                        pub const FUEL: usize = #fuel;
+                    },
+                    quote! { self.fuel = Self::FUEL; }
+                )
+            },
+
+            _ => (quote! {}, quote! {}, quote! {}, quote! {})
+        };
+
+        // Spliced programs run their own label chains first - each only with a whole duration buffered on a gateway it
+        // reads, as the program itself then is
+        let call = |label: &str| match self.error_mode {
+            ErrorMode::Propagate => {
+                let func = self.label_func(label);
+                quote! { self.#func()?; }
+            },
+
+            _ => {
+                let func = format_ident!("label_{}", label.to_case(Case::Snake));
+                quote! { self.#func(); }
+            }
+        };
+
+        let inlined_roots: Vec<_> = self.inlined.iter().map(|inlined| {
+            let call = call(&inlined.root);
+            quote! { #refuel #call }
+        }).collect();
+
+        let inlined_ready: Vec<_> = self.inlined.iter().map(|inlined| {
+            self.ready(&inlined.main, &inlined.gateways.iter().map(|gateway| gateway.as_str()).collect::<Vec<_>>())
+        }).collect();
+
+        let inlined_loops: Vec<_> = self.inlined.iter().zip(inlined_ready.iter()).map(|(inlined, pending)| {
+            let call = call(&inlined.main);
+            quote! {
+                if #pending {
+                    #refuel
+                    #call
+                }
+            }
+        }).collect();
+
+        // Gateways feeding connected programs are emptied by the pipeline - the program itself reads the gateways their
+        // exits feed instead, and spliced programs the gateways feeding them
+        let own_label = if has_main { "main" } else { "root" };
+        let spliced_reads: Vec<&str> = self.inlined.iter().flat_map(|inlined| inlined.gateways.iter().map(|gateway| gateway.as_str())).collect();
+        let own_reads: Vec<&str> = self.gateway_names().into_iter().chain(self.linked_gateways.iter().map(|(name, _, _, _, _)| name.as_str()))
+            .filter(|name| !spliced_reads.contains(name) && !self.links.iter().any(|link| link.feeds.iter().any(|(fed, _)| fed == name)))
+            .collect();
+        let own_ready = self.ready(own_label, &own_reads);
+
+        let own_loop = {
+            let call = call(own_label);
+            match (self.inlined.is_empty(), own_reads.is_empty()) {
+                (true, _) => quote! { #refuel #call },
+                (false, true) => quote! {},
+                (false, false) => {
+                    let pending = &own_ready;
+                    quote! {
+                        if #pending {
+                            #refuel
+                            #call
+                        }
+                    }
+                }
+            }
+        };
+
+        let (run_fn, step_fns) = match self.error_mode {
+            ErrorMode::Propagate => (
+                quote! {
+                    /// Runs the program - its root label, then `main` (or the root again) for as long as the whole durations it reads are buffered
+                    pub fn run(&mut self) -> Result<(), StreamError> {
+                        #(#inlined_roots)*
+                        #refuel
+                        self.#root_func()?;
+                        while self.has_pending_input() {
+                            #(#inlined_loops)*
+                            #own_loop
+                        }
+
+                        Ok(())
+                    }
+                },
+
+                quote! {
+                    fn start(&mut self) -> Result<(), StreamError> {
+                        #(#inlined_roots)*
+                        #refuel
+                        self.#root_func()
+                    }
+
+                    fn run_step(&mut self) -> Result<bool, StreamError> {
+                        if !self.has_pending_input() {
+                            return Ok(false);
+                        }
+
+                        #(#inlined_loops)*
+                        #own_loop
+                        Ok(true)
+                    }
+                }
+            ),
+
+            _ => {
+                let root_label = format_ident!("label_root");
+
+                (
+                    quote! {
+                        /// Runs the program - its root label, then `main` (or the root again) for as long as the whole durations it reads are buffered
+                        pub fn run(&mut self) {
+                            #(#inlined_roots)*
+                            #refuel
+                            self.#root_label();
+                            while self.has_pending_input() {
+                                #(#inlined_loops)*
+                                #own_loop
+                            }
+                        }
+                    },
+
+                    quote! {
+                        fn start(&mut self) -> Result<(), StreamError> {
+                            #(#inlined_roots)*
+                            #refuel
+                            self.#root_label();
+                            Ok(())
+                        }
+
+                        fn run_step(&mut self) -> Result<bool, StreamError> {
+                            if !self.has_pending_input() {
+                                return Ok(false);
+                            }
+
+                            #(#inlined_loops)*
+                            #own_loop
+                            Ok(true)
+                        }
+                    }
+                )
+            }
+        };
+
+        let priority_lit: proc_macro2::TokenStream = match self.priority.as_ref() {
+            Some(ArgType::Number(priority)) => priority.parse().unwrap(),
+            _ => quote! { 0 }
+        };
+
+        let gateway_fields: Vec<_> = self.gateways.iter().map(|gateway_data| {
+            match gateway_data {
+                (ArgType::Name(name), _, _, _, _) => format_ident!("gateway_{}", name.to_case(Case::Snake)),
+                _ => panic!("Unexpected reg_gateway params: {:?}", gateway_data)
+            }
+        }).collect();
+
+        let exit_fields: Vec<_> = self.exit_names().iter().map(|name| format_ident!("exit_{}", name.to_case(Case::Snake))).collect();
+
+        let linked_fields: Vec<_> = self.linked_gateways.iter().map(|(name, _, _, _, _)| format_ident!("gateway_{}", name.as_str().to_case(Case::Snake))).collect();
+
+        let drained_fields: Vec<_> = gateway_fields.iter().chain(linked_fields.iter()).collect();
+        let gateways_drained = if drained_fields.is_empty() {
+            quote! { true }
+        } else {
+            quote! { #(self.#drained_fields.is_drained())&&* }
+        };
+
+        // Spliced programs close the gateways their exits push onto once the gateways they read are drained, as they would their exits
+        let close_spliced: Vec<_> = self.inlined.iter().map(|inlined| {
+            let read: Vec<_> = inlined.gateways.iter().map(|gateway| format_ident!("gateway_{}", gateway.to_case(Case::Snake))).collect();
+            let pushed: Vec<_> = inlined.exits.iter().map(|gateway| format_ident!("gateway_{}", gateway.to_case(Case::Snake))).collect();
+            quote! {
+                if #(self.#read.is_drained())&&* {
+                    #(self.#pushed.close();)*
+                }
+            }
+        }).collect();
+
+        // Input is pending once a label chain - the program's own, or a spliced program's - has what its label needs
+        let chains_ready: Vec<_> = inlined_ready.iter().chain((!own_reads.is_empty()).then_some(&own_ready)).collect();
+        let has_pending_input = match chains_ready.as_slice() {
+            [] => quote! { false },
+            [ready] => quote! { #ready },
+            _ => quote! { #((#chains_ready))||* }
+        };
+
+        // Programs yielding on full exits wait for room before stepping
+        let backpressure = self.backpressure(if has_main { "main" } else { "root" });
+        let has_pending_input = match self.on_full {
+            OnFull::Yield => quote! { (#has_pending_input) && self.backpressure() == PushOutcome::Fits },
+            _ => has_pending_input
+        };
+
+        // Aborted programs never run again, so they have nothing pending
+        let (has_pending_input, error_field, initialize_error, error_accessor) = match self.error_mode {
+            ErrorMode::AbortProgram => (
+                quote! { self.error.is_none() && (#has_pending_input) },
+                quote! { error: Option<StreamError>, },
+                quote! { error: None, },
+                quote! {
+                    /// The error the program aborted with, if any
+                    pub fn error(&self) -> Option<&StreamError> {
+                        self.error.as_ref()
+                    }
+                }
+            ),
+
+            _ => (has_pending_input, quote! {}, quote! {}, quote! {})
+        };
+
+        let expectation_checks: Vec<_> = self.expectations.iter().map(|expectation| {
+            match expectation {
+                (ArgType::Exit(exit_a), ArgType::Exit(exit_b), _) => {
+                    let exit_a_field = format_ident!("exit_{}", exit_a.to_case(Case::Snake));
+                    let exit_b_field = format_ident!("exit_{}", exit_b.to_case(Case::Snake));
+
+                    quote! {
+                        expect_aligned((#exit_a, &self.#exit_a_field), (#exit_b, &self.#exit_b_field))?;
+                    }
+                },
+
+                _ => panic!("Unexpected expect_aligned params: {:?}", expectation)
+            }
+        }).collect();
+
+        let moment_rep = match self.exits.first() {
+            Some((_, _, ArgType::Clock(clock), _, _)) => {
+                let clock_name = clock_type(clock);
+                quote! { <#clock_name as ClockLike>::MomentRep }
+            },
+
+            _ => quote! { () }
+        };
+
+        // Programs without gateways are self-contained, so their expectations can be checked by a generated test (unless generic)
+        let expectation_tests = if self.expectations.is_empty() || !self.gateways.is_empty() || !self.generics.is_empty() {
+            quote! {}
+        } else {
+            let test_mod = format_ident!("{}_expectations", self.name.to_case(Case::Snake));
+            let run_root = match self.error_mode {
+                ErrorMode::Propagate => quote! { prog.label_root().unwrap(); },
+                ErrorMode::Panic => quote! { prog.label_root(); },
+                ErrorMode::AbortProgram => quote! {
+                    prog.label_root();
+                    assert!(prog.error().is_none(), "{:?}", prog.error());
+                }
+            };
+
+            quote! {
+                #[cfg(test)]
+                mod #test_mod {
+                    use super::*;
+
+                    #[test]
+                    fn exits_are_aligned() {
+                        let mut prog = #struct_name::new();
+                        #run_root
+                        prog.check_expectations().unwrap();
+                    }
+                }
+            }
+        };
+
+        let generic_names: Vec<_> = self.generics.iter().map(|generic| match generic {
+            ArgType::Alphabet(name) | ArgType::Clock(name) => format_ident!("{}", name),
+            other => panic!("Unexpected generic parameter: {:?}", other)
+        }).collect();
+
+        let streams = self.gateways.iter().chain(self.exits.iter());
+        if let Some(unused) = self.generics.iter().find(|generic| !streams.clone().any(|(_, alphabet, clock, _, _)| match (generic, alphabet, clock) {
+            (ArgType::Alphabet(param), ArgType::Alphabet(alphabet), _) => alphabet.strip_prefix('$') == Some(param.as_str()),
+            (ArgType::Clock(param), _, ArgType::Clock(clock)) => clock.strip_prefix('$') == Some(param.as_str()),
+            _ => false
+        })) {
+            return Err(Diagnostic::at(&self.span, format!("Program ({}) never uses its generic parameter: {:?}", self.name, unused)));
+        }
+
+        // Generic programs are bounded by the runtime traits, so one definition works for any alphabet or clock
+        let (impl_generics, type_generics) = if self.generics.is_empty() {
+            (quote! {}, quote! {})
+        } else {
+            let bounds: Vec<_> = self.generics.iter().map(|generic| match generic {
+                ArgType::Alphabet(_) => quote! { AlphabetLike },
+                _ => quote! { ClockLike }
+            }).collect();
+
+            (quote! { <#(#generic_names: #bounds),*> }, quote! { <#(#generic_names),*> })
+        };
+
+        let pipeline = self.pipeline(&struct_name, &impl_generics, &type_generics)?;
+
+        let formatted = format_tokens(quote! {
+            #doc
+            #visibility struct #struct_name #impl_generics {
+                #(#gateways)*
+                #(#exits)*
+                #(#counters: usize,)*
+                #(#state_names: #state_types,)*
+                #error_field
+                #fuel_field
+            }
+
+            impl #impl_generics #struct_name #type_generics {
+                pub const fn new() -> Self {
+                    Self {
+                        #(#initialize_gateways)*
+                        #(#initialize_exits)*
+                        #(#counters: 0,)*
+                        #(#state_names: #state_inits,)*
+                        #initialize_error
+                        #initialize_fuel
+                    }
+                }
+
+                #fuel_const
+
+                #error_accessor
+
+                #(#funcs)*
+
+                #run_fn
+
+                /// Checks the program's `expect_aligned` expectations against what is currently buffered on its exits
+                pub fn check_expectations(&self) -> Result<(), ExpectationError<#moment_rep>> {
+                    #(#expectation_checks)*
+                    Ok(())
+                }
+            }
+
+            #expectation_tests
+
+            impl #impl_generics ProgramLike for #struct_name #type_generics {
+                const PRIORITY: u8 = #priority_lit;
+
+                fn has_pending_input(&self) -> bool {
+                    #has_pending_input
+                }
+
+                #backpressure
+
+                fn close_gateways(&mut self) {
+                    #(self.#gateway_fields.close();)*
+                }
+
+                fn propagate_close(&mut self) -> bool {
+                    #(#close_spliced)*
+                    if #gateways_drained {
+                        #(self.#exit_fields.close();)*
+                        true
+                    } else {
+                        false
+                    }
+                }
+
+                #step_fns
+            }
+
+            #pipeline
+        }, format);
+
+        match formatted {
+            Ok(formatted_str) => Ok(formatted_str),
+            Err(FormatError::Rustfmt(err)) => Err(Diagnostic::at(&self.span, format!("Error formatting Program({}):\n{}", self.name, err))),
+            Err(FormatError::Generate(err)) => Err(Diagnostic::at(&self.span, format!("Error generating Program({}):\n{}", self.name, err)))
+        }
+    }
+
+    /// With connected programs, a `Pipeline` struct owns the program and one instance of each program it connects to,
+    /// steps them all through a scheduler, and moves data across the connected streams in between. As a `ProgramGraph`,
+    /// an executor can instead run each of its programs apart, bridging the connected streams.
+    fn pipeline(&self, struct_name: &proc_macro2::Ident, impl_generics: &proc_macro2::TokenStream, type_generics: &proc_macro2::TokenStream) -> Result<proc_macro2::TokenStream, Diagnostic> {
+        if self.links.is_empty() {
+            return Ok(quote! {});
+        }
+
+        let pipeline_name = format_ident!("Pipeline{}", self.name.to_case(Case::Pascal));
+        let visibility = visibility_tokens(self.visibility.as_ref());
+        let doc = format!(" Program ({}), together with every program it connects to", self.name);
+        let programs = proc_macro2::Literal::usize_unsuffixed(self.links.len() + 1);
+
+        let fields: Vec<_> = self.links.iter().map(|link| format_ident!("{}", link.name.to_case(Case::Snake))).collect();
+        let types: Vec<_> = self.links.iter().map(|link| {
+            let kind = if link.pipeline { "Pipeline" } else { "Program" };
+            let name = format_ident!("{}{}", kind, link.program.to_case(Case::Pascal));
+            let type_args: Vec<_> = link.type_args.iter().map(|arg| match arg {
+                ArgType::Alphabet(alphabet) => alphabet_type(alphabet),
+                other => clock_type(other.as_str())
+            }).collect();
+
+            if type_args.is_empty() {
+                quote! { #name }
+            } else {
+                quote! { #name<#(#type_args),*> }
+            }
+        }).collect();
+
+        // The streams of a connected pipeline are those of the program it wraps
+        let mut transfers = vec![];
+        let mut buffered = vec![];
+
+        // Run apart, every connected stream pair gets a bridge - sent on by one program, received by the other
+        let mut bridges = vec![];
+        let (mut program_sends, mut program_receives) = (vec![], vec![]);
+        let (mut sends, mut receives) = (vec![], vec![]);
+        for (link, field) in self.links.iter().zip(fields.iter()) {
+            let streams = if link.pipeline { quote! { self.#field.program } } else { quote! { self.#field } };
+            let own_streams = if link.pipeline { quote! { #field.program } } else { quote! { #field } };
+            let (mut link_sends, mut link_receives) = (vec![], vec![]);
+
+            for (gateway, callee_gateway) in link.feeds.iter() {
+                let gateway_field = format_ident!("gateway_{}", gateway.to_case(Case::Snake));
+                let callee_field = format_ident!("gateway_{}", callee_gateway.to_case(Case::Snake));
+                transfers.push(quote! { moved |= transfer(&mut self.program.#gateway_field, &mut #streams.#callee_field)?; });
+                buffered.push(quote! { !self.program.#gateway_field.is_empty() });
+
+                let (tx, rx) = (format_ident!("tx_{}", bridges.len()), format_ident!("rx_{}", bridges.len()));
+                let (alphabet, clock) = self.stream_types(&self.span, gateway)?;
+                bridges.push(quote! { let (mut #tx, mut #rx) = executor.bridge::<#alphabet, #clock>(); });
+                program_sends.push(quote! { #tx.send(&mut program.#gateway_field) });
+                link_receives.push(quote! { #rx.receive(&mut #own_streams.#callee_field)?; });
+            }
+
+            for (exit, gateway) in link.exits.iter() {
+                let exit_field = format_ident!("exit_{}", exit.to_case(Case::Snake));
+                let gateway_field = format_ident!("gateway_{}", gateway.to_case(Case::Snake));
+                transfers.push(quote! { moved |= transfer(&mut #streams.#exit_field, &mut self.program.#gateway_field)?; });
+                buffered.push(quote! { !#streams.#exit_field.is_empty() });
+
+                let (tx, rx) = (format_ident!("tx_{}", bridges.len()), format_ident!("rx_{}", bridges.len()));
+                let (alphabet, clock) = self.stream_types(&self.span, gateway)?;
+                bridges.push(quote! { let (mut #tx, mut #rx) = executor.bridge::<#alphabet, #clock>(); });
+                link_sends.push(quote! { #tx.send(&mut #own_streams.#exit_field) });
+                program_receives.push(quote! { #rx.receive(&mut program.#gateway_field)?; });
+            }
+
+            sends.push(all_closed(&link_sends));
+            receives.push(link_receives);
+        }
+        let program_sends = all_closed(&program_sends);
+
+        Ok(quote! {
+            #[doc = #doc]
+            #visibility struct #pipeline_name #impl_generics {
+                pub program: #struct_name #type_generics,
+                #(pub #fields: #types,)*
+                scheduler: Scheduler<#programs>
             }
 
-            impl #struct_name {
+            impl #impl_generics #pipeline_name #type_generics {
                 pub const fn new() -> Self {
                     Self {
-                        #(#initialize_gateways)*
-                        #(#initialize_exits)*
+                        program: <#struct_name #type_generics>::new(),
+                        #(#fields: <#types>::new(),)*
+                        scheduler: Scheduler::new(STARVATION_LIMIT)
                     }
                 }
 
-                #(#funcs)*
+                /// Moves everything that fits across connected streams - returns whether anything moved
+                pub fn transfer(&mut self) -> Result<bool, StreamError> {
+                    let mut moved = false;
+                    #(#transfers)*
+                    Ok(moved)
+                }
+
+                /// Runs every program of the pipeline, until none of them has anything left to do - or can make progress
+                pub fn run(&mut self) -> Result<(), StreamError> {
+                    self.start()?;
+                    while self.has_pending_input() && self.run_step()? {}
+
+                    Ok(())
+                }
+
+                /// The program of the pipeline the scheduler caught starving, if any
+                pub fn starvation(&self) -> Option<Starvation> {
+                    self.scheduler.starvation()
+                }
             }
-        });
+
+            impl #impl_generics ProgramLike for #pipeline_name #type_generics {
+                const PRIORITY: u8 = <#struct_name #type_generics as ProgramLike>::PRIORITY;
+
+                fn has_pending_input(&self) -> bool {
+                    #(#buffered ||)* self.program.has_pending_input() #(|| self.#fields.has_pending_input())*
+                }
+
+                fn close_gateways(&mut self) {
+                    self.program.close_gateways();
+                }
+
+                // Closed gateways are carried into connected programs, and their closed exits carried back
+                fn propagate_close(&mut self) -> bool {
+                    let _ = self.transfer();
+                    let mut finished = true;
+                    #(finished &= self.#fields.propagate_close();)*
+                    let _ = self.transfer();
+
+                    self.program.propagate_close() && finished
+                }
+
+                fn start(&mut self) -> Result<(), StreamError> {
+                    #(self.#fields.start()?;)*
+                    self.program.start()
+                }
+
+                fn run_step(&mut self) -> Result<bool, StreamError> {
+                    let moved = self.transfer()?;
+                    let stepped = self.scheduler.round(&mut [&mut self.program, #(&mut self.#fields),*])?;
+                    Ok(self.transfer()? || moved || stepped)
+                }
+            }
+
+            impl #impl_generics ProgramGraph for #pipeline_name #type_generics {
+                fn spawn_all<'a, E: Executor<'a>>(&'a mut self, executor: &mut E) {
+                    #(#bridges)*
+                    let Self { program, #(#fields,)* .. } = self;
+
+                    executor.spawn(program, move |program: &mut #struct_name #type_generics| {
+                        #(#program_receives)*
+                        Ok(#program_sends)
+                    });
+
+                    #(executor.spawn(#fields, move |#fields: &mut #types| {
+                        #(#receives)*
+                        Ok(#sends)
+                    });)*
+                }
+            }
+        })
+    }
+
+    /// The gateway reading from stdin, and where its durations end
+    fn stdin_gateway(&self) -> Option<(&str, StdinMoments)> {
+        self.connectors.iter().find_map(|(stream, connector)| match (stream, connector) {
+            (ArgType::Gateway(name), StdioConnector::Stdin(moments)) => Some((name.as_str(), *moments)),
+            _ => None
+        })
+    }
+
+    /// Programs with gateways or generic parameters depend on whatever would drive them, so only self-contained ones
+    /// (or ones whose only gateway reads from stdin) can be the entry of a generated `main()`
+    pub fn check_runnable(&self) -> Result<(), Diagnostic> {
+        let stdin_gateway = self.stdin_gateway().map(|(name, _)| name);
+
+        if !self.generics.is_empty() {
+            Err(Diagnostic::at(&self.span, format!("Entry program ({}) - generic programs can't be run on their own", self.name)))
+        } else if self.gateway_names().iter().any(|name| Some(*name) != stdin_gateway) {
+            Err(Diagnostic::at(&self.span, format!("Entry program ({}) - programs with gateways can't be run on their own, unless the gateway reads from stdin", self.name)))
+        } else if self.instructions.is_empty() {
+            Err(Diagnostic::at(&self.span, format!("Entry program ({}) - has no instructions to run", self.name)))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// A `fn main()` running the program, then writing each exit's characters to stdout - every
+    /// moment that follows characters ends their line
+    pub fn generate_main(&self, format: bool, programs: &[&Program]) -> Result<String, Diagnostic> {
+        self.check_runnable()?;
+
+        // Programs connecting to others run as part of their pipeline, whose run() always reports errors - unless every
+        // one of them was spliced in
+        let linked = !self.connections().is_empty() && !self.linked(programs)?.links.is_empty();
+        let (struct_name, streams) = match linked {
+            true => (format_ident!("Pipeline{}", self.name.to_case(Case::Pascal)), quote! { prog.program }),
+            false => (format_ident!("Program{}", self.name.to_case(Case::Pascal)), quote! { prog })
+        };
+
+        let error_message = format!("Program ({}) failed: {{:?}}", self.name);
+        let run = match (linked, self.error_mode) {
+            (false, ErrorMode::Panic | ErrorMode::AbortProgram) => quote! { prog.run(); },
+            _ => quote! {
+                if let Err(err) = prog.run() {
+                    eprintln!(#error_message, err);
+                    std::process::exit(1);
+                }
+            }
+        };
+
+        let run_root = match self.error_mode {
+            ErrorMode::AbortProgram => quote! {
+                #run
+                if let Some(err) = #streams.error() {
+                    eprintln!(#error_message, err);
+                    std::process::exit(1);
+                }
+            },
+
+            _ => run
+        };
+
+        let stdout_fields: Vec<_> = self.exits.iter().filter(|(name, _, _, _, _)| self.connector(&ArgType::Exit(name.as_str().to_string())).is_some()).map(|(name, _, _, _, _)| {
+            format_ident!("exit_{}", name.as_str().to_case(Case::Snake))
+        }).collect();
+
+        // Standard streams are read and written as the program runs, through the runtime's io module
+        let run_io = match self.stdin_gateway() {
+            Some((gateway, moments)) => {
+                let gateway_field = format_ident!("gateway_{}", gateway.to_case(Case::Snake));
+                let moments = match moments {
+                    StdinMoments::Line => quote! { io::Moments::PerLine },
+                    StdinMoments::Read => quote! { io::Moments::PerRead },
+                    StdinMoments::Bytes(bytes) => {
+                        let bytes = proc_macro2::Literal::u64_unsuffixed(bytes);
+                        quote! { io::Moments::PerBytes(#bytes) }
+                    }
+                };
+
+                // Aborted programs take no more input, so reading stops with them
+                let stop_aborted = match self.error_mode {
+                    ErrorMode::AbortProgram => quote! {
+                        if #streams.error().is_some() {
+                            return Ok(());
+                        }
+                    },
+
+                    _ => quote! {}
+                };
+
+                Some(quote! {
+                    let mut stdin = io::StdinGateway::stdin(#moments);
+                    let mut stdout = io::StdoutExit::stdout();
+
+                    prog.start()?;
+                    loop {
+                        stdin.feed(&mut #streams.#gateway_field)?;
+                        while prog.has_pending_input() && prog.run_step()? {}
+                        #(stdout.drain(&mut #streams.#stdout_fields)?;)*
+                        #stop_aborted
+
+                        if !prog.has_pending_input() {
+                            if #streams.#gateway_field.is_closed() {
+                                return Ok(());
+                            } else if !#streams.#gateway_field.accepting_pushes() {
+                                // A duration longer than the gateway's buffer can never be run
+                                return Err(StreamError::Exit(ExitError::BufferFull).into());
+                            }
+                        }
+                    }
+                })
+            },
+
+            None if !stdout_fields.is_empty() => Some(quote! {
+                let mut stdout = io::StdoutExit::stdout();
+                #(stdout.drain(&mut #streams.#stdout_fields)?;)*
+                Ok(())
+            }),
+
+            None => None
+        };
+
+        let (run_root, run_io_fn) = match run_io {
+            Some(run_io) => {
+                let run_stdio = quote! {
+                    if let Err(err) = run_io(&mut prog) {
+                        eprintln!(#error_message, err);
+                        std::process::exit(1);
+                    }
+                };
+
+                let run_root = match self.stdin_gateway() {
+                    Some(_) => match self.error_mode {
+                        ErrorMode::AbortProgram => quote! {
+                            #run_stdio
+                            if let Some(err) = #streams.error() {
+                                eprintln!(#error_message, err);
+                                std::process::exit(1);
+                            }
+                        },
+
+                        _ => run_stdio
+                    },
+
+                    None => quote! {
+                        #run_root
+                        #run_stdio
+                    }
+                };
+
+                (run_root, quote! {
+                    /// Runs the program against the standard streams it's connected to
+                    fn run_io(prog: &mut #struct_name) -> Result<(), io::IoError> {
+                        #run_io
+                    }
+                })
+            },
+
+            None => (run_root, quote! {})
+        };
+
+        let write_exits: Vec<_> = self.exits.iter().filter(|(name, _, _, _, _)| self.connector(&ArgType::Exit(name.as_str().to_string())).is_none()).map(|exit_data| match exit_data {
+            (ArgType::Name(name), ArgType::Alphabet(alphabet), _, _, _) => {
+                let exit_field = format_ident!("exit_{}", name.to_case(Case::Snake));
+                let alphabet_name = alphabet_type(alphabet);
+
+                quote! {
+                    let mut line_open = false;
+                    for item in #streams.#exit_field.drain() {
+                        match item {
+                            StreamItem::Character(chr) => {
+                                let rep = <#alphabet_name as AlphabetLike>::to_val(chr) as u32;
+                                write!(out, "{}", char::from_u32(rep).unwrap_or(char::REPLACEMENT_CHARACTER))?;
+                                line_open = true;
+                            },
+
+                            StreamItem::InvalidChar(_) => {
+                                write!(out, "{}", char::REPLACEMENT_CHARACTER)?;
+                                line_open = true;
+                            },
+
+                            StreamItem::Moment(_) => if line_open {
+                                writeln!(out)?;
+                                line_open = false;
+                            },
+
+                            StreamItem::Empty => ()
+                        }
+                    }
+
+                    if line_open {
+                        writeln!(out)?;
+                    }
+                }
+            },
+
+            _ => panic!("Unexpected reg_exit params: {:?}", exit_data)
+        }).collect();
+
+        // The trace covers running the program - not writing out its exits
+        let (start_trace, finish_trace) = match self.trace {
+            true => (quote! { trace::trace_from_env()?; }, quote! { trace::finish()?; }),
+            false => (quote! {}, quote! {})
+        };
+
+        let formatted = format_tokens(quote! {
+            #run_io_fn
+
+            fn main() -> std::io::Result<()> {
+                use std::io::Write;
+                #start_trace
+
+                let mut prog = #struct_name::new();
+                #run_root
+                #finish_trace
+
+                let mut out = std::io::stdout().lock();
+                #({ #write_exits })*
+                out.flush()
+            }
+        }, format);
 
         match formatted {
             Ok(formatted_str) => Ok(formatted_str),
-            Err(rustfmt_wrapper::Error::Rustfmt(err)) => Err(format!("Error formatting Program({}):\n{}", self.name, err)),
-            Err(err) => Err(format!("Error generating Program({}):\n{}", self.name, err))
+            Err(FormatError::Rustfmt(err)) => Err(Diagnostic::at(&self.span, format!("Error formatting main() of Program({}):\n{}", self.name, err))),
+            Err(FormatError::Generate(err)) => Err(Diagnostic::at(&self.span, format!("Error generating main() of Program({}):\n{}", self.name, err)))
+        }
+    }
+}
+
+/// Whether every bridge a program sends on is closed - each send is made, none of them short-circuits
+fn all_closed(sends: &[proc_macro2::TokenStream]) -> proc_macro2::TokenStream {
+    if sends.is_empty() {
+        quote! { true }
+    } else {
+        quote! { #(#sends)&* }
+    }
+}
+
+/// The Rust type of an alphabet - a generated alphabet, or one of the program's generic parameters (`$A`)
+fn alphabet_type(alphabet: &str) -> proc_macro2::Ident {
+    match alphabet.strip_prefix('$') {
+        Some(param) => format_ident!("{}", param),
+        None => format_ident!("Alphabet{}", alphabet.to_case(Case::Pascal))
+    }
+}
+
+/// The Rust type of a clock - a generated clock, or one of the program's generic parameters (`$C`)
+fn clock_type(clock: &str) -> proc_macro2::Ident {
+    match clock.strip_prefix('$') {
+        Some(param) => format_ident!("{}", param),
+        None => format_ident!("Clock{}", clock.to_case(Case::Pascal))
+    }
+}
+
+/// The field of a program holding the moment before which `throttle`s from a gateway onto an exit drop durations
+fn throttle_field(gateway: &str, exit: &str) -> proc_macro2::Ident {
+    format_ident!("throttle_{}_{}", gateway.to_case(Case::Snake), exit.to_case(Case::Snake))
+}
+
+/// The field of a program counting the durations `window`s from a gateway onto an exit merged so far
+fn window_field(gateway: &str, exit: &str) -> proc_macro2::Ident {
+    format_ident!("window_{}_{}", gateway.to_case(Case::Snake), exit.to_case(Case::Snake))
+}
+
+/// Splits a heap-backed buffer size - `dynamic` (unbounded) or `dynamic(CAP)` - into its cap, `None` for fixed-size buffers
+fn parse_dynamic(buf_size: &str) -> Option<Option<&str>> {
+    let buf_size = buf_size.trim();
+
+    if buf_size == "dynamic" {
+        Some(None)
+    } else {
+        buf_size.strip_prefix("dynamic(").and_then(|cap| cap.strip_suffix(')')).map(Some)
+    }
+}
+
+/// How many items a stream's buffer holds - None for `dynamic` buffers without a cap
+fn buffer_items(buf_size: &str) -> Option<u64> {
+    match parse_dynamic(buf_size) {
+        Some(cap) => cap.and_then(parse_number),
+        None => parse_number(buf_size)
+    }
+}
+
+/// The characters an exit may be pushed along the paths through some instructions - `through` the paths pushing no
+/// moment onto it (adding to the duration already open), before the `first` moment and after the `last` one of the
+/// others, and the longest duration opened and closed `within` them. `None` where no path does so.
+#[derive(Debug, Clone, Copy)]
+struct Load {
+    through: Option<u64>,
+    first: Option<u64>,
+    last: Option<u64>,
+    within: u64
+}
+
+/// A load of characters no bound is known for
+const UNBOUNDED_LOAD: u64 = u64::MAX;
+
+/// What bounds the characters pushed reading a gateway - the longest duration it holds, and the decimal digits of its
+/// largest moment
+struct Bounds<'a> {
+    longest_duration: &'a dyn Fn(&str) -> u64,
+    moment_digits: &'a dyn Fn(&str) -> u64
+}
+
+impl Load {
+    const NONE: Self = Self{through: Some(0), first: None, last: None, within: 0};
+
+    /// `chars` characters, and a moment closing them if `closed`
+    const fn chars(chars: u64, closed: bool) -> Self {
+        match closed {
+            true => Self{through: None, first: Some(chars), last: Some(0), within: 0},
+            false => Self{through: Some(chars), first: None, last: None, within: 0}
+        }
+    }
+
+    /// What an instruction pushes onto `exit`
+    fn of(instruction: &Instruction, exit: &str, bounds: &Bounds) -> Self {
+        use Instruction::*;
+        let is_exit = |arg: &ArgType| arg.as_str() == exit;
+        let longest_duration = bounds.longest_duration;
+
+        match instruction {
+            PushChar(_, to) | PushVal(_, to) if is_exit(to) => Self::chars(1, false),
+            PushMoment(_, to) | ForwardMoment(_, to) if is_exit(to) => Self::chars(0, true),
+
+            ForwardDuration(gateway, to) | Filter(gateway, to, _) | FilterKeep(gateway, to, _) | Delay(gateway, to, _) | Throttle(gateway, to, _) if is_exit(to) => {
+                Self::chars(longest_duration(gateway.as_str()), true)
+            },
+
+            Tee(gateway, a, b) if is_exit(a) || is_exit(b) => Self::chars(longest_duration(gateway.as_str()), true),
+            Split(gateway, exits, _) if exits.iter().any(is_exit) => Self::chars(longest_duration(gateway.as_str()), true),
+            Window(gateway, to, durations) if is_exit(to) => {
+                Self::chars(longest_duration(gateway.as_str()).saturating_mul(parse_number(durations.as_str()).unwrap_or(UNBOUNDED_LOAD)), true)
+            },
+
+            // Decimal digits - of the count, or of a moment
+            CountChars(gateway, to) if is_exit(to) => Self::chars(longest_duration(gateway.as_str()).checked_ilog10().map_or(1, |log| log as u64 + 1), true),
+            MinMoment(gateway, to) | MaxMoment(gateway, to) if is_exit(to) => Self::chars((bounds.moment_digits)(gateway.as_str()), true),
+
+            Sync(gateways, exits) => match exits.iter().position(is_exit) {
+                Some(idx) => Self::chars(gateways.get(idx).map_or(0, |gateway| longest_duration(gateway.as_str())), true),
+                None => Self::NONE
+            },
+
+            Zip(gateways, to) if is_exit(to) => {
+                Self::chars(gateways.iter().fold(0, |chars: u64, gateway| chars.saturating_add(longest_duration(gateway.as_str()))), true)
+            },
+
+            _ => Self::NONE
+        }
+    }
+
+    /// These instructions, then the `next`
+    fn then(self, next: Self) -> Self {
+        let sum = |a: Option<u64>, b: Option<u64>| a.zip(b).map(|(a, b)| a.saturating_add(b));
+
+        Self{
+            through: sum(self.through, next.through),
+            first: self.first.max(sum(self.through, next.first)),
+            last: next.last.max(sum(self.last, next.through)),
+            within: self.within.max(next.within).max(sum(self.last, next.first).unwrap_or(0))
+        }
+    }
+
+    /// Either these instructions, or the `other`
+    fn or(self, other: Self) -> Self {
+        Self{
+            through: self.through.max(other.through),
+            first: self.first.max(other.first),
+            last: self.last.max(other.last),
+            within: self.within.max(other.within)
+        }
+    }
+}
+
+/// Where a moment pushed onto an exit comes from - a literal, or the moments of a stream (later by some amount)
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MomentSource {
+    Literal(u64),
+    Stream(String, u64)
+}
+
+impl MomentSource {
+    /// The moment an instruction pushes onto `exit`, if any
+    fn of(instruction: &Instruction, exit: &str) -> Option<Self> {
+        use Instruction::*;
+        let is_exit = |arg: &ArgType| arg.as_str() == exit;
+        let stream = |stream: &ArgType| Some(Self::Stream(stream.as_str().to_string(), 0));
+
+        match instruction {
+            PushMoment(moment, to) if is_exit(to) => match moment.as_str().strip_prefix("Time(").and_then(|stream| stream.strip_suffix(')')) {
+                Some(stream) => Some(Self::Stream(stream.to_string(), 0)),
+                None => parse_number(moment.as_str()).map(Self::Literal)
+            },
+
+            ForwardMoment(gateway, to) | ForwardDuration(gateway, to) | Filter(gateway, to, _) | FilterKeep(gateway, to, _) | Throttle(gateway, to, _) |
+            Window(gateway, to, _) | CountChars(gateway, to) | MinMoment(gateway, to) | MaxMoment(gateway, to) if is_exit(to) => stream(gateway),
+            Tee(gateway, a, b) if is_exit(a) || is_exit(b) => stream(gateway),
+            Split(gateway, exits, _) if exits.iter().any(is_exit) => stream(gateway),
+            Delay(gateway, to, amount) if is_exit(to) => Some(Self::Stream(gateway.as_str().to_string(), parse_number(amount.as_str()).unwrap_or(0))),
+
+            // The earliest pending moment of the gateways
+            Sync(gateways, exits) if exits.iter().any(is_exit) => gateways.first().and_then(stream),
+            Zip(gateways, to) if is_exit(to) => gateways.first().and_then(stream),
+            _ => None
+        }
+    }
+}
+
+impl std::fmt::Display for MomentSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Literal(moment) => write!(f, "moment {}", moment),
+            Self::Stream(stream, 0) => write!(f, "a moment of {}", stream),
+            Self::Stream(stream, later_by) => write!(f, "a moment of {} (plus {})", stream, later_by)
+        }
+    }
+}
+
+/// The moments an exit may be pushed along the paths through some instructions - the `first` and `last` of each
+/// path (with the instruction pushing them), and whether a path pushes none at all (letting them `through`)
+#[derive(Debug, Clone)]
+struct Pushes<'a> {
+    first: Vec<(&'a Span, MomentSource)>,
+    last: Vec<MomentSource>,
+    through: bool
+}
+
+impl<'a> Pushes<'a> {
+    const NONE: Self = Self{first: Vec::new(), last: Vec::new(), through: true};
+
+    fn push(span: &'a Span, source: MomentSource) -> Self {
+        Self{first: vec![(span, source.clone())], last: vec![source], through: false}
+    }
+
+    /// These instructions, then the `next` - the moments `next` may push right after these ones that `may_go_back` are
+    /// recorded as going `backwards`
+    fn then(self, next: Self, may_go_back: &dyn Fn(&MomentSource, &MomentSource) -> bool, backwards: &mut Vec<(&'a Span, MomentSource, MomentSource)>) -> Self {
+        for previous in self.last.iter() {
+            for (span, pushed) in next.first.iter().filter(|(_, pushed)| may_go_back(previous, pushed)) {
+                backwards.push((span, previous.clone(), pushed.clone()));
+            }
+        }
+
+        let mut first = self.first;
+        if self.through {
+            extend_unique(&mut first, next.first);
+        }
+
+        let mut last = next.last;
+        if next.through {
+            extend_unique(&mut last, self.last);
         }
+
+        Self{first, last, through: self.through && next.through}
+    }
+
+    /// Either these instructions, or the `other`
+    fn or(mut self, other: Self) -> Self {
+        extend_unique(&mut self.first, other.first);
+        extend_unique(&mut self.last, other.last);
+        self.through |= other.through;
+        self
+    }
+}
+
+/// Where the paths through some instructions may go without making progress - the labels along one reading no gateway,
+/// if any does, and the first instruction one waits on a gateway with (along with the labels it goes through)
+#[derive(Debug, Clone)]
+struct Progress<'a> {
+    idle: Option<Vec<&'a str>>,
+    waits: Option<(Vec<&'a str>, &'a Span, &'a str)>
+}
+
+impl<'a> Progress<'a> {
+    const END: Self = Self{idle: Some(Vec::new()), waits: None};
+
+    /// Either these instructions, or the `other`
+    fn or(self, other: Self) -> Self {
+        Self{idle: self.idle.or(other.idle), waits: self.waits.or(other.waits)}
+    }
+
+    /// The same paths, starting at `label`
+    fn from(mut self, label: &'a str) -> Self {
+        for labels in self.idle.iter_mut().chain(self.waits.iter_mut().map(|(labels, _, _)| labels)) {
+            labels.insert(0, label);
+        }
+
+        self
+    }
+}
+
+fn extend_unique<T: PartialEq>(into: &mut Vec<T>, items: Vec<T>) {
+    for item in items {
+        if !into.contains(&item) {
+            into.push(item);
+        }
+    }
+}
+
+/// The buffer size the type of a stream carries - `UNBOUNDED`, the cap of a `dynamic` buffer, or a fixed size
+fn buffer_size(buf_size: &str) -> proc_macro2::TokenStream {
+    match parse_dynamic(buf_size) {
+        Some(None) => quote! { UNBOUNDED },
+        Some(Some(cap)) => cap.parse().unwrap(),
+        None => buf_size.parse().unwrap()
+    }
+}
+
+/// The type of a stream's field - a fixed-size Stream, or a VecStream growing up to its cap for `dynamic` buffers
+fn stream_type(alphabet: &str, clock: &str, buf_size: &str) -> proc_macro2::TokenStream {
+    let alphabet_name = alphabet_type(alphabet);
+    let clock_name = clock_type(clock);
+    let size = buffer_size(buf_size);
+
+    match parse_dynamic(buf_size) {
+        Some(_) => quote! { VecStream<#alphabet_name, #clock_name, #size> },
+        None => quote! { Stream<#alphabet_name, #clock_name, #size> }
     }
-}
\ No newline at end of file
+}
+
+/// Character matchers may either name a class of the alphabet, or be a raw (numeric) value
+fn class_or_value(matching: &str) -> ArgType {
+    if matching.starts_with(|chr: char| chr.is_ascii_digit()) {
+        ArgType::Number(matching.to_string())
+    } else {
+        ArgType::Class(matching.to_string())
+    }
+}
+
+/// Splits a reference like `sync2(A|B)` into its owner (`sync2`) and the listed streams (`A`, `B`)
+fn split_stream_ref(reference: &str) -> (&str, Vec<String>) {
+    match reference.split_once('(') {
+        Some((owner, streams)) => {
+            let streams = streams.trim_end_matches(')');
+            (owner, streams.split('|').filter(|stream| !stream.is_empty()).map(|stream| stream.to_string()).collect())
+        },
+
+        None => (reference, vec![])
+    }
+}
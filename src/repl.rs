@@ -0,0 +1,456 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use convert_case::{Case, Casing};
+use quote::{format_ident, quote};
+use crate::{Compiler, Diagnostic, Parser};
+
+/// Steps `:run` takes at most - programs still running after as many are taken to never finish
+pub(crate) const MAX_RUN_STEPS: usize = 100_000;
+
+const HELP: &str = "\
+Statements define alphabets, clocks and programs, as in a .tl file - one statement per line. Commands:
+  :program NAME                  runs NAME (the last program defined, unless set)
+  :feed GATEWAY \"TEXT\" @MOMENT   pushes the bytes of TEXT onto GATEWAY, then the moment - either may be left out
+  :close GATEWAY                 closes GATEWAY - no more input arrives on it
+  :step [N]                      runs N steps (1, unless given), then prints the exits
+  :run                           steps until no input is pending, then prints the exits
+  :exits                         prints everything pushed onto the exits so far
+  :reset                         forgets what was fed and run - the definitions stay
+  :clear                         forgets everything
+  :source                        prints the definitions
+  :rust                          prints the generated Rust
+  :quit                          ends the session
+";
+
+/// What was done to the program - replayed in order every time it runs, as programs are deterministic
+#[derive(Debug, Clone)]
+pub(crate) enum Event {
+    /// The bytes of a text pushed onto a gateway, then maybe a moment
+    Feed { gateway: String, values: Vec<u8>, moment: Option<u64> },
+    Close(String),
+    Step(usize),
+    Run
+}
+
+/// The program a session runs - with what the driver needs to know of it
+pub(crate) struct Entry {
+    pub(crate) name: String,
+    linked: bool,
+    pub(crate) gateways: Vec<String>,
+    pub(crate) exits: Vec<String>
+}
+
+pub enum Reply {
+    Output(String),
+    Quit
+}
+
+/// An interactive session - definitions typed a statement at a time, and input fed to (and steps run on) one of its
+/// programs. Programs run as the Rust they transpile to, built with rustc (`$RUSTC`, or the one on the path), replaying
+/// everything done to them so far each time.
+pub struct Repl {
+    source: String,
+    program: Option<String>,
+    events: Vec<Event>,
+    dir: PathBuf,
+
+    /// The code the binary in `dir` was last built from - it is only rebuilt once that changes
+    built: Option<String>
+}
+
+impl Default for Repl {
+    fn default() -> Self { Self::new() }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self {
+            source: String::new(),
+            program: None,
+            events: vec![],
+            dir: std::env::temp_dir().join(format!("time_lang_repl_{}", std::process::id())),
+            built: None
+        }
+    }
+
+    /// Adds definitions to the session - rejected (with the diagnostic) unless the session still parses with them
+    pub fn define(&mut self, text: &str) -> Result<(), String> {
+        let source = format!("{}{}\n", self.source, text.trim_end());
+
+        let mut parser = Parser::new("repl");
+        parser.set_codegen(false);
+        parser.parse_str(&source).map_err(|diagnostic| diagnostic.render(&source))?;
+
+        self.source = source;
+        Ok(())
+    }
+
+    /// Handles a line typed in - a statement, or a `:command`
+    pub fn eval(&mut self, line: &str) -> Result<Reply, String> {
+        let Some(command) = line.trim().strip_prefix(':') else {
+            return self.define(line).map(|_| Reply::Output(String::new()));
+        };
+
+        let (command, args) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        let args = args.trim();
+
+        let output = match command {
+            "q" | "quit" => return Ok(Reply::Quit),
+            "help" => HELP.to_string(),
+            "source" => self.source.clone(),
+            "rust" => Compiler::compile_str(&self.source).map_err(|diagnostic| diagnostic.render(&self.source))?,
+
+            "program" => {
+                self.program = Some(args.to_string());
+                if let Err(err) = self.entry() {
+                    self.program = None;
+                    return Err(err);
+                }
+
+                String::new()
+            },
+
+            "feed" => {
+                self.replay(parse_feed(args)?)?;
+                String::new()
+            },
+
+            "close" if !args.is_empty() => {
+                self.replay(Event::Close(args.to_string()))?;
+                String::new()
+            },
+
+            "step" => {
+                let steps = match args {
+                    "" => 1,
+                    steps => steps.parse().map_err(|_| format!("Not a number of steps: {}", steps))?
+                };
+
+                let (ran, exits) = self.replay(Event::Step(steps))?;
+                match ran < steps {
+                    true => format!("ran {} of {} steps - no input pending\n{}", ran, steps, exits),
+                    false => format!("ran {} steps\n{}", ran, exits)
+                }
+            },
+
+            "run" => {
+                let (ran, exits) = self.replay(Event::Run)?;
+                match ran >= MAX_RUN_STEPS {
+                    true => format!("stopped after {} steps - the program may never finish\n{}", ran, exits),
+                    false => format!("ran {} steps\n{}", ran, exits)
+                }
+            },
+
+            "exits" => self.run().map(|(_, exits)| exits)?,
+
+            "reset" => {
+                self.events.clear();
+                String::new()
+            },
+
+            "clear" => {
+                self.source.clear();
+                self.program = None;
+                self.events.clear();
+                String::new()
+            },
+
+            _ => return Err(format!("Unknown command: :{} (:help lists the commands)", command))
+        };
+
+        Ok(Reply::Output(output))
+    }
+
+    /// Runs `program` (the last one defined, without one) on `events` alone - returning everything pushed onto each of
+    /// its exits, as the names of the characters and `@MOMENT`s
+    pub(crate) fn run_events(&mut self, program: Option<&str>, events: Vec<Event>) -> Result<Vec<(String, Vec<String>)>, String> {
+        self.program = program.map(|program| program.to_string());
+        self.events = events;
+
+        let (_, exits) = self.run()?;
+        Ok(exits.lines().filter_map(|line| line.split_once(':')).map(|(exit, items)| {
+            (exit.to_string(), items.split_whitespace().map(|item| item.to_string()).collect())
+        }).collect())
+    }
+
+    /// Runs the program with an event added - which is undone unless it ran
+    fn replay(&mut self, event: Event) -> Result<(usize, String), String> {
+        self.events.push(event);
+
+        let ran = self.run();
+        if ran.is_err() {
+            self.events.pop();
+        }
+
+        ran
+    }
+
+    /// The program the session runs - the one set with `:program`, or the last one defined that can run on its own
+    fn entry(&self) -> Result<Entry, String> {
+        Entry::find(&self.source, self.program.as_deref())
+    }
+
+    /// A `fn main()` replaying the session's events on its program, then printing how many steps the last of them ran
+    /// and everything pushed onto each exit
+    fn driver(&self, entry: &Entry) -> Result<String, String> {
+        let (struct_name, streams) = (entry.struct_name(), entry.streams());
+
+        let gateway_field = |gateway: &str| match entry.gateways.iter().any(|name| name == gateway) {
+            true => Ok(format_ident!("gateway_{}", gateway.to_case(Case::Snake))),
+            false => Err(format!("Program ({}) has no gateway {}", entry.name, gateway))
+        };
+
+        let events = self.events.iter().map(|event| Ok(match event {
+            Event::Feed { gateway, values, moment } => {
+                let field = gateway_field(gateway)?;
+                let failed = format!("Feeding {} failed: {{:?}}", gateway);
+                let values = values.iter().map(|value| proc_macro2::Literal::u8_unsuffixed(*value));
+                let moment = moment.map(|moment| {
+                    let moment = proc_macro2::Literal::u64_unsuffixed(moment);
+                    quote! { #streams.#field.push_moment(#moment).map_err(|err| format!(#failed, err))?; }
+                });
+
+                quote! {
+                    #streams.#field.push_vals(&[#(#values),*]).map_err(|err| format!(#failed, err))?;
+                    #moment
+                }
+            },
+
+            Event::Close(gateway) => {
+                let field = gateway_field(gateway)?;
+                quote! { #streams.#field.close(); }
+            },
+
+            Event::Step(_) | Event::Run => {
+                let steps = match event {
+                    Event::Step(steps) => *steps,
+                    _ => MAX_RUN_STEPS
+                };
+
+                quote! {
+                    steps = 0;
+                    while steps < #steps && prog.run_step().map_err(|err| format!("Step {} failed: {:?}", steps + 1, err))? {
+                        steps += 1;
+                    }
+                }
+            }
+        })).collect::<Result<Vec<_>, String>>()?;
+
+        let print_exits = entry.print_exits();
+
+        Ok(quote! {
+            fn replay(prog: &mut #struct_name) -> Result<usize, String> {
+                let mut steps = 0;
+                prog.start().map_err(|err| format!("Starting the program failed: {:?}", err))?;
+                #(#events)*
+                Ok(steps)
+            }
+
+            fn main() {
+                let mut prog = #struct_name::new();
+                match replay(&mut prog) {
+                    Ok(steps) => println!("{}", steps),
+                    Err(err) => {
+                        println!("{}", err);
+                        std::process::exit(2);
+                    }
+                }
+
+                #print_exits
+            }
+        }.to_string())
+    }
+
+    /// Builds the program (unless it was built as it is already) and runs it - returning how many steps the last
+    /// event ran, and what is on the exits
+    fn run(&mut self) -> Result<(usize, String), String> {
+        let entry = self.entry()?;
+        let mut code = compile(&self.source, false).map_err(|diagnostic| diagnostic.render(&self.source))?;
+        code.push_str(&self.driver(&entry)?);
+
+        let binary = self.dir.join(format!("repl{}", std::env::consts::EXE_SUFFIX));
+        if self.built.as_ref() != Some(&code) {
+            build(&self.dir, &binary, &code)?;
+            self.built = Some(code);
+        }
+
+        let output = Command::new(&binary).output().map_err(|err| format!("Could not run {}: {}", binary.display(), err))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() {
+            return Err(format!("{}{}", stdout, String::from_utf8_lossy(&output.stderr)));
+        }
+
+        let (steps, exits) = stdout.split_once('\n').unwrap_or((&stdout, ""));
+        Ok((steps.trim().parse().unwrap_or(0), exits.to_string()))
+    }
+}
+
+impl Entry {
+    /// `program` of a source - or, without one, the last program it defines that can run on its own
+    pub(crate) fn find(source: &str, program: Option<&str>) -> Result<Self, String> {
+        let mut parser = Parser::new("repl");
+        parser.set_codegen(false);
+        parser.parse_str(source).map_err(|diagnostic| diagnostic.render(source))?;
+
+        let program = match program {
+            Some(name) => parser.definitions().filter_map(|state| state.as_program()).find(|prog| prog.name() == name).ok_or_else(|| {
+                format!("Program ({}) is not defined", name)
+            })?,
+
+            None => parser.definitions().filter_map(|state| state.as_program()).filter(|prog| !prog.is_generic()).last().ok_or_else(|| {
+                "No program to run - define one first".to_string()
+            })?
+        };
+
+        if program.is_generic() {
+            return Err(format!("Program ({}) - generic programs can't be run on their own", program.name()));
+        }
+
+        Ok(Self {
+            name: program.name().to_string(),
+            linked: !program.connections().is_empty(),
+            gateways: program.gateway_names().iter().map(|name| name.to_string()).collect(),
+            exits: program.exit_names().iter().map(|name| name.to_string()).collect()
+        })
+    }
+
+    /// The struct generated for the program - its pipeline, when it connects others
+    pub(crate) fn struct_name(&self) -> proc_macro2::Ident {
+        match self.linked {
+            true => format_ident!("Pipeline{}", self.name.to_case(Case::Pascal)),
+            false => format_ident!("Program{}", self.name.to_case(Case::Pascal))
+        }
+    }
+
+    /// Where the gateways and exits are, on a `prog` of the struct
+    pub(crate) fn streams(&self) -> proc_macro2::TokenStream {
+        match self.linked {
+            true => quote! { prog.program },
+            false => quote! { prog }
+        }
+    }
+
+    /// Prints everything pushed onto each exit of `prog` - a line per exit, with the names of its characters and
+    /// `@MOMENT`s
+    pub(crate) fn print_exits(&self) -> proc_macro2::TokenStream {
+        let streams = self.streams();
+        let exit_names = self.exits.iter();
+        let exit_fields = self.exits.iter().map(|exit| format_ident!("exit_{}", exit.to_case(Case::Snake)));
+
+        quote! {
+            #({
+                let items: Vec<String> = Drain::new(&mut #streams.#exit_fields).filter_map(|item| match item {
+                    StreamItem::Character(chr) => Some(format!("{:?}", chr).trim_end_matches("()").to_string()),
+                    StreamItem::Moment(moment) => Some(format!("@{:?}", moment)),
+                    StreamItem::InvalidChar(rep) => Some(format!("invalid({:?})", rep)),
+                    StreamItem::Empty => None
+                }).collect();
+
+                println!("{}: {}", #exit_names, items.join(" "));
+            })*
+        }
+    }
+}
+
+/// A source as self-contained Rust, like Compiler::compile_str - but without the `fn main()` a `set_entry` adds, as
+/// drivers are the binaries'
+pub(crate) fn compile(source: &str, format: bool) -> Result<String, Diagnostic> {
+    let mut parser = Parser::new("repl");
+    parser.set_codegen(false);
+    parser.set_format(format);
+    parser.set_inline_runtime(true);
+    parser.parse_str(source)?;
+    parser.check_cycles().map_err(|err| Diagnostic::new("repl", err))?;
+
+    let mut codegen = parser.into_codegen();
+    codegen.set_entry(None);
+    codegen.generate()
+}
+
+/// Builds `code` into `binary` with rustc (`$RUSTC`, or the one on the path) - the code is written into `dir` first
+pub(crate) fn build(dir: &Path, binary: &Path, code: &str) -> Result<(), String> {
+    let file = binary.with_extension("rs");
+    std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&file, code)).map_err(|err| {
+        format!("Could not write {}: {}", file.display(), err)
+    })?;
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(&rustc).args(["--edition", "2021", "-A", "warnings", "-C", "debuginfo=0", "-o"]).arg(binary).arg(&file).output().map_err(|err| {
+        format!("Could not run {}: {}", rustc, err)
+    })?;
+
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(format!("Building the program failed:\n{}", String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
+impl Drop for Repl {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// `GATEWAY "TEXT" @MOMENT` - either the text or the moment may be left out
+fn parse_feed(args: &str) -> Result<Event, String> {
+    const USAGE: &str = "Usage: :feed GATEWAY \"TEXT\" @MOMENT";
+
+    let (gateway, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let mut rest = rest.trim();
+    if gateway.is_empty() || rest.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let mut values = vec![];
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let (text, after) = parse_quoted(quoted)?;
+        values = text;
+        rest = after.trim();
+    }
+
+    let moment = match rest.strip_prefix('@') {
+        Some(moment) => Some(parse_moment(moment.trim()).ok_or_else(|| format!("Not a moment: {}", moment))?),
+        None if rest.is_empty() => None,
+        None => return Err(USAGE.to_string())
+    };
+
+    Ok(Event::Feed { gateway: gateway.to_string(), values, moment })
+}
+
+/// The bytes of a string literal (its opening quote already taken) - with `\n`, `\t`, `\r`, `\0`, `\\`, `\"` and
+/// `\xNN` escapes - and what follows its closing quote
+pub(crate) fn parse_quoted(quoted: &str) -> Result<(Vec<u8>, &str), String> {
+    let mut bytes = vec![];
+    let mut chars = quoted.char_indices();
+
+    while let Some((idx, chr)) = chars.next() {
+        match chr {
+            '"' => return Ok((bytes, &quoted[idx + 1..])),
+            '\\' => match chars.next().map(|(_, escaped)| escaped) {
+                Some('n') => bytes.push(b'\n'),
+                Some('t') => bytes.push(b'\t'),
+                Some('r') => bytes.push(b'\r'),
+                Some('0') => bytes.push(0),
+                Some('\\') => bytes.push(b'\\'),
+                Some('"') => bytes.push(b'"'),
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).map(|(_, digit)| digit).collect();
+                    bytes.push(u8::from_str_radix(&hex, 16).map_err(|_| format!("Invalid escape: \\x{}", hex))?);
+                },
+
+                escaped => return Err(format!("Invalid escape: \\{}", escaped.map_or(String::new(), String::from)))
+            },
+
+            chr => bytes.extend_from_slice(chr.encode_utf8(&mut [0; 4]).as_bytes())
+        }
+    }
+
+    Err("The text is never closed with a \"".to_string())
+}
+
+pub(crate) fn parse_moment(moment: &str) -> Option<u64> {
+    match moment.strip_prefix("0x").or_else(|| moment.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => moment.parse().ok()
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use convert_case::{Case, Casing};
+use crate::repl::{parse_moment, parse_quoted, Event};
+use crate::{Diagnostic, Parser, Repl, Span};
+
+/// A duration of a stream - the values of its characters, and the moment closing it (None while it is still open)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Duration {
+    pub(crate) moment: Option<u64>,
+    pub(crate) values: Vec<u8>
+}
+
+/// A test of a program - its gateways are fed (in the order given), then it runs until no input is pending, and
+/// what was pushed onto its exits is compared to what's expected
+#[derive(Debug)]
+pub struct TestCase {
+    pub name: String,
+    pub span: Span,
+    program: Option<String>,
+    events: Vec<Event>,
+
+    /// The exits checked - others may hold anything
+    expected: Vec<(String, Vec<Duration>)>
+}
+
+/// How a test ran - the differences from what was expected, if it failed
+#[derive(Debug)]
+pub struct TestResult {
+    pub name: String,
+    pub span: Span,
+    pub failure: Option<String>
+}
+
+/// The tests of a source, written next to it in a `.spec` file - one statement per line:
+///
+/// ```text
+/// test NAME                       starts a test
+/// program NAME                    the program it runs (the last one defined, unless set)
+/// input GATEWAY: (1, "AB") "C"    feeds GATEWAY durations - each (MOMENT, "CHARS"), or "CHARS" left open
+/// close GATEWAY                   closes GATEWAY - no more input arrives on it
+/// expect EXIT: (1, "AB")          what EXIT must hold once the program ran, in the same form
+/// ```
+#[derive(Debug, Default)]
+pub struct Spec {
+    cases: Vec<TestCase>
+}
+
+impl Spec {
+    pub fn parse(filename: &str, text: &str) -> Result<Self, Diagnostic> {
+        let mut spec = Self::default();
+
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let span = Span::new(filename, lineno + 1, 1);
+            let (cmd, args) = line.split_once(char::is_whitespace).map_or((line, ""), |(cmd, args)| (cmd, args.trim()));
+
+            if cmd == "test" && !args.is_empty() {
+                spec.cases.push(TestCase{name: args.to_string(), span, program: None, events: vec![], expected: vec![]});
+                continue;
+            }
+
+            let Some(case) = spec.cases.last_mut() else {
+                return Err(Diagnostic::at(&span, format!("Expected a test NAME before {}", cmd)));
+            };
+
+            match cmd {
+                "program" if !args.is_empty() => case.program = Some(args.to_string()),
+                "close" if !args.is_empty() => case.events.push(Event::Close(args.to_string())),
+
+                "input" | "expect" => {
+                    let (stream, durations) = args.split_once(':').ok_or_else(|| Diagnostic::at(&span, format!("Expected {} STREAM: DURATIONS", cmd)))?;
+                    let durations = parse_durations(durations).map_err(|err| Diagnostic::at(&span, err))?;
+                    let stream = stream.trim().to_string();
+
+                    match cmd {
+                        "input" => case.events.extend(durations.into_iter().map(|duration| Event::Feed{gateway: stream.clone(), values: duration.values, moment: duration.moment})),
+                        _ => case.expected.push((stream, durations))
+                    }
+                },
+
+                _ => return Err(Diagnostic::at(&span, format!("Unknown test statement: {}", line)))
+            }
+        }
+
+        Ok(spec)
+    }
+
+    pub fn cases(&self) -> &[TestCase] {
+        &self.cases
+    }
+
+    /// Runs every test on the programs of `source`, built once per test by the REPL's interpreter
+    pub fn run(&self, filename: &str, source: &str) -> Result<Vec<TestResult>, Diagnostic> {
+        let mut parser = Parser::new(filename);
+        parser.set_codegen(false);
+        parser.parse_str(source)?;
+
+        let mut repl = Repl::new();
+        repl.define(source).map_err(|err| Diagnostic::new(filename, err))?;
+
+        Ok(self.cases.iter().map(|case| TestResult{
+            name: case.name.clone(),
+            span: case.span.clone(),
+            failure: run_case(&mut repl, &parser, case).err()
+        }).collect())
+    }
+}
+
+fn run_case(repl: &mut Repl, parser: &Parser, case: &TestCase) -> Result<(), String> {
+    let mut events = case.events.clone();
+    events.push(Event::Run);
+
+    let exits = repl.run_events(case.program.as_deref(), events)?;
+    let mut programs = parser.definitions().filter_map(|state| state.as_program());
+    let program = match case.program.as_ref() {
+        Some(name) => programs.find(|prog| prog.name() == name),
+        None => programs.filter(|prog| !prog.is_generic()).last()
+    };
+
+    let mut differences = vec![];
+    for (exit, expected) in case.expected.iter() {
+        let Some((_, items)) = exits.iter().find(|(name, _)| name == exit) else {
+            differences.push(format!("exit {} does not exist", exit));
+            continue;
+        };
+
+        let alphabet = program.and_then(|prog| prog.exit_types().into_iter().find(|(name, _, _)| name == exit)).map(|(_, alphabet, _)| alphabet);
+        let actual = exit_durations(parser, alphabet, items)?;
+        if actual != *expected {
+            differences.push(format!("exit {}\n  expected: {}\n  actual:   {}", exit, format_durations(expected), format_durations(&actual)));
+        }
+    }
+
+    match differences.is_empty() {
+        true => Ok(()),
+        false => Err(differences.join("\n"))
+    }
+}
+
+/// The durations on an exit, from the names of its characters and `@MOMENT`s - characters are told apart by their
+/// values in the exit's alphabet
+pub(crate) fn exit_durations(parser: &Parser, alphabet: Option<&str>, items: &[String]) -> Result<Vec<Duration>, String> {
+    let values: BTreeMap<String, String> = alphabet.map_or(vec![], |alphabet| alphabet_chars(parser, alphabet)).into_iter()
+        .map(|(name, value)| (name.to_case(Case::Pascal), value)).collect();
+
+    let mut durations = vec![];
+    let mut current = vec![];
+
+    for item in items.iter() {
+        if let Some(moment) = item.strip_prefix('@') {
+            let moment = parse_moment(moment).ok_or_else(|| format!("Not a moment: {}", moment))?;
+            durations.push(Duration{moment: Some(moment), values: std::mem::take(&mut current)});
+            continue;
+        }
+
+        let value = match item.strip_prefix("invalid(").and_then(|value| value.strip_suffix(')')) {
+            Some(value) => value,
+            None => values.get(item).map(|value| value.as_str()).ok_or_else(|| format!("No character of the exit's alphabet is named {}", item))?
+        };
+
+        current.push(parse_moment(value).and_then(|value| u8::try_from(value).ok()).ok_or_else(|| {
+            format!("Character {} ({}) can't be compared - only byte values can", item, value)
+        })?);
+    }
+
+    if !current.is_empty() {
+        durations.push(Duration{moment: None, values: current});
+    }
+
+    Ok(durations)
+}
+
+/// The characters an alphabet defines - as `(name, value)`
+pub(crate) fn alphabet_chars(parser: &Parser, alphabet: &str) -> Vec<(String, String)> {
+    parser.definitions().filter(|state| state.kind() == "alphabet" && state.name() == alphabet).flat_map(|state| state.statements())
+        .filter(|(cmd, _)| cmd == "def_char").map(|(_, args)| (args[1].clone(), args[0].clone())).collect()
+}
+
+/// `(MOMENT, "CHARS")` or `"CHARS"` (for a duration left open) - optionally separated by commas
+fn parse_durations(text: &str) -> Result<Vec<Duration>, String> {
+    let mut durations = vec![];
+    let mut rest = text.trim_start();
+
+    while let Some(chr) = rest.chars().next() {
+        match chr {
+            ',' => {
+                rest = rest[1..].trim_start();
+                continue;
+            },
+
+            '"' => {
+                let (values, after) = parse_quoted(&rest[1..])?;
+                durations.push(Duration{moment: None, values});
+                rest = after.trim_start();
+            },
+
+            '(' => {
+                let end = rest.find([',', ')']).ok_or_else(|| format!("Never closed: {}", rest))?;
+                let moment = parse_moment(rest[1..end].trim()).ok_or_else(|| format!("Not a moment: {}", rest[1..end].trim()))?;
+
+                // `(MOMENT)` closes a duration without characters
+                let (values, after) = match rest[end..].strip_prefix(')') {
+                    Some(after) => (vec![], after),
+                    None => {
+                        let quoted = rest[end + 1..].trim_start().strip_prefix('"').ok_or_else(|| format!("Expected \"CHARS\" after moment {}", moment))?;
+                        let (values, after) = parse_quoted(quoted)?;
+                        (values, after.trim_start().strip_prefix(')').ok_or_else(|| format!("Expected ) after the characters of moment {}", moment))?)
+                    }
+                };
+
+                durations.push(Duration{moment: Some(moment), values});
+                rest = after.trim_start();
+            },
+
+            _ => return Err(format!("Expected (MOMENT, \"CHARS\") or \"CHARS\", found: {}", rest))
+        }
+    }
+
+    Ok(durations)
+}
+
+/// Durations written the way specs are
+pub(crate) fn format_durations(durations: &[Duration]) -> String {
+    let text = |values: &[u8]| values.iter().map(|value| match *value {
+        b'"' => "\\\"".to_string(),
+        b'\\' => "\\\\".to_string(),
+        b'\n' => "\\n".to_string(),
+        b'\t' => "\\t".to_string(),
+        value if value.is_ascii_graphic() || value == b' ' => (value as char).to_string(),
+        value => format!("\\x{:02x}", value)
+    }).collect::<String>();
+
+    let durations: Vec<String> = durations.iter().map(|duration| match duration.moment {
+        Some(moment) => format!("({}, \"{}\")", moment, text(&duration.values)),
+        None => format!("\"{}\"", text(&duration.values))
+    }).collect();
+
+    match durations.is_empty() {
+        true => "nothing".to_string(),
+        false => durations.join(" ")
+    }
+}
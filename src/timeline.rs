@@ -0,0 +1,215 @@
+use serde_json::Value;
+use crate::{Diagnostic, Span};
+
+/// Sizes of the rendering, in pixels - characters are drawn in a 12px monospace font
+const LABEL_WIDTH: usize = 180;
+const HEADER_HEIGHT: usize = 28;
+const LANE_HEIGHT: usize = 32;
+const LETTER_WIDTH: usize = 7;
+const CHAR_PADDING: usize = 4;
+const MOMENT_GAP: usize = 28;
+
+/// What a stream held - in the order it was pushed onto it
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Char(String),
+
+    /// A value of the alphabet's representation that is none of its characters
+    Invalid(String),
+    Moment(String)
+}
+
+/// A stream of a program, as traced - what was pushed onto it, and what was popped off of it
+#[derive(Debug)]
+struct Lane {
+    program: String,
+    stream: String,
+    pushed: Vec<(u64, Item)>,
+    popped: Vec<(u64, Item)>
+}
+
+/// The items of a lane between two moments - drawn from the column right after the moment before them, and closed by
+/// the moment at `boundary` (an index into the timeline's moments) or still open at the end of the trace
+struct Duration<'a> {
+    column: usize,
+    boundary: Option<usize>,
+    chars: Vec<&'a (u64, Item)>
+}
+
+/// A run of traced programs, laid out on a single time axis - one lane per stream, showing its characters between
+/// the moment boundaries it saw. Moments line up across lanes, like the diagrams in the comments of sync2 and zip2.
+#[derive(Debug, Default)]
+pub struct Timeline {
+    lanes: Vec<Lane>,
+
+    /// Every moment of the trace, in time order (when they are all integers) or in the order they were first seen
+    moments: Vec<String>
+}
+
+impl Timeline {
+    /// Reads a trace of JSON lines, as written by programs generated with --trace - events other than pushes, moments
+    /// and pops (like jumps) are left out
+    pub fn from_trace(filename: &str, trace: &str) -> Result<Self, Diagnostic> {
+        let mut timeline = Self::default();
+
+        for (lineno, line) in trace.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let span = Span::new(filename, lineno + 1, 1);
+            let event: Value = serde_json::from_str(line).map_err(|err| Diagnostic::at(&span, format!("Invalid trace event: {}", err)))?;
+
+            let field = |name: &str| event[name].as_str().map(|value| value.to_string());
+            let (Some(program), Some(kind)) = (field("program"), field("event")) else {
+                return Err(Diagnostic::at(&span, "Invalid trace event - expected a program and an event"));
+            };
+
+            let Some(stream) = field("stream") else {
+                continue;
+            };
+
+            let item = match (field("char"), field("invalid"), field("moment")) {
+                (Some(chr), _, _) => Item::Char(chr),
+                (_, Some(invalid), _) => Item::Invalid(invalid),
+                (_, _, Some(moment)) => Item::Moment(moment),
+                _ => return Err(Diagnostic::at(&span, format!("Invalid trace event - {} holds no character or moment", kind)))
+            };
+
+            if let Item::Moment(moment) = &item {
+                if !timeline.moments.contains(moment) {
+                    timeline.moments.push(moment.clone());
+                }
+            }
+
+            let seq = event["seq"].as_u64().unwrap_or(lineno as u64);
+            let lane = match timeline.lanes.iter().position(|lane| lane.program == program && lane.stream == stream) {
+                Some(lane) => &mut timeline.lanes[lane],
+                None => {
+                    timeline.lanes.push(Lane{program, stream, pushed: vec![], popped: vec![]});
+                    timeline.lanes.last_mut().unwrap()
+                }
+            };
+
+            match kind.as_str() {
+                "push" | "moment" => lane.pushed.push((seq, item)),
+                "pop" => lane.popped.push((seq, item)),
+                _ => return Err(Diagnostic::at(&span, format!("Unknown trace event: {}", kind)))
+            }
+        }
+
+        if timeline.moments.iter().all(|moment| moment.parse::<i128>().is_ok()) {
+            timeline.moments.sort_by_key(|moment| moment.parse::<i128>().unwrap_or_default());
+        }
+
+        Ok(timeline)
+    }
+
+    /// The durations of a lane - of what was pushed onto its stream, or of what was popped off of it when nothing
+    /// traced was pushed (like gateways fed by an untraced caller)
+    fn durations<'a>(&self, lane: &'a Lane) -> Vec<Duration<'a>> {
+        let items = if lane.pushed.is_empty() { &lane.popped } else { &lane.pushed };
+        let mut durations = vec![];
+        let mut chars = vec![];
+        let mut column = 0;
+
+        for item in items.iter() {
+            match &item.1 {
+                Item::Moment(moment) => {
+                    let boundary = self.moments.iter().position(|known| known == moment);
+                    durations.push(Duration{column, boundary, chars: std::mem::take(&mut chars)});
+                    column = boundary.map_or(column, |boundary| boundary + 1);
+                },
+
+                _ => chars.push(item)
+            }
+        }
+
+        if !chars.is_empty() {
+            durations.push(Duration{column, boundary: None, chars});
+        }
+
+        durations
+    }
+
+    /// Renders the timeline as a standalone SVG image - hovering an item shows the trace event it came from
+    pub fn to_svg(&self) -> String {
+        let lanes: Vec<Vec<Duration>> = self.lanes.iter().map(|lane| self.durations(lane)).collect();
+
+        // Every column between two moments is as wide as the most characters any lane starts within it
+        let width_of = |duration: &Duration| duration.chars.iter().map(|(_, item)| item_width(item)).sum::<usize>();
+        let mut columns = vec![0; self.moments.len() + 1];
+        for duration in lanes.iter().flatten() {
+            columns[duration.column] = columns[duration.column].max(width_of(duration));
+        }
+
+        let mut starts = vec![];
+        let mut x = LABEL_WIDTH;
+        for width in columns.iter() {
+            starts.push(x);
+            x += width + MOMENT_GAP;
+        }
+
+        let (width, height) = (x, HEADER_HEIGHT + LANE_HEIGHT * self.lanes.len());
+        let mut svg = vec![
+            format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" font-family="monospace" font-size="12">"#, width, height, width, height),
+            format!(r##"<rect width="{}" height="{}" fill="#fff"/>"##, width, height)
+        ];
+
+        for (idx, moment) in self.moments.iter().enumerate() {
+            let x = starts[idx + 1] - MOMENT_GAP / 2;
+            svg.push(format!(r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#bbb" stroke-dasharray="2,3"/>"##, x, HEADER_HEIGHT - 6, x, height));
+            svg.push(format!(r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, x, HEADER_HEIGHT - 10, escape(moment)));
+        }
+
+        for (idx, (lane, durations)) in self.lanes.iter().zip(lanes.iter()).enumerate() {
+            let y = HEADER_HEIGHT + LANE_HEIGHT * idx;
+            let middle = y + LANE_HEIGHT / 2;
+
+            svg.push(format!(r##"<line x1="0" y1="{}" x2="{}" y2="{}" stroke="#eee"/>"##, y, width, y));
+            svg.push(format!(r#"<text x="6" y="{}"><tspan font-weight="bold">{}</tspan> {}</text>"#, middle + 4, escape(&lane.stream), escape(&lane.program)));
+
+            for duration in durations.iter() {
+                let mut x = starts[duration.column];
+
+                for (seq, item) in duration.chars.iter() {
+                    let (text, fill) = match item {
+                        Item::Invalid(value) => (value, "#fcc"),
+                        Item::Char(chr) | Item::Moment(chr) => (chr, "#def")
+                    };
+
+                    let item_width = item_width(item);
+                    svg.push(format!(
+                        r##"<g><title>#{} {}</title><rect x="{}" y="{}" width="{}" height="{}" rx="3" fill="{}" stroke="#89a"/><text x="{}" y="{}" text-anchor="middle">{}</text></g>"##,
+                        seq, escape(text), x + 1, middle - 10, item_width - 2, 20, fill, x + item_width / 2, middle + 4, escape(text)
+                    ));
+
+                    x += item_width;
+                }
+
+                // The moments a lane saw are solid on it - durations never closed end without one
+                if let Some(boundary) = duration.boundary {
+                    let x = starts[boundary + 1] - MOMENT_GAP / 2;
+                    svg.push(format!(r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#333" stroke-width="2"/>"##, x, y + 4, x, y + LANE_HEIGHT - 4));
+                }
+            }
+        }
+
+        svg.push("</svg>".to_string());
+        svg.join("\n") + "\n"
+    }
+
+    /// Renders the timeline as an HTML page, around its SVG image
+    pub fn to_html(&self) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>time_lang timeline</title>\n</head>\n<body style=\"margin: 0; overflow-x: auto\">\n{}</body>\n</html>\n",
+            self.to_svg()
+        )
+    }
+}
+
+fn item_width(item: &Item) -> usize {
+    match item {
+        Item::Char(text) | Item::Invalid(text) | Item::Moment(text) => text.chars().count() * LETTER_WIDTH + 2 * CHAR_PADDING + 2
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
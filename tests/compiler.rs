@@ -0,0 +1,62 @@
+//! Batch compilation yields an artifact per definition - its generated code, and its IR in the versioned JSON form
+//! `from-ir` reads. Sources that don't check out generate nothing.
+
+use parser::{Compiler, Ir, Parser, IR_VERSION};
+
+const ALPHABETS: &str = "
+defalphabet ABC;
+set_char_type u8;
+def_char 0x41,A;
+";
+
+const PROGRAMS: &str = "
+defclock Counter;
+set_moment_type u32;
+set_clock_repr QUANTITY;
+
+defprogram forward;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+
+label main;
+forward_duration G,E;
+";
+
+#[test]
+fn artifacts_carry_json_ir() {
+    let artifacts = Compiler::compile_all(&[("alphabets.tl", ALPHABETS), ("programs.tl", PROGRAMS)], false)
+        .unwrap_or_else(|diagnostics| panic!("{}", diagnostics));
+
+    let names: Vec<&str> = artifacts.definitions.keys().map(|name| name.as_str()).collect();
+    assert_eq!(names, ["ABC", "Counter", "forward"]);
+
+    let forward = &artifacts.definitions["forward"];
+    assert!(forward.code.contains("struct ProgramForward"));
+
+    let ir = Ir::from_json(&forward.ir).unwrap_or_else(|diagnostic| panic!("{}", diagnostic));
+    assert_eq!(ir.version, IR_VERSION);
+    assert_eq!(ir.definitions.len(), 1);
+    assert_eq!((ir.definitions[0].kind.as_str(), ir.definitions[0].definition.as_str()), ("program", "forward"));
+}
+
+#[test]
+fn diagnostics_point_into_their_source() {
+    let broken = format!("{}forward_duration G,NOPE;\n", PROGRAMS);
+    let diagnostics = Compiler::compile_all(&[("alphabets.tl", ALPHABETS), ("programs.tl", &broken)], false).expect_err("NOPE is undefined");
+
+    let files: Vec<&str> = diagnostics.errors.iter().filter_map(|diagnostic| diagnostic.span.as_ref()).map(|span| span.file.as_str()).collect();
+    assert_eq!(files, ["programs.tl"], "{}", diagnostics);
+}
+
+#[test]
+fn invalid_sources_are_not_generated() {
+    for (statement, undefined) in [("forward_duration G,NOPE;", "NOPE"), ("jump_if_class nowhere,G,A;", "nowhere")] {
+        let source = format!("{}{}{}\n", ALPHABETS, PROGRAMS, statement);
+        let diagnostic = Compiler::compile_str(&source).expect_err(statement);
+        assert!(diagnostic.to_string().contains(undefined), "{}", diagnostic);
+
+        let mut parser = Parser::new("program");
+        parser.parse_str(&source).unwrap_or_else(|diagnostic| panic!("{}", diagnostic));
+        assert!(parser.generate().is_err(), "{}", statement);
+    }
+}
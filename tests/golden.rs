@@ -0,0 +1,44 @@
+//! Generated code must be byte-identical across runs and machines - the bundled example's checked-in transpiled.rs is
+//! the golden file. After an intended codegen change, regenerate it with `cargo run --bin parserbin > examples/hello_world/src/transpiled.rs`
+//! (dropping the final newline println adds).
+
+// The golden file is rustfmt-formatted
+#![cfg(feature = "rustfmt")]
+
+use parser::Parser;
+
+const EXAMPLE: &str = include_str!("../examples/hello_world/src/main.rs");
+const GOLDEN: &str = include_str!("../examples/hello_world/src/transpiled.rs");
+
+/// The example embeds its source the same way parserbin does, as `static PROGRAM: &str = r#"..."#;`
+fn example_source() -> &'static str {
+    let start = EXAMPLE.find("static PROGRAM: &str = r#\"").expect("example has no PROGRAM") + "static PROGRAM: &str = r#\"".len();
+    let len = EXAMPLE[start..].find("\"#;").expect("example PROGRAM is never closed");
+    &EXAMPLE[start..start + len]
+}
+
+fn generate() -> String {
+    let mut parser = Parser::new("program");
+    parser.parse_str(example_source()).unwrap_or_else(|diagnostic| panic!("{}", diagnostic));
+    parser.generate().unwrap_or_else(|diagnostic| panic!("{}", diagnostic))
+}
+
+#[test]
+fn example_matches_golden() {
+    assert!(generate() == GOLDEN, "generated code differs from examples/hello_world/src/transpiled.rs");
+}
+
+#[test]
+fn generation_is_reproducible() {
+    assert_eq!(generate(), generate());
+}
+
+#[test]
+fn last_definition_is_generated() {
+    let mut parser = Parser::new("program");
+    parser.parse_str("set_entry hello;\ndefalphabet ABC;\nset_char_type u8;\ndef_char 0x41,A;\ndefclock Counter;\nset_moment_type u32;\nset_clock_repr QUANTITY;\ndefprogram hello;\nreg_exit E,ABC,Counter,16;\nlabel main;\npush_char A,E;")
+        .unwrap_or_else(|diagnostic| panic!("{}", diagnostic));
+
+    let code = parser.generate().unwrap_or_else(|diagnostic| panic!("{}", diagnostic));
+    assert!(code.contains("pub struct ProgramHello"), "{}", code);
+}
@@ -0,0 +1,184 @@
+//! Programs must run the same stepped one at a time (as the REPL does) and with their generated `run()` - checked on
+//! random inputs by the proptest Harness. Inputs hold up to four durations per gateway, so instructions waiting on more
+//! than that (or on what never comes) are caught hanging, as neither build then finishes.
+
+#![cfg(feature = "proptest")]
+
+use parser::{Harness, Inputs};
+use proptest::test_runner::Config;
+
+const TYPES: &str = "
+defalphabet ABC;
+set_char_type u8;
+def_char 0x41,A;
+def_char 0x42,B;
+def_char 0x43,C;
+def_class AB,A|B;
+
+defclock Counter;
+set_moment_type u32;
+set_clock_repr QUANTITY;
+";
+
+fn harness(program: &str) -> Harness {
+    let source = format!("{}\n{}", TYPES, program);
+    Harness::new(&source, None).unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn check_cases(harness: &Harness) {
+    if let Err(err) = harness.run_cases(Config::with_cases(24)) {
+        panic!("{}", err);
+    }
+}
+
+#[test]
+fn stepping_matches_run() {
+    check_cases(&harness("
+defprogram keep_ab;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+
+label main;
+filter_keep G,E,AB;
+"));
+}
+
+#[test]
+fn throttle_returns_without_enough_durations() {
+    let harness = harness("
+defprogram throttled;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+
+label main;
+throttle G,E,10;
+");
+
+    // A single duration, well short of the threshold - nothing is left to drop, so running stops
+    let inputs = Inputs{gateways: vec![("G".to_string(), vec![(vec![0x41], 1)])]};
+    harness.check(&inputs).unwrap_or_else(|err| panic!("{}", err));
+    check_cases(&harness);
+}
+
+#[test]
+fn main_waits_for_the_gateways_it_reads() {
+    let harness = harness("
+defprogram earliest;
+reg_gateway A,ABC,Counter,16;
+reg_gateway B,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+reg_exit F,ABC,Counter,64;
+
+label main;
+jump_earlier from_a,A,B;
+forward_duration B,F;
+
+label from_a;
+forward_duration A,E;
+");
+
+    // Only B is fed - main jumps to reading A after its first duration, so it must not run with A empty
+    let inputs = Inputs{gateways: vec![("A".to_string(), vec![]), ("B".to_string(), vec![(vec![0x41], 1), (vec![0x42], 2)])]};
+    harness.check(&inputs).unwrap_or_else(|err| panic!("{}", err));
+    check_cases(&harness);
+}
+
+#[test]
+fn window_merges_partial_windows_over_runs() {
+    let harness = harness("
+defprogram windowed;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+
+label main;
+jump_if_class merge,G,AB;
+forward_duration G,E;
+
+label merge;
+window G,E,3;
+");
+
+    // Two durations are less than a window - they are merged as far as they go, and running stops
+    let inputs = Inputs{gateways: vec![("G".to_string(), vec![(vec![0x41], 1), (vec![0x43], 2)])]};
+    harness.check(&inputs).unwrap_or_else(|err| panic!("{}", err));
+    check_cases(&harness);
+}
+
+#[test]
+fn split_round_robin() {
+    check_cases(&harness("
+defprogram dealt;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+reg_exit F,ABC,Counter,64;
+reg_exit H,ABC,Counter,64;
+
+label main;
+split G,E|F|H,round_robin;
+"));
+}
+
+#[test]
+fn delay_shifts_moments() {
+    check_cases(&harness("
+defprogram delayed;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+
+label main;
+delay G,E,5;
+"));
+}
+
+#[test]
+fn pipelines_run_their_connected_programs() {
+    let harness = harness("
+defprogram keep_ab;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,16;
+
+label main;
+filter_keep G,E,AB;
+
+defprogram outer;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+
+connect keep_ab(G),KEPT;
+reg_exit_gateway KEPT(E),K;
+
+label main;
+forward_duration K,E;
+");
+
+    // The connected program runs on what is fed to the pipeline, and the pipeline on what it pushes
+    let inputs = Inputs{gateways: vec![("G".to_string(), vec![(vec![0x41, 0x43], 1), (vec![0x43], 2)])]};
+    harness.check(&inputs).unwrap_or_else(|err| panic!("{}", err));
+    check_cases(&harness);
+}
+
+#[test]
+fn macros_expand_into_running_programs() {
+    check_cases(&harness("
+defmacro forward_ab(GATEWAY, EXIT) {
+  jump_if_class ab,$GATEWAY,AB;
+  drop_duration $GATEWAY;
+
+  label ab;
+  forward_duration $GATEWAY,$EXIT;
+}
+
+defprogram expanded;
+reg_gateway G,ABC,Counter,16;
+reg_gateway H,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+reg_exit F,ABC,Counter,64;
+
+label main;
+jump_earlier from_h,H,G;
+forward_ab G,E;
+
+label from_h;
+forward_ab H,F;
+"));
+}
@@ -0,0 +1,93 @@
+//! With `-O`, labels that never run aren't generated, consecutive characters pushed onto an exit are pushed in one
+//! go and repeated moments are pushed once - without it, every label and push is generated as written. With `-O inline-connect`, connected programs are
+//! spliced into the programs connecting to them.
+
+use parser::Parser;
+
+const SOURCE: &str = "
+defalphabet ABC;
+set_char_type u8;
+def_char 0x41,A;
+def_char 0x42,B;
+
+defclock Counter;
+set_moment_type u32;
+set_clock_repr QUANTITY;
+
+defprogram greet;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+push_char A,E;
+push_val 0x42,E;
+push_moment 3,E;
+push_moment 3,E;
+
+label main;
+jump_earlier never,G,G;
+forward_duration G,E;
+
+label never;
+push_char A,E;
+";
+
+const CONNECTED: &str = "
+defalphabet ABC;
+set_char_type u8;
+def_char 0x41,A;
+
+defclock Counter;
+set_moment_type u32;
+set_clock_repr QUANTITY;
+
+defprogram pass<A: alphabet, C: clock>;
+reg_gateway G,$A,$C,16;
+reg_exit E,$A,$C,16;
+
+label main;
+forward_duration G,E;
+
+defprogram outer;
+reg_gateway G,ABC,Counter,16;
+reg_exit E,ABC,Counter,64;
+
+connect pass(G),PASSED;
+reg_exit_gateway PASSED(E),P;
+
+label main;
+forward_duration P,E;
+";
+
+fn generate_with(source: &str, optimize: bool, inline_connect: bool) -> String {
+    let mut parser = Parser::new("program");
+    parser.set_format(false);
+    parser.set_optimize(optimize);
+    parser.set_inline_connect(inline_connect);
+    parser.parse_str(source).unwrap_or_else(|diagnostic| panic!("{}", diagnostic));
+    parser.generate().unwrap_or_else(|diagnostic| panic!("{}", diagnostic))
+}
+
+#[test]
+fn optimizing_drops_dead_labels_and_batches_pushes() {
+    let plain = generate_with(SOURCE, false, false);
+    assert!(plain.contains("fn label_never"));
+    assert!(!plain.contains("exit_e . push_vals"));
+    assert_eq!(plain.matches("exit_e . push_moment (3)").count(), 2, "{}", plain);
+
+    let optimized = generate_with(SOURCE, true, false);
+    assert!(!optimized.contains("fn label_never"), "{}", optimized);
+    assert!(optimized.contains("fn label_main"));
+    assert!(optimized.contains("exit_e . push_vals"), "{}", optimized);
+    assert_eq!(optimized.matches("exit_e . push_moment (3)").count(), 1, "{}", optimized);
+}
+
+#[test]
+fn inlining_splices_connected_programs() {
+    let plain = generate_with(CONNECTED, true, false);
+    assert!(plain.contains("struct PipelineOuter"));
+
+    // The connected program's exit is the gateway reading it - nothing sits in between
+    let inlined = generate_with(CONNECTED, true, true);
+    assert!(!inlined.contains("struct PipelineOuter"), "{}", inlined);
+    assert!(inlined.contains("fn label_passed_main"), "{}", inlined);
+    assert!(inlined.contains("forward_chars (& mut self . gateway_g , & mut self . gateway_p)"), "{}", inlined);
+}
@@ -0,0 +1,59 @@
+//! Statements wrap across lines anywhere - they only end with their `;`. Macros expand to the statements of their
+//! body, with only the `$PARAM` words of those statements substituted. filter_class is filter, given a class.
+
+use parser::{Ir, Parser};
+
+const TYPES: &str = "
+defalphabet ABC;
+set_char_type u8;
+def_char 0x41,A;
+def_char 0x48,H_UPPERCASE;
+def_class UPPERCASE,A|H_UPPERCASE;
+
+defclock Counter;
+set_moment_type u32;
+set_clock_repr QUANTITY;
+
+defprogram hello;
+reg_gateway G,ABC,Counter,16;
+reg_exit A,ABC,Counter,16;
+
+label main;
+";
+
+fn ir(statement: &str) -> Ir {
+    let mut parser = Parser::new("program");
+    parser.set_codegen(false);
+    parser.parse_str(&format!("{}{}\npush_moment 1,A;\n", TYPES, statement)).unwrap_or_else(|diagnostic| panic!("{}", diagnostic));
+    parser.to_ir()
+}
+
+#[test]
+fn statements_wrap_anywhere() {
+    let unwrapped = ir("push_char H_UPPERCASE,A;");
+
+    assert_eq!(ir("push_char\nH_UPPERCASE,A;"), unwrapped);
+    assert_eq!(ir("push_char H_UPPERCASE\n,A;"), unwrapped);
+    assert_eq!(ir("push_char\n  # the character\n  H_UPPERCASE\n  ,A\n;"), unwrapped);
+}
+
+#[test]
+fn macros_substitute_words_only() {
+    let mut parser = Parser::new("program");
+    parser.set_codegen(false);
+    let source = format!("defmacro say(CHR, EXIT) {{\n  push_char $CHR,$EXIT;\n  push_char \"$CHR;\",$EXIT;\n}}\n{}say H_UPPERCASE,A;\n", TYPES);
+
+    // The string literal is neither split at its ; nor substituted into - so it's an invalid character name
+    let diagnostic = parser.parse_str(&source).expect_err("a string literal is no character");
+    assert!(diagnostic.to_string().contains("\"$CHR;\""), "{}", diagnostic);
+}
+
+#[test]
+fn filter_class_takes_classes_only() {
+    assert_eq!(ir("filter_class G,A,UPPERCASE;"), ir("filter G,A,UPPERCASE;"));
+
+    let mut parser = Parser::new("program");
+    parser.set_codegen(false);
+    let diagnostic = parser.parse_str(&format!("{}filter_class G,A,0x41;\n", TYPES)).expect_err("a value is no class");
+    assert!(diagnostic.to_string().contains("invalid name: 0x41"), "{}", diagnostic);
+}
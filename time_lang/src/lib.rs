@@ -0,0 +1,58 @@
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, LitStr};
+
+/// Expands `.tl` source, given as a string literal, into the generated Rust - the runtime plus every alphabet, clock
+/// and program it defines. The runtime is emitted by every invocation, so use at most one per module.
+///
+/// ```ignore
+/// time_lang::program!(r#"
+/// defalphabet BINARY;
+/// set_char_type u8;
+/// ...
+/// "#);
+/// ```
+#[proc_macro]
+pub fn program(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    let source = literal.value();
+
+    let mut parser = parser::Parser::new("program!");
+    parser.set_codegen(false);
+
+    let diagnostics = match parser.parse_str(&source) {
+        Ok(()) => parser.check(),
+        Err(diagnostic) => vec![diagnostic]
+    };
+
+    if !diagnostics.is_empty() {
+        return compile_errors(&literal, &source, &diagnostics);
+    }
+
+    // rustc doesn't need the expansion formatted - skipping rustfmt keeps builds fast, and working without it installed.
+    // The runtime is inlined, so crates using the macro need no other dependency.
+    parser.set_format(false);
+    parser.set_inline_runtime(true);
+
+    match parser.into_codegen().generate() {
+        Ok(code) => code.parse().unwrap_or_else(|err| {
+            syn::Error::new(literal.span(), format!("Generated code does not parse: {}", err)).to_compile_error().into()
+        }),
+
+        Err(diagnostic) => compile_errors(&literal, &source, &[diagnostic])
+    }
+}
+
+/// The whole literal is underlined by rustc - each error's rendered diagnostic then points into it
+fn compile_errors(literal: &LitStr, source: &str, diagnostics: &[parser::Diagnostic]) -> TokenStream {
+    let mut errors = diagnostics.iter().map(|diagnostic| {
+        let rendered = diagnostic.render(source);
+        syn::Error::new(literal.span(), rendered.strip_prefix("error: ").unwrap_or(&rendered))
+    });
+
+    let mut combined = errors.next().expect("compile_errors needs at least one diagnostic");
+    for error in errors {
+        combined.combine(error);
+    }
+
+    combined.to_compile_error().into()
+}
@@ -0,0 +1,82 @@
+//! Compiles `.tl` sources from a build script, for crates that `include!` the generated code instead of checking it in.
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     if let Err(err) = time_lang_build::compile("src/streams/*.tl") {
+//!         panic!("{}", err);
+//!     }
+//! }
+//!
+//! // src/main.rs
+//! include!(concat!(env!("OUT_DIR"), "/time_lang.rs"));
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use parser::{Compiler, Diagnostics};
+
+/// The file written into `OUT_DIR`, holding the runtime plus every compiled definition
+pub const OUTPUT_FILE: &str = "time_lang.rs";
+
+#[derive(Debug)]
+pub enum Error {
+    Env(&'static str),
+    Pattern(glob::PatternError),
+    Io(PathBuf, std::io::Error),
+    Compile(Diagnostics)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Env(var) => write!(f, "{} is not set - compile is meant to be called from a build script", var),
+            Self::Pattern(err) => write!(f, "Invalid pattern: {}", err),
+            Self::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            Self::Compile(diagnostics) => write!(f, "{}", diagnostics)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Compiles every `.tl` file matching `pattern` (relative to the crate's manifest directory) into `OUT_DIR`, and has
+/// cargo rerun the build script whenever one of them - or the set of matching files - changes
+pub fn compile(pattern: &str) -> Result<PathBuf, Error> {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").map_err(|_| Error::Env("CARGO_MANIFEST_DIR"))?;
+    let out_dir = std::env::var("OUT_DIR").map_err(|_| Error::Env("OUT_DIR"))?;
+
+    let full_pattern = Path::new(&manifest_dir).join(pattern);
+    let mut paths: Vec<PathBuf> = glob::glob(&full_pattern.to_string_lossy()).map_err(Error::Pattern)?.filter_map(Result::ok).collect();
+    paths.sort();
+
+    let out_file = Path::new(&out_dir).join(OUTPUT_FILE);
+    compile_files(&paths, &out_file)?;
+
+    // Directories change when files are added to or removed from them - new matches then get picked up
+    let mut watched: Vec<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+    watched.dedup();
+    for path in watched.into_iter().chain(paths.iter().map(|path| path.as_path())) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+
+    Ok(out_file)
+}
+
+/// Compiles `paths` into a single Rust file at `out_file` - definitions are named after the file they came from in diagnostics
+pub fn compile_files(paths: &[PathBuf], out_file: &Path) -> Result<(), Error> {
+    let sources = paths.iter().map(|path| {
+        std::fs::read_to_string(path).map(|source| (path.to_string_lossy().to_string(), source)).map_err(|err| Error::Io(path.clone(), err))
+    }).collect::<Result<Vec<_>, _>>()?;
+
+    let sources: Vec<(&str, &str)> = sources.iter().map(|(name, source)| (name.as_str(), source.as_str())).collect();
+    let artifacts = Compiler::compile_all(&sources, true).map_err(Error::Compile)?;
+
+    let mut code = artifacts.runtime;
+    for artifact in artifacts.definitions.values() {
+        code.push('\n');
+        code.push_str(&artifact.code);
+    }
+
+    std::fs::write(out_file, code).map_err(|err| Error::Io(out_file.to_path_buf(), err))
+}
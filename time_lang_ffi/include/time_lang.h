@@ -0,0 +1,33 @@
+/* The C ABI of the time_lang compiler - link libtime_lang_ffi (shared or static) */
+#ifndef TIME_LANG_H
+#define TIME_LANG_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TIME_LANG_OK 0
+
+/* The source doesn't compile - the output is the diagnostic */
+#define TIME_LANG_ERROR 1
+
+/* A null pointer, or a source that isn't UTF-8 - the output is left alone */
+#define TIME_LANG_INVALID_ARGUMENT 2
+
+/* Transpiles a .tl source into self-contained Rust (unformatted, with the runtime inlined), setting *output to the
+ * code - or, when the source doesn't compile, to its diagnostic rendered against the source. *output is freed with
+ * time_lang_free. */
+int time_lang_compile(const char *source, char **output);
+
+/* The diagnostic of the last source that didn't compile on the calling thread, as a single-line JSON object - NULL
+ * when every source compiled so far. Freed with time_lang_free. */
+char *time_lang_last_error_json(void);
+
+/* Frees a string returned by the compiler - NULL is ignored */
+void time_lang_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,88 @@
+//! A C ABI for the compiler - for build systems that aren't cargo (CMake, Bazel rules, ...) to transpile `.tl` sources
+//! in-process, linking `libtime_lang_ffi` (shared or static). `include/time_lang.h` declares it:
+//!
+//! ```c
+//! char *output;
+//! if (time_lang_compile(source, &output) != TIME_LANG_OK) {
+//!     char *json = time_lang_last_error_json();
+//!     // ...
+//!     time_lang_free(json);
+//! }
+//!
+//! fputs(output, stderr);
+//! time_lang_free(output);
+//! ```
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use parser::{Compiler, Diagnostic};
+
+pub const TIME_LANG_OK: c_int = 0;
+
+/// The source doesn't compile - the output is the diagnostic
+pub const TIME_LANG_ERROR: c_int = 1;
+
+/// A null pointer, or a source that isn't UTF-8 - the output is left alone
+pub const TIME_LANG_INVALID_ARGUMENT: c_int = 2;
+
+thread_local! {
+    /// The diagnostic of the last source that didn't compile on this thread
+    static LAST_ERROR: RefCell<Option<Diagnostic>> = const { RefCell::new(None) };
+}
+
+/// A string handed over to C - freed with `time_lang_free`. Interior nul bytes can't cross, so they are dropped.
+fn to_c_string(string: String) -> *mut c_char {
+    CString::new(string).unwrap_or_else(|err| {
+        let mut bytes = err.into_vec();
+        bytes.retain(|byte| *byte != 0);
+        CString::new(bytes).unwrap()
+    }).into_raw()
+}
+
+/// Transpiles a nul-terminated `.tl` source into self-contained Rust (unformatted, with the runtime inlined), setting
+/// `*output` to the code - or, when the source doesn't compile, to its diagnostic rendered against the source.
+///
+/// # Safety
+///
+/// `source` must be null or a nul-terminated string, and `output` null or valid to write a pointer to.
+#[no_mangle]
+pub unsafe extern "C" fn time_lang_compile(source: *const c_char, output: *mut *mut c_char) -> c_int {
+    if source.is_null() || output.is_null() {
+        return TIME_LANG_INVALID_ARGUMENT;
+    }
+
+    let Ok(source) = CStr::from_ptr(source).to_str() else {
+        return TIME_LANG_INVALID_ARGUMENT;
+    };
+
+    let (status, code) = match Compiler::compile_str(source) {
+        Ok(code) => (TIME_LANG_OK, code),
+        Err(diagnostic) => {
+            let rendered = diagnostic.render(source);
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(diagnostic));
+            (TIME_LANG_ERROR, rendered)
+        }
+    };
+
+    *output = to_c_string(code);
+    status
+}
+
+/// The diagnostic of the last source that didn't compile on the calling thread, as a single-line JSON object (severity,
+/// line, col, message and note) - null when every source compiled so far. Freed with `time_lang_free`.
+#[no_mangle]
+pub extern "C" fn time_lang_last_error_json() -> *mut c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null_mut(), |diagnostic| to_c_string(diagnostic.to_json())))
+}
+
+/// Frees a string returned by the compiler - null is ignored.
+///
+/// # Safety
+///
+/// `string` must be null, or a string returned by `time_lang_compile` or `time_lang_last_error_json` that wasn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn time_lang_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
@@ -0,0 +1,84 @@
+//! Python bindings for the compiler - to generate and inspect programs from notebooks. Built with maturin (`maturin
+//! develop -m time_lang_py/Cargo.toml`), as the `time_lang` module:
+//!
+//! ```python
+//! import time_lang
+//!
+//! try:
+//!     rust = time_lang.compile(source)
+//!     ir = time_lang.parse(source)
+//! except time_lang.CompileError as err:
+//!     print(err.line, err.col, err.message)
+//! ```
+
+use parser::{Compiler, Diagnostic, Ir, Parser};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+create_exception!(time_lang, CompileError, PyException, "A source that doesn't compile - its diagnostic rendered against the source, with `message`, `line`, `col` and `note` attributes (`line` and `col` are None for problems of the whole source)");
+
+/// Raises a diagnostic as a CompileError
+fn compile_error(py: Python<'_>, diagnostic: &Diagnostic, source: &str) -> PyErr {
+    let err = CompileError::new_err(diagnostic.render(source));
+    let value = err.value(py);
+
+    let attributes = value.setattr("message", &diagnostic.message)
+        .and_then(|_| value.setattr("line", diagnostic.span.as_ref().map(|span| span.line)))
+        .and_then(|_| value.setattr("col", diagnostic.span.as_ref().map(|span| span.col)))
+        .and_then(|_| value.setattr("note", diagnostic.note.as_deref()));
+
+    attributes.err().unwrap_or(err)
+}
+
+/// The IR as plain Python values - `{"version": ..., "definitions": [{"kind", "definition", "statements": [{"cmd",
+/// "args"}]}]}`, as `--emit=ir` prints it
+fn ir_dict<'py>(py: Python<'py>, ir: &Ir) -> PyResult<Bound<'py, PyDict>> {
+    let definitions = PyList::empty(py);
+    for definition in ir.definitions.iter() {
+        let statements = PyList::empty(py);
+        for statement in definition.statements.iter() {
+            let dict = PyDict::new(py);
+            dict.set_item("cmd", &statement.cmd)?;
+            dict.set_item("args", &statement.args)?;
+            statements.append(dict)?;
+        }
+
+        let dict = PyDict::new(py);
+        dict.set_item("kind", &definition.kind)?;
+        dict.set_item("definition", &definition.definition)?;
+        dict.set_item("statements", statements)?;
+        definitions.append(dict)?;
+    }
+
+    let dict = PyDict::new(py);
+    dict.set_item("version", ir.version)?;
+    dict.set_item("definitions", definitions)?;
+    Ok(dict)
+}
+
+/// Transpiles a source into self-contained Rust (unformatted, with the runtime inlined)
+#[pyfunction]
+fn compile(py: Python<'_>, source: &str) -> PyResult<String> {
+    Compiler::compile_str(source).map_err(|diagnostic| compile_error(py, &diagnostic, source))
+}
+
+/// Parses a source into its IR - its definitions, with macros expanded and constants resolved
+#[pyfunction]
+fn parse<'py>(py: Python<'py>, source: &str) -> PyResult<Bound<'py, PyDict>> {
+    let mut parser = Parser::new("program");
+    parser.set_codegen(false);
+    parser.parse_str(source).map_err(|diagnostic| compile_error(py, &diagnostic, source))?;
+
+    ir_dict(py, &parser.to_ir())
+}
+
+#[pymodule]
+#[pyo3(name = "time_lang")]
+fn time_lang_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(compile, module)?)?;
+    module.add_function(wrap_pyfunction!(parse, module)?)?;
+    module.add("CompileError", module.py().get_type::<CompileError>())?;
+    Ok(())
+}
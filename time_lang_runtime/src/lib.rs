@@ -0,0 +1,5309 @@
+//! The shared runtime of time_lang generated code - alphabet, clock and stream traits, and the Stream type.
+//! Generated with `parserbin runtime` - regenerate it rather than editing it by hand.
+
+#![no_std]
+
+use core::default::Default;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+
+#[derive(Debug)]
+pub enum AlphabetError<CharRep: Debug> {
+    UnknownCharacter(CharRep),
+    UnexpectedError(&'static str),
+    NameNotFound(),
+}
+pub trait AlphabetLike: 'static {
+    type CharRep: Copy + Clone + Debug + Send + 'static;
+    type CharEnum: Copy + Clone + Debug + Send + 'static;
+    #[doc = r" The alphabet's name, and a hash of the characters it defines - streams exchanged between processes are"]
+    #[doc = r" checked against both"]
+    const NAME: &'static str;
+    const HASH: u64;
+    fn char_with_name(rep: &str) -> Result<Self::CharEnum, AlphabetError<&str>>;
+    fn to_char(rep: Self::CharRep) -> Result<Self::CharEnum, AlphabetError<Self::CharRep>>;
+    fn to_val(rep: Self::CharEnum) -> Self::CharRep;
+}
+
+pub enum ClockMoment<MomentRep> {
+    UnixSeconds(MomentRep),
+    UnixMilliseconds(MomentRep),
+    UnixMicroseconds(MomentRep),
+    NaturalMilliseconds(MomentRep),
+    NaturalSeconds(MomentRep),
+    NaturalMinutes(MomentRep),
+    NaturalHours(MomentRep),
+    Quantity(MomentRep),
+}
+impl<MomentRep> ClockMoment<MomentRep> {
+    pub fn into_rep(self) -> MomentRep {
+        match self {
+            ClockMoment::UnixSeconds(rep)
+            | ClockMoment::UnixMilliseconds(rep)
+            | ClockMoment::UnixMicroseconds(rep)
+            | ClockMoment::Quantity(rep) => rep,
+            ClockMoment::NaturalMilliseconds(rep)
+            | ClockMoment::NaturalSeconds(rep)
+            | ClockMoment::NaturalMinutes(rep)
+            | ClockMoment::NaturalHours(rep) => rep,
+        }
+    }
+}
+pub trait ClockLike: 'static {
+    type MomentRep: Copy + Clone + Debug + PartialOrd + Send + 'static;
+    #[doc = r" What the clock's moments represent - only clocks representing the same thing can be compared"]
+    const REPRESENTS: &'static str;
+    fn represents(&self) -> &str;
+    fn to_moment(rep: Self::MomentRep) -> ClockMoment<Self::MomentRep>;
+}
+pub trait AddableClockLike<MomentRep: core::ops::Add<Output = MomentRep>> {
+    fn add(moment: ClockMoment<MomentRep>, rep: MomentRep) -> ClockMoment<MomentRep> {
+        match moment {
+            ClockMoment::Quantity(orig_rep) => ClockMoment::Quantity(orig_rep + rep),
+            ClockMoment::UnixMilliseconds(orig_rep) => {
+                ClockMoment::UnixMilliseconds(orig_rep + rep)
+            }
+            ClockMoment::UnixMicroseconds(orig_rep) => {
+                ClockMoment::UnixMicroseconds(orig_rep + rep)
+            }
+            ClockMoment::NaturalMilliseconds(orig_rep) => {
+                ClockMoment::NaturalMilliseconds(orig_rep + rep)
+            }
+            ClockMoment::NaturalSeconds(orig_rep) => ClockMoment::NaturalSeconds(orig_rep + rep),
+            ClockMoment::NaturalMinutes(orig_rep) => ClockMoment::NaturalMinutes(orig_rep + rep),
+            ClockMoment::NaturalHours(orig_rep) => ClockMoment::NaturalHours(orig_rep + rep),
+            ClockMoment::UnixSeconds(orig_rep) => ClockMoment::UnixSeconds(orig_rep + rep),
+        }
+    }
+}
+#[doc = r" Clocks whose moments are unix timestamps - generated for clocks representing `UNIX_SECONDS`,"]
+#[doc = r" `UNIX_MILLISECONDS` or `UNIX_MICROSECONDS` (with an integer moment type)"]
+pub trait UnixClockLike: ClockLike {
+    #[doc = r" How many microseconds since the unix epoch a moment stands for"]
+    const MICROS_PER_MOMENT: u64;
+}
+#[doc = r" A moment of a unix clock - converting to and from `std::time::SystemTime` with the runtime's std feature"]
+pub struct UnixMoment<Clock: UnixClockLike>(pub Clock::MomentRep);
+impl<Clock: UnixClockLike> Clone for UnixMoment<Clock> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<Clock: UnixClockLike> Copy for UnixMoment<Clock> {}
+impl<Clock: UnixClockLike> Debug for UnixMoment<Clock> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("UnixMoment").field(&self.0).finish()
+    }
+}
+impl<Clock: UnixClockLike> UnixMoment<Clock> {
+    pub const fn new(rep: Clock::MomentRep) -> Self {
+        Self(rep)
+    }
+    pub fn rep(self) -> Clock::MomentRep {
+        self.0
+    }
+}
+
+#[derive(Debug)]
+pub enum ExitError {
+    BufferFull,
+    Closed,
+    NotEncodable,
+}
+#[doc = r" Everything that can go wrong while a program runs - generated code returns these instead of panicking"]
+#[derive(Debug)]
+pub enum StreamError {
+    #[doc = r" An exit refused an item"]
+    Exit(ExitError),
+    #[doc = r" A character name or raw value that isn't part of the alphabet"]
+    UnknownChar,
+    #[doc = r" A raw value popped off of a gateway, that isn't a character of its alphabet"]
+    InvalidChar,
+    #[doc = r" The next item on a gateway wasn't the kind the instruction needed"]
+    UnexpectedItem,
+    #[doc = r" A stream's time was needed before it had seen any moment"]
+    NoMoment,
+    #[doc = r" Two clocks were compared while not representing the same thing"]
+    ClockMismatch,
+    #[doc = r" A run of a program's label chain took more than its `set_fuel` limit"]
+    OutOfFuel,
+}
+impl From<ExitError> for StreamError {
+    fn from(err: ExitError) -> Self {
+        Self::Exit(err)
+    }
+}
+pub trait ExitLike<Alphabet: AlphabetLike, Clock: ClockLike> {
+    type InternalItem;
+    type Item;
+    fn set_initial_moment(&mut self, monent: Clock::MomentRep);
+    fn accepting_pushes(&mut self) -> bool;
+    fn push(&mut self, chr: Alphabet::CharEnum) -> Result<(), ExitError>;
+    fn push_moment(&mut self, moment: Clock::MomentRep) -> Result<(), ExitError>;
+    #[doc = r" Pushes a raw value without validating it against the alphabet - used to dead-letter invalid characters"]
+    fn push_invalid(&mut self, rep: Alphabet::CharRep) -> Result<(), ExitError>;
+    #[doc = r" Pushes an item as it is buffered, popped off of a stream of the same alphabet and clock - nothing is encoded"]
+    fn push_raw(&mut self, item: Self::InternalItem) -> Result<(), ExitError>;
+    #[doc = r" Pushes raw values in one go - all of them, or none if they don't fit. Values that aren't characters of the"]
+    #[doc = r" alphabet pop out as InvalidChar."]
+    fn push_vals(&mut self, vals: &[Alphabet::CharRep]) -> Result<(), ExitError>;
+    #[doc = r" Whether a push was rejected since the last call - reading the flag clears it"]
+    fn take_overflowed(&mut self) -> bool;
+    #[doc = r" Marks the end of the stream - nothing can be pushed afterwards, but buffered items can still be popped"]
+    fn close(&mut self);
+    #[doc = r" How many more items can be pushed before a push fails - `usize::MAX` for streams never rejecting pushes"]
+    fn room(&self) -> usize;
+    #[doc = r" How many more items fit in the buffer before it is full - whatever the overflow policy, closed or not"]
+    fn remaining(&self) -> usize;
+    fn push_with_name(&mut self, chr_name: &str) -> Result<(), StreamError> {
+        let chr = Alphabet::char_with_name(chr_name).map_err(|_| StreamError::UnknownChar)?;
+        Ok(self.push(chr)?)
+    }
+}
+#[doc = r" Names of the characters an alphabet must define, to have numbers rendered in it"]
+pub const DIGIT_NAMES: [&str; 10] = [
+    "ZERO", "ONE", "TWO", "THREE", "FOUR", "FIVE", "SIX", "SEVEN", "EIGHT", "NINE",
+];
+#[doc = r" Pushes the decimal digits of `value` onto an exit, as characters of its alphabet"]
+pub fn push_decimal<Alphabet: AlphabetLike, Clock: ClockLike, Exit: ExitLike<Alphabet, Clock>>(
+    exit: &mut Exit,
+    value: u64,
+) -> Result<(), ExitError> {
+    let mut digits = [0usize; 20];
+    let mut len = 0;
+    let mut rest = value;
+    loop {
+        digits[len] = (rest % 10) as usize;
+        len += 1;
+        rest /= 10;
+        if rest == 0 {
+            break;
+        }
+    }
+    for digit in digits[..len].iter().rev() {
+        let chr =
+            Alphabet::char_with_name(DIGIT_NAMES[*digit]).map_err(|_| ExitError::NotEncodable)?;
+        exit.push(chr)?;
+    }
+    Ok(())
+}
+pub trait GatewayLike<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
+    type InternalItem;
+    type Item;
+    fn pop(&mut self) -> Self::Item;
+    fn peek(&self) -> Self::Item;
+    #[doc = r" Pops the next item as it is buffered - characters stay raw values, undecoded"]
+    fn pop_raw(&mut self) -> Self::InternalItem;
+    fn peek_raw(&self) -> Self::InternalItem;
+    fn forward_duration<Exit: ExitLike<Alphabet, Clock>>(
+        &mut self,
+        exit: &mut Exit,
+    ) -> Result<(), StreamError>;
+    fn current_moment(&self) -> Option<Clock::MomentRep>;
+    fn is_empty(&self) -> bool;
+    fn is_closed(&self) -> bool;
+    #[doc = r" How many items are buffered, characters and moments alike"]
+    fn len(&self) -> usize;
+    #[doc = r" How many items can be buffered at once - `UNBOUNDED` for streams growing for as long as there is memory"]
+    fn capacity(&self) -> usize;
+    #[doc = r" How many moments are buffered - each ends a whole duration"]
+    fn buffered_moments(&self) -> usize;
+    #[doc = r" Closed, with every buffered item popped - nothing will ever come out of the stream again"]
+    fn is_drained(&self) -> bool {
+        self.is_closed() && self.is_empty()
+    }
+    #[doc = r" A whole duration is buffered, up to the moment ending it - it can be read without running dry halfway"]
+    fn has_duration(&self) -> bool;
+    fn next_is_character(&self) -> bool;
+    fn next_is_moment(&self) -> bool;
+    #[doc = r" How many items the next buffered duration holds, the moment ending it included - `None` unless a whole"]
+    #[doc = r" duration is buffered"]
+    fn duration_len(&self) -> Option<usize>;
+    #[doc = r" Pops the characters at the front of the stream into `vals`, as raw values - up to the moment ending the"]
+    #[doc = r" duration, a value that isn't a character of the alphabet, or until `vals` is full. Returns how many it popped."]
+    fn pop_duration_into(&mut self, vals: &mut [Alphabet::CharRep]) -> usize;
+    #[doc = r" Whether `exit` has room for the whole next duration - `Fits` as well while no whole duration is buffered"]
+    fn fits_duration<Exit: ExitLike<Alphabet, Clock>>(&self, exit: &Exit) -> PushOutcome {
+        match self.duration_len() {
+            Some(len) if len > exit.room() => PushOutcome::Full {
+                needed: len - exit.room(),
+            },
+            _ => PushOutcome::Fits,
+        }
+    }
+    #[doc = r" Pops the next duration without forwarding it, up to the moment ending it - everything buffered, when not"]
+    #[doc = r" even one whole duration is"]
+    fn skip_duration(&mut self) {
+        while !self.is_empty() {
+            let moment = self.next_is_moment();
+            self.pop();
+            if moment {
+                break;
+            }
+        }
+    }
+}
+#[doc = r" Whether an exit has room for what is about to be pushed onto it"]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PushOutcome {
+    Fits,
+    #[doc = r" The exit needs room for `needed` more items first"]
+    Full {
+        needed: usize,
+    },
+}
+#[derive(Copy, Clone, Debug)]
+pub enum StreamItem<CharacterRep, Moment, InvalidRep = CharacterRep> {
+    Empty,
+    Character(CharacterRep),
+    Moment(Moment),
+    #[doc = r" A raw value popped off of a stream, which is not a character of its alphabet"]
+    InvalidChar(InvalidRep),
+}
+#[allow(clippy::derivable_impls)]
+impl<CharacterRep, Moment, InvalidRep> Default for StreamItem<CharacterRep, Moment, InvalidRep> {
+    fn default() -> Self {
+        Self::Empty
+    }
+}
+#[doc = r" What a stream does with a push once its buffer is full"]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    #[doc = r" The push fails with `ExitError::BufferFull`"]
+    Reject,
+    #[doc = r" The oldest duration is evicted to make room - for lossy sources, where fresh data matters more than old"]
+    OverwriteOldest,
+}
+#[doc = r" Whether a stream checks that moments pushed onto it never go back - and the last one pushed, if it does"]
+#[derive(Copy, Clone, Debug)]
+enum Monotonic<MomentRep> {
+    Unchecked,
+    Checked(Option<MomentRep>),
+}
+impl<MomentRep: Copy + Debug + PartialOrd> Monotonic<MomentRep> {
+    #[doc = r" Panics on a moment earlier than the last one pushed - only in debug builds, the check costing nothing"]
+    #[doc = r" otherwise"]
+    fn pushed(&mut self, moment: MomentRep) {
+        if let Monotonic::Checked(last) = self {
+            if let (true, Some(last)) = (cfg!(debug_assertions), *last) {
+                assert!(
+                    moment >= last,
+                    "moment {:?} pushed after moment {:?}",
+                    moment,
+                    last
+                );
+            }
+            *last = Some(moment);
+        }
+    }
+    #[doc = r" Checks the moments pushed from now on against `moment` - the last one buffered, once a stream is restored"]
+    fn restored(&mut self, moment: Option<MomentRep>) {
+        if let Monotonic::Checked(last) = self {
+            *last = moment;
+        }
+    }
+}
+#[doc = r" Notified of what happens on a stream it is set on - for hosts exporting metrics of throughput, buffer occupancy"]
+#[doc = r" and drops, without touching generated code. Each callback also gets how many items the stream buffers afterwards."]
+pub trait StreamObserver: Sync {
+    #[doc = r" Characters were pushed onto the stream"]
+    fn on_push(&self, _chars: usize, _buffered: usize) {}
+    #[doc = r" A moment was pushed onto the stream, ending a duration"]
+    fn on_moment(&self, _buffered: usize) {}
+    #[doc = r" Items were popped off of the stream"]
+    fn on_pop(&self, _items: usize, _buffered: usize) {}
+    #[doc = r" Items were dropped on a full stream - a push it rejected, or the oldest duration it evicted to make room"]
+    fn on_full(&self, _dropped: usize, _buffered: usize) {}
+}
+pub struct Stream<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
+    buffer: [StreamItem<Alphabet::CharRep, Clock::MomentRep>; BUFFER_SIZE],
+    idx: usize,
+    buffered_total: usize,
+    buffered_moments: usize,
+    buffered_characters: usize,
+    last_seen_moment: Option<Clock::MomentRep>,
+    policy: OverflowPolicy,
+    overflowed: bool,
+    closed: bool,
+    observer: Option<&'static dyn StreamObserver>,
+    monotonic: Monotonic<Clock::MomentRep>,
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> Default
+    for Stream<Alphabet, Clock, BUFFER_SIZE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>
+    Stream<Alphabet, Clock, BUFFER_SIZE>
+{
+    const NON_EMPTY: () = assert!(BUFFER_SIZE > 0, "Streams need a buffer size of at least 1");
+    pub const fn new() -> Self {
+        Self::with_policy(OverflowPolicy::Reject)
+    }
+    pub const fn with_policy(policy: OverflowPolicy) -> Self {
+        let () = Self::NON_EMPTY;
+        Self {
+            buffer: [StreamItem::Empty; BUFFER_SIZE],
+            idx: 0,
+            buffered_total: 0,
+            buffered_moments: 0,
+            buffered_characters: 0,
+            last_seen_moment: None,
+            policy,
+            overflowed: false,
+            closed: false,
+            observer: None,
+            monotonic: Monotonic::Unchecked,
+        }
+    }
+    #[doc = r" Has debug builds panic on any moment pushed onto the stream earlier than the one before it"]
+    pub const fn asserting_monotonic(mut self) -> Self {
+        self.monotonic = Monotonic::Checked(None);
+        self
+    }
+    #[doc = r" Notifies `observer` of every push, pop and drop on the stream from now on - or no one, once it is None"]
+    pub fn set_observer(&mut self, observer: Option<&'static dyn StreamObserver>) {
+        self.observer = observer;
+    }
+    #[doc = r" Evicts the oldest duration to make room - reported to the observer as dropped, rather than popped, so the"]
+    #[doc = r" current moment stays where it was"]
+    fn evict(&mut self) {
+        let (observer, last_seen_moment) = (self.observer.take(), self.last_seen_moment);
+        let before = self.buffered_total;
+        self.skip_duration();
+        (self.observer, self.last_seen_moment) = (observer, last_seen_moment);
+        if let Some(observer) = self.observer {
+            observer.on_full(before - self.buffered_total, self.buffered_total);
+        }
+    }
+    #[doc = r" Wraps an index into the ring buffer - power-of-two sizes take the (cheaper) masking path"]
+    const fn wrap(idx: usize) -> usize {
+        if BUFFER_SIZE.is_power_of_two() {
+            idx & (BUFFER_SIZE - 1)
+        } else {
+            idx % BUFFER_SIZE
+        }
+    }
+    fn inc_index(&mut self) {
+        self.idx = Self::wrap(self.idx + 1);
+    }
+    fn push_item(
+        &mut self,
+        item: StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+    ) -> Result<(), ExitError> {
+        if self.closed {
+            return Err(ExitError::Closed);
+        }
+        if self.buffered_total == BUFFER_SIZE && self.policy == OverflowPolicy::OverwriteOldest {
+            self.evict();
+        }
+        if self.buffered_total < BUFFER_SIZE {
+            match item {
+                StreamItem::Moment(moment) => {
+                    self.monotonic.pushed(moment);
+                    self.buffered_moments += 1
+                }
+                _ => self.buffered_characters += 1,
+            }
+            self.buffer[Self::wrap(self.idx + self.buffered_total)] = item;
+            self.buffered_total += 1;
+            debug_assert!(
+                self.len() <= self.capacity(),
+                "Stream buffered past its capacity"
+            );
+            debug_assert_eq!(
+                self.len(),
+                self.buffered_moments() + self.buffered_characters,
+                "Stream lost count of its items"
+            );
+            match (self.observer, item) {
+                (Some(observer), StreamItem::Moment(_)) => observer.on_moment(self.buffered_total),
+                (Some(observer), _) => observer.on_push(1, self.buffered_total),
+                (None, _) => (),
+            }
+            Ok(())
+        } else {
+            self.overflowed = true;
+            if let Some(observer) = self.observer {
+                observer.on_full(1, self.buffered_total);
+            }
+            Err(ExitError::BufferFull)
+        }
+    }
+    #[doc = r" The moments currently buffered in the stream, oldest first - without popping them"]
+    pub fn moments(&self) -> impl Iterator<Item = Clock::MomentRep> + '_ {
+        (0..self.buffered_total).filter_map(move |offset| {
+            match self.buffer[Self::wrap(self.idx + offset)] {
+                StreamItem::Moment(moment) => Some(moment),
+                _ => None,
+            }
+        })
+    }
+    #[doc = r" Pops every item buffered in the stream, oldest first"]
+    pub fn drain(&mut self) -> Drain<'_, Alphabet, Clock, BUFFER_SIZE> {
+        Drain::new(self)
+    }
+    #[doc = r" The whole state of the stream - what it buffers, the last moment popped off of it, and its flags"]
+    pub fn snapshot(&self) -> StreamSnapshot<Alphabet, Clock, BUFFER_SIZE> {
+        let mut items = [StreamItem::Empty; BUFFER_SIZE];
+        for (offset, item) in items.iter_mut().enumerate().take(self.buffered_total) {
+            *item = self.buffer[Self::wrap(self.idx + offset)];
+        }
+        StreamSnapshot {
+            items,
+            len: self.buffered_total,
+            last_seen_moment: self.last_seen_moment,
+            policy: self.policy,
+            overflowed: self.overflowed,
+            closed: self.closed,
+        }
+    }
+    #[doc = r" Puts the stream back in the state of a snapshot - whatever it buffered until now is dropped"]
+    pub fn restore(&mut self, snapshot: &StreamSnapshot<Alphabet, Clock, BUFFER_SIZE>) {
+        let buffered_moments = snapshot
+            .items()
+            .iter()
+            .filter(|item| matches!(item, StreamItem::Moment(_)))
+            .count();
+        self.buffer = snapshot.items;
+        self.idx = 0;
+        self.buffered_total = snapshot.len;
+        self.buffered_moments = buffered_moments;
+        self.buffered_characters = snapshot.len - buffered_moments;
+        self.last_seen_moment = snapshot.last_seen_moment;
+        self.policy = snapshot.policy;
+        self.overflowed = snapshot.overflowed;
+        self.closed = snapshot.closed;
+        self.monotonic.restored(self.moments().last());
+    }
+}
+#[doc = r" The state of a Stream at one point in time - to persist a paused pipeline and resume it later, or to start"]
+#[doc = r" a test mid-stream"]
+#[derive(Copy, Clone, Debug)]
+pub struct StreamSnapshot<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
+    items: [StreamItem<Alphabet::CharRep, Clock::MomentRep>; BUFFER_SIZE],
+    len: usize,
+    last_seen_moment: Option<Clock::MomentRep>,
+    policy: OverflowPolicy,
+    overflowed: bool,
+    closed: bool,
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>
+    StreamSnapshot<Alphabet, Clock, BUFFER_SIZE>
+{
+    #[doc = r" The items buffered in the stream, oldest first - characters as raw values"]
+    pub fn items(&self) -> &[StreamItem<Alphabet::CharRep, Clock::MomentRep>] {
+        &self.items[..self.len]
+    }
+    #[doc = r" The last moment popped off of the stream"]
+    pub fn current_moment(&self) -> Option<Clock::MomentRep> {
+        self.last_seen_moment
+    }
+    pub fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+#[doc = r" Decodes a buffered item - raw values that aren't characters of the alphabet become InvalidChar"]
+fn decode<Alphabet: AlphabetLike, Clock: ClockLike>(
+    item: StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+) -> StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep> {
+    match item {
+        StreamItem::Character(chr) => match Alphabet::to_char(chr) {
+            Ok(chr) => StreamItem::Character(chr),
+            Err(_) => StreamItem::InvalidChar(chr),
+        },
+        StreamItem::Moment(moment) => StreamItem::Moment(moment),
+        StreamItem::Empty | StreamItem::InvalidChar(_) => StreamItem::Empty,
+    }
+}
+#[doc = r" Forwards the rest of a duration between streams of the same alphabet and clock, moving buffered items across as"]
+#[doc = r" they are - nothing is re-encoded. Stops at a value that isn't a character of the alphabet (left on the gateway,"]
+#[doc = r" for the caller to handle) or once the gateway runs dry - returns whether the moment ending the duration was forwarded."]
+pub fn forward_raw<Alphabet, Clock, Gateway, Exit, const SIZE: usize>(
+    gateway: &mut Gateway,
+    exit: &mut Exit,
+) -> Result<bool, StreamError>
+where
+    Alphabet: AlphabetLike,
+    Clock: ClockLike,
+    Gateway: GatewayLike<
+        Alphabet,
+        Clock,
+        SIZE,
+        InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+    >,
+    Exit: ExitLike<Alphabet, Clock, InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>>,
+{
+    loop {
+        match gateway.peek_raw() {
+            StreamItem::Character(chr) if Alphabet::to_char(chr).is_err() => return Ok(false),
+            StreamItem::Empty | StreamItem::InvalidChar(_) => return Ok(false),
+            item => {
+                exit.push_raw(item)?;
+                gateway.pop_raw();
+                if let StreamItem::Moment(_) = item {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+}
+#[doc = r" Forwards the rest of a duration between streams of the same alphabet, a character at a time - up to the moment"]
+#[doc = r" ending it, forwarded as well. Returns the item it stopped at: that moment, or whatever it couldn't forward (the"]
+#[doc = r" gateway running dry, or a value that isn't a character of the alphabet), for the caller to handle."]
+pub fn forward_chars<Alphabet, Clock, ExitClock, Gateway, Exit, const SIZE: usize>(
+    gateway: &mut Gateway,
+    exit: &mut Exit,
+) -> Result<Gateway::Item, StreamError>
+where
+    Alphabet: AlphabetLike,
+    Clock: ClockLike,
+    ExitClock: ClockLike<MomentRep = Clock::MomentRep>,
+    Gateway: GatewayLike<
+        Alphabet,
+        Clock,
+        SIZE,
+        Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>,
+    >,
+    Exit: ExitLike<Alphabet, ExitClock>,
+{
+    loop {
+        match gateway.pop() {
+            StreamItem::Character(chr) => exit.push(chr)?,
+            StreamItem::Moment(moment) => {
+                exit.push_moment(moment)?;
+                return Ok(StreamItem::Moment(moment));
+            }
+            item => return Ok(item),
+        }
+    }
+}
+#[doc = r" Forwards the moment at the front of a gateway - failing with `StreamError::UnexpectedItem` if anything else is"]
+pub fn forward_moment<Alphabet, Clock, ExitClock, Gateway, Exit, const SIZE: usize>(
+    gateway: &mut Gateway,
+    exit: &mut Exit,
+) -> Result<(), StreamError>
+where
+    Alphabet: AlphabetLike,
+    Clock: ClockLike,
+    ExitClock: ClockLike<MomentRep = Clock::MomentRep>,
+    Gateway: GatewayLike<
+        Alphabet,
+        Clock,
+        SIZE,
+        Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>,
+    >,
+    Exit: ExitLike<Alphabet, ExitClock>,
+{
+    if !gateway.next_is_moment() {
+        return Err(StreamError::UnexpectedItem);
+    }
+    match gateway.pop() {
+        StreamItem::Moment(moment) => Ok(exit.push_moment(moment)?),
+        _ => Err(StreamError::UnexpectedItem),
+    }
+}
+#[doc = r" A stream, whichever way it buffers its items - what hosts and executors move data in and out of programs with"]
+pub trait StreamLike<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>:
+    ExitLike<Alphabet, Clock>
+    + GatewayLike<
+        Alphabet,
+        Clock,
+        BUFFER_SIZE,
+        Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>,
+    >
+{
+    #[doc = r" The moments currently buffered in the stream, oldest first - without popping them"]
+    fn moments(&self) -> impl Iterator<Item = Clock::MomentRep> + '_;
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>
+    StreamLike<Alphabet, Clock, BUFFER_SIZE> for Stream<Alphabet, Clock, BUFFER_SIZE>
+{
+    fn moments(&self) -> impl Iterator<Item = Clock::MomentRep> + '_ {
+        Stream::moments(self)
+    }
+}
+pub struct Drain < 's , Alphabet : AlphabetLike , Clock : ClockLike , const BUFFER_SIZE : usize , Buffer : GatewayLike < Alphabet , Clock , BUFFER_SIZE , Item = StreamItem < Alphabet :: CharEnum , Clock :: MomentRep , Alphabet :: CharRep >> = Stream < Alphabet , Clock , BUFFER_SIZE >> { stream : & 's mut Buffer , types : PhantomData < (Alphabet , Clock) > }
+impl<
+        's,
+        Alphabet: AlphabetLike,
+        Clock: ClockLike,
+        const BUFFER_SIZE: usize,
+        Buffer: GatewayLike<
+            Alphabet,
+            Clock,
+            BUFFER_SIZE,
+            Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>,
+        >,
+    > Drain<'s, Alphabet, Clock, BUFFER_SIZE, Buffer>
+{
+    pub fn new(stream: &'s mut Buffer) -> Self {
+        Self {
+            stream,
+            types: PhantomData,
+        }
+    }
+}
+impl<
+        Alphabet: AlphabetLike,
+        Clock: ClockLike,
+        const BUFFER_SIZE: usize,
+        Buffer: GatewayLike<
+            Alphabet,
+            Clock,
+            BUFFER_SIZE,
+            Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>,
+        >,
+    > Iterator for Drain<'_, Alphabet, Clock, BUFFER_SIZE, Buffer>
+{
+    type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stream.is_empty() {
+            None
+        } else {
+            Some(self.stream.pop())
+        }
+    }
+}
+impl<'s, Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> IntoIterator
+    for &'s mut Stream<Alphabet, Clock, BUFFER_SIZE>
+{
+    type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+    type IntoIter = Drain<'s, Alphabet, Clock, BUFFER_SIZE>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.drain()
+    }
+}
+#[derive(Debug)]
+pub enum ExpectationError<MomentRep> {
+    MissingMoment {
+        stream: &'static str,
+        other: &'static str,
+        moment: MomentRep,
+    },
+}
+#[doc = r" Walks the buffered moments of both streams in lockstep, checking every moment of `a` also appears on `b`"]
+pub fn expect_aligned<AlphabetA, AlphabetB, Clock, A, B, const SIZE_A: usize, const SIZE_B: usize>(
+    a: (&'static str, &A),
+    b: (&'static str, &B),
+) -> Result<(), ExpectationError<Clock::MomentRep>>
+where
+    AlphabetA: AlphabetLike,
+    AlphabetB: AlphabetLike,
+    Clock: ClockLike,
+    A: StreamLike<AlphabetA, Clock, SIZE_A>,
+    B: StreamLike<AlphabetB, Clock, SIZE_B>,
+{
+    let mut moments_b = b.1.moments().peekable();
+    for moment in a.1.moments() {
+        while moments_b.next_if(|other| *other < moment).is_some() {}
+        match moments_b.peek() {
+            Some(other) if *other == moment => (),
+            _ => {
+                return Err(ExpectationError::MissingMoment {
+                    stream: a.0,
+                    other: b.0,
+                    moment,
+                })
+            }
+        }
+    }
+    Ok(())
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> ExitLike<Alphabet, Clock>
+    for Stream<Alphabet, Clock, BUFFER_SIZE>
+{
+    type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
+    type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+    fn set_initial_moment(&mut self, moment: Clock::MomentRep) {
+        self.last_seen_moment = Some(moment);
+    }
+    fn accepting_pushes(&mut self) -> bool {
+        !self.closed
+            && (self.buffered_total < BUFFER_SIZE || self.policy == OverflowPolicy::OverwriteOldest)
+    }
+    fn push(&mut self, chr: Alphabet::CharEnum) -> Result<(), ExitError> {
+        self.push_item(Self::InternalItem::Character(Alphabet::to_val(chr)))
+    }
+    fn push_moment(&mut self, moment: Clock::MomentRep) -> Result<(), ExitError> {
+        self.push_item(Self::InternalItem::Moment(moment))
+    }
+    fn push_invalid(&mut self, rep: Alphabet::CharRep) -> Result<(), ExitError> {
+        self.push_item(Self::InternalItem::Character(rep))
+    }
+    fn push_raw(&mut self, item: Self::InternalItem) -> Result<(), ExitError> {
+        match item {
+            Self::InternalItem::Empty => Ok(()),
+            Self::InternalItem::InvalidChar(rep) => self.push_invalid(rep),
+            item => self.push_item(item),
+        }
+    }
+    fn push_vals(&mut self, vals: &[Alphabet::CharRep]) -> Result<(), ExitError> {
+        if self.closed {
+            return Err(ExitError::Closed);
+        } else if self.policy == OverflowPolicy::OverwriteOldest {
+            return vals
+                .iter()
+                .try_for_each(|val| self.push_item(Self::InternalItem::Character(*val)));
+        } else if vals.len() > BUFFER_SIZE - self.buffered_total {
+            self.overflowed = true;
+            if let Some(observer) = self.observer {
+                observer.on_full(vals.len(), self.buffered_total);
+            }
+            return Err(ExitError::BufferFull);
+        }
+        let start = Self::wrap(self.idx + self.buffered_total);
+        let (to_end, from_start) = vals.split_at(vals.len().min(BUFFER_SIZE - start));
+        let (front, back) = self.buffer.split_at_mut(start);
+        for (slot, val) in back
+            .iter_mut()
+            .zip(to_end)
+            .chain(front.iter_mut().zip(from_start))
+        {
+            *slot = Self::InternalItem::Character(*val);
+        }
+        self.buffered_characters += vals.len();
+        self.buffered_total += vals.len();
+        if let (Some(observer), false) = (self.observer, vals.is_empty()) {
+            observer.on_push(vals.len(), self.buffered_total);
+        }
+        Ok(())
+    }
+    fn take_overflowed(&mut self) -> bool {
+        core::mem::take(&mut self.overflowed)
+    }
+    fn close(&mut self) {
+        self.closed = true;
+    }
+    fn room(&self) -> usize {
+        match (self.closed, self.policy) {
+            (true, _) => 0,
+            (false, OverflowPolicy::OverwriteOldest) => usize::MAX,
+            (false, OverflowPolicy::Reject) => self.remaining(),
+        }
+    }
+    fn remaining(&self) -> usize {
+        BUFFER_SIZE - self.buffered_total
+    }
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>
+    GatewayLike<Alphabet, Clock, BUFFER_SIZE> for Stream<Alphabet, Clock, BUFFER_SIZE>
+{
+    type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
+    type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+    fn pop(&mut self) -> Self::Item {
+        decode::<Alphabet, Clock>(self.pop_raw())
+    }
+    fn peek(&self) -> Self::Item {
+        decode::<Alphabet, Clock>(self.peek_raw())
+    }
+    fn pop_raw(&mut self) -> Self::InternalItem {
+        let last = core::mem::take(&mut self.buffer[self.idx]);
+        match last {
+            Self::InternalItem::Character(_) => {
+                self.inc_index();
+                self.buffered_characters -= 1;
+                self.buffered_total -= 1;
+            }
+            Self::InternalItem::Moment(moment) => {
+                self.inc_index();
+                self.buffered_moments -= 1;
+                self.buffered_total -= 1;
+                self.last_seen_moment = Some(moment);
+            }
+            Self::InternalItem::Empty | Self::InternalItem::InvalidChar(_) => return last,
+        }
+        if let Some(observer) = self.observer {
+            observer.on_pop(1, self.buffered_total);
+        }
+        last
+    }
+    fn peek_raw(&self) -> Self::InternalItem {
+        self.buffer[self.idx]
+    }
+    fn forward_duration<Exit: ExitLike<Alphabet, Clock>>(
+        &mut self,
+        exit: &mut Exit,
+    ) -> Result<(), StreamError> {
+        while self.next_is_character() {
+            match self.pop() {
+                Self::Item::Character(chr) => exit.push(chr)?,
+                Self::Item::InvalidChar(_) => return Err(StreamError::InvalidChar),
+                _ => return Err(StreamError::UnexpectedItem),
+            }
+        }
+        Ok(())
+    }
+    fn current_moment(&self) -> Option<Clock::MomentRep> {
+        self.last_seen_moment
+    }
+    fn is_empty(&self) -> bool {
+        self.buffered_total == 0
+    }
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+    fn len(&self) -> usize {
+        self.buffered_total
+    }
+    fn capacity(&self) -> usize {
+        BUFFER_SIZE
+    }
+    fn buffered_moments(&self) -> usize {
+        self.buffered_moments
+    }
+    fn has_duration(&self) -> bool {
+        self.buffered_moments > 0
+    }
+    fn next_is_character(&self) -> bool {
+        matches!(self.buffer[self.idx], Self::InternalItem::Character(_))
+    }
+    fn next_is_moment(&self) -> bool {
+        matches!(self.buffer[self.idx], Self::InternalItem::Moment(_))
+    }
+    fn duration_len(&self) -> Option<usize> {
+        if self.buffered_moments == 0 {
+            return None;
+        }
+        (0..self.buffered_total)
+            .find(|offset| {
+                matches!(
+                    self.buffer[Self::wrap(self.idx + offset)],
+                    Self::InternalItem::Moment(_)
+                )
+            })
+            .map(|offset| offset + 1)
+    }
+    fn pop_duration_into(&mut self, vals: &mut [Alphabet::CharRep]) -> usize {
+        let mut len = 0;
+        while len < vals.len() && len < self.buffered_total {
+            let slot = &mut self.buffer[Self::wrap(self.idx + len)];
+            match *slot {
+                Self::InternalItem::Character(val) if Alphabet::to_char(val).is_ok() => {
+                    vals[len] = val
+                }
+                _ => break,
+            }
+            *slot = Self::InternalItem::Empty;
+            len += 1;
+        }
+        self.idx = Self::wrap(self.idx + len);
+        self.buffered_characters -= len;
+        self.buffered_total -= len;
+        debug_assert_eq!(
+            self.len(),
+            self.buffered_moments() + self.buffered_characters,
+            "Stream lost count of its items"
+        );
+        if let (Some(observer), true) = (self.observer, len > 0) {
+            observer.on_pop(len, self.buffered_total);
+        }
+        len
+    }
+}
+
+#[doc = r" A fixed-size stream shared by one producer and one consumer (an interrupt handler and the main loop, or two"]
+#[doc = r" threads) without a lock - split it into a Producer pushing onto it and a Consumer popping off of it"]
+pub struct AtomicStream<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
+    buffer: [core::cell::UnsafeCell<StreamItem<Alphabet::CharRep, Clock::MomentRep>>; BUFFER_SIZE],
+    pushed: core::sync::atomic::AtomicUsize,
+    popped: core::sync::atomic::AtomicUsize,
+    pushed_moments: core::sync::atomic::AtomicUsize,
+    popped_moments: core::sync::atomic::AtomicUsize,
+    initial_moment: core::cell::UnsafeCell<Option<Clock::MomentRep>>,
+    has_initial_moment: core::sync::atomic::AtomicBool,
+    closed: core::sync::atomic::AtomicBool,
+}
+unsafe impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> Sync
+    for AtomicStream<Alphabet, Clock, BUFFER_SIZE>
+{
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> Default
+    for AtomicStream<Alphabet, Clock, BUFFER_SIZE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>
+    AtomicStream<Alphabet, Clock, BUFFER_SIZE>
+{
+    const NON_EMPTY: () = assert!(BUFFER_SIZE > 0, "Streams need a buffer size of at least 1");
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SLOT: core::cell::UnsafeCell<StreamItem<Alphabet::CharRep, Clock::MomentRep>> =
+        core::cell::UnsafeCell::new(StreamItem::Empty);
+    pub const fn new() -> Self {
+        let () = Self::NON_EMPTY;
+        Self {
+            buffer: [Self::EMPTY_SLOT; BUFFER_SIZE],
+            pushed: core::sync::atomic::AtomicUsize::new(0),
+            popped: core::sync::atomic::AtomicUsize::new(0),
+            pushed_moments: core::sync::atomic::AtomicUsize::new(0),
+            popped_moments: core::sync::atomic::AtomicUsize::new(0),
+            initial_moment: core::cell::UnsafeCell::new(None),
+            has_initial_moment: core::sync::atomic::AtomicBool::new(false),
+            closed: core::sync::atomic::AtomicBool::new(false),
+        }
+    }
+    #[doc = r" The two ends of the stream - each may be handed to a different thread (or interrupt handler)"]
+    pub fn split(
+        &mut self,
+    ) -> (
+        Producer<'_, Alphabet, Clock, BUFFER_SIZE>,
+        Consumer<'_, Alphabet, Clock, BUFFER_SIZE>,
+    ) {
+        (
+            Producer {
+                stream: self,
+                overflowed: false,
+            },
+            Consumer {
+                stream: self,
+                last_seen_moment: None,
+            },
+        )
+    }
+    fn len(&self) -> usize {
+        let popped = self.popped.load(core::sync::atomic::Ordering::Acquire);
+        self.pushed
+            .load(core::sync::atomic::Ordering::Acquire)
+            .wrapping_sub(popped)
+    }
+    #[doc = r" The item `offset` items past the oldest one - only the consumer may call this, for published items"]
+    fn slot(&self, offset: usize) -> StreamItem<Alphabet::CharRep, Clock::MomentRep> {
+        let idx = self
+            .popped
+            .load(core::sync::atomic::Ordering::Relaxed)
+            .wrapping_add(offset)
+            % BUFFER_SIZE;
+        unsafe { *self.buffer[idx].get() }
+    }
+}
+#[doc = r" The pushing end of an AtomicStream"]
+pub struct Producer<'s, Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
+    stream: &'s AtomicStream<Alphabet, Clock, BUFFER_SIZE>,
+    overflowed: bool,
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>
+    Producer<'_, Alphabet, Clock, BUFFER_SIZE>
+{
+    fn push_item(
+        &mut self,
+        item: StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+    ) -> Result<(), ExitError> {
+        use core::sync::atomic::Ordering;
+        if self.stream.closed.load(Ordering::Relaxed) {
+            return Err(ExitError::Closed);
+        }
+        let pushed = self.stream.pushed.load(Ordering::Relaxed);
+        if pushed.wrapping_sub(self.stream.popped.load(Ordering::Acquire)) == BUFFER_SIZE {
+            self.overflowed = true;
+            return Err(ExitError::BufferFull);
+        }
+        if let StreamItem::Moment(_) = item {
+            let moments = self.stream.pushed_moments.load(Ordering::Relaxed);
+            self.stream
+                .pushed_moments
+                .store(moments.wrapping_add(1), Ordering::Release);
+        }
+        unsafe { *self.stream.buffer[pushed % BUFFER_SIZE].get() = item };
+        self.stream
+            .pushed
+            .store(pushed.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> ExitLike<Alphabet, Clock>
+    for Producer<'_, Alphabet, Clock, BUFFER_SIZE>
+{
+    type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
+    type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+    #[doc = r" Only the first call has an effect - the consumer reports the moment as current until it pops one"]
+    fn set_initial_moment(&mut self, moment: Clock::MomentRep) {
+        use core::sync::atomic::Ordering;
+        if !self.stream.has_initial_moment.load(Ordering::Relaxed) {
+            unsafe { *self.stream.initial_moment.get() = Some(moment) };
+            self.stream
+                .has_initial_moment
+                .store(true, Ordering::Release);
+        }
+    }
+    fn accepting_pushes(&mut self) -> bool {
+        self.room() > 0
+    }
+    fn push(&mut self, chr: Alphabet::CharEnum) -> Result<(), ExitError> {
+        self.push_item(StreamItem::Character(Alphabet::to_val(chr)))
+    }
+    fn push_moment(&mut self, moment: Clock::MomentRep) -> Result<(), ExitError> {
+        self.push_item(StreamItem::Moment(moment))
+    }
+    fn push_invalid(&mut self, rep: Alphabet::CharRep) -> Result<(), ExitError> {
+        self.push_item(StreamItem::Character(rep))
+    }
+    fn push_raw(&mut self, item: Self::InternalItem) -> Result<(), ExitError> {
+        match item {
+            Self::InternalItem::Empty => Ok(()),
+            Self::InternalItem::InvalidChar(rep) => self.push_invalid(rep),
+            item => self.push_item(item),
+        }
+    }
+    fn push_vals(&mut self, vals: &[Alphabet::CharRep]) -> Result<(), ExitError> {
+        use core::sync::atomic::Ordering;
+        if self.stream.closed.load(Ordering::Relaxed) {
+            return Err(ExitError::Closed);
+        } else if vals.len() > self.room() {
+            self.overflowed = true;
+            return Err(ExitError::BufferFull);
+        }
+        let pushed = self.stream.pushed.load(Ordering::Relaxed);
+        for (offset, val) in vals.iter().enumerate() {
+            unsafe {
+                *self.stream.buffer[pushed.wrapping_add(offset) % BUFFER_SIZE].get() =
+                    StreamItem::Character(*val)
+            };
+        }
+        self.stream
+            .pushed
+            .store(pushed.wrapping_add(vals.len()), Ordering::Release);
+        Ok(())
+    }
+    fn take_overflowed(&mut self) -> bool {
+        core::mem::take(&mut self.overflowed)
+    }
+    fn close(&mut self) {
+        self.stream
+            .closed
+            .store(true, core::sync::atomic::Ordering::Release);
+    }
+    fn room(&self) -> usize {
+        if self
+            .stream
+            .closed
+            .load(core::sync::atomic::Ordering::Relaxed)
+        {
+            0
+        } else {
+            self.remaining()
+        }
+    }
+    fn remaining(&self) -> usize {
+        BUFFER_SIZE - self.stream.len()
+    }
+}
+#[doc = r" The popping end of an AtomicStream"]
+pub struct Consumer<'s, Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> {
+    stream: &'s AtomicStream<Alphabet, Clock, BUFFER_SIZE>,
+    last_seen_moment: Option<Clock::MomentRep>,
+}
+impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize>
+    GatewayLike<Alphabet, Clock, BUFFER_SIZE> for Consumer<'_, Alphabet, Clock, BUFFER_SIZE>
+{
+    type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
+    type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+    fn pop(&mut self) -> Self::Item {
+        decode::<Alphabet, Clock>(self.pop_raw())
+    }
+    fn peek(&self) -> Self::Item {
+        decode::<Alphabet, Clock>(self.peek_raw())
+    }
+    fn pop_raw(&mut self) -> Self::InternalItem {
+        use core::sync::atomic::Ordering;
+        let item = self.peek_raw();
+        if let StreamItem::Empty = item {
+            return item;
+        }
+        let popped = self.stream.popped.load(Ordering::Relaxed);
+        self.stream
+            .popped
+            .store(popped.wrapping_add(1), Ordering::Release);
+        if let Self::InternalItem::Moment(moment) = item {
+            let moments = self.stream.popped_moments.load(Ordering::Relaxed);
+            self.stream
+                .popped_moments
+                .store(moments.wrapping_add(1), Ordering::Release);
+            self.last_seen_moment = Some(moment);
+        }
+        item
+    }
+    fn peek_raw(&self) -> Self::InternalItem {
+        if self.stream.len() == 0 {
+            StreamItem::Empty
+        } else {
+            self.stream.slot(0)
+        }
+    }
+    fn forward_duration<Exit: ExitLike<Alphabet, Clock>>(
+        &mut self,
+        exit: &mut Exit,
+    ) -> Result<(), StreamError> {
+        while self.next_is_character() {
+            match self.pop() {
+                Self::Item::Character(chr) => exit.push(chr)?,
+                Self::Item::InvalidChar(_) => return Err(StreamError::InvalidChar),
+                _ => return Err(StreamError::UnexpectedItem),
+            }
+        }
+        Ok(())
+    }
+    fn current_moment(&self) -> Option<Clock::MomentRep> {
+        if self.last_seen_moment.is_none()
+            && self
+                .stream
+                .has_initial_moment
+                .load(core::sync::atomic::Ordering::Acquire)
+        {
+            unsafe { *self.stream.initial_moment.get() }
+        } else {
+            self.last_seen_moment
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.stream.len() == 0
+    }
+    fn is_closed(&self) -> bool {
+        self.stream
+            .closed
+            .load(core::sync::atomic::Ordering::Acquire)
+    }
+    fn len(&self) -> usize {
+        self.stream.len()
+    }
+    fn capacity(&self) -> usize {
+        BUFFER_SIZE
+    }
+    fn buffered_moments(&self) -> usize {
+        use core::sync::atomic::Ordering;
+        let popped = self.stream.popped_moments.load(Ordering::Relaxed);
+        self.stream
+            .pushed_moments
+            .load(Ordering::Acquire)
+            .wrapping_sub(popped)
+    }
+    fn has_duration(&self) -> bool {
+        self.buffered_moments() > 0
+    }
+    fn next_is_character(&self) -> bool {
+        matches!(self.peek_raw(), Self::InternalItem::Character(_))
+    }
+    fn next_is_moment(&self) -> bool {
+        matches!(self.peek_raw(), Self::InternalItem::Moment(_))
+    }
+    fn duration_len(&self) -> Option<usize> {
+        if !self.has_duration() {
+            return None;
+        }
+        (0..self.stream.len())
+            .find(|offset| matches!(self.stream.slot(*offset), Self::InternalItem::Moment(_)))
+            .map(|offset| offset + 1)
+    }
+    fn pop_duration_into(&mut self, vals: &mut [Alphabet::CharRep]) -> usize {
+        let available = self.stream.len();
+        let mut len = 0;
+        while len < vals.len() && len < available {
+            match self.stream.slot(len) {
+                Self::InternalItem::Character(val) if Alphabet::to_char(val).is_ok() => {
+                    vals[len] = val
+                }
+                _ => break,
+            }
+            len += 1;
+        }
+        let popped = self
+            .stream
+            .popped
+            .load(core::sync::atomic::Ordering::Relaxed);
+        self.stream.popped.store(
+            popped.wrapping_add(len),
+            core::sync::atomic::Ordering::Release,
+        );
+        len
+    }
+}
+
+pub trait ProgramLike {
+    #[doc = r" Scheduling priority of the program - higher priorities are stepped first"]
+    const PRIORITY: u8;
+    #[doc = r" A gateway the program reads has a whole duration buffered - stepping on less could leave it waiting on"]
+    #[doc = r" input halfway through"]
+    fn has_pending_input(&self) -> bool;
+    #[doc = r" Closes every gateway - hosts call this once no more input will arrive"]
+    fn close_gateways(&mut self);
+    #[doc = r" Whether the exits a step forwards durations onto have room for them - programs yielding on full exits"]
+    #[doc = r" (`set_on_full yield`) have no input pending until they do"]
+    fn backpressure(&self) -> PushOutcome {
+        PushOutcome::Fits
+    }
+    #[doc = r" End-of-stream propagation: once every gateway is closed and drained, every exit is closed too (so"]
+    #[doc = r" downstream programs reading those exits can finish in turn). Returns whether the program has finished."]
+    fn propagate_close(&mut self) -> bool;
+    #[doc = r" Runs the root label - once, before the program is stepped"]
+    fn start(&mut self) -> Result<(), StreamError>;
+    #[doc = r" Runs the program's `main` label (or its root, without one) once, if it has input pending - returns"]
+    #[doc = r" whether it ran"]
+    fn run_step(&mut self) -> Result<bool, StreamError>;
+}
+#[doc = r" The object-safe side of a program, so that one scheduler can step programs of different types"]
+pub trait Steppable {
+    fn priority(&self) -> u8;
+    fn pending(&self) -> bool;
+    fn step(&mut self) -> Result<bool, StreamError>;
+}
+impl<Program: ProgramLike> Steppable for Program {
+    fn priority(&self) -> u8 {
+        Program::PRIORITY
+    }
+    fn pending(&self) -> bool {
+        self.has_pending_input()
+    }
+    fn step(&mut self) -> Result<bool, StreamError> {
+        self.run_step()
+    }
+}
+#[doc = r" Moves every item that fits from an exit onto a gateway, and closes the gateway once the exit is drained -"]
+#[doc = r" how pipelines carry data across connected streams. Returns whether anything changed."]
+pub fn transfer<Alphabet, Clock, From, To, const FROM: usize, const TO: usize>(
+    from: &mut From,
+    to: &mut To,
+) -> Result<bool, StreamError>
+where
+    Alphabet: AlphabetLike,
+    Clock: ClockLike,
+    From: GatewayLike<
+        Alphabet,
+        Clock,
+        FROM,
+        Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>,
+    >,
+    To: StreamLike<Alphabet, Clock, TO>,
+{
+    let mut moved = false;
+    while !from.is_empty() && to.accepting_pushes() {
+        match from.pop() {
+            StreamItem::Character(chr) => to.push(chr)?,
+            StreamItem::Moment(moment) => to.push_moment(moment)?,
+            StreamItem::InvalidChar(rep) => to.push_invalid(rep)?,
+            StreamItem::Empty => break,
+        }
+        moved = true;
+    }
+    if from.is_drained() && !to.is_closed() {
+        to.close();
+        moved = true;
+    }
+    Ok(moved)
+}
+#[derive(Copy, Clone, Debug)]
+pub struct Starvation {
+    pub program: usize,
+    pub rounds: usize,
+}
+#[doc = r" Tracks, per program, how many scheduler rounds have passed without it being stepped while it had pending input"]
+pub struct Watchdog<const PROGRAMS: usize> {
+    rounds_waiting: [usize; PROGRAMS],
+    limit: usize,
+}
+impl<const PROGRAMS: usize> Watchdog<PROGRAMS> {
+    pub const fn new(limit: usize) -> Self {
+        Self {
+            rounds_waiting: [0; PROGRAMS],
+            limit,
+        }
+    }
+    pub fn round(
+        &mut self,
+        stepped: &[bool; PROGRAMS],
+        pending: &[bool; PROGRAMS],
+    ) -> Option<Starvation> {
+        let mut starved = None;
+        for program in 0..PROGRAMS {
+            if stepped[program] || !pending[program] {
+                self.rounds_waiting[program] = 0;
+            } else {
+                self.rounds_waiting[program] += 1;
+                if self.rounds_waiting[program] >= self.limit && starved.is_none() {
+                    starved = Some(Starvation {
+                        program,
+                        rounds: self.rounds_waiting[program],
+                    });
+                }
+            }
+        }
+        starved
+    }
+}
+#[doc = r" How many rounds a generated pipeline lets a program go unstepped with pending input before reporting it"]
+pub const STARVATION_LIMIT: usize = 16;
+#[doc = r" Steps a fixed set of programs in rounds - every program with pending input is stepped once per round,"]
+#[doc = r" highest priority first, while a watchdog looks out for programs that never get to run"]
+pub struct Scheduler<const PROGRAMS: usize> {
+    watchdog: Watchdog<PROGRAMS>,
+    starvation: Option<Starvation>,
+}
+impl<const PROGRAMS: usize> Scheduler<PROGRAMS> {
+    pub const fn new(starvation_limit: usize) -> Self {
+        Self {
+            watchdog: Watchdog::new(starvation_limit),
+            starvation: None,
+        }
+    }
+    #[doc = r" Steps every program with pending input once - returns whether any of them ran"]
+    pub fn round(
+        &mut self,
+        programs: &mut [&mut dyn Steppable; PROGRAMS],
+    ) -> Result<bool, StreamError> {
+        let mut order: [usize; PROGRAMS] = core::array::from_fn(|idx| idx);
+        order.sort_unstable_by_key(|idx| (core::cmp::Reverse(programs[*idx].priority()), *idx));
+        let mut stepped = [false; PROGRAMS];
+        let mut pending = [false; PROGRAMS];
+        for idx in order {
+            pending[idx] = programs[idx].pending();
+            if pending[idx] {
+                stepped[idx] = programs[idx].step()?;
+            }
+        }
+        self.starvation = self.watchdog.round(&stepped, &pending);
+        Ok(stepped.iter().any(|stepped| *stepped))
+    }
+    #[doc = r" The program the watchdog caught starving in the last round, if any"]
+    pub fn starvation(&self) -> Option<Starvation> {
+        self.starvation
+    }
+}
+#[doc = r" The sending end of a bridge between programs run apart from each other - it drains a stream (an exit, or"]
+#[doc = r" a gateway feeding a connected program) into the bridge"]
+pub trait BridgeSender<Alphabet: AlphabetLike, Clock: ClockLike> {
+    #[doc = r" Moves items off of `from` for as long as the bridge has room, and closes the bridge once `from` is"]
+    #[doc = r" drained - returns whether the bridge is closed"]
+    fn send<From: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+        &mut self,
+        from: &mut From,
+    ) -> bool;
+}
+#[doc = r" The receiving end of a bridge - it pushes what was sent onto a gateway, and closes the gateway along with"]
+#[doc = r" the bridge"]
+pub trait BridgeReceiver<Alphabet: AlphabetLike, Clock: ClockLike> {
+    fn receive<To: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+        &mut self,
+        to: &mut To,
+    ) -> Result<(), StreamError>;
+}
+#[doc = r" Runs programs apart from each other, with bridges carrying data across their connected streams"]
+pub trait Executor<'a> {
+    type Sender<Alphabet: AlphabetLike, Clock: ClockLike>: BridgeSender<Alphabet, Clock> + Send + 'a;
+    type Receiver<Alphabet: AlphabetLike, Clock: ClockLike>: BridgeReceiver<Alphabet, Clock>
+        + Send
+        + 'a;
+    fn bridge<Alphabet: AlphabetLike, Clock: ClockLike>(
+        &mut self,
+    ) -> (
+        Self::Sender<Alphabet, Clock>,
+        Self::Receiver<Alphabet, Clock>,
+    );
+    #[doc = r" Runs a program until it has finished - `io` moves data between its streams and their bridges, and"]
+    #[doc = r" returns whether every bridge it sends on is closed"]
+    fn spawn<Program, Io>(&mut self, program: &'a mut Program, io: Io)
+    where
+        Program: ProgramLike + Send,
+        Io: FnMut(&mut Program) -> Result<bool, StreamError> + Send + 'a;
+}
+#[doc = r" A pipeline that can be taken apart - each of its programs is handed to an executor, bridged to the others"]
+pub trait ProgramGraph {
+    fn spawn_all<'a, E: Executor<'a>>(&'a mut self, executor: &mut E);
+}
+
+#[doc = r" Streams buffering on the heap - growing as items are pushed, up to a configurable cap"]
+#[cfg(feature = "alloc")]
+mod vec_stream {
+    extern crate alloc;
+    use super::*;
+    use alloc::collections::VecDeque;
+    #[doc = r" The cap of a stream that may grow for as long as there is memory"]
+    pub const UNBOUNDED: usize = usize::MAX;
+    #[doc = r" A stream whose buffer grows as items are pushed, instead of being allocated upfront - pushes are only"]
+    #[doc = r" rejected once `CAP` items are buffered"]
+    pub struct VecStream<Alphabet: AlphabetLike, Clock: ClockLike, const CAP: usize> {
+        buffer: VecDeque<StreamItem<Alphabet::CharRep, Clock::MomentRep>>,
+        buffered_moments: usize,
+        last_seen_moment: Option<Clock::MomentRep>,
+        policy: OverflowPolicy,
+        overflowed: bool,
+        closed: bool,
+        observer: Option<&'static dyn StreamObserver>,
+        monotonic: Monotonic<Clock::MomentRep>,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike, const CAP: usize> Default
+        for VecStream<Alphabet, Clock, CAP>
+    {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike, const CAP: usize> VecStream<Alphabet, Clock, CAP> {
+        const NON_EMPTY: () = assert!(CAP > 0, "Streams need a cap of at least 1");
+        pub const fn new() -> Self {
+            Self::with_policy(OverflowPolicy::Reject)
+        }
+        pub const fn with_policy(policy: OverflowPolicy) -> Self {
+            let () = Self::NON_EMPTY;
+            Self {
+                buffer: VecDeque::new(),
+                buffered_moments: 0,
+                last_seen_moment: None,
+                policy,
+                overflowed: false,
+                closed: false,
+                observer: None,
+                monotonic: Monotonic::Unchecked,
+            }
+        }
+        #[doc = r" Has debug builds panic on any moment pushed onto the stream earlier than the one before it"]
+        pub const fn asserting_monotonic(mut self) -> Self {
+            self.monotonic = Monotonic::Checked(None);
+            self
+        }
+        #[doc = r" Notifies `observer` of every push, pop and drop on the stream from now on - or no one, once it is None"]
+        pub fn set_observer(&mut self, observer: Option<&'static dyn StreamObserver>) {
+            self.observer = observer;
+        }
+        #[doc = r" Evicts the oldest duration to make room - reported to the observer as dropped, rather than popped, so the"]
+        #[doc = r" current moment stays where it was"]
+        fn evict(&mut self) {
+            let (observer, last_seen_moment) = (self.observer.take(), self.last_seen_moment);
+            let before = self.buffer.len();
+            self.skip_duration();
+            (self.observer, self.last_seen_moment) = (observer, last_seen_moment);
+            if let Some(observer) = self.observer {
+                observer.on_full(before - self.buffer.len(), self.buffer.len());
+            }
+        }
+        fn push_item(
+            &mut self,
+            item: StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+        ) -> Result<(), ExitError> {
+            if self.closed {
+                return Err(ExitError::Closed);
+            }
+            if self.buffer.len() == CAP && self.policy == OverflowPolicy::OverwriteOldest {
+                self.evict();
+            }
+            if self.buffer.len() < CAP {
+                if let StreamItem::Moment(moment) = item {
+                    self.monotonic.pushed(moment);
+                    self.buffered_moments += 1;
+                }
+                self.buffer.push_back(item);
+                debug_assert!(
+                    self.len() <= self.capacity(),
+                    "Stream buffered past its capacity"
+                );
+                debug_assert!(
+                    self.buffered_moments() <= self.len(),
+                    "Stream lost count of its moments"
+                );
+                match (self.observer, item) {
+                    (Some(observer), StreamItem::Moment(_)) => {
+                        observer.on_moment(self.buffer.len())
+                    }
+                    (Some(observer), _) => observer.on_push(1, self.buffer.len()),
+                    (None, _) => (),
+                }
+                Ok(())
+            } else {
+                self.overflowed = true;
+                if let Some(observer) = self.observer {
+                    observer.on_full(1, self.buffer.len());
+                }
+                Err(ExitError::BufferFull)
+            }
+        }
+        #[doc = r" The moments currently buffered in the stream, oldest first - without popping them"]
+        pub fn moments(&self) -> impl Iterator<Item = Clock::MomentRep> + '_ {
+            self.buffer.iter().filter_map(|item| match item {
+                StreamItem::Moment(moment) => Some(*moment),
+                _ => None,
+            })
+        }
+        #[doc = r" Pops every item buffered in the stream, oldest first"]
+        pub fn drain(&mut self) -> Drain<'_, Alphabet, Clock, CAP, Self> {
+            Drain::new(self)
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike, const CAP: usize>
+        StreamLike<Alphabet, Clock, CAP> for VecStream<Alphabet, Clock, CAP>
+    {
+        fn moments(&self) -> impl Iterator<Item = Clock::MomentRep> + '_ {
+            VecStream::moments(self)
+        }
+    }
+    impl<'s, Alphabet: AlphabetLike, Clock: ClockLike, const CAP: usize> IntoIterator
+        for &'s mut VecStream<Alphabet, Clock, CAP>
+    {
+        type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+        type IntoIter = Drain<'s, Alphabet, Clock, CAP, VecStream<Alphabet, Clock, CAP>>;
+        fn into_iter(self) -> Self::IntoIter {
+            self.drain()
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike, const CAP: usize> ExitLike<Alphabet, Clock>
+        for VecStream<Alphabet, Clock, CAP>
+    {
+        type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
+        type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+        fn set_initial_moment(&mut self, moment: Clock::MomentRep) {
+            self.last_seen_moment = Some(moment);
+        }
+        fn accepting_pushes(&mut self) -> bool {
+            !self.closed
+                && (self.buffer.len() < CAP || self.policy == OverflowPolicy::OverwriteOldest)
+        }
+        fn push(&mut self, chr: Alphabet::CharEnum) -> Result<(), ExitError> {
+            self.push_item(StreamItem::Character(Alphabet::to_val(chr)))
+        }
+        fn push_moment(&mut self, moment: Clock::MomentRep) -> Result<(), ExitError> {
+            self.push_item(StreamItem::Moment(moment))
+        }
+        fn push_invalid(&mut self, rep: Alphabet::CharRep) -> Result<(), ExitError> {
+            self.push_item(StreamItem::Character(rep))
+        }
+        fn push_raw(&mut self, item: Self::InternalItem) -> Result<(), ExitError> {
+            match item {
+                Self::InternalItem::Empty => Ok(()),
+                Self::InternalItem::InvalidChar(rep) => self.push_invalid(rep),
+                item => self.push_item(item),
+            }
+        }
+        fn push_vals(&mut self, vals: &[Alphabet::CharRep]) -> Result<(), ExitError> {
+            if self.closed {
+                return Err(ExitError::Closed);
+            } else if self.policy == OverflowPolicy::OverwriteOldest {
+                return vals
+                    .iter()
+                    .try_for_each(|val| self.push_item(StreamItem::Character(*val)));
+            } else if vals.len() > CAP - self.buffer.len() {
+                self.overflowed = true;
+                if let Some(observer) = self.observer {
+                    observer.on_full(vals.len(), self.buffer.len());
+                }
+                return Err(ExitError::BufferFull);
+            }
+            self.buffer
+                .extend(vals.iter().map(|val| StreamItem::Character(*val)));
+            if let (Some(observer), false) = (self.observer, vals.is_empty()) {
+                observer.on_push(vals.len(), self.buffer.len());
+            }
+            Ok(())
+        }
+        fn take_overflowed(&mut self) -> bool {
+            core::mem::take(&mut self.overflowed)
+        }
+        fn close(&mut self) {
+            self.closed = true;
+        }
+        fn room(&self) -> usize {
+            match (self.closed, self.policy) {
+                (true, _) => 0,
+                (false, OverflowPolicy::OverwriteOldest) => usize::MAX,
+                (false, OverflowPolicy::Reject) => self.remaining(),
+            }
+        }
+        fn remaining(&self) -> usize {
+            CAP - self.buffer.len()
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike, const CAP: usize>
+        GatewayLike<Alphabet, Clock, CAP> for VecStream<Alphabet, Clock, CAP>
+    {
+        type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
+        type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+        fn pop(&mut self) -> Self::Item {
+            decode::<Alphabet, Clock>(self.pop_raw())
+        }
+        fn peek(&self) -> Self::Item {
+            decode::<Alphabet, Clock>(self.peek_raw())
+        }
+        fn pop_raw(&mut self) -> Self::InternalItem {
+            let Some(item) = self.buffer.pop_front() else {
+                return Self::InternalItem::Empty;
+            };
+            if let Self::InternalItem::Moment(moment) = item {
+                self.buffered_moments -= 1;
+                self.last_seen_moment = Some(moment);
+            }
+            if let Some(observer) = self.observer {
+                observer.on_pop(1, self.buffer.len());
+            }
+            item
+        }
+        fn peek_raw(&self) -> Self::InternalItem {
+            self.buffer.front().copied().unwrap_or_default()
+        }
+        fn forward_duration<Exit: ExitLike<Alphabet, Clock>>(
+            &mut self,
+            exit: &mut Exit,
+        ) -> Result<(), StreamError> {
+            while self.next_is_character() {
+                match self.pop() {
+                    Self::Item::Character(chr) => exit.push(chr)?,
+                    Self::Item::InvalidChar(_) => return Err(StreamError::InvalidChar),
+                    _ => return Err(StreamError::UnexpectedItem),
+                }
+            }
+            Ok(())
+        }
+        fn current_moment(&self) -> Option<Clock::MomentRep> {
+            self.last_seen_moment
+        }
+        fn is_empty(&self) -> bool {
+            self.buffer.is_empty()
+        }
+        fn is_closed(&self) -> bool {
+            self.closed
+        }
+        fn len(&self) -> usize {
+            self.buffer.len()
+        }
+        fn capacity(&self) -> usize {
+            CAP
+        }
+        fn buffered_moments(&self) -> usize {
+            self.buffered_moments
+        }
+        fn has_duration(&self) -> bool {
+            self.buffered_moments > 0
+        }
+        fn next_is_character(&self) -> bool {
+            matches!(self.buffer.front(), Some(Self::InternalItem::Character(_)))
+        }
+        fn next_is_moment(&self) -> bool {
+            matches!(self.buffer.front(), Some(Self::InternalItem::Moment(_)))
+        }
+        fn duration_len(&self) -> Option<usize> {
+            self.buffer
+                .iter()
+                .position(|item| matches!(item, Self::InternalItem::Moment(_)))
+                .map(|idx| idx + 1)
+        }
+        fn pop_duration_into(&mut self, vals: &mut [Alphabet::CharRep]) -> usize {
+            let mut len = 0;
+            while len < vals.len() {
+                match self.buffer.front() {
+                    Some(Self::InternalItem::Character(val)) if Alphabet::to_char(*val).is_ok() => {
+                        vals[len] = *val
+                    }
+                    _ => break,
+                }
+                self.buffer.pop_front();
+                len += 1;
+            }
+            if let (Some(observer), true) = (self.observer, len > 0) {
+                observer.on_pop(len, self.buffer.len());
+            }
+            len
+        }
+    }
+    #[cfg(feature = "serde")]
+    mod serde_impls {
+        use super::*;
+        use crate::serde_impls::{check_len, StreamRepr};
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        impl<Alphabet: AlphabetLike, Clock: ClockLike, const CAP: usize> Serialize
+            for VecStream<Alphabet, Clock, CAP>
+        where
+            Alphabet::CharRep: Serialize,
+            Clock::MomentRep: Serialize,
+        {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                StreamRepr {
+                    items: &self.buffer,
+                    current_moment: self.last_seen_moment,
+                    policy: self.policy,
+                    overflowed: self.overflowed,
+                    closed: self.closed,
+                }
+                .serialize(serializer)
+            }
+        }
+        impl<'de, Alphabet: AlphabetLike, Clock: ClockLike, const CAP: usize> Deserialize<'de>
+            for VecStream<Alphabet, Clock, CAP>
+        where
+            Alphabet::CharRep: Deserialize<'de>,
+            Clock::MomentRep: Deserialize<'de>,
+        {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let repr = StreamRepr::<
+                    VecDeque<StreamItem<Alphabet::CharRep, Clock::MomentRep>>,
+                    Clock::MomentRep,
+                >::deserialize(deserializer)?;
+                check_len(repr.items.len(), CAP)?;
+                Ok(Self {
+                    buffered_moments: repr
+                        .items
+                        .iter()
+                        .filter(|item| matches!(item, StreamItem::Moment(_)))
+                        .count(),
+                    buffer: repr.items,
+                    last_seen_moment: repr.current_moment,
+                    policy: repr.policy,
+                    overflowed: repr.overflowed,
+                    closed: repr.closed,
+                    observer: None,
+                    monotonic: Monotonic::Unchecked,
+                })
+            }
+        }
+    }
+}
+#[cfg(feature = "alloc")]
+pub use vec_stream::*;
+
+#[doc = r" Runs every program of a pipeline on its own thread, with bounded channels carrying data between them"]
+#[cfg(feature = "std")]
+pub mod threaded {
+    extern crate std;
+    use super::*;
+    use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError, TrySendError};
+    use std::thread::{Scope, ScopedJoinHandle};
+    use std::vec::Vec;
+    type Item<Alphabet, Clock> = StreamItem<
+        <Alphabet as AlphabetLike>::CharEnum,
+        <Clock as ClockLike>::MomentRep,
+        <Alphabet as AlphabetLike>::CharRep,
+    >;
+    #[doc = r" Sends on a bounded channel - dropping the channel's sender is what closes the bridge"]
+    pub struct ChannelSender<Alphabet: AlphabetLike, Clock: ClockLike> {
+        tx: Option<SyncSender<Item<Alphabet, Clock>>>,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> BridgeSender<Alphabet, Clock>
+        for ChannelSender<Alphabet, Clock>
+    {
+        fn send<From: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> bool {
+            let Some(tx) = &self.tx else { return true };
+            while !from.is_empty() {
+                match tx.try_send(from.peek()) {
+                    Ok(()) => {
+                        from.pop();
+                    }
+                    Err(TrySendError::Full(_)) => return false,
+                    Err(TrySendError::Disconnected(_)) => {
+                        while !from.is_empty() {
+                            from.pop();
+                        }
+                    }
+                }
+            }
+            if from.is_drained() {
+                self.tx = None;
+            }
+            self.tx.is_none()
+        }
+    }
+    pub struct ChannelReceiver<Alphabet: AlphabetLike, Clock: ClockLike> {
+        rx: Receiver<Item<Alphabet, Clock>>,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> BridgeReceiver<Alphabet, Clock>
+        for ChannelReceiver<Alphabet, Clock>
+    {
+        fn receive<To: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+        ) -> Result<(), StreamError> {
+            while to.accepting_pushes() {
+                match self.rx.try_recv() {
+                    Ok(StreamItem::Character(chr)) => to.push(chr)?,
+                    Ok(StreamItem::Moment(moment)) => to.push_moment(moment)?,
+                    Ok(StreamItem::InvalidChar(rep)) => to.push_invalid(rep)?,
+                    Ok(StreamItem::Empty) => (),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => to.close(),
+                }
+            }
+            Ok(())
+        }
+    }
+    #[doc = r" Spawns one scoped thread per program - bridges are channels holding up to `capacity` items"]
+    pub struct ThreadedExecutor<'scope, 'env> {
+        scope: &'scope Scope<'scope, 'env>,
+        capacity: usize,
+        threads: Vec<ScopedJoinHandle<'scope, Result<(), StreamError>>>,
+    }
+    impl<'scope, 'env> ThreadedExecutor<'scope, 'env> {
+        pub fn new(scope: &'scope Scope<'scope, 'env>, capacity: usize) -> Self {
+            Self {
+                scope,
+                capacity,
+                threads: Vec::new(),
+            }
+        }
+        #[doc = r" Waits for every program to finish - the first error any of them ran into is returned"]
+        pub fn join(self) -> Result<(), StreamError> {
+            let mut result = Ok(());
+            for thread in self.threads {
+                let finished = thread
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+                result = result.and(finished);
+            }
+            result
+        }
+    }
+    impl<'scope, 'env> Executor<'scope> for ThreadedExecutor<'scope, 'env> {
+        type Sender<Alphabet: AlphabetLike, Clock: ClockLike> = ChannelSender<Alphabet, Clock>;
+        type Receiver<Alphabet: AlphabetLike, Clock: ClockLike> = ChannelReceiver<Alphabet, Clock>;
+        fn bridge<Alphabet: AlphabetLike, Clock: ClockLike>(
+            &mut self,
+        ) -> (
+            Self::Sender<Alphabet, Clock>,
+            Self::Receiver<Alphabet, Clock>,
+        ) {
+            let (tx, rx) = sync_channel(self.capacity);
+            (ChannelSender { tx: Some(tx) }, ChannelReceiver { rx })
+        }
+        fn spawn<Program, Io>(&mut self, program: &'scope mut Program, mut io: Io)
+        where
+            Program: ProgramLike + Send,
+            Io: FnMut(&mut Program) -> Result<bool, StreamError> + Send + 'scope,
+        {
+            self.threads.push(self.scope.spawn(move || {
+                program.start()?;
+                loop {
+                    io(program)?;
+                    let stepped = program.run_step()?;
+                    let finished = program.propagate_close();
+                    if io(program)? && finished {
+                        return Ok(());
+                    }
+                    if !stepped {
+                        std::thread::yield_now();
+                    }
+                }
+            }));
+        }
+    }
+    #[doc = r" Runs a pipeline with each of its programs on a thread of its own, until all of them have finished"]
+    pub fn run_threaded<Graph: ProgramGraph>(
+        graph: &mut Graph,
+        capacity: usize,
+    ) -> Result<(), StreamError> {
+        std::thread::scope(|scope| {
+            let mut executor = ThreadedExecutor::new(scope, capacity);
+            graph.spawn_all(&mut executor);
+            executor.join()
+        })
+    }
+}
+
+#[doc = r" Runs every program of a pipeline as a future - a program with nothing to do awaits its bridges, rather than"]
+#[doc = r" blocking the thread polling it"]
+#[cfg(feature = "async")]
+pub mod asynchronous {
+    extern crate std;
+    use super::*;
+    use core::cell::RefCell;
+    use core::future::{poll_fn, Future};
+    use core::pin::Pin;
+    use core::task::{Context, Poll, Waker};
+    use std::boxed::Box;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+    use std::vec::Vec;
+    type Item<Alphabet, Clock> = StreamItem<
+        <Alphabet as AlphabetLike>::CharEnum,
+        <Clock as ClockLike>::MomentRep,
+        <Alphabet as AlphabetLike>::CharRep,
+    >;
+    #[doc = r" How many steps a program takes per poll, before it lets the other futures have a go"]
+    pub const STEP_BUDGET: usize = 64;
+    std::thread_local! { # [doc = r" The waker of the future being polled - bridges that can't move anything register it"] static CURRENT : RefCell < Option < Waker >> = const { RefCell :: new (None) } ; }
+    #[doc = r" Makes `waker` the current one until dropped"]
+    struct Current;
+    impl Current {
+        fn enter(waker: &Waker) -> Self {
+            CURRENT.with(|current| *current.borrow_mut() = Some(waker.clone()));
+            Self
+        }
+    }
+    impl Drop for Current {
+        fn drop(&mut self) {
+            CURRENT.with(|current| *current.borrow_mut() = None);
+        }
+    }
+    fn current() -> Option<Waker> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+    fn wake(waker: &mut Option<Waker>) {
+        if let Some(waker) = waker.take() {
+            waker.wake();
+        }
+    }
+    struct Channel<T> {
+        items: VecDeque<T>,
+        capacity: usize,
+        closed: bool,
+        receiver_gone: bool,
+        sending: Option<Waker>,
+        receiving: Option<Waker>,
+    }
+    fn lock<T>(channel: &Mutex<Channel<T>>) -> MutexGuard<'_, Channel<T>> {
+        channel.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    pub struct AsyncSender<Alphabet: AlphabetLike, Clock: ClockLike> {
+        channel: Arc<Mutex<Channel<Item<Alphabet, Clock>>>>,
+        closed: bool,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> AsyncSender<Alphabet, Clock> {
+        #[doc = r" Sends everything on `from`, awaiting room on the bridge - resolves once `from` is drained"]
+        pub async fn flush<From: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) {
+            poll_fn(|cx| {
+                let _current = Current::enter(cx.waker());
+                if self.send(from) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> BridgeSender<Alphabet, Clock>
+        for AsyncSender<Alphabet, Clock>
+    {
+        fn send<From: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> bool {
+            if self.closed {
+                return true;
+            }
+            let mut channel = lock(&self.channel);
+            let mut moved = false;
+            while !from.is_empty() {
+                if channel.receiver_gone {
+                    from.pop();
+                } else if channel.items.len() < channel.capacity {
+                    channel.items.push_back(from.pop());
+                    moved = true;
+                } else {
+                    channel.sending = current();
+                    break;
+                }
+            }
+            if from.is_drained() {
+                channel.closed = true;
+                self.closed = true;
+            }
+            if moved || self.closed {
+                wake(&mut channel.receiving);
+            }
+            self.closed
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> Drop for AsyncSender<Alphabet, Clock> {
+        fn drop(&mut self) {
+            let mut channel = lock(&self.channel);
+            channel.closed = true;
+            wake(&mut channel.receiving);
+        }
+    }
+    pub struct AsyncReceiver<Alphabet: AlphabetLike, Clock: ClockLike> {
+        channel: Arc<Mutex<Channel<Item<Alphabet, Clock>>>>,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> AsyncReceiver<Alphabet, Clock> {
+        #[doc = r" Pops the next item off of `gateway`, awaiting the bridge while the gateway is empty - `Empty` once"]
+        #[doc = r" both are drained"]
+        pub async fn pop<Gateway: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+            &mut self,
+            gateway: &mut Gateway,
+        ) -> Result<Item<Alphabet, Clock>, StreamError> {
+            poll_fn(|cx| {
+                let _current = Current::enter(cx.waker());
+                self.receive(gateway)?;
+                if gateway.is_empty() && !gateway.is_closed() {
+                    Poll::Pending
+                } else {
+                    Poll::Ready(Ok(gateway.pop()))
+                }
+            })
+            .await
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> BridgeReceiver<Alphabet, Clock>
+        for AsyncReceiver<Alphabet, Clock>
+    {
+        fn receive<To: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+        ) -> Result<(), StreamError> {
+            let mut channel = lock(&self.channel);
+            let mut moved = false;
+            while to.accepting_pushes() {
+                match channel.items.pop_front() {
+                    Some(StreamItem::Character(chr)) => to.push(chr)?,
+                    Some(StreamItem::Moment(moment)) => to.push_moment(moment)?,
+                    Some(StreamItem::InvalidChar(rep)) => to.push_invalid(rep)?,
+                    Some(StreamItem::Empty) => (),
+                    None if channel.closed => to.close(),
+                    None => {
+                        channel.receiving = current();
+                        break;
+                    }
+                }
+                moved = true;
+            }
+            if moved {
+                wake(&mut channel.sending);
+            }
+            Ok(())
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> Drop for AsyncReceiver<Alphabet, Clock> {
+        fn drop(&mut self) {
+            let mut channel = lock(&self.channel);
+            channel.receiver_gone = true;
+            wake(&mut channel.sending);
+        }
+    }
+    #[doc = r" A bridge of its own, for hosts feeding or reading programs run by an async executor"]
+    pub fn bridge<Alphabet: AlphabetLike, Clock: ClockLike>(
+        capacity: usize,
+    ) -> (AsyncSender<Alphabet, Clock>, AsyncReceiver<Alphabet, Clock>) {
+        let channel = Arc::new(Mutex::new(Channel {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+            closed: false,
+            receiver_gone: false,
+            sending: None,
+            receiving: None,
+        }));
+        (
+            AsyncSender {
+                channel: channel.clone(),
+                closed: false,
+            },
+            AsyncReceiver { channel },
+        )
+    }
+    type Io<'a, Program> = Box<dyn FnMut(&mut Program) -> Result<bool, StreamError> + Send + 'a>;
+    #[doc = r" A program as a future - it steps for as long as it has input, then waits on its bridges"]
+    struct ProgramTask<'a, Program> {
+        program: &'a mut Program,
+        io: Io<'a, Program>,
+        started: bool,
+    }
+    impl<Program: ProgramLike> Future for ProgramTask<'_, Program> {
+        type Output = Result<(), StreamError>;
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let task = self.get_mut();
+            let _current = Current::enter(cx.waker());
+            if !task.started {
+                task.started = true;
+                task.program.start()?;
+            }
+            for _ in 0..STEP_BUDGET {
+                (task.io)(task.program)?;
+                let stepped = task.program.run_step()?;
+                let finished = task.program.propagate_close();
+                if (task.io)(task.program)? && finished {
+                    return Poll::Ready(Ok(()));
+                }
+                if !stepped && !task.program.has_pending_input() {
+                    return Poll::Pending;
+                }
+            }
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+    type Task<'a> = Pin<Box<dyn Future<Output = Result<(), StreamError>> + Send + 'a>>;
+    #[doc = r" Collects one future per program - bridges are channels holding up to `capacity` items"]
+    pub struct AsyncExecutor<'a> {
+        capacity: usize,
+        tasks: Vec<Option<Task<'a>>>,
+    }
+    impl<'a> AsyncExecutor<'a> {
+        pub fn new(capacity: usize) -> Self {
+            Self {
+                capacity,
+                tasks: Vec::new(),
+            }
+        }
+        #[doc = r" Polls every program until all of them have finished - the first error any of them ran into is"]
+        #[doc = r" returned"]
+        pub async fn join(mut self) -> Result<(), StreamError> {
+            let mut result = Ok(());
+            poll_fn(|cx| {
+                for slot in self.tasks.iter_mut() {
+                    if let Some(Poll::Ready(finished)) =
+                        slot.as_mut().map(|task| task.as_mut().poll(cx))
+                    {
+                        *slot = None;
+                        result = core::mem::replace(&mut result, Ok(())).and(finished);
+                    }
+                }
+                if self.tasks.iter().all(Option::is_none) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+            result
+        }
+    }
+    impl<'a> Executor<'a> for AsyncExecutor<'a> {
+        type Sender<Alphabet: AlphabetLike, Clock: ClockLike> = AsyncSender<Alphabet, Clock>;
+        type Receiver<Alphabet: AlphabetLike, Clock: ClockLike> = AsyncReceiver<Alphabet, Clock>;
+        fn bridge<Alphabet: AlphabetLike, Clock: ClockLike>(
+            &mut self,
+        ) -> (
+            Self::Sender<Alphabet, Clock>,
+            Self::Receiver<Alphabet, Clock>,
+        ) {
+            bridge(self.capacity)
+        }
+        fn spawn<Program, Io>(&mut self, program: &'a mut Program, io: Io)
+        where
+            Program: ProgramLike + Send,
+            Io: FnMut(&mut Program) -> Result<bool, StreamError> + Send + 'a,
+        {
+            self.tasks.push(Some(Box::pin(ProgramTask {
+                program,
+                io: Box::new(io),
+                started: false,
+            })));
+        }
+    }
+    #[doc = r" Runs a pipeline with each of its programs as a future, until all of them have finished"]
+    pub async fn run_async<Graph: ProgramGraph>(
+        graph: &mut Graph,
+        capacity: usize,
+    ) -> Result<(), StreamError> {
+        let mut executor = AsyncExecutor::new(capacity);
+        graph.spawn_all(&mut executor);
+        executor.join().await
+    }
+}
+
+#[doc = r" Runs programs against scripted input on a virtual clock, recording everything they output - reproducible"]
+#[doc = r" runs of timing-sensitive programs, without waiting on a real clock"]
+#[cfg(feature = "std")]
+pub mod sim {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+    type Item<Alphabet, Clock> = StreamItem<
+        <Alphabet as AlphabetLike>::CharEnum,
+        <Clock as ClockLike>::MomentRep,
+        <Alphabet as AlphabetLike>::CharRep,
+    >;
+    #[doc = r" Input for a gateway - at each moment of the virtual clock, a duration of characters ended by that moment"]
+    pub struct Schedule<Alphabet: AlphabetLike, Clock: ClockLike> {
+        entries: Vec<(u64, Vec<Alphabet::CharEnum>)>,
+        next: usize,
+        delivered_chars: usize,
+        _clock: core::marker::PhantomData<Clock>,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> Default for Schedule<Alphabet, Clock> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> Schedule<Alphabet, Clock> {
+        pub const fn new() -> Self {
+            Self {
+                entries: Vec::new(),
+                next: 0,
+                delivered_chars: 0,
+                _clock: core::marker::PhantomData,
+            }
+        }
+        #[doc = r" Schedules `data` to arrive at `moment` - entries are delivered in order of their moments"]
+        pub fn at(mut self, moment: u64, data: &[Alphabet::CharEnum]) -> Self {
+            let idx = self.entries.partition_point(|(at, _)| *at <= moment);
+            self.entries.insert(idx, (moment, data.to_vec()));
+            self
+        }
+        #[doc = r" The moment the next undelivered entry is due at - `None` once everything was delivered"]
+        pub fn next_moment(&self) -> Option<u64> {
+            self.entries.get(self.next).map(|(at, _)| *at)
+        }
+        #[doc = r" Pushes every entry due by `now` onto `to`, for as long as it has room - `to` is closed once the whole"]
+        #[doc = r" schedule was delivered"]
+        pub fn deliver<To: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+            &mut self,
+            now: u64,
+            to: &mut To,
+        ) -> Result<(), StreamError>
+        where
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            while let Some((at, data)) = self.entries.get(self.next).filter(|(at, _)| *at <= now) {
+                while self.delivered_chars < data.len() && to.accepting_pushes() {
+                    to.push(data[self.delivered_chars])?;
+                    self.delivered_chars += 1;
+                }
+                if self.delivered_chars < data.len() || !to.accepting_pushes() {
+                    return Ok(());
+                }
+                let moment =
+                    Clock::MomentRep::try_from(*at).map_err(|_| ExitError::NotEncodable)?;
+                to.push_moment(moment)?;
+                self.next += 1;
+                self.delivered_chars = 0;
+            }
+            if self.next_moment().is_none() && !to.is_closed() {
+                to.close();
+            }
+            Ok(())
+        }
+    }
+    #[doc = r" Everything that came out of an exit, along with the virtual moment it was recorded at"]
+    pub struct Recording<Alphabet: AlphabetLike, Clock: ClockLike> {
+        items: Vec<(u64, Item<Alphabet, Clock>)>,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> Default for Recording<Alphabet, Clock> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> Recording<Alphabet, Clock> {
+        pub const fn new() -> Self {
+            Self { items: Vec::new() }
+        }
+        #[doc = r" Pops everything buffered on `from`"]
+        pub fn record<From: StreamLike<Alphabet, Clock, SIZE>, const SIZE: usize>(
+            &mut self,
+            now: u64,
+            from: &mut From,
+        ) {
+            self.items.extend(Drain::new(from).map(|item| (now, item)));
+        }
+        pub fn items(&self) -> &[(u64, Item<Alphabet, Clock>)] {
+            &self.items
+        }
+    }
+    #[doc = r" Drives one program (or pipeline) on a virtual clock - it steps the program until it has nothing left to"]
+    #[doc = r" do, then jumps straight to the next moment input is due at"]
+    pub struct SimExecutor {
+        now: u64,
+        steps: usize,
+    }
+    impl Default for SimExecutor {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    impl SimExecutor {
+        pub const fn new() -> Self {
+            Self { now: 0, steps: 0 }
+        }
+        #[doc = r" The virtual clock"]
+        pub fn now(&self) -> u64 {
+            self.now
+        }
+        #[doc = r" How many steps the programs took so far"]
+        pub fn steps(&self) -> usize {
+            self.steps
+        }
+        #[doc = r" Runs `program` until it has finished, or no input is due any more - `io` delivers the input due by"]
+        #[doc = r" the virtual moment it's given and records output, and returns when input is due next"]
+        pub fn run<Program, Io>(
+            &mut self,
+            program: &mut Program,
+            mut io: Io,
+        ) -> Result<(), StreamError>
+        where
+            Program: ProgramLike,
+            Io: FnMut(&mut Program, u64) -> Result<Option<u64>, StreamError>,
+        {
+            program.start()?;
+            loop {
+                let mut next = io(program, self.now)?;
+                let mut stepped = false;
+                while program.run_step()? {
+                    self.steps += 1;
+                    stepped = true;
+                    next = io(program, self.now)?;
+                }
+                let finished = program.propagate_close();
+                io(program, self.now)?;
+                match next {
+                    _ if finished => return Ok(()),
+                    Some(moment) if moment > self.now => self.now = moment,
+                    Some(_) if stepped => (),
+                    _ => return Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[doc = r" Converts moments of unix clocks to and from `SystemTime`"]
+#[cfg(feature = "std")]
+mod system_time {
+    extern crate std;
+    use super::*;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    const NANOS_PER_MICRO: i128 = 1000;
+    #[doc = r" The moment a timestamp falls in - timestamps the clock's moment type can't hold (before the epoch, for"]
+    #[doc = r" unsigned moments) are not encodable"]
+    impl<Clock: UnixClockLike> TryFrom<SystemTime> for UnixMoment<Clock>
+    where
+        Clock::MomentRep: TryFrom<i128>,
+    {
+        type Error = ExitError;
+        fn try_from(time: SystemTime) -> Result<Self, ExitError> {
+            let nanos = match time.duration_since(UNIX_EPOCH) {
+                Ok(since) => since.as_nanos() as i128,
+                Err(before) => -(before.duration().as_nanos() as i128),
+            };
+            let rep = nanos.div_euclid(Clock::MICROS_PER_MOMENT as i128 * NANOS_PER_MICRO);
+            Clock::MomentRep::try_from(rep)
+                .map(Self)
+                .map_err(|_| ExitError::NotEncodable)
+        }
+    }
+    #[doc = r" When the moment starts - panicking past what `SystemTime` holds, as adding durations onto it does"]
+    impl<Clock: UnixClockLike> From<UnixMoment<Clock>> for SystemTime
+    where
+        Clock::MomentRep: Into<i128>,
+    {
+        fn from(moment: UnixMoment<Clock>) -> Self {
+            let micros = moment.0.into() * Clock::MICROS_PER_MOMENT as i128;
+            let abs = micros.unsigned_abs();
+            let since = Duration::new(
+                (abs / 1_000_000) as u64,
+                (abs % 1_000_000) as u32 * NANOS_PER_MICRO as u32,
+            );
+            if micros < 0 {
+                UNIX_EPOCH - since
+            } else {
+                UNIX_EPOCH + since
+            }
+        }
+    }
+}
+
+#[doc = r" Converts moments of unix clocks to and from the `time` crate's dates and times - to log them in a human-readable"]
+#[doc = r" form, or take them from parsed ISO 8601 inputs"]
+#[cfg(feature = "time")]
+mod datetime {
+    use super::*;
+    use ::time::OffsetDateTime;
+    const NANOS_PER_MICRO: i128 = 1000;
+    impl<Clock: UnixClockLike> UnixMoment<Clock>
+    where
+        Clock::MomentRep: Into<i128>,
+    {
+        #[doc = r" When the moment starts, in UTC - None past what `OffsetDateTime` holds"]
+        pub fn to_datetime(self) -> Option<OffsetDateTime> {
+            let nanos = self
+                .0
+                .into()
+                .checked_mul(Clock::MICROS_PER_MOMENT as i128 * NANOS_PER_MICRO)?;
+            OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+        }
+    }
+    impl<Clock: UnixClockLike> UnixMoment<Clock>
+    where
+        Clock::MomentRep: TryFrom<i128>,
+    {
+        #[doc = r" The moment a date and time falls in - not encodable when the clock's moment type can't hold it"]
+        pub fn from_datetime(datetime: OffsetDateTime) -> Result<Self, ExitError> {
+            let rep = datetime
+                .unix_timestamp_nanos()
+                .div_euclid(Clock::MICROS_PER_MOMENT as i128 * NANOS_PER_MICRO);
+            Clock::MomentRep::try_from(rep)
+                .map(Self)
+                .map_err(|_| ExitError::NotEncodable)
+        }
+    }
+}
+
+#[doc = r" Bridges streams and std's readers, writers and files - as plain bytes (for byte-sized characters), or in a binary wire"]
+#[doc = r" format carrying moments along, for programs exchanging streams across processes (over pipes, or TCP)"]
+#[cfg(feature = "std")]
+pub mod io {
+    extern crate std;
+    use super::*;
+    use std::format;
+    use std::io::{BufRead, Read, Write};
+    use std::string::String;
+    use std::vec::Vec;
+    #[derive(Debug)]
+    pub enum IoError {
+        Io(std::io::Error),
+        Stream(StreamError),
+        Wire(WireError),
+    }
+    impl From<WireError> for IoError {
+        fn from(err: WireError) -> Self {
+            Self::Wire(err)
+        }
+    }
+    impl From<std::io::Error> for IoError {
+        fn from(err: std::io::Error) -> Self {
+            Self::Io(err)
+        }
+    }
+    impl From<StreamError> for IoError {
+        fn from(err: StreamError) -> Self {
+            Self::Stream(err)
+        }
+    }
+    impl From<ExitError> for IoError {
+        fn from(err: ExitError) -> Self {
+            Self::Stream(StreamError::Exit(err))
+        }
+    }
+    #[doc = r" Where durations of bytes end - each read, each line (the newline itself becomes the moment), or every so"]
+    #[doc = r" many bytes"]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum Moments {
+        PerRead,
+        PerLine,
+        PerBytes(usize),
+    }
+    #[doc = r" Feeds what a reader reads onto a gateway - the moments it emits count durations, starting at 1"]
+    pub struct ReaderGateway<R: Read> {
+        reader: R,
+        moments: Moments,
+        buffer: Vec<u8>,
+        read: usize,
+        pos: usize,
+        count: u64,
+        open_duration: bool,
+        duration_bytes: usize,
+        eof: bool,
+    }
+    impl<R: Read> ReaderGateway<R> {
+        pub fn new(reader: R, moments: Moments) -> Self {
+            Self::with_capacity(reader, moments, 4096)
+        }
+        #[doc = r" Reads at most `capacity` bytes at a time"]
+        pub fn with_capacity(reader: R, moments: Moments, capacity: usize) -> Self {
+            Self {
+                reader,
+                moments,
+                buffer: std :: vec ! [0 ; capacity],
+                read: 0,
+                pos: 0,
+                count: 0,
+                open_duration: false,
+                duration_bytes: 0,
+                eof: false,
+            }
+        }
+        pub fn into_inner(self) -> R {
+            self.reader
+        }
+        fn push_moment<
+            Alphabet: AlphabetLike,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            const SIZE: usize,
+        >(
+            &mut self,
+            to: &mut To,
+        ) -> Result<(), IoError>
+        where
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            let moment =
+                Clock::MomentRep::try_from(self.count + 1).map_err(|_| ExitError::NotEncodable)?;
+            to.push_moment(moment)?;
+            self.count += 1;
+            self.open_duration = false;
+            self.duration_bytes = 0;
+            Ok(())
+        }
+        #[doc = r" Pushes what was read onto `to` for as long as it has room, reading once more when everything read so"]
+        #[doc = r" far was pushed - `to` is closed once the reader hits its end"]
+        pub fn feed<Alphabet, Clock, To, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            let mut reads = 0;
+            while to.accepting_pushes() {
+                if matches ! (self . moments , Moments :: PerBytes (bytes) if self . open_duration && self . duration_bytes >= bytes)
+                {
+                    self.push_moment(to)?;
+                } else if self.pos < self.read {
+                    let byte = self.buffer[self.pos];
+                    if self.moments == Moments::PerLine && byte == b'\n' {
+                        self.push_moment(to)?;
+                    } else {
+                        match Alphabet::to_char(byte) {
+                            Ok(chr) => to.push(chr)?,
+                            Err(_) => to.push_invalid(byte)?,
+                        }
+                        self.open_duration = true;
+                        self.duration_bytes += 1;
+                    }
+                    self.pos += 1;
+                } else if self.moments == Moments::PerRead && self.open_duration {
+                    self.push_moment(to)?;
+                } else if self.eof {
+                    if self.open_duration {
+                        self.push_moment(to)?;
+                    }
+                    to.close();
+                } else if reads == 0 {
+                    self.read = self.reader.read(&mut self.buffer)?;
+                    self.pos = 0;
+                    self.eof = self.read == 0;
+                    reads += 1;
+                } else {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+    #[doc = r" Feeds stdin onto a gateway - what entry programs read their `from=stdin` gateway with"]
+    pub type StdinGateway = ReaderGateway<std::io::Stdin>;
+    impl ReaderGateway<std::io::Stdin> {
+        pub fn stdin(moments: Moments) -> Self {
+            Self::new(std::io::stdin(), moments)
+        }
+    }
+    #[doc = r" Drains an exit into a writer - characters as their byte values, and (per line) every moment as a newline"]
+    pub struct WriterExit<W: Write> {
+        writer: W,
+        moments: Moments,
+        pending: Vec<u8>,
+    }
+    impl<W: Write> WriterExit<W> {
+        pub fn new(writer: W, moments: Moments) -> Self {
+            Self {
+                writer,
+                moments,
+                pending: Vec::new(),
+            }
+        }
+        pub fn into_inner(self) -> W {
+            self.writer
+        }
+        #[doc = r" Writes everything buffered on `from` - the writer is flushed once `from` is drained"]
+        pub fn drain<Alphabet, Clock, From, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            From: StreamLike<Alphabet, Clock, SIZE>,
+        {
+            for item in Drain::new(from) {
+                match item {
+                    StreamItem::Character(chr) => self.pending.push(Alphabet::to_val(chr)),
+                    StreamItem::InvalidChar(byte) => self.pending.push(byte),
+                    StreamItem::Moment(_) if self.moments == Moments::PerLine => {
+                        self.pending.push(b'\n')
+                    }
+                    StreamItem::Moment(_) | StreamItem::Empty => (),
+                }
+            }
+            self.writer.write_all(&self.pending)?;
+            self.pending.clear();
+            if from.is_drained() {
+                self.writer.flush()?;
+            }
+            Ok(())
+        }
+    }
+    #[doc = r" Writes an exit to stdout, every moment ending a line - what entry programs write their `to=stdout` exits with"]
+    pub type StdoutExit = WriterExit<std::io::Stdout>;
+    impl WriterExit<std::io::Stdout> {
+        pub fn stdout() -> Self {
+            Self::new(std::io::stdout(), Moments::PerLine)
+        }
+    }
+    #[doc = r" Input that doesn't hold what its format says it should - an index entry that doesn't fit the data file it"]
+    #[doc = r" goes along with, a malformed CSV row, ..."]
+    fn invalid_data(message: impl Into<String>) -> IoError {
+        IoError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            message.into(),
+        ))
+    }
+    enum FileSource {
+        Derived(ReaderGateway<std::fs::File>),
+        Indexed {
+            data: std::io::BufReader<std::fs::File>,
+            index: std::io::Lines<std::io::BufReader<std::fs::File>>,
+            offset: u64,
+            next: Option<(u64, u64)>,
+        },
+    }
+    #[doc = r" Feeds a file onto a gateway - with moments derived from its bytes (each line, or every so many bytes) as a"]
+    #[doc = r" ReaderGateway does, or read from the index a FileExit wrote along with it"]
+    pub struct FileGateway {
+        source: FileSource,
+    }
+    impl FileGateway {
+        pub fn open(path: impl AsRef<std::path::Path>, moments: Moments) -> Result<Self, IoError> {
+            let file = std::fs::File::open(path)?;
+            Ok(Self {
+                source: FileSource::Derived(ReaderGateway::new(file, moments)),
+            })
+        }
+        #[doc = r" Reads a data file along with its moment index - see FileExit for the format of both"]
+        pub fn open_indexed(
+            path: impl AsRef<std::path::Path>,
+            index_path: impl AsRef<std::path::Path>,
+        ) -> Result<Self, IoError> {
+            let data = std::io::BufReader::new(std::fs::File::open(path)?);
+            let index = std::io::BufReader::new(std::fs::File::open(index_path)?).lines();
+            Ok(Self {
+                source: FileSource::Indexed {
+                    data,
+                    index,
+                    offset: 0,
+                    next: None,
+                },
+            })
+        }
+        #[doc = r" Pushes what was read onto `to` for as long as it has room - `to` is closed once the file was read"]
+        #[doc = r" entirely. Characters following the last moment of an index stay an open duration."]
+        pub fn feed<Alphabet, Clock, To, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            let (data, index, offset, next) = match &mut self.source {
+                FileSource::Derived(reader) => return reader.feed(to),
+                FileSource::Indexed {
+                    data,
+                    index,
+                    offset,
+                    next,
+                } => (data, index, offset, next),
+            };
+            while to.accepting_pushes() {
+                if next.is_none() {
+                    *next = match index.next().transpose()? {
+                        Some(line) => {
+                            let entry = line.split_once(' ').and_then(|(at, moment)| {
+                                Some((at.parse().ok()?, moment.parse().ok()?))
+                            });
+                            Some(entry.ok_or_else(|| invalid_data("malformed index entry"))?)
+                        }
+                        None => None,
+                    };
+                }
+                match *next {
+                    Some((at, _)) if at < *offset => {
+                        return Err(invalid_data("index entries out of order"))
+                    }
+                    Some((at, moment)) if at == *offset => {
+                        let moment = Clock::MomentRep::try_from(moment)
+                            .map_err(|_| ExitError::NotEncodable)?;
+                        to.push_moment(moment)?;
+                        *next = None;
+                        continue;
+                    }
+                    _ => (),
+                }
+                let byte = match data.fill_buf()?.first() {
+                    Some(byte) => *byte,
+                    None if next.is_some() => {
+                        return Err(invalid_data("index entry past the end of the file"))
+                    }
+                    None => {
+                        to.close();
+                        break;
+                    }
+                };
+                match Alphabet::to_char(byte) {
+                    Ok(chr) => to.push(chr)?,
+                    Err(_) => to.push_invalid(byte)?,
+                }
+                data.consume(1);
+                *offset += 1;
+            }
+            Ok(())
+        }
+    }
+    #[doc = r" Drains an exit into a data file holding its characters (as their byte values), and an index of its moments"]
+    #[doc = r" - a line per moment, holding how many bytes of the data file come before it and its value (`12 3`) - so a"]
+    #[doc = r" FileGateway can read the stream back as it was"]
+    pub struct FileExit {
+        data: std::io::BufWriter<std::fs::File>,
+        index: std::io::BufWriter<std::fs::File>,
+        offset: u64,
+    }
+    impl FileExit {
+        pub fn create(
+            path: impl AsRef<std::path::Path>,
+            index_path: impl AsRef<std::path::Path>,
+        ) -> Result<Self, IoError> {
+            Ok(Self {
+                data: std::io::BufWriter::new(std::fs::File::create(path)?),
+                index: std::io::BufWriter::new(std::fs::File::create(index_path)?),
+                offset: 0,
+            })
+        }
+        #[doc = r" Writes everything buffered on `from` - both files are flushed once `from` is drained"]
+        pub fn drain<Alphabet, Clock, From, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            From: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: Into<u64>,
+        {
+            for item in Drain::new(from) {
+                match item {
+                    StreamItem::Character(chr) => self.data.write_all(&[Alphabet::to_val(chr)])?,
+                    StreamItem::InvalidChar(byte) => self.data.write_all(&[byte])?,
+                    StreamItem::Moment(moment) => {
+                        writeln!(self.index, "{} {}", self.offset, moment.into())?;
+                        continue;
+                    }
+                    StreamItem::Empty => continue,
+                }
+                self.offset += 1;
+            }
+            if from.is_drained() {
+                self.data.flush()?;
+                self.index.flush()?;
+            }
+            Ok(())
+        }
+    }
+    #[doc = r#" Splits a CSV record into its fields - quoted fields (`"a, ""b"""`) may hold commas, quotes and newlines."#]
+    #[doc = r" None while a quoted field is still open at the end of the record."]
+    fn csv_fields(record: &str) -> Option<Vec<String>> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = record.chars().peekable();
+        while let Some(chr) = chars.next() {
+            match (chr, quoted) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                ('"', true) => quoted = false,
+                ('"', false) if field.is_empty() => quoted = true,
+                (',', false) => fields.push(core::mem::take(&mut field)),
+                (chr, _) => field.push(chr),
+            }
+        }
+        if quoted {
+            return None;
+        }
+        fields.push(field);
+        Some(fields)
+    }
+    fn csv_digits(text: Option<&str>) -> Option<i64> {
+        text.filter(|text| !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit()))?
+            .parse()
+            .ok()
+    }
+    #[doc = r" Days from 1970-01-01 to a date of the proleptic Gregorian calendar"]
+    fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+    #[doc = r" Milliseconds since the unix epoch of an RFC 3339 date and time - `2024-01-31T12:00:00Z`,"]
+    #[doc = r" `2024-01-31 12:00:00.250+02:00`, ... (UTC when the offset is left out)"]
+    fn parse_datetime(text: &str) -> Option<i64> {
+        let bytes = text.as_bytes();
+        if bytes.len() < 19
+            || bytes[4] != b'-'
+            || bytes[7] != b'-'
+            || !matches!(bytes[10], b'T' | b't' | b' ')
+            || bytes[13] != b':'
+            || bytes[16] != b':'
+        {
+            return None;
+        }
+        let (year, month, day) = (
+            csv_digits(text.get(0..4))?,
+            csv_digits(text.get(5..7))?,
+            csv_digits(text.get(8..10))?,
+        );
+        let (hour, minute, second) = (
+            csv_digits(text.get(11..13))?,
+            csv_digits(text.get(14..16))?,
+            csv_digits(text.get(17..19))?,
+        );
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return None;
+        }
+        let mut rest = &text[19..];
+        let mut millis = 0;
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let digits = fraction
+                .bytes()
+                .take_while(|byte| byte.is_ascii_digit())
+                .count();
+            if digits == 0 {
+                return None;
+            }
+            millis = fraction
+                .bytes()
+                .take(digits)
+                .chain(core::iter::repeat(b'0'))
+                .take(3)
+                .fold(0, |millis, digit| millis * 10 + (digit - b'0') as i64);
+            rest = &fraction[digits..];
+        }
+        let offset = match rest {
+            "" | "Z" | "z" => 0,
+            offset if offset.len() == 6 && offset.as_bytes()[3] == b':' => {
+                let minutes = csv_digits(offset.get(1..3))? * 60 + csv_digits(offset.get(4..6))?;
+                match offset.as_bytes()[0] {
+                    b'+' => minutes * 60,
+                    b'-' => -minutes * 60,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        let seconds =
+            days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+        Some(seconds * 1000 + millis)
+    }
+    #[doc = r" A timestamp of an event in the units of a clock - integers are taken as they are, while RFC 3339 dates and"]
+    #[doc = r" times (any ISO 8601 ones, with the time feature) are converted for clocks representing unix seconds or"]
+    #[doc = r" milliseconds"]
+    fn parse_timestamp(text: &str, represents: &str) -> Option<u64> {
+        let text = text.trim();
+        if let Some(timestamp) = csv_digits(Some(text)) {
+            return u64::try_from(timestamp).ok();
+        }
+        let millis = parse_datetime(text);
+        #[cfg(feature = "time")]
+        let millis = millis.or_else(|| parse_iso8601(text));
+        unix_millis_as(millis?, represents)
+    }
+    #[doc = r" Milliseconds since the unix epoch of an ISO 8601 date and time - in the basic format (`20240131T120000Z`),"]
+    #[doc = r" with week dates (`2024-W05-3T12:00Z`), ..."]
+    #[cfg(feature = "time")]
+    fn parse_iso8601(text: &str) -> Option<i64> {
+        use ::time::format_description::well_known::Iso8601;
+        let datetime = ::time::OffsetDateTime::parse(text, &Iso8601::DEFAULT).ok()?;
+        i64::try_from(datetime.unix_timestamp_nanos().div_euclid(1_000_000)).ok()
+    }
+    #[doc = r" How many milliseconds a moment of a clock representing unix seconds or milliseconds stands for - None for"]
+    #[doc = r" clocks representing anything else"]
+    fn unix_unit_millis(represents: &str) -> Option<i64> {
+        match represents
+            .chars()
+            .filter(|chr| *chr != '_')
+            .collect::<String>()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "unixseconds" => Some(1000),
+            "unixmilliseconds" => Some(1),
+            _ => None,
+        }
+    }
+    #[doc = r" Milliseconds since the unix epoch, in the units of a clock representing unix seconds or milliseconds - None"]
+    #[doc = r" for clocks representing anything else"]
+    fn unix_millis_as(millis: i64, represents: &str) -> Option<u64> {
+        u64::try_from(millis.div_euclid(unix_unit_millis(represents)?)).ok()
+    }
+    #[doc = r" How many milliseconds a moment of a clock representing natural milliseconds, seconds, minutes or hours"]
+    #[doc = r" stands for - None for clocks representing anything else"]
+    fn natural_unit_millis(represents: &str) -> Option<u128> {
+        match represents
+            .chars()
+            .filter(|chr| *chr != '_')
+            .collect::<String>()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "naturalmilliseconds" => Some(1),
+            "naturalseconds" => Some(1000),
+            "naturalminutes" => Some(60_000),
+            "naturalhours" => Some(3_600_000),
+            _ => None,
+        }
+    }
+    #[doc = r" Ticks every so many milliseconds of wall-clock time, pushing a moment onto the gateways it drives at each"]
+    #[doc = r" tick - the time elapsed since it was created for clocks representing natural time (`NATURAL_MILLISECONDS`,"]
+    #[doc = r" ...), the time of the tick for clocks representing unix time, or counting ticks (from 1) for other clocks."]
+    #[doc = r" Ticks missed while the program was busy are caught up on, one at a time."]
+    pub struct RealtimeClockDriver {
+        period: std::time::Duration,
+        started: std::time::Instant,
+        started_unix: std::time::Duration,
+        ticks: u64,
+        due: u64,
+    }
+    impl RealtimeClockDriver {
+        pub fn new(period: std::time::Duration) -> Self {
+            assert!(
+                !period.is_zero(),
+                "RealtimeClockDriver - the period must not be zero"
+            );
+            Self {
+                period,
+                started: std::time::Instant::now(),
+                started_unix: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default(),
+                ticks: 0,
+                due: 0,
+            }
+        }
+        pub fn every_millis(millis: u64) -> Self {
+            Self::new(std::time::Duration::from_millis(millis))
+        }
+        #[doc = r" How many ticks were advanced to so far"]
+        pub fn ticks(&self) -> u64 {
+            self.ticks
+        }
+        #[doc = r" When the next tick is due"]
+        pub fn next_tick(&self) -> std::time::Instant {
+            self.started + self.elapsed(self.ticks + 1)
+        }
+        fn elapsed(&self, tick: u64) -> std::time::Duration {
+            self.period
+                .saturating_mul(u32::try_from(tick).unwrap_or(u32::MAX))
+        }
+        #[doc = r" Advances to the next tick, if it is due - the moment of which `push` then pushes onto each gateway"]
+        pub fn advance(&mut self) -> bool {
+            if self.due == self.ticks {
+                let since_start = self.started.elapsed().as_nanos();
+                self.due = u64::try_from(since_start / self.period.as_nanos()).unwrap_or(u64::MAX);
+            }
+            let advanced = self.ticks < self.due;
+            if advanced {
+                self.ticks += 1;
+            }
+            advanced
+        }
+        #[doc = r" Sleeps until the next tick is due, then advances to it"]
+        pub fn wait(&mut self) {
+            std::thread::sleep(
+                self.next_tick()
+                    .saturating_duration_since(std::time::Instant::now()),
+            );
+            while !self.advance() {
+                std::thread::yield_now();
+            }
+        }
+        #[doc = r" The moment of the current tick, in the units of a clock"]
+        pub fn moment(&self, represents: &str) -> u64 {
+            self.moment_at(self.ticks, represents)
+        }
+        fn moment_at(&self, tick: u64, represents: &str) -> u64 {
+            let elapsed = self.elapsed(tick);
+            if let Some(unit) = natural_unit_millis(represents) {
+                u64::try_from(elapsed.as_millis() / unit).unwrap_or(u64::MAX)
+            } else if unix_unit_millis(represents).is_some() {
+                let millis = (self.started_unix + elapsed).as_millis();
+                unix_millis_as(i64::try_from(millis).unwrap_or(i64::MAX), represents)
+                    .unwrap_or(tick)
+            } else {
+                tick
+            }
+        }
+        #[doc = r" Pushes the moment of the current tick onto `to` - unless it is no later than the last moment pushed"]
+        #[doc = r" onto it, as happens for ticks shorter than a moment of its clock"]
+        pub fn push<Alphabet, Clock, To, const SIZE: usize>(
+            &self,
+            to: &mut To,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            let moment = self.moment(Clock::REPRESENTS);
+            if self.ticks == 0 || self.moment_at(self.ticks - 1, Clock::REPRESENTS) >= moment {
+                return Ok(());
+            }
+            let moment = Clock::MomentRep::try_from(moment).map_err(|_| ExitError::NotEncodable)?;
+            to.push_moment(moment)?;
+            Ok(())
+        }
+    }
+    #[doc = r" Pushes timestamped events onto a gateway - the payload of each event as characters (its bytes), and a"]
+    #[doc = r" moment at each new timestamp, ending the duration of the events before it"]
+    struct EventFeeder {
+        payload: Vec<u8>,
+        pos: usize,
+        timestamp: Option<u64>,
+        ending: Option<u64>,
+    }
+    impl EventFeeder {
+        const fn new() -> Self {
+            Self {
+                payload: Vec::new(),
+                pos: 0,
+                timestamp: None,
+                ending: None,
+            }
+        }
+        #[doc = r" Pushes what is pending of the events so far onto `to` - returns whether `to` has room for more"]
+        fn push_pending<Alphabet, Clock, To, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+        ) -> Result<bool, IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            while to.accepting_pushes() {
+                if let Some(timestamp) = self.ending {
+                    let moment = Clock::MomentRep::try_from(timestamp)
+                        .map_err(|_| ExitError::NotEncodable)?;
+                    to.push_moment(moment)?;
+                    self.ending = None;
+                } else if self.pos < self.payload.len() {
+                    let byte = self.payload[self.pos];
+                    match Alphabet::to_char(byte) {
+                        Ok(chr) => to.push(chr)?,
+                        Err(_) => to.push_invalid(byte)?,
+                    }
+                    self.pos += 1;
+                } else {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        #[doc = r" Starts pushing the next event - timestamps can't go backwards, so one earlier than the timestamp"]
+        #[doc = r" before (which is returned) is left out"]
+        fn start(&mut self, timestamp: u64, payload: &[u8]) -> Result<(), u64> {
+            match self.timestamp {
+                Some(previous) if timestamp < previous => return Err(previous),
+                Some(previous) if timestamp > previous => self.ending = Some(previous),
+                _ => (),
+            }
+            self.timestamp = Some(timestamp);
+            self.payload.clear();
+            self.payload.extend_from_slice(payload);
+            self.pos = 0;
+            Ok(())
+        }
+        #[doc = r" Ends the duration of the last timestamp - no more events follow"]
+        fn end(&mut self) {
+            self.ending = self.timestamp.take();
+        }
+    }
+    #[doc = r" Feeds CSV rows of events onto a gateway - the payload of each row as characters (its bytes), and a moment"]
+    #[doc = r" at each new timestamp, ending the duration of the rows before it. Timestamps can't go backwards."]
+    pub struct CsvGateway<R: BufRead> {
+        reader: R,
+        timestamp_column: usize,
+        payload_column: usize,
+        header: bool,
+        row: usize,
+        events: EventFeeder,
+        eof: bool,
+    }
+    impl<R: BufRead> CsvGateway<R> {
+        #[doc = r" Reads events off of the given columns (counting from 0) of each row"]
+        pub fn new(reader: R, timestamp_column: usize, payload_column: usize) -> Self {
+            Self {
+                reader,
+                timestamp_column,
+                payload_column,
+                header: false,
+                row: 0,
+                events: EventFeeder::new(),
+                eof: false,
+            }
+        }
+        #[doc = r" Skips the first row, naming the columns"]
+        pub fn with_header(mut self) -> Self {
+            self.header = true;
+            self
+        }
+        pub fn into_inner(self) -> R {
+            self.reader
+        }
+        #[doc = r" The fields of the next row - blank lines are skipped"]
+        fn read_row(&mut self) -> Result<Option<Vec<String>>, IoError> {
+            let mut record = String::new();
+            loop {
+                if self.reader.read_line(&mut record)? == 0 {
+                    return match record.trim().is_empty() {
+                        true => Ok(None),
+                        false => Err(invalid_data(format!(
+                            "CSV row {} - quoted field is never closed",
+                            self.row + 1
+                        ))),
+                    };
+                }
+                if record.trim().is_empty() {
+                    record.clear();
+                } else if let Some(fields) = csv_fields(record.trim_end_matches(['\r', '\n'])) {
+                    self.row += 1;
+                    return Ok(Some(fields));
+                }
+            }
+        }
+        #[doc = r" Pushes the events read onto `to` for as long as it has room - `to` is closed once the reader hits its"]
+        #[doc = r" end, after a moment ending the last timestamp's duration"]
+        pub fn feed<Alphabet, Clock, To, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            while self.events.push_pending(to)? {
+                if self.eof {
+                    to.close();
+                    break;
+                }
+                let fields = match self.read_row()? {
+                    Some(_) if self.header && self.row == 1 => continue,
+                    Some(fields) => fields,
+                    None => {
+                        self.eof = true;
+                        self.events.end();
+                        continue;
+                    }
+                };
+                let (Some(timestamp), Some(payload)) = (
+                    fields.get(self.timestamp_column),
+                    fields.get(self.payload_column),
+                ) else {
+                    return Err(invalid_data(format!(
+                        "CSV row {} - has only {} columns",
+                        self.row,
+                        fields.len()
+                    )));
+                };
+                let timestamp = parse_timestamp (timestamp , Clock :: REPRESENTS) . ok_or_else (|| { invalid_data (format ! ("CSV row {} - timestamp ({}) isn't a number, or a date and time the clock ({}) can represent" , self . row , timestamp , Clock :: REPRESENTS)) }) ? ;
+                self.events
+                    .start(timestamp, payload.as_bytes())
+                    .map_err(|previous| {
+                        invalid_data(format!(
+                            "CSV row {} - timestamp ({}) is earlier than the one before ({})",
+                            self.row, timestamp, previous
+                        ))
+                    })?;
+            }
+            Ok(())
+        }
+    }
+    impl CsvGateway<std::io::BufReader<std::fs::File>> {
+        pub fn open(
+            path: impl AsRef<std::path::Path>,
+            timestamp_column: usize,
+            payload_column: usize,
+        ) -> Result<Self, IoError> {
+            Ok(Self::new(
+                std::io::BufReader::new(std::fs::File::open(path)?),
+                timestamp_column,
+                payload_column,
+            ))
+        }
+    }
+    #[doc = r" A packet timestamp (microseconds since the unix epoch) in the units of a clock - converted for clocks"]
+    #[doc = r" representing unix seconds or milliseconds, and taken as it is for any other clock"]
+    fn unix_micros_as(micros: u64, represents: &str) -> u64 {
+        unix_unit_millis(represents).map_or(micros, |unit| micros / (unit as u64 * 1000))
+    }
+    #[doc = r" Packets larger than this are taken for a corrupt capture, whichever snapshot length its header gives"]
+    const PCAP_MAX_PACKET: u32 = 0x0400_0000;
+    #[doc = r" Replays a pcap capture onto a gateway - the bytes of each packet as characters, and a moment at each new"]
+    #[doc = r" packet timestamp, ending the duration of the packets before it. Timestamps are microseconds since the unix"]
+    #[doc = r" epoch (for clocks representing `UNIX_MICROSECONDS`, or anything other than unix time), converted for"]
+    #[doc = r" clocks representing unix seconds or milliseconds. They can't go backwards."]
+    pub struct PcapGateway<R: Read> {
+        reader: R,
+        big_endian: bool,
+        nanos: bool,
+        link_type: u32,
+        packet: usize,
+        data: Vec<u8>,
+        events: EventFeeder,
+        eof: bool,
+    }
+    impl<R: Read> PcapGateway<R> {
+        #[doc = r" Reads the capture's header - microsecond and nanosecond captures are taken, in either byte order"]
+        pub fn new(mut reader: R) -> Result<Self, IoError> {
+            let mut header = [0; 24];
+            if read_full(&mut reader, &mut header)? < header.len() {
+                return Err(invalid_data("pcap - the capture header is cut short"));
+            }
+            let (big_endian, nanos) = match header[..4] {
+                [0xd4, 0xc3, 0xb2, 0xa1] => (false, false),
+                [0xa1, 0xb2, 0xc3, 0xd4] => (true, false),
+                [0x4d, 0x3c, 0xb2, 0xa1] => (false, true),
+                [0xa1, 0xb2, 0x3c, 0x4d] => (true, true),
+                _ => {
+                    return Err(invalid_data(
+                        "pcap - not a pcap capture (pcapng captures are not supported)",
+                    ))
+                }
+            };
+            let mut gateway = Self {
+                reader,
+                big_endian,
+                nanos,
+                link_type: 0,
+                packet: 0,
+                data: Vec::new(),
+                events: EventFeeder::new(),
+                eof: false,
+            };
+            gateway.link_type = gateway.u32_at(&header, 20);
+            Ok(gateway)
+        }
+        #[doc = r" The link-layer header type packets start with - 1 for Ethernet, 101 for raw IP, ..."]
+        pub fn link_type(&self) -> u32 {
+            self.link_type
+        }
+        #[doc = r" How many packets were read so far"]
+        pub fn packets(&self) -> usize {
+            self.packet
+        }
+        pub fn into_inner(self) -> R {
+            self.reader
+        }
+        fn u32_at(&self, bytes: &[u8], at: usize) -> u32 {
+            let word = [bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]];
+            if self.big_endian {
+                u32::from_be_bytes(word)
+            } else {
+                u32::from_le_bytes(word)
+            }
+        }
+        #[doc = r" Reads the next packet into `data` - returning its timestamp, in microseconds"]
+        fn read_packet(&mut self) -> Result<Option<u64>, IoError> {
+            let mut header = [0; 16];
+            match read_full(&mut self.reader, &mut header)? {
+                0 => return Ok(None),
+                16 => (),
+                _ => {
+                    return Err(invalid_data(format!(
+                        "pcap packet {} - its header is cut short",
+                        self.packet + 1
+                    )))
+                }
+            }
+            self.packet += 1;
+            let (seconds, fraction, length) = (
+                self.u32_at(&header, 0),
+                self.u32_at(&header, 4),
+                self.u32_at(&header, 8),
+            );
+            if length > PCAP_MAX_PACKET {
+                return Err(invalid_data(format!(
+                    "pcap packet {} - is {} bytes long",
+                    self.packet, length
+                )));
+            }
+            self.data.resize(length as usize, 0);
+            if read_full(&mut self.reader, &mut self.data)? < self.data.len() {
+                return Err(invalid_data(format!(
+                    "pcap packet {} - is cut short",
+                    self.packet
+                )));
+            }
+            let micros = if self.nanos {
+                fraction as u64 / 1000
+            } else {
+                fraction as u64
+            };
+            Ok(Some(seconds as u64 * 1_000_000 + micros))
+        }
+        #[doc = r" Pushes the packets read onto `to` for as long as it has room - `to` is closed once the capture ends,"]
+        #[doc = r" after a moment ending the last timestamp's duration"]
+        pub fn feed<Alphabet, Clock, To, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            while self.events.push_pending(to)? {
+                if self.eof {
+                    to.close();
+                    break;
+                }
+                let Some(micros) = self.read_packet()? else {
+                    self.eof = true;
+                    self.events.end();
+                    continue;
+                };
+                let timestamp = unix_micros_as(micros, Clock::REPRESENTS);
+                self.events
+                    .start(timestamp, &self.data)
+                    .map_err(|previous| {
+                        invalid_data(format!(
+                            "pcap packet {} - timestamp ({}) is earlier than the one before ({})",
+                            self.packet, timestamp, previous
+                        ))
+                    })?;
+            }
+            Ok(())
+        }
+    }
+    impl PcapGateway<std::io::BufReader<std::fs::File>> {
+        pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, IoError> {
+            Self::new(std::io::BufReader::new(std::fs::File::open(path)?))
+        }
+    }
+    #[doc = r" Reads until `buffer` is full or the reader hits its end - returning how much was read"]
+    fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize, IoError> {
+        let mut read = 0;
+        while read < buffer.len() {
+            match reader.read(&mut buffer[read..]) {
+                Ok(0) => break,
+                Ok(bytes) => read += bytes,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(read)
+    }
+    #[doc = r" Feeds JSON Lines of events onto a gateway - one object per line, its time field a timestamp (a number, or"]
+    #[doc = r" an RFC 3339 string) and its data field the characters (a string, or an array of raw values). A moment is"]
+    #[doc = r" pushed at each new timestamp, ending the duration of the objects before it. Timestamps can't go backwards."]
+    #[cfg(feature = "json")]
+    pub struct JsonlGateway<R: BufRead> {
+        reader: R,
+        time_field: String,
+        data_field: String,
+        line: usize,
+        events: EventFeeder,
+        eof: bool,
+    }
+    #[cfg(feature = "json")]
+    impl<R: BufRead> JsonlGateway<R> {
+        pub fn new(
+            reader: R,
+            time_field: impl Into<String>,
+            data_field: impl Into<String>,
+        ) -> Self {
+            Self {
+                reader,
+                time_field: time_field.into(),
+                data_field: data_field.into(),
+                line: 0,
+                events: EventFeeder::new(),
+                eof: false,
+            }
+        }
+        pub fn into_inner(self) -> R {
+            self.reader
+        }
+        #[doc = r" The object on the next line - blank lines are skipped"]
+        fn read_object(
+            &mut self,
+        ) -> Result<Option<serde_json::Map<String, serde_json::Value>>, IoError> {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                self.line += 1;
+                if !line.trim().is_empty() {
+                    break;
+                }
+            }
+            match serde_json::from_str(&line) {
+                Ok(serde_json::Value::Object(object)) => Ok(Some(object)),
+                Ok(_) => Err(invalid_data(format!(
+                    "JSON line {} - isn't an object",
+                    self.line
+                ))),
+                Err(err) => Err(invalid_data(format!("JSON line {} - {}", self.line, err))),
+            }
+        }
+        #[doc = r" Pushes the events read onto `to` for as long as it has room - `to` is closed once the reader hits its"]
+        #[doc = r" end, after a moment ending the last timestamp's duration"]
+        pub fn feed<Alphabet, Clock, To, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            use serde_json::Value;
+            while self.events.push_pending(to)? {
+                if self.eof {
+                    to.close();
+                    break;
+                }
+                let Some(object) = self.read_object()? else {
+                    self.eof = true;
+                    self.events.end();
+                    continue;
+                };
+                let timestamp = match object.get(&self.time_field) {
+                    Some(Value::Number(number)) => number.as_u64(),
+                    Some(Value::String(text)) => parse_timestamp(text, Clock::REPRESENTS),
+                    _ => None,
+                };
+                let timestamp = timestamp . ok_or_else (|| { invalid_data (format ! ("JSON line {} - {} isn't a timestamp (or a date and time) the clock ({}) can represent" , self . line , self . time_field , Clock :: REPRESENTS)) }) ? ;
+                let data = match object.get(&self.data_field) {
+                    Some(Value::String(text)) => Some(text.as_bytes().to_vec()),
+                    Some(Value::Array(values)) => values
+                        .iter()
+                        .map(|value| value.as_u64().and_then(|value| u8::try_from(value).ok()))
+                        .collect(),
+                    Some(Value::Null) | None => Some(Vec::new()),
+                    _ => None,
+                };
+                let data = data.ok_or_else(|| {
+                    invalid_data(format!(
+                        "JSON line {} - {} isn't a string, or an array of raw values",
+                        self.line, self.data_field
+                    ))
+                })?;
+                self.events.start(timestamp, &data).map_err(|previous| {
+                    invalid_data(format!(
+                        "JSON line {} - timestamp ({}) is earlier than the one before ({})",
+                        self.line, timestamp, previous
+                    ))
+                })?;
+            }
+            Ok(())
+        }
+    }
+    #[doc = r" Drains an exit into JSON Lines - one object per duration, its time field the moment ending it and its data"]
+    #[doc = r" field its characters (as a string, invalid UTF-8 replaced). Characters of a duration not ended yet wait for"]
+    #[doc = r" its moment."]
+    #[cfg(feature = "json")]
+    pub struct JsonlExit<W: Write> {
+        writer: W,
+        time_field: String,
+        data_field: String,
+        pending: Vec<u8>,
+    }
+    #[cfg(feature = "json")]
+    impl<W: Write> JsonlExit<W> {
+        pub fn new(
+            writer: W,
+            time_field: impl Into<String>,
+            data_field: impl Into<String>,
+        ) -> Self {
+            Self {
+                writer,
+                time_field: time_field.into(),
+                data_field: data_field.into(),
+                pending: Vec::new(),
+            }
+        }
+        pub fn into_inner(self) -> W {
+            self.writer
+        }
+        #[doc = r" Writes every duration buffered on `from` - the writer is flushed once `from` is drained"]
+        pub fn drain<Alphabet, Clock, From, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            From: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: Into<u64>,
+        {
+            for item in Drain::new(from) {
+                match item {
+                    StreamItem::Character(chr) => self.pending.push(Alphabet::to_val(chr)),
+                    StreamItem::InvalidChar(byte) => self.pending.push(byte),
+                    StreamItem::Moment(moment) => {
+                        let mut object = serde_json::Map::new();
+                        object.insert(self.time_field.clone(), moment.into().into());
+                        object.insert(
+                            self.data_field.clone(),
+                            String::from_utf8_lossy(&self.pending).into(),
+                        );
+                        serde_json::to_writer(&mut self.writer, &object)
+                            .map_err(std::io::Error::from)?;
+                        self.writer.write_all(b"\n")?;
+                        self.pending.clear();
+                    }
+                    StreamItem::Empty => (),
+                }
+            }
+            if from.is_drained() {
+                self.writer.flush()?;
+            }
+            Ok(())
+        }
+    }
+    #[doc = r" Writes the durations of an exit to a Parquet file - batched into Arrow record batches of so many rows, each"]
+    #[doc = r" row the moment ending a duration (a `moment` UInt64 column) and its characters as their byte values (a"]
+    #[doc = r" `payload` Binary column). The file is complete once `finish`ed."]
+    #[cfg(feature = "parquet")]
+    pub struct ParquetExit<W: Write + Send> {
+        writer: parquet::arrow::ArrowWriter<W>,
+        schema: arrow_schema::SchemaRef,
+        batch_rows: usize,
+        moments: arrow_array::builder::UInt64Builder,
+        payloads: arrow_array::builder::BinaryBuilder,
+        pending: Vec<u8>,
+    }
+    #[cfg(feature = "parquet")]
+    impl<W: Write + Send> ParquetExit<W> {
+        pub fn new(writer: W, batch_rows: usize) -> Result<Self, IoError> {
+            use arrow_schema::{DataType, Field, Schema};
+            let schema = std::sync::Arc::new(Schema::new(std::vec![
+                Field::new("moment", DataType::UInt64, false),
+                Field::new("payload", DataType::Binary, false)
+            ]));
+            Ok(Self {
+                writer: parquet::arrow::ArrowWriter::try_new(writer, schema.clone(), None)
+                    .map_err(client_error)?,
+                schema,
+                batch_rows: batch_rows.max(1),
+                moments: arrow_array::builder::UInt64Builder::with_capacity(batch_rows),
+                payloads: arrow_array::builder::BinaryBuilder::new(),
+                pending: Vec::new(),
+            })
+        }
+        #[doc = r" The schema of the record batches written"]
+        pub fn schema(&self) -> arrow_schema::SchemaRef {
+            self.schema.clone()
+        }
+        #[doc = r" Writes the rows batched so far as a record batch"]
+        fn write_batch(&mut self) -> Result<(), IoError> {
+            use arrow_array::builder::ArrayBuilder;
+            if self.moments.is_empty() {
+                return Ok(());
+            }
+            let columns: Vec<arrow_array::ArrayRef> = std::vec![
+                std::sync::Arc::new(self.moments.finish()),
+                std::sync::Arc::new(self.payloads.finish())
+            ];
+            let batch = arrow_array::RecordBatch::try_new(self.schema.clone(), columns)
+                .map_err(client_error)?;
+            self.writer.write(&batch).map_err(client_error)
+        }
+        #[doc = r" Batches every duration buffered on `from` - writing a record batch whenever one is full, and what's left"]
+        #[doc = r" of it (flushing a row group) once `from` is drained"]
+        pub fn drain<Alphabet, Clock, From, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            From: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: Into<u64>,
+        {
+            use arrow_array::builder::ArrayBuilder;
+            for item in Drain::new(from) {
+                match item {
+                    StreamItem::Character(chr) => self.pending.push(Alphabet::to_val(chr)),
+                    StreamItem::InvalidChar(byte) => self.pending.push(byte),
+                    StreamItem::Moment(moment) => {
+                        self.moments.append_value(moment.into());
+                        self.payloads.append_value(&self.pending);
+                        self.pending.clear();
+                        if self.moments.len() >= self.batch_rows {
+                            self.write_batch()?;
+                        }
+                    }
+                    StreamItem::Empty => (),
+                }
+            }
+            if from.is_drained() {
+                self.write_batch()?;
+                self.writer.flush().map_err(client_error)?;
+            }
+            Ok(())
+        }
+        #[doc = r" Writes what's left batched and the file's footer - characters after the last moment are left out"]
+        pub fn finish(mut self) -> Result<W, IoError> {
+            self.write_batch()?;
+            self.writer.into_inner().map_err(client_error)
+        }
+    }
+    #[cfg(feature = "parquet")]
+    impl ParquetExit<std::fs::File> {
+        pub fn create(
+            path: impl AsRef<std::path::Path>,
+            batch_rows: usize,
+        ) -> Result<Self, IoError> {
+            Self::new(std::fs::File::create(path)?, batch_rows)
+        }
+    }
+    #[doc = r" Why a stream read in the wire format was rejected"]
+    #[derive(Debug)]
+    pub enum WireError {
+        #[doc = r" The reader doesn't carry a stream in the wire format - or one of a version this runtime can't read"]
+        UnknownFormat,
+        #[doc = r" The stream was written in another alphabet - or in one of the same name, defining other characters"]
+        AlphabetMismatch { name: String, hash: u64 },
+        #[doc = r" The stream's moments represent something else than the clock's"]
+        ClockMismatch { represents: String },
+        #[doc = r" A frame doesn't match its checksum"]
+        Corrupted,
+        #[doc = r" A frame that can't be decoded - an unknown tag, or a value out of range"]
+        Malformed(&'static str),
+    }
+    const WIRE_VERSION: u8 = 1;
+    const WIRE_CHARACTERS: u8 = 0;
+    const WIRE_MOMENT: u8 = 1;
+    const WIRE_END: u8 = 2;
+    const WIRE_HEADER: u8 = 3;
+    #[doc = r" The longest string a header may carry - alphabet names and clock reprs are short, and a corrupted length"]
+    #[doc = r" is rejected before anything is allocated for it, ahead of the checksum"]
+    const WIRE_MAX_STR: usize = 256;
+    #[doc = r" CRC32 (the IEEE polynomial, as zlib and Ethernet use it) of each byte value"]
+    const CRC32_TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut byte = 0;
+        while byte < 256 {
+            let mut crc = byte as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[byte] = crc;
+            byte += 1;
+        }
+        table
+    };
+    fn crc32(bytes: &[u8]) -> u32 {
+        !bytes.iter().fold(!0u32, |crc, byte| {
+            CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+        })
+    }
+    fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+    fn write_str(out: &mut Vec<u8>, value: &str) {
+        write_varint(out, value.len() as u64);
+        out.extend_from_slice(value.as_bytes());
+    }
+    #[doc = r" Reads a frame's bytes - keeping them, for its checksum"]
+    struct FrameReader<'r, R: Read> {
+        reader: &'r mut R,
+        frame: Vec<u8>,
+    }
+    impl<R: Read> FrameReader<'_, R> {
+        fn read(&mut self, len: usize) -> Result<&[u8], IoError> {
+            let start = self.frame.len();
+            self.frame.resize(start + len, 0);
+            self.reader.read_exact(&mut self.frame[start..])?;
+            Ok(&self.frame[start..])
+        }
+        fn read_varint(&mut self) -> Result<u64, IoError> {
+            let mut value = 0u64;
+            for shift in (0..64).step_by(7) {
+                let byte = self.read(1)?[0];
+                value |= u64::from(byte & 0x7F) << shift;
+                if byte & 0x80 == 0 {
+                    return Ok(value);
+                }
+            }
+            Err(WireError::Malformed("varint longer than 64 bits").into())
+        }
+        fn read_str(&mut self) -> Result<String, IoError> {
+            let len = self.read_varint()?;
+            let len = usize::try_from(len)
+                .ok()
+                .filter(|len| *len <= WIRE_MAX_STR)
+                .ok_or(WireError::Malformed("string too long"))?;
+            Ok(String::from_utf8_lossy(self.read(len)?).into_owned())
+        }
+        #[doc = r" Checks the frame read so far against the checksum ending it"]
+        fn check(&mut self) -> Result<(), IoError> {
+            let expected = crc32(&self.frame);
+            let mut checksum = [0u8; 4];
+            self.reader.read_exact(&mut checksum)?;
+            if u32::from_le_bytes(checksum) == expected {
+                Ok(())
+            } else {
+                Err(WireError::Corrupted.into())
+            }
+        }
+    }
+    #[doc = r" Writes streams in the wire format, for a `WireDecoder` to read back - in another process, most likely"]
+    #[derive(Debug, Default)]
+    pub struct WireEncoder {
+        header_written: bool,
+    }
+    impl WireEncoder {
+        pub fn new() -> Self {
+            Self {
+                header_written: false,
+            }
+        }
+        fn frame(out: &mut Vec<u8>, start: usize) {
+            let checksum = crc32(&out[start..]);
+            out.extend_from_slice(&checksum.to_le_bytes());
+        }
+        #[doc = r" Writes everything buffered on `from` to `writer` - after the header, on the first call - then the end"]
+        #[doc = r" of the stream, once `from` is drained. Raw values move across as they are, whether they are characters"]
+        #[doc = r" of the alphabet or not."]
+        pub fn encode_to<Alphabet, Clock, From, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+            writer: &mut impl Write,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike,
+            Clock: ClockLike,
+            From: GatewayLike<
+                Alphabet,
+                Clock,
+                SIZE,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            >,
+            Alphabet::CharRep: Into<u64>,
+            Clock::MomentRep: Into<u64>,
+        {
+            let width = core::mem::size_of::<Alphabet::CharRep>();
+            let mut out = Vec::new();
+            let mut run = Vec::new();
+            if !self.header_written {
+                out.extend_from_slice(&[WIRE_HEADER, WIRE_VERSION]);
+                write_str(&mut out, Alphabet::NAME);
+                out.extend_from_slice(&Alphabet::HASH.to_le_bytes());
+                write_str(&mut out, Clock::REPRESENTS);
+                out.push(width as u8);
+                Self::frame(&mut out, 0);
+            }
+            loop {
+                match from.pop_raw() {
+                    StreamItem::Character(chr) => run.push(chr),
+                    item => {
+                        if !run.is_empty() {
+                            let start = out.len();
+                            out.push(WIRE_CHARACTERS);
+                            write_varint(&mut out, run.len() as u64);
+                            for chr in run.drain(..) {
+                                out.extend_from_slice(
+                                    &Into::<u64>::into(chr).to_le_bytes()[..width],
+                                );
+                            }
+                            Self::frame(&mut out, start);
+                        }
+                        match item {
+                            StreamItem::Moment(moment) => {
+                                let start = out.len();
+                                out.push(WIRE_MOMENT);
+                                write_varint(&mut out, moment.into());
+                                Self::frame(&mut out, start);
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+            }
+            if from.is_drained() {
+                let start = out.len();
+                out.push(WIRE_END);
+                Self::frame(&mut out, start);
+            }
+            writer.write_all(&out)?;
+            self.header_written = true;
+            Ok(())
+        }
+    }
+    #[doc = r" Reads streams written by a `WireEncoder` - keeping what was read until the stream it is pushed onto has"]
+    #[doc = r" room. Streams of another alphabet or clock are rejected, as are corrupted frames."]
+    pub struct WireDecoder<Alphabet: AlphabetLike, Clock: ClockLike> {
+        pending: std::collections::VecDeque<StreamItem<Alphabet::CharRep, Clock::MomentRep>>,
+        header_read: bool,
+        ended: bool,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> Default for WireDecoder<Alphabet, Clock> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> WireDecoder<Alphabet, Clock> {
+        pub fn new() -> Self {
+            Self {
+                pending: std::collections::VecDeque::new(),
+                header_read: false,
+                ended: false,
+            }
+        }
+        #[doc = r" Whether the end of the stream was read"]
+        pub fn is_ended(&self) -> bool {
+            self.ended
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> WireDecoder<Alphabet, Clock>
+    where
+        Alphabet::CharRep: TryFrom<u64>,
+        Clock::MomentRep: TryFrom<u64>,
+    {
+        fn read_header(frame: &mut FrameReader<'_, impl Read>) -> Result<(), IoError> {
+            if frame.read(1)?[0] != WIRE_VERSION {
+                return Err(WireError::UnknownFormat.into());
+            }
+            let name = frame.read_str()?;
+            let hash = u64::from_le_bytes(frame.read(8)?.try_into().unwrap());
+            let represents = frame.read_str()?;
+            let width = frame.read(1)?[0];
+            frame.check()?;
+            if name != Alphabet::NAME
+                || hash != Alphabet::HASH
+                || usize::from(width) != core::mem::size_of::<Alphabet::CharRep>()
+            {
+                return Err(WireError::AlphabetMismatch { name, hash }.into());
+            }
+            if represents != Clock::REPRESENTS {
+                return Err(WireError::ClockMismatch { represents }.into());
+            }
+            Ok(())
+        }
+        fn read_frame(&mut self, reader: &mut impl Read) -> Result<(), IoError> {
+            let mut tag = [0u8];
+            reader.read_exact(&mut tag)?;
+            let mut frame = FrameReader {
+                reader,
+                frame: std::vec![tag[0]],
+            };
+            match tag[0] {
+                WIRE_HEADER if !self.header_read => {
+                    Self::read_header(&mut frame)?;
+                    self.header_read = true;
+                    return Ok(());
+                }
+                _ if !self.header_read => return Err(WireError::UnknownFormat.into()),
+                WIRE_CHARACTERS => {
+                    let width = core::mem::size_of::<Alphabet::CharRep>();
+                    let len = frame.read_varint()?;
+                    let mut chars = Vec::new();
+                    for _ in 0..len {
+                        let mut bytes = [0u8; 8];
+                        bytes[..width].copy_from_slice(frame.read(width)?);
+                        let chr = Alphabet::CharRep::try_from(u64::from_le_bytes(bytes))
+                            .map_err(|_| WireError::Malformed("character out of range"))?;
+                        chars.push(StreamItem::Character(chr));
+                    }
+                    frame.check()?;
+                    self.pending.extend(chars);
+                }
+                WIRE_MOMENT => {
+                    let moment = Clock::MomentRep::try_from(frame.read_varint()?)
+                        .map_err(|_| WireError::Malformed("moment out of range"))?;
+                    frame.check()?;
+                    self.pending.push_back(StreamItem::Moment(moment));
+                }
+                WIRE_END => {
+                    frame.check()?;
+                    self.ended = true;
+                }
+                _ => return Err(WireError::Malformed("unknown frame tag").into()),
+            }
+            Ok(())
+        }
+        #[doc = r" Pushes what was read onto `to` for as long as it has room, reading one more frame (blocking until it is"]
+        #[doc = r" whole) when everything read so far was pushed - `to` is closed once the stream ends. Returns whether"]
+        #[doc = r" anything was pushed, or `to` closed."]
+        pub fn decode_from<To, const SIZE: usize>(
+            &mut self,
+            reader: &mut impl Read,
+            to: &mut To,
+        ) -> Result<bool, IoError>
+        where
+            To: StreamLike<Alphabet, Clock, SIZE>
+                + ExitLike<
+                    Alphabet,
+                    Clock,
+                    InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+                >,
+        {
+            let mut moved = false;
+            loop {
+                while let Some(item) = self.pending.front() {
+                    if to.room() == 0 {
+                        return Ok(moved);
+                    }
+                    to.push_raw(*item)?;
+                    self.pending.pop_front();
+                    moved = true;
+                }
+                if self.ended {
+                    if !to.is_closed() {
+                        to.close();
+                        moved = true;
+                    }
+                    return Ok(moved);
+                }
+                if moved {
+                    return Ok(true);
+                }
+                self.read_frame(reader)?;
+            }
+        }
+    }
+    #[doc = r" Sends a stream to a TcpGatewaySource, in the wire format - connecting when there is something to send, and"]
+    #[doc = r" reconnecting once a connection drops. Items being sent when a connection drops are lost."]
+    pub struct TcpExitSink<Addr: std::net::ToSocketAddrs> {
+        addr: Addr,
+        connection: Option<(std::net::TcpStream, WireEncoder)>,
+        ended: bool,
+    }
+    impl<Addr: std::net::ToSocketAddrs> TcpExitSink<Addr> {
+        pub fn new(addr: Addr) -> Self {
+            Self {
+                addr,
+                connection: None,
+                ended: false,
+            }
+        }
+        pub fn is_connected(&self) -> bool {
+            self.connection.is_some()
+        }
+        #[doc = r" Sends everything buffered on `from` - then the end of the stream, once `from` is drained. Returns whether"]
+        #[doc = r" anything was sent: while no connection can be made, everything stays buffered on `from` for the next"]
+        #[doc = r" call. Errors writing to the connection drop it, to reconnect on the next call."]
+        pub fn send<Alphabet, Clock, From, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> Result<bool, IoError>
+        where
+            Alphabet: AlphabetLike,
+            Clock: ClockLike,
+            From: GatewayLike<
+                Alphabet,
+                Clock,
+                SIZE,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            >,
+            Alphabet::CharRep: Into<u64>,
+            Clock::MomentRep: Into<u64>,
+        {
+            if self.ended || (from.is_empty() && !from.is_drained()) {
+                return Ok(false);
+            }
+            if self.connection.is_none() {
+                match std::net::TcpStream::connect(&self.addr) {
+                    Ok(socket) => {
+                        socket.set_nodelay(true)?;
+                        self.connection = Some((socket, WireEncoder::new()));
+                    }
+                    Err(_) => return Ok(false),
+                }
+            }
+            let (socket, encoder) = self.connection.as_mut().unwrap();
+            if let Err(err) = encoder.encode_to(from, socket) {
+                self.connection = None;
+                return Err(err);
+            }
+            if from.is_drained() {
+                self.ended = true;
+                self.connection = None;
+            }
+            Ok(true)
+        }
+    }
+    #[doc = r" Receives streams a TcpExitSink sends - from one sink at a time, accepting the next connection once a sink"]
+    #[doc = r" drops its connection before the end of its stream"]
+    pub struct TcpGatewaySource<Alphabet: AlphabetLike, Clock: ClockLike> {
+        listener: std::net::TcpListener,
+        connection: Option<(std::net::TcpStream, WireDecoder<Alphabet, Clock>)>,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike> TcpGatewaySource<Alphabet, Clock>
+    where
+        Alphabet::CharRep: TryFrom<u64>,
+        Clock::MomentRep: TryFrom<u64>,
+    {
+        pub fn bind(addr: impl std::net::ToSocketAddrs) -> Result<Self, IoError> {
+            Ok(Self {
+                listener: std::net::TcpListener::bind(addr)?,
+                connection: None,
+            })
+        }
+        #[doc = r" Where sinks connect to - the port picked, when bound to port 0"]
+        pub fn local_addr(&self) -> Result<std::net::SocketAddr, IoError> {
+            Ok(self.listener.local_addr()?)
+        }
+        pub fn is_connected(&self) -> bool {
+            self.connection.is_some()
+        }
+        #[doc = r" Pushes what was received onto `to` for as long as it has room - blocking until a sink connects, or sends"]
+        #[doc = r" a frame, when nothing is pending. `to` is closed once the stream ends. Returns whether anything was"]
+        #[doc = r" pushed, or `to` closed."]
+        pub fn receive<To, const SIZE: usize>(&mut self, to: &mut To) -> Result<bool, IoError>
+        where
+            To: StreamLike<Alphabet, Clock, SIZE>
+                + ExitLike<
+                    Alphabet,
+                    Clock,
+                    InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+                >,
+        {
+            loop {
+                if self.connection.is_none() {
+                    let (socket, _) = self.listener.accept()?;
+                    self.connection = Some((socket, WireDecoder::new()));
+                }
+                let (socket, decoder) = self.connection.as_mut().unwrap();
+                match decoder.decode_from(socket, to) {
+                    Err(IoError::Io(_)) => self.connection = None,
+                    result => return result,
+                }
+            }
+        }
+    }
+    #[doc = r" How many requests (publishes, subscriptions) an MQTT client queues up before blocking"]
+    #[cfg(feature = "mqtt")]
+    const MQTT_CAPACITY: usize = 64;
+    #[doc = r" How long an MQTT connection waits before reconnecting, once it dropped"]
+    #[cfg(feature = "mqtt")]
+    const MQTT_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+    #[doc = r" An error of a library the adapters wrap (MQTT, Kafka, Parquet) - as an io::Error of its own kind"]
+    #[cfg(any(feature = "mqtt", feature = "kafka", feature = "parquet"))]
+    fn client_error(err: impl std::error::Error + Send + Sync + 'static) -> IoError {
+        IoError::Io(std::io::Error::other(err))
+    }
+    #[doc = r" Feeds MQTT messages published on a topic onto a gateway - each message a duration of its payload's bytes,"]
+    #[doc = r" ended by the moment it arrived at: in unix seconds or milliseconds, as the clock represents them, or"]
+    #[doc = r" counting messages (from 1) for other clocks. The topic is subscribed to again whenever the connection is"]
+    #[doc = r" re-established."]
+    #[cfg(feature = "mqtt")]
+    pub struct MqttGateway {
+        client: rumqttc::Client,
+        connection: rumqttc::Connection,
+        topic: String,
+        qos: rumqttc::QoS,
+        payload: Vec<u8>,
+        pos: usize,
+        arrival: Option<u64>,
+        last_arrival: u64,
+        count: u64,
+    }
+    #[cfg(feature = "mqtt")]
+    impl MqttGateway {
+        pub fn subscribe(
+            options: rumqttc::MqttOptions,
+            topic: impl Into<String>,
+            qos: rumqttc::QoS,
+        ) -> Self {
+            let (client, connection) = rumqttc::Client::new(options, MQTT_CAPACITY);
+            Self {
+                client,
+                connection,
+                topic: topic.into(),
+                qos,
+                payload: Vec::new(),
+                pos: 0,
+                arrival: None,
+                last_arrival: 0,
+                count: 0,
+            }
+        }
+        #[doc = r" When a message arrived, in the units of the clock - never earlier than the message before"]
+        fn arrival<Clock: ClockLike>(&mut self) -> u64 {
+            let since_epoch = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default();
+            self.count += 1;
+            let arrival = unix_millis_as(since_epoch.as_millis() as i64, Clock::REPRESENTS)
+                .unwrap_or(self.count);
+            self.last_arrival = self.last_arrival.max(arrival);
+            self.last_arrival
+        }
+        #[doc = r" Pushes messages onto `to` for as long as it has room - waiting up to `timeout` for one to arrive, while"]
+        #[doc = r" nothing was pushed. Returns whether anything was pushed. Connection errors are returned as they happen,"]
+        #[doc = r" the connection being re-established on the next call."]
+        pub fn receive<Alphabet, Clock, To, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+            timeout: std::time::Duration,
+        ) -> Result<bool, IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            use rumqttc::{Event, Packet, RecvTimeoutError, TryRecvError};
+            let deadline = std::time::Instant::now() + timeout;
+            let mut pushed = false;
+            loop {
+                while let Some(arrival) = self.arrival {
+                    if !to.accepting_pushes() {
+                        return Ok(pushed);
+                    }
+                    if self.pos < self.payload.len() {
+                        let byte = self.payload[self.pos];
+                        match Alphabet::to_char(byte) {
+                            Ok(chr) => to.push(chr)?,
+                            Err(_) => to.push_invalid(byte)?,
+                        }
+                        self.pos += 1;
+                    } else {
+                        let moment = Clock::MomentRep::try_from(arrival)
+                            .map_err(|_| ExitError::NotEncodable)?;
+                        to.push_moment(moment)?;
+                        self.arrival = None;
+                    }
+                    pushed = true;
+                }
+                let now = std::time::Instant::now();
+                let event = if pushed || now >= deadline {
+                    match self.connection.try_recv() {
+                        Ok(event) => event,
+                        Err(TryRecvError::Empty) => return Ok(pushed),
+                        Err(TryRecvError::Disconnected) => {
+                            to.close();
+                            return Ok(true);
+                        }
+                    }
+                } else {
+                    match self.connection.recv_timeout(deadline - now) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => return Ok(pushed),
+                        Err(RecvTimeoutError::Disconnected) => {
+                            to.close();
+                            return Ok(true);
+                        }
+                    }
+                };
+                match event.map_err(client_error)? {
+                    Event::Incoming(Packet::ConnAck(_)) => self
+                        .client
+                        .subscribe(self.topic.as_str(), self.qos)
+                        .map_err(client_error)?,
+                    Event::Incoming(Packet::Publish(publish)) => {
+                        self.payload.clear();
+                        self.payload.extend_from_slice(&publish.payload);
+                        self.pos = 0;
+                        self.arrival = Some(self.arrival::<Clock>());
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+    #[doc = r" Publishes the durations of an exit as MQTT messages on a topic - each message the characters of a duration"]
+    #[doc = r" (as their byte values), published once its moment is drained. The connection is driven (and re-established,"]
+    #[doc = r" once it drops) on a thread of its own."]
+    #[cfg(feature = "mqtt")]
+    pub struct MqttExit {
+        client: rumqttc::Client,
+        topic: String,
+        qos: rumqttc::QoS,
+        pending: Vec<u8>,
+        driver: std::thread::JoinHandle<()>,
+    }
+    #[cfg(feature = "mqtt")]
+    impl MqttExit {
+        pub fn connect(
+            options: rumqttc::MqttOptions,
+            topic: impl Into<String>,
+            qos: rumqttc::QoS,
+        ) -> Self {
+            let (client, mut connection) = rumqttc::Client::new(options, MQTT_CAPACITY);
+            let driver = std::thread::spawn(move || {
+                for event in connection.iter() {
+                    match event {
+                        Ok(rumqttc::Event::Outgoing(rumqttc::Outgoing::Disconnect)) => break,
+                        Ok(_) => (),
+                        Err(_) => std::thread::sleep(MQTT_RETRY_DELAY),
+                    }
+                }
+            });
+            Self {
+                client,
+                topic: topic.into(),
+                qos,
+                pending: Vec::new(),
+                driver,
+            }
+        }
+        #[doc = r" Publishes every duration buffered on `from` - blocking while too many messages wait to be sent."]
+        #[doc = r" Characters of a duration not ended yet wait for its moment."]
+        pub fn publish<Alphabet, Clock, From, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            From: StreamLike<Alphabet, Clock, SIZE>,
+        {
+            for item in Drain::new(from) {
+                match item {
+                    StreamItem::Character(chr) => self.pending.push(Alphabet::to_val(chr)),
+                    StreamItem::InvalidChar(byte) => self.pending.push(byte),
+                    StreamItem::Moment(_) => {
+                        let payload = core::mem::take(&mut self.pending);
+                        self.client
+                            .publish(self.topic.as_str(), self.qos, false, payload)
+                            .map_err(client_error)?;
+                    }
+                    StreamItem::Empty => (),
+                }
+            }
+            Ok(())
+        }
+        #[doc = r" Disconnects, once every message published so far was sent"]
+        pub fn disconnect(self) -> Result<(), IoError> {
+            self.client.disconnect().map_err(client_error)?;
+            self.driver
+                .join()
+                .map_err(|_| IoError::Io(std::io::Error::other("MQTT connection thread panicked")))
+        }
+    }
+    #[doc = r" What a KafkaGateway does with records whose timestamp is earlier than that of a record before them"]
+    #[cfg(feature = "kafka")]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum OutOfOrder {
+        #[doc = r" Records are pushed as they arrive, each ended by its own timestamp - even when that is earlier than"]
+        #[doc = r" the moment before"]
+        Forward,
+        #[doc = r" Records are held back until a record `lateness` later (in the clock's units) arrives, and pushed in"]
+        #[doc = r" timestamp order - records arriving later still are pushed at the moment of the record before"]
+        Reorder { lateness: u64 },
+    }
+    #[doc = r" A Kafka record timestamp (milliseconds since the unix epoch) in the units of a clock - unix seconds or"]
+    #[doc = r" milliseconds, or the milliseconds as they are for other clocks"]
+    #[cfg(feature = "kafka")]
+    fn kafka_timestamp(millis: i64, represents: &str) -> u64 {
+        unix_millis_as(millis, represents).unwrap_or(millis.max(0) as u64)
+    }
+    #[doc = r" Feeds the records of Kafka topics onto a gateway - each record a duration of its payload's bytes, ended by"]
+    #[doc = r" its timestamp (see OutOfOrder for records out of order). Records without a timestamp take the time they"]
+    #[doc = r" were received at."]
+    #[cfg(feature = "kafka")]
+    pub struct KafkaGateway {
+        consumer: rdkafka::consumer::BaseConsumer,
+        out_of_order: OutOfOrder,
+        held: std::collections::BTreeMap<(u64, u64), Vec<u8>>,
+        held_count: u64,
+        latest: u64,
+        released: u64,
+        ready: std::collections::VecDeque<(u64, Vec<u8>)>,
+        payload: Vec<u8>,
+        pos: usize,
+        moment: Option<u64>,
+    }
+    #[cfg(feature = "kafka")]
+    impl KafkaGateway {
+        #[doc = r" Consumes `topics` with a consumer created from `config` - which sets its `bootstrap.servers` and"]
+        #[doc = r" `group.id`"]
+        pub fn subscribe(
+            config: &rdkafka::ClientConfig,
+            topics: &[&str],
+            out_of_order: OutOfOrder,
+        ) -> Result<Self, IoError> {
+            use rdkafka::consumer::Consumer;
+            let consumer: rdkafka::consumer::BaseConsumer =
+                config.create().map_err(client_error)?;
+            consumer.subscribe(topics).map_err(client_error)?;
+            Ok(Self {
+                consumer,
+                out_of_order,
+                held: std::collections::BTreeMap::new(),
+                held_count: 0,
+                latest: 0,
+                released: 0,
+                ready: std::collections::VecDeque::new(),
+                payload: Vec::new(),
+                pos: 0,
+                moment: None,
+            })
+        }
+        #[doc = r" Records held back to be reordered - not pushed yet, as a later record could still precede them"]
+        pub fn held(&self) -> usize {
+            self.held.len()
+        }
+        #[doc = r" Lets every record held back be pushed, as if no earlier record could still arrive - before shutting"]
+        #[doc = r" down, say"]
+        pub fn release_held(&mut self) {
+            for ((timestamp, _), payload) in core::mem::take(&mut self.held) {
+                self.released = timestamp;
+                self.ready.push_back((timestamp, payload));
+            }
+        }
+        fn arrive(&mut self, timestamp: u64, payload: Vec<u8>) {
+            let OutOfOrder::Reorder { lateness } = self.out_of_order else {
+                self.ready.push_back((timestamp, payload));
+                return;
+            };
+            let timestamp = timestamp.max(self.released);
+            self.held.insert((timestamp, self.held_count), payload);
+            self.held_count += 1;
+            self.latest = self.latest.max(timestamp);
+            while let Some(entry) = self.held.first_entry() {
+                if entry.key().0.saturating_add(lateness) > self.latest {
+                    break;
+                }
+                let ((timestamp, _), payload) = entry.remove_entry();
+                self.released = timestamp;
+                self.ready.push_back((timestamp, payload));
+            }
+        }
+        #[doc = r" Pushes records onto `to` for as long as it has room - waiting up to `timeout` for one to arrive, while"]
+        #[doc = r" nothing was pushed. Returns whether anything was pushed."]
+        pub fn receive<Alphabet, Clock, To, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+            timeout: std::time::Duration,
+        ) -> Result<bool, IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            use rdkafka::Message;
+            let deadline = std::time::Instant::now() + timeout;
+            let mut pushed = false;
+            loop {
+                while let Some(moment) = self.moment {
+                    if !to.accepting_pushes() {
+                        return Ok(pushed);
+                    }
+                    if self.pos < self.payload.len() {
+                        let byte = self.payload[self.pos];
+                        match Alphabet::to_char(byte) {
+                            Ok(chr) => to.push(chr)?,
+                            Err(_) => to.push_invalid(byte)?,
+                        }
+                        self.pos += 1;
+                    } else {
+                        let moment = Clock::MomentRep::try_from(moment)
+                            .map_err(|_| ExitError::NotEncodable)?;
+                        to.push_moment(moment)?;
+                        self.moment = None;
+                    }
+                    pushed = true;
+                }
+                if let Some((timestamp, payload)) = self.ready.pop_front() {
+                    self.payload = payload;
+                    self.pos = 0;
+                    self.moment = Some(timestamp);
+                    continue;
+                }
+                let wait = match pushed {
+                    true => std::time::Duration::ZERO,
+                    false => deadline.saturating_duration_since(std::time::Instant::now()),
+                };
+                let (timestamp, payload) = match self.consumer.poll(wait) {
+                    None => return Ok(pushed),
+                    Some(Err(err)) => return Err(client_error(err)),
+                    Some(Ok(message)) => (
+                        message.timestamp().to_millis(),
+                        message.payload().unwrap_or_default().to_vec(),
+                    ),
+                };
+                let timestamp = timestamp.unwrap_or_else(|| {
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis() as i64
+                });
+                self.arrive(kafka_timestamp(timestamp, Clock::REPRESENTS), payload);
+            }
+        }
+    }
+    #[doc = r" Produces the durations of an exit as Kafka records on a topic - each record the characters of a duration"]
+    #[doc = r" (as their byte values), timestamped with its moment: converted from unix seconds or milliseconds, or taken"]
+    #[doc = r" as milliseconds for other clocks. Characters of a duration not ended yet wait for its moment."]
+    #[cfg(feature = "kafka")]
+    pub struct KafkaExit {
+        producer: rdkafka::producer::BaseProducer,
+        topic: String,
+        pending: Vec<u8>,
+    }
+    #[cfg(feature = "kafka")]
+    impl KafkaExit {
+        #[doc = r" Produces onto `topic` with a producer created from `config` - which sets its `bootstrap.servers`"]
+        pub fn new(
+            config: &rdkafka::ClientConfig,
+            topic: impl Into<String>,
+        ) -> Result<Self, IoError> {
+            Ok(Self {
+                producer: config.create().map_err(client_error)?,
+                topic: topic.into(),
+                pending: Vec::new(),
+            })
+        }
+        #[doc = r" Produces every duration buffered on `from` - blocking while the producer's queue is full"]
+        pub fn send<Alphabet, Clock, From, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> Result<(), IoError>
+        where
+            Alphabet: AlphabetLike<CharRep = u8>,
+            Clock: ClockLike,
+            From: StreamLike<Alphabet, Clock, SIZE>,
+            Clock::MomentRep: Into<u64>,
+        {
+            use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+            use rdkafka::producer::BaseRecord;
+            for item in Drain::new(from) {
+                match item {
+                    StreamItem::Character(chr) => self.pending.push(Alphabet::to_val(chr)),
+                    StreamItem::InvalidChar(byte) => self.pending.push(byte),
+                    StreamItem::Moment(moment) => {
+                        let unit = unix_unit_millis(Clock::REPRESENTS).unwrap_or(1);
+                        let millis = moment.into().saturating_mul(unit as u64);
+                        let mut record = BaseRecord::<(), [u8]>::to(&self.topic)
+                            .payload(&self.pending)
+                            .timestamp(millis as i64);
+                        loop {
+                            match self.producer.send(record) {
+                                Ok(()) => break,
+                                Err((
+                                    KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull),
+                                    unsent,
+                                )) => {
+                                    self.producer.poll(std::time::Duration::from_millis(100));
+                                    record = unsent;
+                                }
+                                Err((err, _)) => return Err(client_error(err)),
+                            }
+                        }
+                        self.pending.clear();
+                    }
+                    StreamItem::Empty => (),
+                }
+            }
+            self.producer.poll(std::time::Duration::ZERO);
+            Ok(())
+        }
+        #[doc = r" Waits up to `timeout` for every record produced so far to be delivered"]
+        pub fn flush(&mut self, timeout: std::time::Duration) -> Result<(), IoError> {
+            use rdkafka::producer::Producer;
+            self.producer.flush(timeout).map_err(client_error)
+        }
+    }
+}
+
+#[doc = r" Serialize and Deserialize for the runtime's types - streams (and their snapshots) serialize as the items they"]
+#[doc = r" buffer, oldest first, along with their state. Characters stay raw values, so alphabets need no serde support."]
+#[doc = r" A program's state is held by its streams - serializing them persists the program."]
+#[cfg(feature = "serde")]
+mod serde_impls {
+    extern crate alloc;
+    use super::*;
+    use alloc::vec::Vec;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "ExitError")]
+    enum ExitErrorDef {
+        BufferFull,
+        Closed,
+        NotEncodable,
+    }
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "StreamError")]
+    enum StreamErrorDef {
+        Exit(#[serde(with = "ExitErrorDef")] ExitError),
+        UnknownChar,
+        InvalidChar,
+        UnexpectedItem,
+        NoMoment,
+        ClockMismatch,
+        OutOfFuel,
+    }
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "OverflowPolicy")]
+    enum OverflowPolicyDef {
+        Reject,
+        OverwriteOldest,
+    }
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "PushOutcome")]
+    enum PushOutcomeDef {
+        Fits,
+        Full { needed: usize },
+    }
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "StreamItem")]
+    enum StreamItemRepr<CharacterRep, Moment, InvalidRep> {
+        Empty,
+        Character(CharacterRep),
+        Moment(Moment),
+        InvalidChar(InvalidRep),
+    }
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "ClockMoment")]
+    enum ClockMomentRepr<MomentRep> {
+        UnixSeconds(MomentRep),
+        UnixMilliseconds(MomentRep),
+        UnixMicroseconds(MomentRep),
+        NaturalMilliseconds(MomentRep),
+        NaturalSeconds(MomentRep),
+        NaturalMinutes(MomentRep),
+        NaturalHours(MomentRep),
+        Quantity(MomentRep),
+    }
+    #[doc = r" How streams serialize, whichever way they buffer their items - `Items` is borrowed to serialize, and owned"]
+    #[doc = r" once deserialized"]
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Stream")]
+    pub(crate) struct StreamRepr<Items, MomentRep> {
+        pub(crate) items: Items,
+        pub(crate) current_moment: Option<MomentRep>,
+        #[serde(with = "OverflowPolicyDef")]
+        pub(crate) policy: OverflowPolicy,
+        pub(crate) overflowed: bool,
+        pub(crate) closed: bool,
+    }
+    #[doc = r" Fails deserializing a stream holding more items than it can buffer"]
+    pub(crate) fn check_len<E: Error>(len: usize, capacity: usize) -> Result<(), E> {
+        if len > capacity {
+            Err(E::custom(format_args!(
+                "{} items buffered, in a stream of size {}",
+                len, capacity
+            )))
+        } else {
+            Ok(())
+        }
+    }
+    impl Serialize for ExitError {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            ExitErrorDef::serialize(self, serializer)
+        }
+    }
+    impl<'de> Deserialize<'de> for ExitError {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            ExitErrorDef::deserialize(deserializer)
+        }
+    }
+    impl Serialize for StreamError {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            StreamErrorDef::serialize(self, serializer)
+        }
+    }
+    impl<'de> Deserialize<'de> for StreamError {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            StreamErrorDef::deserialize(deserializer)
+        }
+    }
+    impl Serialize for OverflowPolicy {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            OverflowPolicyDef::serialize(self, serializer)
+        }
+    }
+    impl<'de> Deserialize<'de> for OverflowPolicy {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            OverflowPolicyDef::deserialize(deserializer)
+        }
+    }
+    impl Serialize for PushOutcome {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            PushOutcomeDef::serialize(self, serializer)
+        }
+    }
+    impl<'de> Deserialize<'de> for PushOutcome {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            PushOutcomeDef::deserialize(deserializer)
+        }
+    }
+    impl<CharacterRep: Serialize, Moment: Serialize, InvalidRep: Serialize> Serialize
+        for StreamItem<CharacterRep, Moment, InvalidRep>
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                StreamItem::Empty => StreamItemRepr::Empty,
+                StreamItem::Character(chr) => StreamItemRepr::Character(chr),
+                StreamItem::Moment(moment) => StreamItemRepr::Moment(moment),
+                StreamItem::InvalidChar(rep) => StreamItemRepr::InvalidChar(rep),
+            }
+            .serialize(serializer)
+        }
+    }
+    impl<
+            'de,
+            CharacterRep: Deserialize<'de>,
+            Moment: Deserialize<'de>,
+            InvalidRep: Deserialize<'de>,
+        > Deserialize<'de> for StreamItem<CharacterRep, Moment, InvalidRep>
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(match StreamItemRepr::deserialize(deserializer)? {
+                StreamItemRepr::Empty => StreamItem::Empty,
+                StreamItemRepr::Character(chr) => StreamItem::Character(chr),
+                StreamItemRepr::Moment(moment) => StreamItem::Moment(moment),
+                StreamItemRepr::InvalidChar(rep) => StreamItem::InvalidChar(rep),
+            })
+        }
+    }
+    impl<MomentRep: Serialize> Serialize for ClockMoment<MomentRep> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                ClockMoment::UnixSeconds(rep) => ClockMomentRepr::UnixSeconds(rep),
+                ClockMoment::UnixMilliseconds(rep) => ClockMomentRepr::UnixMilliseconds(rep),
+                ClockMoment::UnixMicroseconds(rep) => ClockMomentRepr::UnixMicroseconds(rep),
+                ClockMoment::NaturalMilliseconds(rep) => ClockMomentRepr::NaturalMilliseconds(rep),
+                ClockMoment::NaturalSeconds(rep) => ClockMomentRepr::NaturalSeconds(rep),
+                ClockMoment::NaturalMinutes(rep) => ClockMomentRepr::NaturalMinutes(rep),
+                ClockMoment::NaturalHours(rep) => ClockMomentRepr::NaturalHours(rep),
+                ClockMoment::Quantity(rep) => ClockMomentRepr::Quantity(rep),
+            }
+            .serialize(serializer)
+        }
+    }
+    impl<'de, MomentRep: Deserialize<'de>> Deserialize<'de> for ClockMoment<MomentRep> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(match ClockMomentRepr::deserialize(deserializer)? {
+                ClockMomentRepr::UnixSeconds(rep) => ClockMoment::UnixSeconds(rep),
+                ClockMomentRepr::UnixMilliseconds(rep) => ClockMoment::UnixMilliseconds(rep),
+                ClockMomentRepr::UnixMicroseconds(rep) => ClockMoment::UnixMicroseconds(rep),
+                ClockMomentRepr::NaturalMilliseconds(rep) => ClockMoment::NaturalMilliseconds(rep),
+                ClockMomentRepr::NaturalSeconds(rep) => ClockMoment::NaturalSeconds(rep),
+                ClockMomentRepr::NaturalMinutes(rep) => ClockMoment::NaturalMinutes(rep),
+                ClockMomentRepr::NaturalHours(rep) => ClockMoment::NaturalHours(rep),
+                ClockMomentRepr::Quantity(rep) => ClockMoment::Quantity(rep),
+            })
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> Serialize
+        for StreamSnapshot<Alphabet, Clock, BUFFER_SIZE>
+    where
+        Alphabet::CharRep: Serialize,
+        Clock::MomentRep: Serialize,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            StreamRepr {
+                items: self.items(),
+                current_moment: self.last_seen_moment,
+                policy: self.policy,
+                overflowed: self.overflowed,
+                closed: self.closed,
+            }
+            .serialize(serializer)
+        }
+    }
+    impl<'de, Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> Deserialize<'de>
+        for StreamSnapshot<Alphabet, Clock, BUFFER_SIZE>
+    where
+        Alphabet::CharRep: Deserialize<'de>,
+        Clock::MomentRep: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = StreamRepr::<
+                Vec<StreamItem<Alphabet::CharRep, Clock::MomentRep>>,
+                Clock::MomentRep,
+            >::deserialize(deserializer)?;
+            check_len(repr.items.len(), BUFFER_SIZE)?;
+            let mut items = [StreamItem::Empty; BUFFER_SIZE];
+            items[..repr.items.len()].copy_from_slice(&repr.items);
+            Ok(StreamSnapshot {
+                items,
+                len: repr.items.len(),
+                last_seen_moment: repr.current_moment,
+                policy: repr.policy,
+                overflowed: repr.overflowed,
+                closed: repr.closed,
+            })
+        }
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> Serialize
+        for Stream<Alphabet, Clock, BUFFER_SIZE>
+    where
+        Alphabet::CharRep: Serialize,
+        Clock::MomentRep: Serialize,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.snapshot().serialize(serializer)
+        }
+    }
+    impl<'de, Alphabet: AlphabetLike, Clock: ClockLike, const BUFFER_SIZE: usize> Deserialize<'de>
+        for Stream<Alphabet, Clock, BUFFER_SIZE>
+    where
+        Alphabet::CharRep: Deserialize<'de>,
+        Clock::MomentRep: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let mut stream = Self::new();
+            stream.restore(&StreamSnapshot::deserialize(deserializer)?);
+            Ok(stream)
+        }
+    }
+}
+
+#[doc = r" Records what programs generated with `--trace` do - every push onto and pop off of their streams, and every jump"]
+#[doc = r" between their labels. The `trace` feature writes them out as JSON lines, carrying the moment of the stream they"]
+#[doc = r" happened on rather than the wall-clock time, so traces of runs on the same input diff cleanly. The `tracing`"]
+#[doc = r" feature emits them as `tracing` events instead, within a span per label function run."]
+#[cfg(any(feature = "trace", feature = "tracing"))]
+pub mod trace {
+    extern crate std;
+    use super::*;
+    #[cfg(feature = "trace")]
+    pub use jsonl::*;
+    #[cfg(feature = "trace")]
+    mod jsonl {
+        extern crate std;
+        use std::boxed::Box;
+        use std::format;
+        use std::fs::File;
+        use std::io::{self, LineWriter, Write};
+        use std::string::String;
+        use std::sync::{Mutex, MutexGuard, PoisonError};
+        struct Sink {
+            out: Box<dyn Write + Send>,
+            seq: u64,
+        }
+        static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+        fn sink() -> MutexGuard<'static, Option<Sink>> {
+            SINK.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+        #[doc = r" Writes events to `out` from now on - whatever they were written to before is flushed"]
+        pub fn trace_to<W: Write + Send + 'static>(out: W) {
+            if let Some(mut previous) = sink().replace(Sink {
+                out: Box::new(out),
+                seq: 0,
+            }) {
+                let _ = previous.out.flush();
+            }
+        }
+        #[doc = r" Writes events to the file `TIME_LANG_TRACE` names - to stderr, unless it is set. Each event is written out"]
+        #[doc = r" whole, so a trace ends with the last event recorded even when its program exits on an error."]
+        pub fn trace_from_env() -> io::Result<()> {
+            match std::env::var_os("TIME_LANG_TRACE") {
+                Some(path) => trace_to(LineWriter::new(File::create(path)?)),
+                None => trace_to(io::stderr()),
+            }
+            Ok(())
+        }
+        #[doc = r" Stops recording events, flushing those recorded so far"]
+        pub fn finish() -> io::Result<()> {
+            match sink().take() {
+                Some(mut previous) => previous.out.flush(),
+                None => Ok(()),
+            }
+        }
+        #[doc = r" A JSON string holding `text`"]
+        pub(super) fn json_str(text: &str) -> String {
+            let mut json = String::with_capacity(text.len() + 2);
+            json.push('"');
+            for chr in text.chars() {
+                match chr {
+                    '"' => json.push_str("\\\""),
+                    '\\' => json.push_str("\\\\"),
+                    chr if (chr as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", chr as u32)),
+                    chr => json.push(chr),
+                }
+            }
+            json.push('"');
+            json
+        }
+        #[doc = r" Writes an event - `fields` are its own fields, after the ones every event has. Events that can't be"]
+        #[doc = r" written are dropped, rather than failing the program they trace."]
+        pub(super) fn record(program: &str, event: &str, fields: &str) {
+            let mut sink = sink();
+            let Some(sink) = sink.as_mut() else { return };
+            let _ = writeln!(
+                sink.out,
+                "{{\"seq\":{},\"program\":{},\"event\":{}{}}}",
+                sink.seq,
+                json_str(program),
+                json_str(event),
+                fields
+            );
+            sink.seq += 1;
+        }
+    }
+    #[cfg(feature = "trace")]
+    fn debug_str<T: Debug>(value: &T) -> std::string::String {
+        let debug = std::format!("{:?}", value);
+        jsonl::json_str(debug.strip_suffix("()").unwrap_or(&debug))
+    }
+    #[doc = r" A jump to another label, from the statement on `line`"]
+    pub fn jump(program: &'static str, label: &'static str, line: usize) {
+        #[cfg(feature = "trace")]
+        jsonl::record(
+            program,
+            "jump",
+            &std::format!(",\"to\":{},\"line\":{}", jsonl::json_str(label), line),
+        );
+        #[cfg(feature = "tracing")]
+        :: tracing :: trace ! (target : "time_lang" , program , to = label , line , "jump");
+    }
+    #[doc = r" Entered for as long as a label function runs - a `tracing` span, with the `tracing` feature"]
+    pub struct LabelSpan {
+        #[cfg(feature = "tracing")]
+        _span: ::tracing::span::EnteredSpan,
+    }
+    pub fn label(program: &'static str, label: &'static str) -> LabelSpan {
+        #[cfg(not(feature = "tracing"))]
+        let _ = (program, label);
+        LabelSpan {
+            #[cfg(feature = "tracing")]
+            _span: :: tracing :: trace_span ! (target : "time_lang" , "label" , program , label)
+                .entered(),
+        }
+    }
+    #[doc = r" A stream traced under its name, in the program it belongs to - with the last moment pushed onto it and the"]
+    #[doc = r" last one popped off of it, which time its events. Only ever holding the alphabet's values, it is Send"]
+    #[doc = r" whatever the alphabet type is, like the streams it wraps."]
+    pub struct Traced<Alphabet: AlphabetLike, Clock: ClockLike, Inner, const BUFFER_SIZE: usize> {
+        inner: Inner,
+        program: &'static str,
+        name: &'static str,
+        pushed_moment: Option<Clock::MomentRep>,
+        popped_moment: Option<Clock::MomentRep>,
+        types: PhantomData<fn() -> Alphabet>,
+    }
+    impl<Alphabet: AlphabetLike, Clock: ClockLike, Inner, const BUFFER_SIZE: usize>
+        Traced<Alphabet, Clock, Inner, BUFFER_SIZE>
+    {
+        pub const fn new(inner: Inner, program: &'static str, name: &'static str) -> Self {
+            Self {
+                inner,
+                program,
+                name,
+                pushed_moment: None,
+                popped_moment: None,
+                types: PhantomData,
+            }
+        }
+        pub fn inner(&self) -> &Inner {
+            &self.inner
+        }
+        #[doc = r" The stream traced - to set an observer on it"]
+        pub fn inner_mut(&mut self) -> &mut Inner {
+            &mut self.inner
+        }
+    }
+    impl<Alphabet, Clock, Inner, const BUFFER_SIZE: usize> Traced<Alphabet, Clock, Inner, BUFFER_SIZE>
+    where
+        Alphabet: AlphabetLike,
+        Clock: ClockLike,
+        Inner: StreamLike<Alphabet, Clock, BUFFER_SIZE>
+            + ExitLike<
+                Alphabet,
+                Clock,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            > + GatewayLike<
+                Alphabet,
+                Clock,
+                BUFFER_SIZE,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            >,
+    {
+        #[doc = r" Pops every item buffered in the stream, oldest first - each traced as it is popped"]
+        pub fn drain(&mut self) -> Drain<'_, Alphabet, Clock, BUFFER_SIZE, Self> {
+            Drain::new(self)
+        }
+        fn record(
+            &self,
+            event: &'static str,
+            item: &StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>,
+            at: Option<Clock::MomentRep>,
+        ) {
+            if let StreamItem::Empty = item {
+                return;
+            }
+            #[cfg(feature = "trace")]
+            {
+                let item = match item {
+                    StreamItem::Character(chr) => std::format!(",\"char\":{}", debug_str(chr)),
+                    StreamItem::Moment(moment) => std::format!(",\"moment\":{}", debug_str(moment)),
+                    StreamItem::InvalidChar(rep) => std::format!(",\"invalid\":{}", debug_str(rep)),
+                    StreamItem::Empty => std::string::String::new(),
+                };
+                let at = at.map_or(std::string::String::new(), |at| {
+                    std::format!(",\"at\":{}", debug_str(&at))
+                });
+                jsonl::record(
+                    self.program,
+                    event,
+                    &std::format!(",\"stream\":{}{}{}", jsonl::json_str(self.name), item, at),
+                );
+            }
+            #[cfg(feature = "tracing")]
+            :: tracing :: trace ! (target : "time_lang" , program = self . program , stream = self . name , item = ? item , at = ? at , buffered = self . inner . len () , capacity = self . inner . capacity () , "{}" , event);
+            #[cfg(not(feature = "tracing"))]
+            let _ = at;
+        }
+        #[doc = r" A push that went through - moments pushed are `moment` events, and time the pushes after them"]
+        fn pushed(
+            &mut self,
+            item: StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>,
+        ) {
+            match item {
+                StreamItem::Moment(moment) => {
+                    self.record("moment", &item, self.pushed_moment);
+                    self.pushed_moment = Some(moment);
+                }
+                _ => self.record("push", &item, self.pushed_moment),
+            }
+        }
+        fn popped(
+            &mut self,
+            item: StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>,
+        ) {
+            self.record("pop", &item, self.popped_moment);
+            if let StreamItem::Moment(moment) = item {
+                self.popped_moment = Some(moment);
+            }
+        }
+    }
+    impl<Alphabet, Clock, Inner, const BUFFER_SIZE: usize> ExitLike<Alphabet, Clock>
+        for Traced<Alphabet, Clock, Inner, BUFFER_SIZE>
+    where
+        Alphabet: AlphabetLike,
+        Clock: ClockLike,
+        Inner: StreamLike<Alphabet, Clock, BUFFER_SIZE>
+            + ExitLike<
+                Alphabet,
+                Clock,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            > + GatewayLike<
+                Alphabet,
+                Clock,
+                BUFFER_SIZE,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            >,
+    {
+        type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
+        type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+        fn set_initial_moment(&mut self, moment: Clock::MomentRep) {
+            self.pushed_moment = Some(moment);
+            self.inner.set_initial_moment(moment)
+        }
+        fn accepting_pushes(&mut self) -> bool {
+            self.inner.accepting_pushes()
+        }
+        fn push(&mut self, chr: Alphabet::CharEnum) -> Result<(), ExitError> {
+            self.inner.push(chr)?;
+            self.pushed(StreamItem::Character(chr));
+            Ok(())
+        }
+        fn push_moment(&mut self, moment: Clock::MomentRep) -> Result<(), ExitError> {
+            self.inner.push_moment(moment)?;
+            self.pushed(StreamItem::Moment(moment));
+            Ok(())
+        }
+        fn push_invalid(&mut self, rep: Alphabet::CharRep) -> Result<(), ExitError> {
+            self.inner.push_invalid(rep)?;
+            self.pushed(decode::<Alphabet, Clock>(StreamItem::Character(rep)));
+            Ok(())
+        }
+        fn push_raw(&mut self, item: Self::InternalItem) -> Result<(), ExitError> {
+            ExitLike::push_raw(&mut self.inner, item)?;
+            self.pushed(decode::<Alphabet, Clock>(item));
+            Ok(())
+        }
+        fn push_vals(&mut self, vals: &[Alphabet::CharRep]) -> Result<(), ExitError> {
+            self.inner.push_vals(vals)?;
+            for val in vals {
+                self.pushed(decode::<Alphabet, Clock>(StreamItem::Character(*val)));
+            }
+            Ok(())
+        }
+        fn take_overflowed(&mut self) -> bool {
+            self.inner.take_overflowed()
+        }
+        fn close(&mut self) {
+            self.inner.close()
+        }
+        fn room(&self) -> usize {
+            self.inner.room()
+        }
+        fn remaining(&self) -> usize {
+            self.inner.remaining()
+        }
+    }
+    impl<Alphabet, Clock, Inner, const BUFFER_SIZE: usize> GatewayLike<Alphabet, Clock, BUFFER_SIZE>
+        for Traced<Alphabet, Clock, Inner, BUFFER_SIZE>
+    where
+        Alphabet: AlphabetLike,
+        Clock: ClockLike,
+        Inner: StreamLike<Alphabet, Clock, BUFFER_SIZE>
+            + ExitLike<
+                Alphabet,
+                Clock,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            > + GatewayLike<
+                Alphabet,
+                Clock,
+                BUFFER_SIZE,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            >,
+    {
+        type InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>;
+        type Item = StreamItem<Alphabet::CharEnum, Clock::MomentRep, Alphabet::CharRep>;
+        fn pop(&mut self) -> Self::Item {
+            let item = self.inner.pop();
+            self.popped(item);
+            item
+        }
+        fn peek(&self) -> Self::Item {
+            self.inner.peek()
+        }
+        fn pop_raw(&mut self) -> Self::InternalItem {
+            let item = self.inner.pop_raw();
+            self.popped(decode::<Alphabet, Clock>(item));
+            item
+        }
+        fn peek_raw(&self) -> Self::InternalItem {
+            self.inner.peek_raw()
+        }
+        fn forward_duration<Exit: ExitLike<Alphabet, Clock>>(
+            &mut self,
+            exit: &mut Exit,
+        ) -> Result<(), StreamError> {
+            while self.next_is_character() {
+                match self.pop() {
+                    StreamItem::Character(chr) => exit.push(chr)?,
+                    StreamItem::InvalidChar(_) => return Err(StreamError::InvalidChar),
+                    _ => return Err(StreamError::UnexpectedItem),
+                }
+            }
+            Ok(())
+        }
+        fn current_moment(&self) -> Option<Clock::MomentRep> {
+            self.inner.current_moment()
+        }
+        fn is_empty(&self) -> bool {
+            self.inner.is_empty()
+        }
+        fn is_closed(&self) -> bool {
+            self.inner.is_closed()
+        }
+        fn len(&self) -> usize {
+            self.inner.len()
+        }
+        fn capacity(&self) -> usize {
+            self.inner.capacity()
+        }
+        fn buffered_moments(&self) -> usize {
+            self.inner.buffered_moments()
+        }
+        fn has_duration(&self) -> bool {
+            self.inner.has_duration()
+        }
+        fn next_is_character(&self) -> bool {
+            self.inner.next_is_character()
+        }
+        fn next_is_moment(&self) -> bool {
+            self.inner.next_is_moment()
+        }
+        fn duration_len(&self) -> Option<usize> {
+            self.inner.duration_len()
+        }
+        fn pop_duration_into(&mut self, vals: &mut [Alphabet::CharRep]) -> usize {
+            let len = self.inner.pop_duration_into(vals);
+            for val in &vals[..len] {
+                self.popped(decode::<Alphabet, Clock>(StreamItem::Character(*val)));
+            }
+            len
+        }
+    }
+    impl<Alphabet, Clock, Inner, const BUFFER_SIZE: usize> StreamLike<Alphabet, Clock, BUFFER_SIZE>
+        for Traced<Alphabet, Clock, Inner, BUFFER_SIZE>
+    where
+        Alphabet: AlphabetLike,
+        Clock: ClockLike,
+        Inner: StreamLike<Alphabet, Clock, BUFFER_SIZE>
+            + ExitLike<
+                Alphabet,
+                Clock,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            > + GatewayLike<
+                Alphabet,
+                Clock,
+                BUFFER_SIZE,
+                InternalItem = StreamItem<Alphabet::CharRep, Clock::MomentRep>,
+            >,
+    {
+        fn moments(&self) -> impl Iterator<Item = Clock::MomentRep> + '_ {
+            self.inner.moments()
+        }
+    }
+}
+
+#[doc = r" Bridges streams and embedded-hal serial interfaces - for generated programs running on microcontrollers, reading"]
+#[doc = r" and writing a UART. Nothing here blocks, or needs a heap: poll the adapters from the main loop (or an interrupt)."]
+#[cfg(feature = "embedded-hal")]
+pub mod embedded {
+    use super::*;
+    use embedded_hal::serial;
+    use embedded_hal::timer::{CountDown, Periodic};
+    #[derive(Debug)]
+    pub enum SerialError<E> {
+        Serial(E),
+        Stream(StreamError),
+    }
+    impl<E> From<StreamError> for SerialError<E> {
+        fn from(err: StreamError) -> Self {
+            Self::Stream(err)
+        }
+    }
+    impl<E> From<ExitError> for SerialError<E> {
+        fn from(err: ExitError) -> Self {
+            Self::Stream(StreamError::Exit(err))
+        }
+    }
+    #[doc = r" Fills a gateway with the words a serial interface reads - each time the (periodic, already started) timer's"]
+    #[doc = r" count down finishes, a moment ends the duration of the words read since. Moments count durations, starting"]
+    #[doc = r" at 1."]
+    pub struct SerialGateway<Serial, Timer> {
+        serial: Serial,
+        timer: Timer,
+        count: u64,
+    }
+    impl<Serial, Timer: CountDown + Periodic> SerialGateway<Serial, Timer> {
+        pub fn new(serial: Serial, timer: Timer) -> Self {
+            Self {
+                serial,
+                timer,
+                count: 0,
+            }
+        }
+        #[doc = r" Releases the serial interface and timer"]
+        pub fn free(self) -> (Serial, Timer) {
+            (self.serial, self.timer)
+        }
+        #[doc = r" Pushes what was read onto `to` for as long as it has room, and words are available - returns whether"]
+        #[doc = r" anything was pushed"]
+        pub fn poll<Alphabet, Clock, To, const SIZE: usize>(
+            &mut self,
+            to: &mut To,
+        ) -> Result<bool, SerialError<Serial::Error>>
+        where
+            Alphabet: AlphabetLike,
+            Clock: ClockLike,
+            To: StreamLike<Alphabet, Clock, SIZE>,
+            Serial: serial::Read<Alphabet::CharRep>,
+            Clock::MomentRep: TryFrom<u64>,
+        {
+            let mut pushed = false;
+            while to.accepting_pushes() {
+                if self.timer.wait().is_ok() {
+                    let moment = Clock::MomentRep::try_from(self.count + 1)
+                        .map_err(|_| ExitError::NotEncodable)?;
+                    to.push_moment(moment)?;
+                    self.count += 1;
+                } else {
+                    match self.serial.read() {
+                        Ok(word) => match Alphabet::to_char(word) {
+                            Ok(chr) => to.push(chr)?,
+                            Err(_) => to.push_invalid(word)?,
+                        },
+                        Err(nb::Error::WouldBlock) => break,
+                        Err(nb::Error::Other(err)) => return Err(SerialError::Serial(err)),
+                    }
+                }
+                pushed = true;
+            }
+            Ok(pushed)
+        }
+    }
+    #[doc = r" Drains an exit into a serial interface - characters (and invalid raw values) as words, and optionally a"]
+    #[doc = r" delimiter word for every moment"]
+    pub struct SerialExit<Serial, Word> {
+        serial: Serial,
+        delimiter: Option<Word>,
+    }
+    impl<Serial, Word: Copy> SerialExit<Serial, Word> {
+        pub fn new(serial: Serial) -> Self {
+            Self {
+                serial,
+                delimiter: None,
+            }
+        }
+        #[doc = r" Writes `delimiter` for every moment - a newline, say"]
+        pub fn with_delimiter(serial: Serial, delimiter: Word) -> Self {
+            Self {
+                serial,
+                delimiter: Some(delimiter),
+            }
+        }
+        #[doc = r" Releases the serial interface"]
+        pub fn free(self) -> Serial {
+            self.serial
+        }
+        #[doc = r" Writes what is buffered on `from` for as long as the serial interface takes words - items are only popped"]
+        #[doc = r" once written. Returns whether anything was written."]
+        pub fn poll<Alphabet, Clock, From, const SIZE: usize>(
+            &mut self,
+            from: &mut From,
+        ) -> Result<bool, SerialError<Serial::Error>>
+        where
+            Alphabet: AlphabetLike<CharRep = Word>,
+            Clock: ClockLike,
+            From: GatewayLike<
+                Alphabet,
+                Clock,
+                SIZE,
+                InternalItem = StreamItem<Word, Clock::MomentRep>,
+            >,
+            Serial: serial::Write<Word>,
+        {
+            let mut written = false;
+            loop {
+                let word = match from.peek_raw() {
+                    StreamItem::Character(word) | StreamItem::InvalidChar(word) => Some(word),
+                    StreamItem::Moment(_) => self.delimiter,
+                    StreamItem::Empty => break,
+                };
+                if let Some(word) = word {
+                    match self.serial.write(word) {
+                        Ok(()) => (),
+                        Err(nb::Error::WouldBlock) => return Ok(written),
+                        Err(nb::Error::Other(err)) => return Err(SerialError::Serial(err)),
+                    }
+                }
+                from.pop_raw();
+                written = true;
+            }
+            match self.serial.flush() {
+                Ok(()) | Err(nb::Error::WouldBlock) => Ok(written),
+                Err(nb::Error::Other(err)) => Err(SerialError::Serial(err)),
+            }
+        }
+    }
+}
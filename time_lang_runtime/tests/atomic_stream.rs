@@ -0,0 +1,92 @@
+//! An AtomicStream split into a Producer and a Consumer hands over items in the order they were pushed, and never counts
+//! more moments buffered than it holds - with both ends on the same thread, or each on its own.
+
+mod common;
+
+use common::{AlphabetAb, CharAb, ClockCounter};
+use time_lang_runtime::*;
+
+type Atomic<const SIZE: usize> = AtomicStream<AlphabetAb, ClockCounter, SIZE>;
+
+#[test]
+fn pops_what_was_pushed() {
+    let mut stream = Atomic::<4>::new();
+    let (mut producer, mut consumer) = stream.split();
+
+    producer.push(CharAb::A).unwrap();
+    producer.push(CharAb::B).unwrap();
+    assert!(!consumer.has_duration());
+
+    producer.push_moment(7).unwrap();
+    producer.push(CharAb::A).unwrap();
+    assert!(matches!(producer.push(CharAb::B), Err(ExitError::BufferFull)));
+    assert!(producer.take_overflowed());
+
+    assert_eq!(consumer.buffered_moments(), 1);
+    assert_eq!(consumer.duration_len(), Some(3));
+    assert!(matches!(consumer.pop(), StreamItem::Character(CharAb::A)));
+    assert!(matches!(consumer.pop(), StreamItem::Character(CharAb::B)));
+    assert!(matches!(consumer.pop(), StreamItem::Moment(7)));
+    assert_eq!(consumer.current_moment(), Some(7));
+    assert_eq!(consumer.buffered_moments(), 0);
+    assert!(matches!(consumer.pop(), StreamItem::Character(CharAb::A)));
+    assert!(matches!(consumer.pop(), StreamItem::Empty));
+}
+
+#[test]
+fn hands_over_between_threads() {
+    const DURATIONS: u32 = 20_000;
+
+    let mut stream = Atomic::<8>::new();
+    let (mut producer, mut consumer) = stream.split();
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            for moment in 0..DURATIONS {
+                // Each duration is AB, ended by its moment
+                for chr in [Some(CharAb::A), Some(CharAb::B), None] {
+                    loop {
+                        let pushed = match chr {
+                            Some(chr) => producer.push(chr),
+                            None => producer.push_moment(moment)
+                        };
+
+                        match pushed {
+                            Ok(()) => break,
+                            Err(ExitError::BufferFull) => std::thread::yield_now(),
+                            Err(err) => panic!("push failed: {:?}", err)
+                        }
+                    }
+                }
+            }
+
+            producer.close();
+        });
+
+        let mut expected = 0;
+        while !consumer.is_drained() {
+            let item = consumer.pop();
+
+            // Counted before they're published, moments may be counted a little early - but never more than fit
+            let moments = consumer.buffered_moments();
+            assert!(moments <= consumer.capacity(), "counted {} moments buffered", moments);
+
+            match item {
+                StreamItem::Empty => std::thread::yield_now(),
+                StreamItem::Character(chr) => {
+                    let position = expected % 3;
+                    assert!(position < 2, "character {:?} where moment {} was expected", chr, expected / 3);
+                    assert_eq!(chr, [CharAb::A, CharAb::B][position as usize]);
+                    expected += 1;
+                },
+                StreamItem::Moment(moment) => {
+                    assert_eq!((expected % 3, moment), (2, expected / 3));
+                    expected += 1;
+                },
+                StreamItem::InvalidChar(rep) => panic!("invalid character {}", rep)
+            }
+        }
+
+        assert_eq!(expected, DURATIONS * 3);
+    });
+}
@@ -0,0 +1,56 @@
+//! An alphabet and a clock to test streams with - written out as `parserbin` would generate them
+
+use time_lang_runtime::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CharAb {
+    A,
+    B
+}
+
+pub struct AlphabetAb {}
+
+impl AlphabetLike for AlphabetAb {
+    type CharRep = u8;
+    type CharEnum = CharAb;
+    const NAME: &'static str = "AB";
+    const HASH: u64 = 0x41;
+
+    fn char_with_name(name: &str) -> Result<CharAb, AlphabetError<&str>> {
+        match name {
+            "A" => Ok(CharAb::A),
+            "B" => Ok(CharAb::B),
+            _ => Err(AlphabetError::NameNotFound())
+        }
+    }
+
+    fn to_char(rep: u8) -> Result<CharAb, AlphabetError<u8>> {
+        match rep {
+            0x41 => Ok(CharAb::A),
+            0x42 => Ok(CharAb::B),
+            _ => Err(AlphabetError::UnknownCharacter(rep))
+        }
+    }
+
+    fn to_val(chr: CharAb) -> u8 {
+        match chr {
+            CharAb::A => 0x41,
+            CharAb::B => 0x42
+        }
+    }
+}
+
+pub struct ClockCounter {}
+
+impl ClockLike for ClockCounter {
+    type MomentRep = u32;
+    const REPRESENTS: &'static str = "QUANTITY";
+
+    fn represents(&self) -> &str {
+        Self::REPRESENTS
+    }
+
+    fn to_moment(rep: u32) -> ClockMoment<u32> {
+        ClockMoment::Quantity(rep)
+    }
+}
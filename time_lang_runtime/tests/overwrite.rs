@@ -0,0 +1,34 @@
+//! Streams overwriting their oldest duration when full drop it without popping it - their current moment stays where
+//! it was, until a moment is actually popped.
+
+mod common;
+
+use common::{AlphabetAb, CharAb, ClockCounter};
+use time_lang_runtime::*;
+
+/// Fills a four item stream with two durations (A ended by 1, B by 2), then pushes one more A - evicting the first
+fn evicts_oldest<S: StreamLike<AlphabetAb, ClockCounter, 4>>(mut stream: S) {
+    stream.set_initial_moment(0);
+    stream.push(CharAb::A).unwrap();
+    stream.push_moment(1).unwrap();
+    stream.push(CharAb::B).unwrap();
+    stream.push_moment(2).unwrap();
+
+    stream.push(CharAb::A).unwrap();
+    assert_eq!(stream.current_moment(), Some(0));
+
+    assert!(matches!(stream.pop(), StreamItem::Character(CharAb::B)));
+    assert!(matches!(stream.pop(), StreamItem::Moment(2)));
+    assert_eq!(stream.current_moment(), Some(2));
+}
+
+#[test]
+fn stream_keeps_its_moment() {
+    evicts_oldest(Stream::<AlphabetAb, ClockCounter, 4>::with_policy(OverflowPolicy::OverwriteOldest));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn vec_stream_keeps_its_moment() {
+    evicts_oldest(VecStream::<AlphabetAb, ClockCounter, 4>::with_policy(OverflowPolicy::OverwriteOldest));
+}
@@ -0,0 +1,66 @@
+//! Streams written by a WireEncoder read back the same through a WireDecoder - frames that were corrupted on the way,
+//! or carry lengths no header has, are rejected.
+
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{AlphabetAb, CharAb, ClockCounter};
+use time_lang_runtime::io::*;
+use time_lang_runtime::*;
+
+type Ab = Stream<AlphabetAb, ClockCounter, 16>;
+
+/// The wire format of two durations - AB ended by 3, and an invalid value ended by 9 - then the end of the stream
+fn encoded() -> Vec<u8> {
+    let mut from = Ab::new();
+    from.push(CharAb::A).unwrap();
+    from.push(CharAb::B).unwrap();
+    from.push_moment(3).unwrap();
+    from.push_invalid(0x7F).unwrap();
+    from.push_moment(9).unwrap();
+    from.close();
+
+    let mut bytes = Vec::new();
+    WireEncoder::new().encode_to(&mut from, &mut bytes).unwrap();
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Result<Ab, IoError> {
+    let mut reader = bytes;
+    let mut decoder = WireDecoder::<AlphabetAb, ClockCounter>::new();
+    let mut to = Ab::new();
+
+    while !decoder.is_ended() {
+        decoder.decode_from(&mut reader, &mut to)?;
+    }
+
+    Ok(to)
+}
+
+#[test]
+fn round_trips() {
+    let mut to = decode(&encoded()).unwrap();
+    let items: Vec<String> = Drain::new(&mut to).map(|item| format!("{:?}", item)).collect();
+
+    assert_eq!(items, ["Character(A)", "Character(B)", "Moment(3)", "InvalidChar(127)", "Moment(9)"]);
+    assert!(to.is_drained());
+}
+
+#[test]
+fn rejects_corrupted_frames() {
+    // The first moment's frame follows the header (27 bytes) and the run of AB (8 bytes) - its 3 becomes a 4, which
+    // its checksum doesn't match
+    let mut bytes = encoded();
+    assert_eq!(bytes[35..37], [1, 3]);
+    bytes[36] = 4;
+
+    assert!(matches!(decode(&bytes), Err(IoError::Wire(WireError::Corrupted))));
+}
+
+#[test]
+fn rejects_overlong_strings() {
+    // A header whose alphabet name claims to be 2^56 bytes long
+    let header = [3, 1, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01];
+    assert!(matches!(decode(&header), Err(IoError::Wire(WireError::Malformed(_)))));
+}
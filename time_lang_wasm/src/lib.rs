@@ -0,0 +1,29 @@
+//! The compiler built for the browser - transpiles `.tl` sources client-side, for a playground.
+//!
+//! ```sh
+//! wasm-pack build time_lang_wasm --target web
+//! ```
+//!
+//! ```js
+//! import init, { compile_str } from "./pkg/time_lang_wasm.js";
+//!
+//! await init();
+//! const rust = compile_str(source);
+//! ```
+
+use parser::Compiler;
+use wasm_bindgen::prelude::*;
+
+/// Transpiles a `.tl` source into self-contained Rust (unformatted, with the runtime inlined) - throwing the
+/// diagnostic rendered against the source, as the CLI prints it, when it doesn't compile
+#[wasm_bindgen]
+pub fn compile_str(source: &str) -> Result<String, String> {
+    Compiler::compile_str(source).map_err(|diagnostic| diagnostic.render(source))
+}
+
+/// Like `compile_str`, but throwing the diagnostic as a single-line JSON object (severity, line, col, message and
+/// note) - for editors marking the offending statement
+#[wasm_bindgen]
+pub fn compile_str_json(source: &str) -> Result<String, String> {
+    Compiler::compile_str(source).map_err(|diagnostic| diagnostic.to_json())
+}